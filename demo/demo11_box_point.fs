@@ -1,59 +1,39 @@
 #version 330 core
+#include "include/lighting.glsl"
+
 out vec4 FragColor;
 
 struct Material {
     sampler2D diffuse;
-    vec3 specular;    
-    float shininess;
-}; 
-
-struct Light {
-    vec3 position;
-
-    vec3 ambient;
-    vec3 diffuse;
+#ifdef HAS_SPECULAR_MAP
+    sampler2D specular;
+#else
     vec3 specular;
-
-    float constant;
-    float linear;
-    float quadratic;
+#endif
+    float shininess;
 };
 
-in vec3 FragPos;  
+in vec3 FragPos;
 in vec3 Normal;
 in vec2 TexCoords;
-  
+
 uniform vec3 viewPos;
 uniform Material material;
-uniform Light light;
+uniform PointLight light;
 
 void main()
 {
-    // ambient
-    vec3 ambient = light.ambient * vec3(texture(material.diffuse, TexCoords));
-  	
-    // diffuse 
+    Surface surface;
+    surface.diffuse = vec3(texture(material.diffuse, TexCoords));
+#ifdef HAS_SPECULAR_MAP
+    surface.specular = vec3(texture(material.specular, TexCoords));
+#else
+    surface.specular = material.specular;
+#endif
+    surface.shininess = material.shininess;
+
     vec3 norm = normalize(Normal);
-    vec3 lightDir = normalize(light.position - FragPos);
-    // vec3 lightDir = normalize(-light.direction);
-    float diff = max(dot(norm, lightDir), 0.0);
-    vec3 diffuse = light.diffuse * diff * vec3(texture(material.diffuse, TexCoords));
-    
-    // specular
     vec3 viewDir = normalize(viewPos - FragPos);
-    vec3 reflectDir = reflect(-lightDir, norm);  
-    float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
-    vec3 specular = light.specular * (spec * material.specular);
-
-    // attenuation
-    float distance = length(light.position - FragPos);
-    float attenuation = 1.0 / (light.constant + light.linear * distance 
-        + light.quadratic * (distance * distance));
-
-    ambient *= attenuation;
-    diffuse *= attenuation;
-    specular *= attenuation;
-        
-    vec3 result = ambient + diffuse + specular;
+    vec3 result = CalcPointLight(light, surface, norm, FragPos, viewDir);
     FragColor = vec4(result, 1.0);
-} 
\ No newline at end of file
+}
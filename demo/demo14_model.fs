@@ -1,11 +1,43 @@
 #version 330 core
 out vec4 FragColor;
 
+in vec3 FragPos;
+in vec3 Normal;
 in vec2 TexCoords;
+#ifdef HAS_NORMAL_MAP
+in vec3 Tangent;
+in vec3 Bitangent;
+
+uniform sampler2D texture_normal1;
+#endif
+
+// factors of glTF materials, neutral for other formats (see models::Material)
+struct Material {
+    vec4 baseColor;
+    vec3 emissive;
+};
 
 uniform sampler2D texture_diffuse1;
+uniform mat3 texture_diffuse1_transform;
+uniform Material material;
+uniform vec3 lightDir;
+uniform vec3 viewPos;
 
 void main()
-{    
-    FragColor = texture(texture_diffuse1, TexCoords);
-}
\ No newline at end of file
+{
+    vec3 normal = normalize(Normal);
+#ifdef HAS_NORMAL_MAP
+    // tangent space normal from [0, 1] to [-1, 1], then to world space
+    mat3 TBN = mat3(normalize(Tangent), normalize(Bitangent), normal);
+    normal = normalize(TBN * (texture(texture_normal1, TexCoords).rgb * 2.0 - 1.0));
+#endif
+
+    vec2 uv = (texture_diffuse1_transform * vec3(TexCoords, 1.0)).xy;
+    vec3 color = texture(texture_diffuse1, uv).rgb * material.baseColor.rgb;
+    vec3 toLight = normalize(-lightDir);
+    float diff = max(dot(normal, toLight), 0.0);
+    vec3 halfway = normalize(toLight + normalize(viewPos - FragPos));
+    float spec = pow(max(dot(normal, halfway), 0.0), 32.0);
+
+    FragColor = vec4(color * (0.2 + 0.8 * diff) + vec3(0.2) * spec + material.emissive, 1.0);
+}
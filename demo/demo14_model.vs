@@ -2,8 +2,16 @@
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec3 aNormal;
 layout (location = 2) in vec2 aTexCoords;
+layout (location = 3) in vec3 aTangent;
+layout (location = 4) in vec3 aBitangent;
 
+out vec3 FragPos;
+out vec3 Normal;
 out vec2 TexCoords;
+#ifdef HAS_NORMAL_MAP
+out vec3 Tangent;
+out vec3 Bitangent;
+#endif
 
 uniform mat4 model;
 uniform mat4 view;
@@ -11,6 +19,13 @@ uniform mat4 projection;
 
 void main()
 {
-    TexCoords = aTexCoords;    
-    gl_Position = projection * view * model * vec4(aPos, 1.0);
-}
\ No newline at end of file
+    mat3 normalMatrix = transpose(inverse(mat3(model)));
+    FragPos = vec3(model * vec4(aPos, 1.0));
+    Normal = normalMatrix * aNormal;
+    TexCoords = aTexCoords;
+#ifdef HAS_NORMAL_MAP
+    Tangent = normalMatrix * aTangent;
+    Bitangent = normalMatrix * aBitangent;
+#endif
+    gl_Position = projection * view * vec4(FragPos, 1.0);
+}
@@ -0,0 +1,22 @@
+#version 430 core
+layout (local_size_x = 8, local_size_y = 8) in;
+
+layout (std430, binding = 0) buffer Heights
+{
+    float heights[];
+};
+
+uniform int size;
+uniform float time;
+
+void main()
+{
+    uvec2 p = gl_GlobalInvocationID.xy;
+    // the last work groups run past the edge
+    if (p.x >= uint(size) || p.y >= uint(size))
+        return;
+
+    // same waves as `height` in demo23.rs
+    vec2 uv = vec2(p) / float(size - 1);
+    heights[p.y * uint(size) + p.x] = 0.3 * sin(uv.x * 9.0 + time) * cos(uv.y * 7.0 - time * 0.7);
+}
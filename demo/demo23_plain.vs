@@ -0,0 +1,17 @@
+#version 330 core
+layout (location = 0) in vec3 aPos;
+layout (location = 2) in vec2 aTexCoords;
+
+out float Height;
+
+uniform sampler2D heights;
+uniform mat4 model;
+uniform mat4 view;
+uniform mat4 projection;
+
+// demo23_terrain.tes without tessellation, for contexts older than 4.0
+void main()
+{
+    Height = textureLod(heights, aTexCoords, 0.0).r;
+    gl_Position = projection * view * model * vec4(aPos + vec3(0.0, Height, 0.0), 1.0);
+}
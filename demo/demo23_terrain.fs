@@ -0,0 +1,10 @@
+#version 330 core
+in float Height;
+out vec4 FragColor;
+
+void main()
+{
+    vec3 low = vec3(0.1, 0.3, 0.7);
+    vec3 high = vec3(0.9, 0.9, 0.8);
+    FragColor = vec4(mix(low, high, clamp(Height / 0.6 + 0.5, 0.0, 1.0)), 1.0);
+}
@@ -0,0 +1,12 @@
+#version 400 core
+layout (location = 0) in vec3 aPos;
+layout (location = 2) in vec2 aTexCoords;
+
+out vec2 TcsTexCoords;
+
+void main()
+{
+    // placed by the evaluation shader
+    gl_Position = vec4(aPos, 1.0);
+    TcsTexCoords = aTexCoords;
+}
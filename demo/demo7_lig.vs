@@ -2,8 +2,12 @@
 layout (location = 0) in vec3 aPos;
 
 uniform mat4 model;
+#ifdef CAMERA_BLOCK
+#include "include/camera.glsl"
+#else
 uniform mat4 view;
 uniform mat4 projection;
+#endif
 
 void main()
 {
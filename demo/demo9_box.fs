@@ -3,7 +3,11 @@ out vec4 FragColor;
 
 struct Material {
     sampler2D diffuse;
-    vec3 specular;    
+#ifdef HAS_SPECULAR_MAP
+    sampler2D specular;
+#else
+    vec3 specular;
+#endif
     float shininess;
 }; 
 
@@ -38,7 +42,11 @@ void main()
     vec3 viewDir = normalize(viewPos - FragPos);
     vec3 reflectDir = reflect(-lightDir, norm);  
     float spec = pow(max(dot(viewDir, reflectDir), 0.0), material.shininess);
-    vec3 specular = light.specular * (spec * material.specular);  
+#ifdef HAS_SPECULAR_MAP
+    vec3 specular = light.specular * spec * vec3(texture(material.specular, TexCoords));
+#else
+    vec3 specular = light.specular * (spec * material.specular);
+#endif
         
     vec3 result = ambient + diffuse + specular;
     FragColor = vec4(result, 1.0);
@@ -8,8 +8,12 @@ out vec3 Normal;
 out vec2 TexCoords;
 
 uniform mat4 model;
+#ifdef CAMERA_BLOCK
+#include "include/camera.glsl"
+#else
 uniform mat4 view;
 uniform mat4 projection;
+#endif
 
 void main()
 {
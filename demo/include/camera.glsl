@@ -0,0 +1,6 @@
+// Camera shared by every program through a uniform buffer, see gfx::lights::CameraBlock
+layout (std140) uniform Camera {
+    mat4 projection;
+    mat4 view;
+    vec3 viewPos;
+};
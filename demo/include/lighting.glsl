@@ -0,0 +1,90 @@
+// Shared Phong lighting, the uniform struct layouts match gfx::lights
+
+struct DirLight {
+    vec3 direction;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+struct PointLight {
+    vec3 position;
+
+    float constant;
+    float linear;
+    float quadratic;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+struct SpotLight {
+    vec3 position;
+    vec3 direction;
+    float cutOff;
+    float outerCutOff;
+
+    float constant;
+    float linear;
+    float quadratic;
+
+    vec3 ambient;
+    vec3 diffuse;
+    vec3 specular;
+};
+
+// surface properties sampled from the material of the fragment
+struct Surface {
+    vec3 diffuse;
+    vec3 specular;
+    float shininess;
+};
+
+float Attenuation(float constant, float linear, float quadratic, float distance)
+{
+    return 1.0 / (constant + linear * distance + quadratic * (distance * distance));
+}
+
+// ambient is scaled by ambientScale, diffuse and specular by scale
+vec3 Phong(vec3 lightDir, vec3 ambient, vec3 diffuse, vec3 specular, Surface surface, vec3 normal, vec3 viewDir, float ambientScale, float scale)
+{
+    // diffuse shading
+    float diff = max(dot(normal, lightDir), 0.0);
+    // specular shading
+    vec3 reflectDir = reflect(-lightDir, normal);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), surface.shininess);
+    // combine results
+    vec3 a = ambient * surface.diffuse;
+    vec3 d = diffuse * diff * surface.diffuse;
+    vec3 s = specular * spec * surface.specular;
+    return a * ambientScale + (d + s) * scale;
+}
+
+// calculates the color when using a directional light.
+vec3 CalcDirLight(DirLight light, Surface surface, vec3 normal, vec3 viewDir)
+{
+    vec3 lightDir = normalize(-light.direction);
+    return Phong(lightDir, light.ambient, light.diffuse, light.specular, surface, normal, viewDir, 1.0, 1.0);
+}
+
+// calculates the color when using a point light.
+vec3 CalcPointLight(PointLight light, Surface surface, vec3 normal, vec3 fragPos, vec3 viewDir)
+{
+    vec3 lightDir = normalize(light.position - fragPos);
+    float attenuation = Attenuation(light.constant, light.linear, light.quadratic, length(light.position - fragPos));
+    return Phong(lightDir, light.ambient, light.diffuse, light.specular, surface, normal, viewDir, attenuation, attenuation);
+}
+
+// calculates the color when using a spot light.
+vec3 CalcSpotLight(SpotLight light, Surface surface, vec3 normal, vec3 fragPos, vec3 viewDir)
+{
+    vec3 lightDir = normalize(light.position - fragPos);
+    float attenuation = Attenuation(light.constant, light.linear, light.quadratic, length(light.position - fragPos));
+    // spotlight intensity (soft edges), the ambient term is lit outside the cone too
+    float theta = dot(lightDir, normalize(-light.direction));
+    float epsilon = light.cutOff - light.outerCutOff;
+    float intensity = clamp((theta - light.outerCutOff) / epsilon, 0.0, 1.0);
+    return Phong(lightDir, light.ambient, light.diffuse, light.specular, surface, normal, viewDir, attenuation, attenuation * intensity);
+}
@@ -0,0 +1,12 @@
+// Scene lights shared through a uniform buffer, see gfx::lights::LightsBlock
+#include "lighting.glsl"
+
+#ifndef NUM_POINT_LIGHTS
+#define NUM_POINT_LIGHTS 4
+#endif
+
+layout (std140) uniform Lights {
+    DirLight dirLight;
+    PointLight pointLights[NUM_POINT_LIGHTS];
+    SpotLight spotLight;
+};
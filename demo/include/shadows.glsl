@@ -0,0 +1,35 @@
+// Shadow map lookups with percentage-closer filtering
+
+// side of the square PCF kernel in texels, override it with a define
+#ifndef PCF_KERNEL
+#define PCF_KERNEL 3
+#endif
+
+// returns 1.0 for a fragment in full shadow, 0.0 for a fully lit one
+float ShadowCalculation(sampler2D shadowMap, vec4 fragPosLightSpace, vec3 normal, vec3 lightDir)
+{
+    // perform perspective divide
+    vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
+    // transform to [0,1] range
+    projCoords = projCoords * 0.5 + 0.5;
+    // keep the shadow at 0.0 when outside the far_plane region of the light's frustum.
+    if(projCoords.z > 1.0)
+        return 0.0;
+    // get depth of current fragment from light's perspective
+    float currentDepth = projCoords.z;
+    // calculate bias (based on depth map resolution and slope)
+    float bias = max(0.05 * (1.0 - dot(normal, lightDir)), 0.005);
+    // PCF
+    float shadow = 0.0;
+    vec2 texelSize = 1.0 / textureSize(shadowMap, 0);
+    const int half_kernel = PCF_KERNEL / 2;
+    for(int x = -half_kernel; x <= half_kernel; ++x)
+    {
+        for(int y = -half_kernel; y <= half_kernel; ++y)
+        {
+            float pcfDepth = texture(shadowMap, projCoords.xy + vec2(x, y) * texelSize).r;
+            shadow += currentDepth - bias > pcfDepth ? 1.0 : 0.0;
+        }
+    }
+    return shadow / float(PCF_KERNEL * PCF_KERNEL);
+}
@@ -0,0 +1,24 @@
+// Linear blend skinning with the bone attributes of Mesh vertices, the bone
+// palette is uploaded by Animator::upload
+#ifndef MAX_BONES
+#define MAX_BONES 100
+#endif
+#define MAX_BONE_INFLUENCE 4
+
+layout (location = 5) in ivec4 aBoneIds;
+layout (location = 6) in vec4 aBoneWeights;
+
+uniform mat4 bones[MAX_BONES];
+
+mat4 skinMatrix()
+{
+    float total = aBoneWeights.x + aBoneWeights.y + aBoneWeights.z + aBoneWeights.w;
+    // vertices without bone influences stay in place
+    if (total <= 0.0)
+        return mat4(1.0);
+
+    mat4 skin = mat4(0.0);
+    for (int i = 0; i < MAX_BONE_INFLUENCE; i++)
+        skin += bones[clamp(aBoneIds[i], 0, MAX_BONES - 1)] * aBoneWeights[i];
+    return skin;
+}
@@ -1,4 +1,6 @@
 #version 330 core
+#include "../include/camera.glsl"
+
 out vec4 FragColor;
 
 in VS_OUT {
@@ -11,7 +13,6 @@ uniform sampler2D diffuseTexture;
 uniform samplerCube depthMap;
 
 uniform vec3 lightPos;
-uniform vec3 viewPos;
 
 uniform float far_plane;
 uniform bool shadows;
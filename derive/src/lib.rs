@@ -0,0 +1,97 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitInt};
+
+/// Implements `open_gl::gfx::vertex::VertexLayout` from the fields marked
+/// `#[vertex(location = N)]`, the others are skipped. `normalized` reads
+/// integers as [0, 1] (or [-1, 1]) floats, `half` reads `u16`s as f16 floats.
+///
+/// ```ignore
+/// #[repr(C)]
+/// #[derive(Clone, Copy, VertexLayout)]
+/// struct Particle {
+///     #[vertex(location = 0)]
+///     position: Vec3,
+///     #[vertex(location = 1, normalized)]
+///     color: [u8; 4],
+/// }
+/// ```
+#[proc_macro_derive(VertexLayout, attributes(vertex))]
+pub fn derive_vertex_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(f) => &f.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "VertexLayout needs a struct with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "VertexLayout can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut attribs = Vec::new();
+    for field in fields {
+        let Some(attr) = field.attrs.iter().find(|a| a.path().is_ident("vertex")) else {
+            continue;
+        };
+        let mut location = None;
+        let mut normalized = false;
+        let mut half = false;
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("location") {
+                location = Some(meta.value()?.parse::<LitInt>()?.base10_parse::<u32>()?);
+            } else if meta.path.is_ident("normalized") {
+                normalized = true;
+            } else if meta.path.is_ident("half") {
+                half = true;
+            } else {
+                return Err(meta.error("expected `location = N`, `normalized` or `half`"));
+            }
+            Ok(())
+        })?;
+        let Some(location) = location else {
+            return Err(syn::Error::new_spanned(attr, "missing `location = N`"));
+        };
+
+        let name = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let mut attrib = quote! {
+            ::open_gl::gfx::vertex::Attrib::new::<#ty>(
+                #location,
+                ::std::mem::offset_of!(Self, #name),
+            )
+        };
+        if normalized {
+            attrib = quote! { #attrib.normalized() };
+        }
+        if half {
+            attrib = quote! { #attrib.half() };
+        }
+        attribs.push(attrib);
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::open_gl::gfx::vertex::VertexLayout for #name #ty_generics #where_clause {
+            fn attributes() -> ::std::vec::Vec<::open_gl::gfx::vertex::Attrib> {
+                ::std::vec![#(#attribs),*]
+            }
+        }
+    })
+}
@@ -0,0 +1,132 @@
+/// Prints what a model cache file holds, e.g.
+/// `cargo run --bin model_cache -- demo/backpack/backpack.obj`
+extern crate open_gl;
+use open_gl::gfx::model_cache;
+use open_gl::gfx::models::Model;
+use std::fs;
+use std::io::prelude::*;
+use std::process;
+
+fn main() {
+    let mut stderr = std::io::stderr();
+
+    let files: Vec<String> = std::env::args().skip(1).collect();
+    if files.is_empty() {
+        writeln!(&mut stderr, "Usage: model_cache <model or .mcache file>...").expect("stderr failure");
+        writeln!(
+            &mut stderr,
+            "Model files are looked up in MODEL_CACHE_DIR when it is set, next to them otherwise"
+        )
+        .expect("stderr failure");
+        process::exit(1);
+    }
+    if let Ok(dir) = std::env::var("MODEL_CACHE_DIR") {
+        model_cache::set_cache_dir(Some(dir.into()));
+    }
+
+    let mut failed = false;
+    for file in files {
+        if let Err(e) = dump(&file) {
+            writeln!(&mut stderr, "{}: {}", file, e).expect("stderr failure");
+            failed = true;
+        }
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn dump(file: &str) -> Result<(), String> {
+    let (path, source) = match file.strip_suffix(".mcache") {
+        Some(source) if model_cache::cache_dir().is_none() => (file.into(), source.to_string()),
+        // the source can't be told from a name in the cache directory
+        Some(_) => (file.into(), file.to_string()),
+        None => (model_cache::cache_path(file), file.to_string()),
+    };
+    let data = fs::read(&path).map_err(|e| format!("error reading {}: {}", path.display(), e))?;
+    let header = model_cache::read_header(&data)?;
+    println!(
+        "{}: version {}, importer {}, {} bytes",
+        path.display(),
+        header.version,
+        header.importer,
+        data.len()
+    );
+    if header.version != model_cache::VERSION {
+        println!("  written by another version, the model is imported again");
+        return Ok(());
+    }
+
+    let cached = fs::metadata(&path).and_then(|m| m.modified()).ok();
+    for dep in header.dependencies.iter() {
+        let state = match fs::metadata(dep).and_then(|m| m.modified()) {
+            Err(_) => "missing",
+            Ok(m) if cached.is_some_and(|c| m > c) => "newer than the cache",
+            Ok(_) => "up to date",
+        };
+        println!("  depends on {} ({})", dep, state);
+    }
+
+    let model = model_cache::decode(&data, &source)?;
+    for (i, mesh) in model.meshes.iter().enumerate() {
+        println!(
+            "  mesh {}: {} vertices, {} triangles, {} indices{}",
+            i,
+            mesh.vertices.len(),
+            mesh.indices.len() / 3,
+            if mesh.short_indices { "u16" } else { "u32" },
+            if mesh.skinned { ", skinned" } else { "" }
+        );
+        let m = &mesh.material;
+        println!(
+            "    base color {:?}, metallic {}, roughness {}, emissive {:?}, specular {:?}, shininess {}",
+            m.base_color.as_array(),
+            m.metallic,
+            m.roughness,
+            m.emissive.as_array(),
+            m.specular.as_array(),
+            m.shininess
+        );
+        for t in mesh.textures.iter() {
+            println!(
+                "    {} (uv {}): {}",
+                t.tex_type.uniform_prefix(),
+                t.uv_set,
+                t.path
+            );
+        }
+        let (min, max) = (mesh.aabb.min, mesh.aabb.max);
+        println!("    bounds {:?} - {:?}", min.as_array(), max.as_array());
+    }
+
+    if !model.nodes.is_empty() {
+        println!("  nodes:");
+        print_node(&model, 0, 2);
+    }
+    if !model.skeleton.is_empty() {
+        println!("  skeleton: {} bones", model.skeleton.len());
+    }
+    for clip in model.animations.iter() {
+        println!(
+            "  animation {:?}: {:.2} s, {} channels",
+            clip.name,
+            clip.duration,
+            clip.channels.len()
+        );
+    }
+    Ok(())
+}
+
+fn print_node(model: &Model, node: usize, depth: usize) {
+    let meshes = model.instances.iter().filter(|i| i.node == node).count();
+    let name = &model.nodes[node].name;
+    if meshes > 0 {
+        println!("{:indent$}{} ({} meshes)", "", name, meshes, indent = depth * 2);
+    } else {
+        println!("{:indent$}{}", "", name, indent = depth * 2);
+    }
+    for child in model.nodes[node].children.iter() {
+        print_node(model, *child, depth + 1);
+    }
+}
+
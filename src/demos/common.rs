@@ -1,5 +1,7 @@
 use crate::gfx::glutils::*;
 use crate::gfx::models::*;
+use crate::gfx::optimize;
+use crate::gfx::vertex::{build_vao, VertexLayout};
 use gl33::*;
 use ultraviolet::*;
 
@@ -21,6 +23,11 @@ macro_rules! impl_demo_trait {
 
 pub(crate) use impl_demo_trait;
 
+/// Directory for debug texture dumps, enabled by setting `DUMP_TEXTURES_DIR`
+pub fn texture_dump_dir() -> Option<String> {
+    std::env::var("DUMP_TEXTURES_DIR").ok()
+}
+
 pub const DEFAULT_SIMPL_CUBE_VERT: [f32; 108] = [
     -0.5, -0.5, -0.5, 0.5, -0.5, -0.5, 0.5, 0.5, -0.5, 0.5, 0.5, -0.5, -0.5, 0.5, -0.5, -0.5, -0.5,
     -0.5, -0.5, -0.5, 0.5, 0.5, -0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, 0.5, -0.5, 0.5, 0.5, -0.5,
@@ -31,6 +38,14 @@ pub const DEFAULT_SIMPL_CUBE_VERT: [f32; 108] = [
     0.5, -0.5, 0.5, 0.5, -0.5, 0.5, -0.5,
 ];
 
+/// Vertex of `DEFAULT_SIMPL_CUBE_VERT`
+#[repr(C)]
+#[derive(Clone, Copy, VertexLayout)]
+pub struct PosVertex {
+    #[vertex(location = 0)]
+    pub position: Vec3,
+}
+
 #[derive(Default)]
 pub struct SimplestCubeObj {
     vbo: u32,
@@ -40,29 +55,19 @@ pub struct SimplestCubeObj {
 impl SimplestCubeObj {
     // 6 sides x 2 triangles x 3 vertices
     pub fn from(gl: &GlFns, data: [f32; 108]) -> Result<Self, String> {
-        let mut vao: u32 = 0;
         let mut vbo: u32 = 0;
 
         unsafe {
-            gl.GenVertexArrays(1, &mut vao);
-            if vao == 0 {
-                return Err("failed: gl.GenVertexArrays(1, &mut cube.vao)".to_string());
-            }
-
             gl.GenBuffers(1, &mut vbo);
             if vbo == 0 {
                 return Err("failed: gl.GenBuffers(1, &mut vbo)".to_string());
             }
 
             gl.BindBuffer(GL_ARRAY_BUFFER, vbo);
-
-            gl_buffer_data_arr_stat(gl, &data);
-
-            gl.BindVertexArray(vao);
-
-            // position attribute
-            gl_vertex_attrib_ptr_enab(gl, 0, 3, 3, 0);
         }
+        gl_buffer_data_arr_stat(gl, &data);
+
+        let vao = build_vao::<PosVertex>(gl, vbo, None)?;
 
         Ok(SimplestCubeObj {
             vbo,
@@ -71,15 +76,7 @@ impl SimplestCubeObj {
     }
 
     pub fn add_another_cube(&mut self, gl: &GlFns) -> usize {
-        let mut vao: u32 = 0;
-        unsafe {
-            gl.GenVertexArrays(1, &mut vao);
-            gl.BindVertexArray(vao);
-
-            gl.BindBuffer(GL_ARRAY_BUFFER, self.vbo);
-
-            gl_vertex_attrib_ptr_enab(gl, 0, 3, 3, 0);
-        }
+        let vao = build_vao::<PosVertex>(gl, self.vbo, None).expect("failed add_another_cube");
 
         self.vaos.push(vao);
 
@@ -176,6 +173,18 @@ pub const DEFAULT_POS_NORM_TEX_CUBE_VERT: [f32; 288] = [
 //      1.0, -1.0,  1.0,    0.0,  1.0,  0.0,  0.0,  1.0
 // ];
 
+/// Vertex of `DEFAULT_POS_NORM_TEX_CUBE_VERT`
+#[repr(C)]
+#[derive(Clone, Copy, VertexLayout)]
+pub struct PosNormTexVertex {
+    #[vertex(location = 0)]
+    pub position: Vec3,
+    #[vertex(location = 1)]
+    pub normal: Vec3,
+    #[vertex(location = 2)]
+    pub tex_coords: Vec2,
+}
+
 #[derive(Default)]
 pub struct NormTexCubeObj {
     vbo: u32,
@@ -185,35 +194,19 @@ pub struct NormTexCubeObj {
 impl NormTexCubeObj {
     // 6 sides x 2 triangles x 3 vertices x 3 normal x 2 tex coord
     pub fn from(gl: &GlFns, data: [f32; 288]) -> Result<Self, String> {
-        let mut vao: u32 = 0;
         let mut vbo: u32 = 0;
 
         unsafe {
-            gl.GenVertexArrays(1, &mut vao);
-            if vao == 0 {
-                return Err("failed: gl.GenVertexArrays(1, &mut cube.vao)".to_string());
-            }
-
             gl.GenBuffers(1, &mut vbo);
             if vbo == 0 {
                 return Err("failed: gl.GenBuffers(1, &mut vbo)".to_string());
             }
 
             gl.BindBuffer(GL_ARRAY_BUFFER, vbo);
-
-            gl_buffer_data_arr_stat(gl, &data);
-
-            gl.BindVertexArray(vao);
-
-            // position attribute
-            gl_vertex_attrib_ptr_enab(gl, 0, 3, 8, 0);
-
-            // normals attribute
-            gl_vertex_attrib_ptr_enab(gl, 1, 3, 8, 3);
-
-            // tex coords attribute
-            gl_vertex_attrib_ptr_enab(gl, 2, 2, 8, 6);
         }
+        gl_buffer_data_arr_stat(gl, &data);
+
+        let vao = build_vao::<PosNormTexVertex>(gl, vbo, None)?;
 
         Ok(NormTexCubeObj {
             vbo,
@@ -222,22 +215,8 @@ impl NormTexCubeObj {
     }
 
     pub fn add_another_cube(&mut self, gl: &GlFns) -> usize {
-        let mut vao: u32 = 0;
-        unsafe {
-            gl.GenVertexArrays(1, &mut vao);
-            gl.BindVertexArray(vao);
-
-            gl.BindBuffer(GL_ARRAY_BUFFER, self.vbo);
-
-            // position attribute
-            gl_vertex_attrib_ptr_enab(gl, 0, 3, 8, 0);
-
-            // normals attribute
-            gl_vertex_attrib_ptr_enab(gl, 1, 3, 8, 3);
-
-            // tex coords attribute
-            gl_vertex_attrib_ptr_enab(gl, 2, 2, 8, 6);
-        }
+        let vao =
+            build_vao::<PosNormTexVertex>(gl, self.vbo, None).expect("failed add_another_cube");
 
         self.vaos.push(vao);
 
@@ -252,6 +231,18 @@ impl NormTexCubeObj {
     }
 }
 
+/// Vertex of `gen_textured_box_2d`
+#[repr(C)]
+#[derive(Clone, Copy, VertexLayout)]
+pub struct PosColorTexVertex {
+    #[vertex(location = 0)]
+    pub position: Vec3,
+    #[vertex(location = 1)]
+    pub color: Vec3,
+    #[vertex(location = 2)]
+    pub tex_coords: Vec2,
+}
+
 pub fn gen_textured_box_2d(gl: &GlFns) -> u32 {
     #[rustfmt::skip]
     const VERTICES: [f32; 32] = [
@@ -266,35 +257,36 @@ pub fn gen_textured_box_2d(gl: &GlFns) -> u32 {
         1, 2, 3, // second triangle
     ];
 
-    let mut vao = 0;
+    let mut vbo = 0;
+    let mut ebo = 0;
     unsafe {
-        gl.GenVertexArrays(1, &mut vao);
-        assert_ne!(vao, 0);
-        gl.BindVertexArray(vao);
-
-        let mut vbo = 0;
         gl.GenBuffers(1, &mut vbo);
         assert_ne!(vbo, 0);
+        gl.GenBuffers(1, &mut ebo);
+        assert_ne!(ebo, 0);
 
         gl.BindBuffer(gl33::GL_ARRAY_BUFFER, vbo);
-
-        gl_buffer_data_arr_stat(gl, &VERTICES);
-
-        let mut ebo = 0;
-        gl.GenBuffers(1, &mut ebo);
-        gl.BindBuffer(gl33::GL_ELEMENT_ARRAY_BUFFER, ebo);
     }
+    gl_buffer_data_arr_stat(gl, &VERTICES);
+
+    // the element buffer binding is part of the VAO, fill it once bound
+    let vao =
+        build_vao::<PosColorTexVertex>(gl, vbo, Some(ebo)).expect("failed gen_textured_box_2d");
     gl_buffer_data_element_stat(gl, &INDICES);
-    // position attribute
-    gl_vertex_attrib_ptr_enab(gl, 0, 3, 8, 0);
-    // color attribute
-    gl_vertex_attrib_ptr_enab(gl, 1, 3, 8, 3);
-    // texture coord attribute
-    gl_vertex_attrib_ptr_enab(gl, 2, 2, 8, 6);
 
     vao
 }
 
+/// Vertex of `gen_textured_box_3d`
+#[repr(C)]
+#[derive(Clone, Copy, VertexLayout)]
+pub struct PosTexVertex {
+    #[vertex(location = 0)]
+    pub position: Vec3,
+    #[vertex(location = 1)]
+    pub tex_coords: Vec2,
+}
+
 pub fn gen_textured_box_3d(gl: &GlFns) -> u32 {
     const VERTICES: [f32; 180] = [
         -0.5, -0.5, -0.5, 0.0, 0.0, 0.5, -0.5, -0.5, 1.0, 0.0, 0.5, 0.5, -0.5, 1.0, 1.0, 0.5, 0.5,
@@ -310,49 +302,43 @@ pub fn gen_textured_box_3d(gl: &GlFns) -> u32 {
         -0.5, 0.5, 0.5, 0.0, 0.0, -0.5, 0.5, -0.5, 0.0, 1.0,
     ];
 
+    let mut vbo = 0;
     unsafe {
-        let mut vao = 0;
-        gl.GenVertexArrays(1, &mut vao);
-        assert_ne!(vao, 0);
-        gl.BindVertexArray(vao);
-
-        let mut vbo = 0;
         gl.GenBuffers(1, &mut vbo);
         assert_ne!(vbo, 0);
 
         gl.BindBuffer(gl33::GL_ARRAY_BUFFER, vbo);
-
-        gl_buffer_data_arr_stat(gl, &VERTICES);
-        gl_vertex_attrib_ptr_enab(gl, 0, 3, 5, 0);
-        gl_vertex_attrib_ptr_enab(gl, 1, 2, 5, 3);
-
-        vao
     }
+    gl_buffer_data_arr_stat(gl, &VERTICES);
+
+    build_vao::<PosTexVertex>(gl, vbo, None).expect("failed gen_textured_box_3d")
 }
 
 // array format:
 // position.xyz normal.xyz tex_coords.xy
 pub fn setup_model_from_slice(d: &[f32]) -> Model {
-    let mut i = 0;
-    let mut model = Model::default();
-    model.meshes.push(Mesh::default());
-
-    loop {
-        let v = Vertex {
-            position: Vec3::new(d[i], d[i + 1], d[i + 2]),
-            normal: Vec3::new(d[i + 3], d[i + 4], d[i + 5]),
-            tex_coords: Vec2::new(d[i + 6], d[i + 7]),
-        };
-
-        model.meshes[0].vertices.push(v);
-        let vlen = model.meshes[0].vertices.len();
-        model.meshes[0].indices.push((vlen - 1) as u32);
-
-        i += 8;
-        if i >= d.len() {
-            break;
-        }
+    // the arrays repeat shared corners, index them once
+    let corners: Vec<&[f32]> = d.chunks_exact(8).collect();
+    let mut indices: Vec<u32> = (0..corners.len() as u32).collect();
+    let corners = optimize::deduplicate(&corners, &mut indices, |c| {
+        c.iter().map(|f| f.to_bits()).collect::<Vec<_>>()
+    });
+
+    let mut mesh = Mesh {
+        indices,
+        ..Default::default()
+    };
+    for c in corners {
+        mesh.vertices.push(Vertex {
+            position: Vec3::new(c[0], c[1], c[2]),
+            normal: Vec3::new(c[3], c[4], c[5]),
+            tex_coords: Vec2::new(c[6], c[7]),
+            ..Default::default()
+        });
     }
+    mesh.generate_tangents();
+    let mut model = Model::default();
+    model.meshes.push(mesh);
     model
 }
 
@@ -1,8 +1,10 @@
 use super::common::*;
 use crate::demos::Demo;
+use crate::gfx::vertex::{build_vao, VertexLayout};
 use crate::gfx::{glutils::*, shaders::Shaders, system};
 use gl33::*;
 use std::time::Instant;
+use ultraviolet::Vec3;
 
 pub struct Demo1 {
     pub name: &'static str,
@@ -101,14 +103,8 @@ fn prepare_triangle(gl: &GlFns) -> u32 {
     type Vertex = [f32; 3];
     const VERTICES: [Vertex; 3] = [[-0.9, -0.9, 0.0], [0.1, -0.9, 0.0], [-0.4, 0.1, 0.0]];
 
-    let mut vao = 0;
-
+    let mut vbo = 0;
     unsafe {
-        gl.GenVertexArrays(1, &mut vao);
-        assert_ne!(vao, 0);
-        gl.BindVertexArray(vao);
-
-        let mut vbo = 0;
         gl.GenBuffers(1, &mut vbo);
         assert_ne!(vbo, 0);
 
@@ -116,9 +112,7 @@ fn prepare_triangle(gl: &GlFns) -> u32 {
     }
 
     gl_buffer_data_arr_stat(gl, &VERTICES);
-    gl_vertex_attrib_ptr_enab(gl, 0, 3, 3, 0);
-
-    vao
+    build_vao::<PosVertex>(gl, vbo, None).expect("failed prepare_triangle")
 }
 
 fn prepare_rectangle(gl: &GlFns) -> u32 {
@@ -132,28 +126,31 @@ fn prepare_rectangle(gl: &GlFns) -> u32 {
 
     const INDICES: [u32; 6] = [0, 1, 3, 1, 2, 3];
 
+    let mut vbo = 0;
+    let mut ebo = 0;
     unsafe {
-        let mut vao = 0;
-        gl.GenVertexArrays(1, &mut vao);
-        assert_ne!(vao, 0);
-        gl.BindVertexArray(vao);
-
-        let mut vbo = 0;
         gl.GenBuffers(1, &mut vbo);
         assert_ne!(vbo, 0);
+        gl.GenBuffers(1, &mut ebo);
+        assert_ne!(ebo, 0);
 
         gl.BindBuffer(gl33::GL_ARRAY_BUFFER, vbo);
+    }
+    gl_buffer_data_arr_stat(gl, &VERTICES);
 
-        gl_buffer_data_arr_stat(gl, &VERTICES);
-
-        let mut ebo = 0;
-        gl.GenBuffers(1, &mut ebo);
-        gl.BindBuffer(gl33::GL_ELEMENT_ARRAY_BUFFER, ebo);
+    // the element buffer binding is part of the VAO, fill it once bound
+    let vao = build_vao::<PosVertex>(gl, vbo, Some(ebo)).expect("failed prepare_rectangle");
+    gl_buffer_data_element_stat(gl, &INDICES);
+    vao
+}
 
-        gl_buffer_data_element_stat(gl, &INDICES);
-        gl_vertex_attrib_ptr_enab(gl, 0, 3, 3, 0);
-        vao
-    }
+#[repr(C)]
+#[derive(Clone, Copy, VertexLayout)]
+struct PosColorVertex {
+    #[vertex(location = 0)]
+    position: Vec3,
+    #[vertex(location = 1)]
+    color: Vec3,
 }
 
 fn prepare_triangle_colored(gl: &GlFns) -> u32 {
@@ -167,24 +164,14 @@ fn prepare_triangle_colored(gl: &GlFns) -> u32 {
         0.0,    0.0,    0.1,
     ];
 
+    let mut vbo = 0;
     unsafe {
-        let mut vao = 0;
-        gl.GenVertexArrays(1, &mut vao);
-        assert_ne!(vao, 0);
-        gl.BindVertexArray(vao);
-
-        let mut vbo = 0;
         gl.GenBuffers(1, &mut vbo);
         assert_ne!(vbo, 0);
 
         gl.BindBuffer(gl33::GL_ARRAY_BUFFER, vbo);
-
-        gl_buffer_data_arr_stat(gl, &VERTICES);
-
-        gl_vertex_attrib_ptr_enab(gl, 0, 3, 6, 0);
-
-        gl_vertex_attrib_ptr_enab(gl, 1, 3, 6, 3);
-
-        vao
     }
+    gl_buffer_data_arr_stat(gl, &VERTICES);
+
+    build_vao::<PosColorVertex>(gl, vbo, None).expect("failed prepare_triangle_colored")
 }
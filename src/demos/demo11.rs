@@ -2,6 +2,7 @@ use super::common::*;
 use crate::demos::Demo;
 use crate::gfx::camera::{CamMovement, Camera};
 use crate::gfx::lights::{MaterialTex, MaterialTexMap, PointLight, VSMatrices};
+use crate::gfx::shader_library::ShaderLibrary;
 use crate::gfx::{glutils::*, shaders::Shaders, system, system::IoEvents, utils::*};
 use std::time::Instant;
 use ultraviolet::*;
@@ -50,10 +51,9 @@ const KEY_RIGHT: u8 = 0x08;
 
 pub struct DemoImpl {
     lamp_shader: Shaders,
-    cube_shader: Shaders,
+    box_shaders: ShaderLibrary,
     cube_mat: MaterialTex,
     light: PointLight,
-    cube_sm_shader: Shaders,
     cube_sm_mat: MaterialTexMap,
     cubes: NormTexCubeObj,
     mvp: VSMatrices,
@@ -69,10 +69,9 @@ impl DemoImpl {
     fn new() -> Self {
         DemoImpl {
             lamp_shader: Shaders::default(),
-            cube_shader: Shaders::default(),
+            box_shaders: ShaderLibrary::default(),
             cube_mat: MaterialTex::default(),
             light: PointLight::default(),
-            cube_sm_shader: Shaders::default(),
             cube_sm_mat: MaterialTexMap::default(),
             cubes: NormTexCubeObj::default(),
             mvp: VSMatrices::default(),
@@ -88,17 +87,11 @@ impl DemoImpl {
         self.lamp_shader =
             Shaders::from_files(&system.gl, "./demo/demo7_lig.vs", "./demo/demo7_lig.fs")?;
 
-        self.cube_shader = Shaders::from_files(
-            &system.gl,
-            "./demo/demo9_box.vs",
-            "./demo/demo11_box_point.fs",
-        )?;
-
-        self.cube_sm_shader = Shaders::from_files(
-            &system.gl,
-            "./demo/demo9_box.vs",
-            "./demo/demo11_box_specular_map_point.fs",
-        )?;
+        self.box_shaders = ShaderLibrary::new("./demo/demo9_box.vs", "./demo/demo11_box_point.fs");
+        // compile both variants up front, render fetches them from the cache
+        self.box_shaders.variant(&system.gl, &[])?;
+        self.box_shaders
+            .variant(&system.gl, &["HAS_SPECULAR_MAP"])?;
 
         self.texture = load_texture(&system.gl, "./demo/container2.png")?;
         self.texture_specular_map = load_texture(&system.gl, "./demo/container2_specular.png")?;
@@ -132,6 +125,8 @@ impl DemoImpl {
     }
 
     fn update_logic(&mut self, system: &system::System) -> Result<(), String> {
+        self.box_shaders.reload_if_changed(&system.gl);
+
         if self.timer.elapsed().as_millis() > 10 || self.first_logic_pass {
             self.first_logic_pass = false;
             self.timer = Instant::now();
@@ -198,11 +193,12 @@ impl DemoImpl {
 
     fn render(&mut self, system: &system::System) -> Result<(), String> {
         // draw the cube object
-        self.cube_shader.use_program(&system.gl);
-        self.cube_mat.pass_uniforms(&system.gl, &self.cube_shader);
-        self.light.pass_uniforms(&system.gl, &self.cube_shader);
+        let cube_shader = self.box_shaders.variant(&system.gl, &[])?;
+        cube_shader.use_program(&system.gl);
+        self.cube_mat.pass_uniforms(&system.gl, cube_shader);
+        self.light.pass_uniforms(&system.gl, cube_shader);
 
-        self.cube_shader.set_vec3(
+        cube_shader.set_vec3(
             &system.gl,
             "viewPos",
             self.camera.position.x,
@@ -212,21 +208,20 @@ impl DemoImpl {
 
         self.mvp.view = self.camera.get_view_matrix();
         self.mvp.model = Mat4::default();
-        self.mvp.pass_uniforms(&system.gl, &self.cube_shader);
+        self.mvp.pass_uniforms(&system.gl, cube_shader);
 
-        unsafe {
-            system.gl.ActiveTexture(gl33::GL_TEXTURE0);
-            system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.texture);
-        }
+        cube_shader.bind_texture(&system.gl, "material.diffuse", self.texture);
         self.cubes.draw(&system.gl, 0);
 
         // draw the cube object with specular map
-        self.cube_sm_shader.use_program(&system.gl);
-        self.cube_sm_mat
-            .pass_uniforms(&system.gl, &self.cube_sm_shader);
-        self.light.pass_uniforms(&system.gl, &self.cube_sm_shader);
-
-        self.cube_sm_shader.set_vec3(
+        let cube_sm_shader = self
+            .box_shaders
+            .variant(&system.gl, &["HAS_SPECULAR_MAP"])?;
+        cube_sm_shader.use_program(&system.gl);
+        self.cube_sm_mat.pass_uniforms(&system.gl, cube_sm_shader);
+        self.light.pass_uniforms(&system.gl, cube_sm_shader);
+
+        cube_sm_shader.set_vec3(
             &system.gl,
             "viewPos",
             self.camera.position.x,
@@ -236,16 +231,9 @@ impl DemoImpl {
 
         self.mvp.model = Mat4::default();
         self.mvp.model.translate(&Vec3::new(1.1, 0.0, 0.0));
-        self.mvp.pass_uniforms(&system.gl, &self.cube_sm_shader);
-        unsafe {
-            system.gl.ActiveTexture(gl33::GL_TEXTURE0);
-            system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.texture);
-
-            system.gl.ActiveTexture(gl33::GL_TEXTURE1);
-            system
-                .gl
-                .BindTexture(gl33::GL_TEXTURE_2D, self.texture_specular_map);
-        }
+        self.mvp.pass_uniforms(&system.gl, cube_sm_shader);
+        cube_sm_shader.bind_texture(&system.gl, "material.diffuse", self.texture);
+        cube_sm_shader.bind_texture(&system.gl, "material.specular", self.texture_specular_map);
         self.cubes.draw(&system.gl, 2);
 
         for i in 0..10 {
@@ -254,16 +242,9 @@ impl DemoImpl {
             self.mvp.model =
                 Mat4::from_rotation_around(Vec4::new(1.0, 0.3, 0.5, 0.0), angle.to_radians());
             self.mvp.model.translate(&Self::CUBE_POSITIONS[i]);
-            self.mvp.pass_uniforms(&system.gl, &self.cube_sm_shader);
-            unsafe {
-                system.gl.ActiveTexture(gl33::GL_TEXTURE0);
-                system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.texture);
-
-                system.gl.ActiveTexture(gl33::GL_TEXTURE1);
-                system
-                    .gl
-                    .BindTexture(gl33::GL_TEXTURE_2D, self.texture_specular_map);
-            }
+            self.mvp.pass_uniforms(&system.gl, cube_sm_shader);
+            cube_sm_shader.bind_texture(&system.gl, "material.diffuse", self.texture);
+            cube_sm_shader.bind_texture(&system.gl, "material.specular", self.texture_specular_map);
             self.cubes.draw(&system.gl, i + 3);
         }
 
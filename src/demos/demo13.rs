@@ -1,8 +1,13 @@
 use super::common::*;
 use crate::demos::Demo;
 use crate::gfx::camera::{CamMovement, Camera};
-use crate::gfx::lights::{DirLight, MaterialTexMap, PointLight, SpotLight, VSMatrices};
-use crate::gfx::{glutils::*, shaders::Shaders, system, system::IoEvents, utils::*};
+use crate::gfx::lights::{
+    CameraBlock, DirLight, LightsBlock, MaterialTexMap, PointLight, SpotLight, VSMatrices,
+    MAX_POINT_LIGHTS,
+};
+use crate::gfx::shader_library::ShaderLibrary;
+use crate::gfx::uniform_buffer::{UniformBuffer, CAMERA_BINDING, LIGHTS_BINDING};
+use crate::gfx::{glutils::*, system, system::IoEvents, utils::*};
 use gl33::GlFns;
 use std::time::Instant;
 use ultraviolet::*;
@@ -62,7 +67,7 @@ const CUBE_POSITIONS: [Vec3; 10] = [
     Vec3::new(-1.3, 1.0, -1.5),
 ];
 
-const POINT_LIGHT_POSITIONS: [Vec3; 4] = [
+const POINT_LIGHT_POSITIONS: [Vec3; MAX_POINT_LIGHTS] = [
     Vec3::new(0.7, 0.2, 2.0),
     Vec3::new(2.3, -3.3, -4.0),
     Vec3::new(-4.0, 2.0, -12.0),
@@ -72,7 +77,7 @@ const POINT_LIGHT_POSITIONS: [Vec3; 4] = [
 struct MultLights {
     material: MaterialTexMap,
     dir_light: DirLight,
-    point_lights: [PointLight; 4],
+    point_lights: [PointLight; MAX_POINT_LIGHTS],
     spot_light: SpotLight,
 }
 
@@ -148,23 +153,25 @@ impl MultLights {
         }
     }
 
-    fn pass_uniforms(&mut self, gl: &GlFns, shader: &Shaders, camera: &Camera) {
-        self.material.pass_uniforms(gl, shader);
-        self.dir_light.pass_uniforms(gl, shader);
-        self.point_lights[0].pass_uniforms(gl, shader);
-        self.point_lights[1].pass_uniforms(gl, shader);
-        self.point_lights[2].pass_uniforms(gl, shader);
-        self.point_lights[3].pass_uniforms(gl, shader);
-
+    // the flashlight follows the camera
+    fn block(&mut self, camera: &Camera) -> LightsBlock {
         self.spot_light.position = camera.position;
         self.spot_light.direction = camera.front;
-        self.spot_light.pass_uniforms(gl, shader);
+        LightsBlock {
+            dir_light: (&self.dir_light).into(),
+            point_lights: self.point_lights.each_ref().map(|l| l.into()),
+            spot_light: (&self.spot_light).into(),
+        }
     }
 }
 
 pub struct DemoImpl {
-    lamp_shader: Shaders,
-    cube_shader: Shaders,
+    lamp_shaders: ShaderLibrary,
+    cube_shaders: ShaderLibrary,
+    // both programs read the camera from the same uniform buffer
+    features: Vec<String>,
+    camera_ubo: UniformBuffer<CameraBlock>,
+    lights_ubo: UniformBuffer<LightsBlock>,
     lights: MultLights,
     cubes: NormTexCubeObj,
     mvp: VSMatrices,
@@ -179,8 +186,17 @@ pub struct DemoImpl {
 impl DemoImpl {
     fn new() -> Self {
         DemoImpl {
-            lamp_shader: Shaders::default(),
-            cube_shader: Shaders::default(),
+            lamp_shaders: ShaderLibrary::new("./demo/demo7_lig.vs", "./demo/demo7_lig.fs"),
+            cube_shaders: ShaderLibrary::new(
+                "./demo/demo9_box.vs",
+                "./demo/demo13_multiple_lights.fs",
+            ),
+            features: vec![
+                "CAMERA_BLOCK".to_string(),
+                format!("NUM_POINT_LIGHTS={}", MAX_POINT_LIGHTS),
+            ],
+            camera_ubo: UniformBuffer::default(),
+            lights_ubo: UniformBuffer::default(),
             lights: MultLights::new(),
             cubes: NormTexCubeObj::default(),
             mvp: VSMatrices::default(),
@@ -193,14 +209,9 @@ impl DemoImpl {
         }
     }
     fn init(&mut self, system: &system::System) -> Result<(), String> {
-        self.lamp_shader =
-            Shaders::from_files(&system.gl, "./demo/demo7_lig.vs", "./demo/demo7_lig.fs")?;
-
-        self.cube_shader = Shaders::from_files(
-            &system.gl,
-            "./demo/demo9_box.vs",
-            "./demo/demo13_multiple_lights.fs",
-        )?;
+        self.camera_ubo = UniformBuffer::new(&system.gl, CAMERA_BINDING)?;
+        self.lights_ubo = UniformBuffer::new(&system.gl, LIGHTS_BINDING)?;
+        self.bind_blocks(&system.gl)?;
 
         self.texture = load_texture(&system.gl, "./demo/container2.png")?;
         self.texture_specular_map = load_texture(&system.gl, "./demo/container2_specular.png")?;
@@ -219,6 +230,12 @@ impl DemoImpl {
             self.first_logic_pass = false;
             self.timer = Instant::now();
 
+            // edit demo13_multiple_lights.fs while the demo runs
+            let lamp_reloaded = self.lamp_shaders.reload_if_changed(&system.gl);
+            if self.cube_shaders.reload_if_changed(&system.gl) || lamp_reloaded {
+                self.bind_blocks(&system.gl)?;
+            }
+
             // process io
             for io in system.events.iter() {
                 match io {
@@ -269,18 +286,20 @@ impl DemoImpl {
 
     fn render(&mut self, system: &system::System) -> Result<(), String> {
         self.mvp.view = self.camera.get_view_matrix();
-        // draw the cube object with specular map
-        self.cube_shader.use_program(&system.gl);
-        self.lights
-            .pass_uniforms(&system.gl, &self.cube_shader, &self.camera);
-
-        self.cube_shader.set_vec3(
+        // camera and lights are uploaded once per frame for every program
+        self.camera_ubo.update(
             &system.gl,
-            "viewPos",
-            self.camera.position.x,
-            self.camera.position.y,
-            self.camera.position.z,
+            &CameraBlock::new(self.mvp.projection, self.mvp.view, self.camera.position),
         );
+        self.lights_ubo
+            .update(&system.gl, &self.lights.block(&self.camera));
+
+        let features: Vec<&str> = self.features.iter().map(String::as_str).collect();
+
+        // draw the cube object with specular map
+        let cube_shader = self.cube_shaders.variant(&system.gl, &features)?;
+        cube_shader.use_program(&system.gl);
+        self.lights.material.pass_uniforms(&system.gl, cube_shader);
 
         for (i, p) in CUBE_POSITIONS.iter().enumerate() {
             self.mvp.model = Mat4::default();
@@ -288,7 +307,7 @@ impl DemoImpl {
             self.mvp.model =
                 Mat4::from_rotation_around(Vec4::new(1.0, 0.3, 0.5, 0.0), angle.to_radians());
             self.mvp.model.translate(p);
-            self.mvp.pass_uniforms(&system.gl, &self.cube_shader);
+            cube_shader.set(&system.gl, "model", &self.mvp.model);
             unsafe {
                 system.gl.ActiveTexture(gl33::GL_TEXTURE0);
                 system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.texture);
@@ -302,18 +321,29 @@ impl DemoImpl {
         }
 
         // draw the lamp objects
-        self.lamp_shader.use_program(&system.gl);
+        let lamp_shader = self.lamp_shaders.variant(&system.gl, &features)?;
+        lamp_shader.use_program(&system.gl);
         for p in POINT_LIGHT_POSITIONS {
             self.mvp.model = Mat4::default();
             self.mvp.model.translate(&p);
             self.mvp.model = self.mvp.model * Mat4::from_scale(0.2);
-            self.mvp.pass_uniforms(&system.gl, &self.lamp_shader);
+            lamp_shader.set(&system.gl, "model", &self.mvp.model);
             self.cubes.draw(&system.gl, 0);
         }
 
         Ok(())
     }
 
+    /// Also compiles the variants on the first call
+    fn bind_blocks(&mut self, gl: &GlFns) -> Result<(), String> {
+        let features: Vec<&str> = self.features.iter().map(String::as_str).collect();
+        let lamp_shader = self.lamp_shaders.variant(gl, &features)?;
+        self.camera_ubo.bind_block(gl, lamp_shader, "Camera")?;
+        let cube_shader = self.cube_shaders.variant(gl, &features)?;
+        self.camera_ubo.bind_block(gl, cube_shader, "Camera")?;
+        self.lights_ubo.bind_block(gl, cube_shader, "Lights")
+    }
+
     fn build_projection_matrix(&mut self, system: &system::System, fov_rad: f32) {
         self.mvp.projection = projection::rh_yup::perspective_gl(
             fov_rad,
@@ -3,6 +3,8 @@ use crate::demos::Demo;
 use crate::gfx::camera::{CamMovement, Camera};
 use crate::gfx::lights::VSMatrices;
 use crate::gfx::models::*;
+use crate::gfx::primitives;
+use crate::gfx::shader_library::parse_features;
 use crate::gfx::shaders::*;
 use crate::gfx::{system, system::IoEvents, utils::*};
 use std::time::Instant;
@@ -80,24 +82,45 @@ impl DemoImpl {
     }
     fn init(&mut self, system: &system::System) -> Result<(), String> {
         self.build_projection_matrix(system, 45.0f32.to_radians());
-        self.camera.position.z += 7.0;
         self.camera.mouse_sensitivity = 0.1;
 
-        self.model = ModelWrapT::Some(Box::new(setup_model_box(DEFAULT_POS_NORM_TEX_CUBE_VERT)));
-        self.backpack = ModelWrapT::Some(Box::new(
-            Model::from(&system.gl, "./demo/backpack/backpack.obj").unwrap(),
-        ));
+        self.model = ModelWrapT::Some(Box::new(primitives::cube(1.0, 1)));
+        self.backpack = ModelWrapT::Some(Box::new(Model::from(
+            &system.gl,
+            "./demo/backpack/backpack.obj",
+        )?));
 
         self.model.as_mut().unwrap().setup(&system.gl)?;
         self.backpack.as_mut().unwrap().setup(&system.gl)?;
+        // whatever the model's size, start with all of it in view
+        self.camera.frame(
+            &self.backpack.as_ref().unwrap().sphere,
+            45.0f32.to_radians(),
+            (system.w as f32) / (system.h as f32),
+        );
 
         self.shader =
             Shaders::from_files(&system.gl, "./demo/demo7_lig.vs", "./demo/demo7_lig.fs")?;
 
-        self.model_shader = Shaders::from_files(
+        // the backpack ships a normal map, shade without it if it fails to load
+        let has_normal_map = self
+            .backpack
+            .as_ref()
+            .unwrap()
+            .meshes
+            .iter()
+            .any(|m| m.textures.iter().any(|t| t.tex_type == TexType::Normal));
+        let features: &[&str] = if has_normal_map {
+            &["HAS_NORMAL_MAP"]
+        } else {
+            &[]
+        };
+        self.model_shader = Shaders::from_files_with_defines(
             &system.gl,
             "./demo/demo14_model.vs",
             "./demo/demo14_model.fs",
+            "",
+            &parse_features(features)?,
         )?;
 
         Ok(())
@@ -162,16 +185,23 @@ impl DemoImpl {
         self.shader.use_program(&system.gl);
         self.mvp.model.translate(&Vec3::new(0.0, 0.0, -5.0));
         self.mvp.pass_uniforms(&system.gl, &self.shader);
-        self.model.as_mut().unwrap().draw(&system.gl, &self.shader);
+        self.model
+            .as_ref()
+            .unwrap()
+            .draw_at(&system.gl, &self.shader, &self.mvp.model);
 
         self.model_shader.use_program(&system.gl);
         self.mvp.model = Mat4::default();
         self.mvp.pass_uniforms(&system.gl, &self.model_shader);
+        self.model_shader
+            .set(&system.gl, "lightDir", &Vec3::new(-0.3, -0.5, -1.0));
+        self.model_shader
+            .set(&system.gl, "viewPos", &self.camera.position);
 
         self.backpack
-            .as_mut()
+            .as_ref()
             .unwrap()
-            .draw(&system.gl, &self.model_shader);
+            .draw_at(&system.gl, &self.model_shader, &self.mvp.model);
 
         Ok(())
     }
@@ -1,10 +1,11 @@
 use super::common::*;
 use crate::demos::Demo;
+use crate::gfx::assets::{AssetManager, ShadersHandle, TextureHandle};
 use crate::gfx::camera::{CamMovement, Camera};
 use crate::gfx::lights::VSMatrices;
 use crate::gfx::models::*;
-use crate::gfx::shaders::*;
-use crate::gfx::{glutils::*, system, system::IoEvents, utils::*};
+use crate::gfx::primitives;
+use crate::gfx::{system, system::IoEvents, utils::*};
 use std::time::Instant;
 use ultraviolet::*;
 
@@ -41,6 +42,7 @@ impl DemoN {
                 system.draw_to_screen();
             }
         }
+        demo.release(&system.gl);
         Ok(())
     }
 }
@@ -61,16 +63,17 @@ type ModelWrapT = Option<Box<Model>>;
 pub struct DemoImpl {
     mvp: VSMatrices,
     obj_cube: ModelWrapT,
-    tex_cube: u32,
+    tex_cube: TextureHandle,
     obj_plane: ModelWrapT,
-    tex_plane: u32,
+    tex_plane: TextureHandle,
     obj_grass: ModelWrapT,
-    tex_grass: u32,
+    tex_grass: TextureHandle,
     obj_transparent: ModelWrapT,
-    tex_transparent: u32,
-    shader: Shaders,
-    stencil_shader: Shaders,
-    discard_shader: Shaders,
+    tex_transparent: TextureHandle,
+    shader: ShadersHandle,
+    stencil_shader: ShadersHandle,
+    discard_shader: ShadersHandle,
+    assets: AssetManager,
     timer: Instant,
     first_logic_pass: bool,
     camera: Camera,
@@ -122,16 +125,17 @@ impl DemoImpl {
         DemoImpl {
             mvp: VSMatrices::default(),
             obj_cube: ModelWrapT::None,
-            tex_cube: 0,
+            tex_cube: Default::default(),
             obj_plane: ModelWrapT::None,
-            tex_plane: 0,
+            tex_plane: Default::default(),
             obj_grass: ModelWrapT::None,
-            tex_grass: 0,
+            tex_grass: Default::default(),
             obj_transparent: ModelWrapT::None,
-            tex_transparent: 0,
-            shader: Shaders::default(),
-            stencil_shader: Shaders::default(),
-            discard_shader: Shaders::default(),
+            tex_transparent: Default::default(),
+            shader: Default::default(),
+            stencil_shader: Default::default(),
+            discard_shader: Default::default(),
+            assets: AssetManager::new(),
             timer: Instant::now(),
             first_logic_pass: true,
             camera: Camera::new(),
@@ -155,7 +159,7 @@ impl DemoImpl {
         self.camera.position.z += 7.0;
         self.camera.mouse_sensitivity = 0.1;
 
-        self.obj_cube = ModelWrapT::Some(Box::new(setup_model_box(DEFAULT_POS_NORM_TEX_CUBE_VERT)));
+        self.obj_cube = ModelWrapT::Some(Box::new(primitives::cube(1.0, 1)));
         self.obj_plane = ModelWrapT::Some(Box::new(setup_model_plane(DEFAULT_PLANE)));
         self.obj_grass = ModelWrapT::Some(Box::new(setup_model_plane(GRASS_QUAD)));
         self.obj_transparent = ModelWrapT::Some(Box::new(setup_model_plane(TRANSPARENT_QUAD)));
@@ -165,8 +169,8 @@ impl DemoImpl {
         self.obj_grass.as_mut().unwrap().setup(&system.gl)?;
         self.obj_transparent.as_mut().unwrap().setup(&system.gl)?;
 
-        self.tex_cube = load_texture(&system.gl, "./demo/marble.jpg")?;
-        self.tex_plane = load_texture(&system.gl, "./demo/metal.png")?;
+        self.tex_cube = self.assets.texture(&system.gl, "./demo/marble.jpg")?;
+        self.tex_plane = self.assets.texture(&system.gl, "./demo/metal.png")?;
         use gl33::*;
         let params = [
             (GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE),
@@ -174,14 +178,22 @@ impl DemoImpl {
             (GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR),
             (GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR),
         ];
-        self.tex_grass = load_texture_params(&system.gl, "./demo/grass.png", &params)?;
-        self.tex_transparent = load_texture_params(&system.gl, "./demo/window.png", &params)?;
-
-        self.shader = Shaders::from_files(&system.gl, "./demo/demo15.vs", "./demo/demo15.fs")?;
+        self.tex_grass = self
+            .assets
+            .texture_params(&system.gl, "./demo/grass.png", &params)?;
+        self.tex_transparent =
+            self.assets
+                .texture_params(&system.gl, "./demo/window.png", &params)?;
+
+        self.shader = self
+            .assets
+            .shaders(&system.gl, "./demo/demo15.vs", "./demo/demo15.fs")?;
         self.stencil_shader =
-            Shaders::from_files(&system.gl, "./demo/demo15.vs", "./demo/demo15_monocol.fs")?;
+            self.assets
+                .shaders(&system.gl, "./demo/demo15.vs", "./demo/demo15_monocol.fs")?;
         self.discard_shader =
-            Shaders::from_files(&system.gl, "./demo/demo15.vs", "./demo/demo15_discard.fs")?;
+            self.assets
+                .shaders(&system.gl, "./demo/demo15.vs", "./demo/demo15_discard.fs")?;
 
         Ok(())
     }
@@ -191,6 +203,9 @@ impl DemoImpl {
             self.first_logic_pass = false;
             self.timer = Instant::now();
 
+            // edit the demo15 shaders while the demo runs
+            self.assets.reload_changed_shaders(&system.gl);
+
             self.process_io(system);
         }
 
@@ -336,9 +351,25 @@ impl DemoImpl {
             self.draw_window(&system.gl);
         }
 
+        // free what the handles dropped this frame left behind
+        self.assets.collect_garbage(&system.gl);
+
         Ok(())
     }
 
+    /// Drops the asset handles and frees their GPU objects, the context is
+    /// still current
+    fn release(&mut self, gl: &gl33::GlFns) {
+        self.tex_cube = Default::default();
+        self.tex_plane = Default::default();
+        self.tex_grass = Default::default();
+        self.tex_transparent = Default::default();
+        self.shader = Default::default();
+        self.stencil_shader = Default::default();
+        self.discard_shader = Default::default();
+        self.assets.collect_garbage(gl);
+    }
+
     fn build_projection_matrix(&mut self, system: &system::System, fov_rad: f32) {
         self.mvp.projection = projection::rh_yup::perspective_gl(
             fov_rad,
@@ -352,60 +383,69 @@ impl DemoImpl {
         self.shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_plane);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_plane.id);
         }
         self.mvp.pass_uniforms(gl, &self.shader);
 
-        self.obj_plane.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_plane
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 
     fn draw_cube(&mut self, gl: &gl33::GlFns) {
         self.shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_cube);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_cube.id);
         }
 
         self.mvp.pass_uniforms(gl, &self.shader);
-        self.obj_cube.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_cube
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 
     fn draw_cube_st_eff(&mut self, gl: &gl33::GlFns) {
         self.stencil_shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_cube);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_cube.id);
         }
 
         self.mvp.pass_uniforms(gl, &self.stencil_shader);
         self.obj_cube
-            .as_mut()
+            .as_ref()
             .unwrap()
-            .draw(gl, &self.stencil_shader);
+            .draw_at(gl, &self.stencil_shader, &self.mvp.model);
     }
 
     fn draw_grass(&mut self, gl: &gl33::GlFns) {
         self.discard_shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_grass);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_grass.id);
         }
 
         self.mvp.pass_uniforms(gl, &self.discard_shader);
         self.obj_grass
-            .as_mut()
+            .as_ref()
             .unwrap()
-            .draw(gl, &self.discard_shader);
+            .draw_at(gl, &self.discard_shader, &self.mvp.model);
     }
 
     fn draw_window(&mut self, gl: &gl33::GlFns) {
         self.shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_transparent);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_transparent.id);
         }
 
         self.mvp.pass_uniforms(gl, &self.shader);
-        self.obj_grass.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_grass
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 }
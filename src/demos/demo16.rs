@@ -1,10 +1,12 @@
 use super::common::*;
 use crate::demos::Demo;
+use crate::gfx::assets::{AssetManager, ShadersHandle, TextureHandle};
 use crate::gfx::camera::{CamMovement, Camera};
 use crate::gfx::lights::VSMatrices;
 use crate::gfx::models::*;
+use crate::gfx::primitives;
 use crate::gfx::shaders::*;
-use crate::gfx::{framebuffer::*, glutils::*, system, system::IoEvents, utils::*};
+use crate::gfx::{framebuffer::*, system, system::IoEvents, utils::*};
 use std::time::Instant;
 use ultraviolet::*;
 
@@ -41,6 +43,7 @@ impl DemoN {
                 system.draw_to_screen();
             }
         }
+        demo.release(&system.gl);
         Ok(())
     }
 }
@@ -249,17 +252,31 @@ void main()
 }
 ";
 
+pub(super) const INLINE_SHADERS: [(&str, &str, &str); 7] = [
+    ("demo16::VERTEX_CODE", "vs", VERTEX_CODE),
+    ("demo16::FRAGMENT_CODE", "fs", FRAGMENT_CODE),
+    ("demo16::FRAGMENT_CODE_INV", "fs", FRAGMENT_CODE_INV),
+    (
+        "demo16::FRAGMENT_CODE_GREYSCALE",
+        "fs",
+        FRAGMENT_CODE_GREYSCALE,
+    ),
+    ("demo16::FRAGMENT_CODE_KERN1", "fs", FRAGMENT_CODE_KERN1),
+    ("demo16::FRAGMENT_CODE_KERN2", "fs", FRAGMENT_CODE_KERN2),
+    ("demo16::FRAGMENT_CODE_KERN3", "fs", FRAGMENT_CODE_KERN3),
+];
+
 pub struct DemoImpl {
     mvp: VSMatrices,
     mvp2: VSMatrices,
     obj_cube: ModelWrapT,
-    tex_cube: u32,
+    tex_cube: TextureHandle,
     obj_plane: ModelWrapT,
     obj_plane2: ModelWrapT,
-    tex_plane: u32,
+    tex_plane: TextureHandle,
     tex_plane_angle: f32,
     angle_dx: f32,
-    shader: Shaders,
+    shader: ShadersHandle,
     quad_shaders: Vec<Shaders>,
     shader_cnt: u32,
     frame_buffer: FrameBuffer,
@@ -267,6 +284,7 @@ pub struct DemoImpl {
     first_logic_pass: bool,
     camera: Camera,
     io_flags: BitFields<u16>,
+    assets: AssetManager,
 }
 
 impl DemoImpl {
@@ -275,13 +293,13 @@ impl DemoImpl {
             mvp: VSMatrices::default(),
             mvp2: VSMatrices::default(),
             obj_cube: ModelWrapT::None,
-            tex_cube: 0,
+            tex_cube: Default::default(),
             obj_plane: ModelWrapT::None,
             obj_plane2: ModelWrapT::None,
-            tex_plane: 0,
+            tex_plane: Default::default(),
             tex_plane_angle: 45.0,
             angle_dx: 1.0,
-            shader: Shaders::default(),
+            shader: Default::default(),
             quad_shaders: Default::default(),
             shader_cnt: 0,
             frame_buffer: Default::default(),
@@ -289,6 +307,7 @@ impl DemoImpl {
             first_logic_pass: true,
             camera: Camera::new(),
             io_flags: BitFields::<u16>::default(),
+            assets: AssetManager::new(),
         }
     }
 
@@ -297,7 +316,7 @@ impl DemoImpl {
         self.camera.position.z += 7.0;
         self.camera.mouse_sensitivity = 0.1;
 
-        self.obj_cube = ModelWrapT::Some(Box::new(setup_model_box(DEFAULT_POS_NORM_TEX_CUBE_VERT)));
+        self.obj_cube = ModelWrapT::Some(Box::new(primitives::cube(1.0, 1)));
         self.obj_plane = ModelWrapT::Some(Box::new(setup_model_plane(DEFAULT_PLANE)));
         self.obj_plane2 = ModelWrapT::Some(Box::new(setup_model_plane(DEFAULT_PLANE2)));
 
@@ -305,10 +324,12 @@ impl DemoImpl {
         self.obj_plane.as_mut().unwrap().setup(&system.gl)?;
         self.obj_plane2.as_mut().unwrap().setup(&system.gl)?;
 
-        self.tex_cube = load_texture(&system.gl, "./demo/marble.jpg")?;
-        self.tex_plane = load_texture(&system.gl, "./demo/metal.png")?;
+        self.tex_cube = self.assets.texture(&system.gl, "./demo/marble.jpg")?;
+        self.tex_plane = self.assets.texture(&system.gl, "./demo/metal.png")?;
 
-        self.shader = Shaders::from_files(&system.gl, "./demo/demo15.vs", "./demo/demo15.fs")?;
+        self.shader = self
+            .assets
+            .shaders(&system.gl, "./demo/demo15.vs", "./demo/demo15.fs")?;
 
         self.quad_shaders
             .push(Shaders::from_str(&system.gl, VERTEX_CODE, FRAGMENT_CODE)?);
@@ -345,6 +366,9 @@ impl DemoImpl {
             self.first_logic_pass = false;
             self.timer = Instant::now();
 
+            // edit the demo15 shaders while the demo runs
+            self.assets.reload_changed_shaders(&system.gl);
+
             self.process_io(system);
 
             self.tex_plane_angle += self.angle_dx;
@@ -479,9 +503,21 @@ impl DemoImpl {
         self.mvp2.model.translate(&Vec3::new(0.0, 0.0, dz));
         self.draw_plane_from_fb_tex(&system.gl);
 
+        // free what the handles dropped this frame left behind
+        self.assets.collect_garbage(&system.gl);
+
         Ok(())
     }
 
+    /// Drops the asset handles and frees their GPU objects, the context is
+    /// still current
+    fn release(&mut self, gl: &gl33::GlFns) {
+        self.tex_cube = Default::default();
+        self.tex_plane = Default::default();
+        self.shader = Default::default();
+        self.assets.collect_garbage(gl);
+    }
+
     fn build_projection_matrix(&mut self, system: &system::System, fov_rad: f32) {
         self.mvp.projection = projection::rh_yup::perspective_gl(
             fov_rad,
@@ -502,22 +538,28 @@ impl DemoImpl {
         self.shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_plane);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_plane.id);
         }
         self.mvp.pass_uniforms(gl, &self.shader);
 
-        self.obj_plane.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_plane
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 
     fn draw_cube(&mut self, gl: &gl33::GlFns) {
         self.shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_cube);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_cube.id);
         }
 
         self.mvp.pass_uniforms(gl, &self.shader);
-        self.obj_cube.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_cube
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 
     fn draw_plane_from_fb_tex(&mut self, gl: &gl33::GlFns) {
@@ -530,9 +572,10 @@ impl DemoImpl {
         self.mvp2
             .pass_uniforms(gl, &self.quad_shaders[shader_i as usize]);
 
-        self.obj_plane2
-            .as_mut()
-            .unwrap()
-            .draw(gl, &self.quad_shaders[shader_i as usize]);
+        self.obj_plane2.as_ref().unwrap().draw_at(
+            gl,
+            &self.quad_shaders[shader_i as usize],
+            &self.mvp2.model,
+        );
     }
 }
@@ -255,6 +255,14 @@ void main()
 }
 ";
 
+pub(super) const INLINE_SHADERS: [(&str, &str, &str); 5] = [
+    ("demo17::SKYBOX_VS", "vs", SKYBOX_VS),
+    ("demo17::SKYBOX_FS", "fs", SKYBOX_FS),
+    ("demo17::REFL_VS", "vs", REFL_VS),
+    ("demo17::REFL_FS", "fs", REFL_FS),
+    ("demo17::REFR_FS", "fs", REFR_FS),
+];
+
 impl DemoImpl {
     fn new() -> Self {
         DemoImpl {
@@ -574,7 +582,10 @@ impl DemoImpl {
         }
         self.mvp.pass_uniforms(gl, &self.shader);
 
-        self.obj_plane.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_plane
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 
     fn draw_cube_refl(&mut self, gl: &gl33::GlFns) {
@@ -621,7 +632,10 @@ impl DemoImpl {
         }
 
         self.mvp.pass_uniforms(gl, &self.shader);
-        self.obj_cube.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_cube
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 
     fn draw_cube_st_eff(&mut self, gl: &gl33::GlFns) {
@@ -633,9 +647,9 @@ impl DemoImpl {
 
         self.mvp.pass_uniforms(gl, &self.stencil_shader);
         self.obj_cube
-            .as_mut()
+            .as_ref()
             .unwrap()
-            .draw(gl, &self.stencil_shader);
+            .draw_at(gl, &self.stencil_shader, &self.mvp.model);
     }
 
     fn draw_grass(&mut self, gl: &gl33::GlFns) {
@@ -647,9 +661,9 @@ impl DemoImpl {
 
         self.mvp.pass_uniforms(gl, &self.discard_shader);
         self.obj_grass
-            .as_mut()
+            .as_ref()
             .unwrap()
-            .draw(gl, &self.discard_shader);
+            .draw_at(gl, &self.discard_shader, &self.mvp.model);
     }
 
     fn draw_window(&mut self, gl: &gl33::GlFns) {
@@ -660,6 +674,9 @@ impl DemoImpl {
         }
 
         self.mvp.pass_uniforms(gl, &self.shader);
-        self.obj_grass.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_grass
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 }
@@ -1,10 +1,12 @@
 use super::common::*;
 use crate::demos::Demo;
+use crate::gfx::assets::{AssetManager, ModelHandle, ShadersHandle, TextureHandle};
 use crate::gfx::camera::{CamMovement, Camera};
 use crate::gfx::lights::VSMatrices;
 use crate::gfx::models::*;
+use crate::gfx::primitives;
 use crate::gfx::shaders::*;
-use crate::gfx::{glutils::*, system, system::IoEvents, utils::*};
+use crate::gfx::{system, system::IoEvents, utils::*};
 use std::f32::consts;
 use std::time::Instant;
 use ultraviolet::*;
@@ -42,6 +44,7 @@ impl DemoN {
                 system.draw_to_screen();
             }
         }
+        demo.release(&system.gl);
         Ok(())
     }
 }
@@ -62,11 +65,11 @@ type ModelWrapT = Option<Box<Model>>;
 pub struct DemoImpl {
     mvp: VSMatrices,
     obj_cube: ModelWrapT,
-    tex_cube: u32,
+    tex_cube: TextureHandle,
     obj_plane: ModelWrapT,
-    tex_plane: u32,
-    shader: Shaders,
-    backpack: ModelWrapT,
+    tex_plane: TextureHandle,
+    shader: ShadersHandle,
+    backpack: ModelHandle,
     model_shader_explode: Shaders,
     model_shader_normals: Shaders,
     shader_tick: f32,
@@ -74,6 +77,7 @@ pub struct DemoImpl {
     first_logic_pass: bool,
     camera: Camera,
     io_flags: BitFields<u16>,
+    assets: AssetManager,
 }
 
 impl DemoImpl {
@@ -81,14 +85,15 @@ impl DemoImpl {
         DemoImpl {
             mvp: VSMatrices::default(),
             obj_cube: ModelWrapT::None,
-            tex_cube: 0,
+            tex_cube: Default::default(),
             obj_plane: ModelWrapT::None,
-            tex_plane: 0,
-            backpack: ModelWrapT::None,
+            tex_plane: Default::default(),
+            backpack: Default::default(),
             model_shader_explode: Default::default(),
             model_shader_normals: Default::default(),
             shader_tick: consts::PI / 2.0,
-            shader: Shaders::default(),
+            shader: Default::default(),
+            assets: AssetManager::new(),
             timer: Instant::now(),
             first_logic_pass: true,
             camera: Camera::new(),
@@ -101,19 +106,18 @@ impl DemoImpl {
         self.camera.position.z += 7.0;
         self.camera.mouse_sensitivity = 0.1;
 
-        self.obj_cube = ModelWrapT::Some(Box::new(setup_model_box(DEFAULT_POS_NORM_TEX_CUBE_VERT)));
+        self.obj_cube = ModelWrapT::Some(Box::new(primitives::cube(1.0, 1)));
         self.obj_plane = ModelWrapT::Some(Box::new(setup_model_plane(DEFAULT_PLANE)));
 
         self.obj_cube.as_mut().unwrap().setup(&system.gl)?;
         self.obj_plane.as_mut().unwrap().setup(&system.gl)?;
 
-        self.tex_cube = load_texture(&system.gl, "./demo/marble.jpg")?;
-        self.tex_plane = load_texture(&system.gl, "./demo/metal.png")?;
+        self.tex_cube = self.assets.texture(&system.gl, "./demo/marble.jpg")?;
+        self.tex_plane = self.assets.texture(&system.gl, "./demo/metal.png")?;
 
-        self.backpack = ModelWrapT::Some(Box::new(
-            Model::from(&system.gl, "./demo/backpack/backpack.obj").unwrap(),
-        ));
-        self.backpack.as_mut().unwrap().setup(&system.gl)?;
+        self.backpack = self
+            .assets
+            .model(&system.gl, "./demo/backpack/backpack.obj")?;
         self.model_shader_explode = Shaders::from_files_full(
             &system.gl,
             "./demo/demo18_geom.vs",
@@ -128,7 +132,9 @@ impl DemoImpl {
             "./demo/demo18_norm.gs",
         )?;
 
-        self.shader = Shaders::from_files(&system.gl, "./demo/demo15.vs", "./demo/demo15.fs")?;
+        self.shader = self
+            .assets
+            .shaders(&system.gl, "./demo/demo15.vs", "./demo/demo15.fs")?;
         Ok(())
     }
 
@@ -136,6 +142,9 @@ impl DemoImpl {
         if self.timer.elapsed().as_millis() > 10 || self.first_logic_pass {
             self.first_logic_pass = false;
             self.timer = Instant::now();
+
+            // edit the demo15 shaders while the demo runs
+            self.assets.reload_changed_shaders(&system.gl);
             self.shader_tick += 0.1;
 
             self.process_io(system);
@@ -244,9 +253,7 @@ impl DemoImpl {
             .pass_uniforms(&system.gl, &self.model_shader_explode);
 
         self.backpack
-            .as_mut()
-            .unwrap()
-            .draw(&system.gl, &self.model_shader_explode);
+            .draw_at(&system.gl, &self.model_shader_explode, &self.mvp.model);
 
         // Draw backpack with normals
         self.mvp.model = Mat4::default();
@@ -254,21 +261,30 @@ impl DemoImpl {
         self.shader.use_program(&system.gl);
         self.mvp.pass_uniforms(&system.gl, &self.shader);
         self.backpack
-            .as_mut()
-            .unwrap()
-            .draw(&system.gl, &self.shader);
+            .draw_at(&system.gl, &self.shader, &self.mvp.model);
         // draw normals
         self.model_shader_normals.use_program(&system.gl);
         self.mvp
             .pass_uniforms(&system.gl, &self.model_shader_normals);
         self.backpack
-            .as_mut()
-            .unwrap()
-            .draw(&system.gl, &self.model_shader_normals);
+            .draw_at(&system.gl, &self.model_shader_normals, &self.mvp.model);
+
+        // free what the handles dropped this frame left behind
+        self.assets.collect_garbage(&system.gl);
 
         Ok(())
     }
 
+    /// Drops the asset handles and frees their GPU objects, the context is
+    /// still current
+    fn release(&mut self, gl: &gl33::GlFns) {
+        self.tex_cube = Default::default();
+        self.tex_plane = Default::default();
+        self.shader = Default::default();
+        self.backpack = Default::default();
+        self.assets.collect_garbage(gl);
+    }
+
     fn build_projection_matrix(&mut self, system: &system::System, fov_rad: f32) {
         self.mvp.projection = projection::rh_yup::perspective_gl(
             fov_rad,
@@ -282,21 +298,27 @@ impl DemoImpl {
         self.shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_plane);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_plane.id);
         }
         self.mvp.pass_uniforms(gl, &self.shader);
 
-        self.obj_plane.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_plane
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 
     fn draw_cube(&mut self, gl: &gl33::GlFns) {
         self.shader.use_program(gl);
         unsafe {
             gl.ActiveTexture(gl33::GL_TEXTURE0);
-            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_cube);
+            gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_cube.id);
         }
 
         self.mvp.pass_uniforms(gl, &self.shader);
-        self.obj_cube.as_mut().unwrap().draw(gl, &self.shader);
+        self.obj_cube
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.shader, &self.mvp.model);
     }
 }
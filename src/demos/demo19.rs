@@ -5,6 +5,7 @@ use crate::gfx::lights::VSMatrices;
 use crate::gfx::models::Model;
 use crate::gfx::shaders::Shaders;
 use crate::gfx::system;
+use crate::gfx::vertex::{bind_attributes, VertexLayout};
 use gl33::*;
 use rand::Rng;
 use std::time::Instant;
@@ -49,6 +50,14 @@ impl DemoN {
 
 type ModelWrapT = Option<Box<Model>>;
 
+/// Per instance data of the rocks, after the vertex attributes of `Mesh`
+#[repr(C)]
+#[derive(Clone, Copy, VertexLayout)]
+struct RockInstance {
+    #[vertex(location = 8)]
+    model: Mat4,
+}
+
 pub struct DemoImpl {
     mvp: VSMatrices,
     inputs: usr_inputs::Io,
@@ -61,7 +70,7 @@ pub struct DemoImpl {
     planet: ModelWrapT,
     planet_shader: Shaders,
     // asteroids
-    asteroids: Vec<Mat4>,
+    asteroids: Vec<RockInstance>,
 }
 
 impl DemoImpl {
@@ -89,6 +98,12 @@ impl DemoImpl {
 
         // load objects
         self.rock = ModelWrapT::Some(Box::new(Model::from(&system.gl, "./demo/rock/rock.obj")?));
+        // every rock is drawn 100,000 times, it pays to optimize its mesh
+        let (before, after) = self.rock.as_mut().unwrap().optimize();
+        println!(
+            "optimized the rock mesh\n  before: {}\n  after:  {}",
+            before, after
+        );
         self.rock.as_mut().unwrap().setup(&system.gl)?;
         self.planet = ModelWrapT::Some(Box::new(Model::from(
             &system.gl,
@@ -100,7 +115,7 @@ impl DemoImpl {
         self.planet_shader = Shaders::from_str(&system.gl, PLANET_VS, PLANET_FS)?;
 
         self.gen_asteroids(100_000);
-        self.init_rocks(&system.gl);
+        self.init_rocks(&system.gl)?;
 
         Ok(())
     }
@@ -132,7 +147,10 @@ impl DemoImpl {
         self.planet_shader.use_program(gl);
         self.mvp.pass_uniforms(gl, &self.planet_shader);
 
-        self.planet.as_mut().unwrap().draw(gl, &self.planet_shader);
+        self.planet
+            .as_ref()
+            .unwrap()
+            .draw_at(gl, &self.planet_shader, &self.mvp.model);
     }
 
     fn draw_asteroids(&mut self, gl: &gl33::GlFns) {
@@ -147,7 +165,7 @@ impl DemoImpl {
                 gl.DrawElementsInstanced(
                     GL_TRIANGLES,
                     mesh.indices.len() as i32,
-                    GL_UNSIGNED_INT,
+                    mesh.index_type(),
                     std::ptr::null(),
                     100_000,
                 );
@@ -181,75 +199,29 @@ impl DemoImpl {
             model = model * Mat4::from_scale(scale);
             model = model * Mat4::from_rotation_around(Vec4::new(0.4, 0.6, 0.8, 1.0), rot_angle);
 
-            self.asteroids.push(model);
+            self.asteroids.push(RockInstance { model });
         }
     }
 
-    fn init_rocks(&mut self, gl: &GlFns) {
+    fn init_rocks(&mut self, gl: &GlFns) -> Result<(), String> {
         let mut buffer = 0;
         unsafe {
             gl.GenBuffers(1, &mut buffer);
             gl.BindBuffer(GL_ARRAY_BUFFER, buffer);
             gl.BufferData(
                 GL_ARRAY_BUFFER,
-                (std::mem::size_of::<Mat4>() * self.asteroids.len()) as isize,
+                std::mem::size_of_val(self.asteroids.as_slice()) as isize,
                 self.asteroids.as_ptr().cast(),
                 GL_STATIC_DRAW,
             );
         }
 
         for vao in self.rock.as_ref().unwrap().meshes.iter().map(|x| x.gl_vao) {
-            unsafe {
-                gl.BindVertexArray(vao);
-
-                gl.EnableVertexAttribArray(3);
-                gl.VertexAttribPointer(
-                    3,
-                    4,
-                    GL_FLOAT,
-                    0,
-                    std::mem::size_of::<Mat4>() as i32,
-                    std::ptr::null(),
-                );
-
-                gl.EnableVertexAttribArray(4);
-                gl.VertexAttribPointer(
-                    4,
-                    4,
-                    GL_FLOAT,
-                    0,
-                    std::mem::size_of::<Mat4>() as i32,
-                    std::mem::size_of::<Vec4>() as *const _,
-                );
-
-                gl.EnableVertexAttribArray(5);
-                gl.VertexAttribPointer(
-                    5,
-                    4,
-                    GL_FLOAT,
-                    0,
-                    std::mem::size_of::<Mat4>() as i32,
-                    (2 * std::mem::size_of::<Vec4>()) as *const _,
-                );
-
-                gl.EnableVertexAttribArray(6);
-                gl.VertexAttribPointer(
-                    6,
-                    4,
-                    GL_FLOAT,
-                    0,
-                    std::mem::size_of::<Mat4>() as i32,
-                    (3 * std::mem::size_of::<Vec4>()) as *const _,
-                );
-
-                gl.VertexAttribDivisor(3, 1);
-                gl.VertexAttribDivisor(4, 1);
-                gl.VertexAttribDivisor(5, 1);
-                gl.VertexAttribDivisor(6, 1);
-
-                gl.BindVertexArray(0);
-            }
+            gl.BindVertexArray(vao);
+            bind_attributes::<RockInstance>(gl, 1)?;
+            gl.BindVertexArray(0);
         }
+        Ok(())
     }
 
     fn build_projection_matrix(&mut self, system: &system::System, fov_rad: f32) {
@@ -298,7 +270,7 @@ const ASTEROID_VS: &str = "
 #version 330 core
 layout (location = 0) in vec3 aPos;
 layout (location = 2) in vec2 aTexCoords;
-layout (location = 3) in mat4 aInstanceMatrix;
+layout (location = 8) in mat4 aInstanceMatrix;
 
 out vec2 TexCoords;
 
@@ -325,3 +297,10 @@ void main()
     FragColor = texture(texture_diffuse1, TexCoords);
 }
 ";
+
+pub(super) const INLINE_SHADERS: [(&str, &str, &str); 4] = [
+    ("demo19::PLANET_VS", "vs", PLANET_VS),
+    ("demo19::PLANET_FS", "fs", PLANET_FS),
+    ("demo19::ASTEROID_VS", "vs", ASTEROID_VS),
+    ("demo19::ASTEROID_FS", "fs", ASTEROID_FS),
+];
@@ -1,11 +1,15 @@
 use super::common::*;
 use crate::demos::Demo;
+use crate::gfx::bounds::{self, Aabb};
 use crate::gfx::camera::Camera;
-use crate::gfx::glutils::{check_gl_err, load_texture};
-use crate::gfx::lights::VSMatrices;
+use crate::gfx::glutils::{check_gl_err, load_texture_or_fallback, save_texture};
+use crate::gfx::lights::{CameraBlock, VSMatrices};
 use crate::gfx::models::Model;
+use crate::gfx::primitives;
+use crate::gfx::shader_library::ShaderLibrary;
 use crate::gfx::shaders::Shaders;
 use crate::gfx::system;
+use crate::gfx::uniform_buffer::{UniformBuffer, CAMERA_BINDING};
 use gl33::*;
 // use rand::Rng;
 use std::time::Instant;
@@ -56,20 +60,23 @@ pub struct DemoImpl {
     timer: Instant,
     first_logic_pass: bool,
     camera: Camera,
+    camera_ubo: UniformBuffer<CameraBlock>,
     // shaders
-    shader: Shaders,
+    shaders: ShaderLibrary,
     simple_depth_shader: Shaders,
     debug_depth_shader: Shaders,
     // scene objects
     plane: ModelWrapT,
     cube: ModelWrapT,
     tex_wood: u32,
+    cubes_aabb: Aabb,
     // depth map frame buffer
     depth_map_fbo: u32,
     depth_map: u32,
     light_pos: Vec3,
     shadow_width: i32,
     shadow_height: i32,
+    textures_dumped: bool,
 }
 
 impl DemoImpl {
@@ -80,20 +87,23 @@ impl DemoImpl {
             timer: Instant::now(),
             first_logic_pass: true,
             camera: Camera::new(),
+            camera_ubo: UniformBuffer::default(),
             // shaders
-            shader: Shaders::default(),
+            shaders: ShaderLibrary::default(),
             simple_depth_shader: Shaders::default(),
             debug_depth_shader: Shaders::default(),
             // scene objects
             plane: ModelWrapT::default(),
             cube: ModelWrapT::default(),
             tex_wood: 0,
+            cubes_aabb: Aabb::default(),
             // depth map fram buffer
             depth_map_fbo: 0,
             depth_map: 0,
             light_pos: Vec3::new(-2.0, 4.0, -1.0),
             shadow_width: 1024,
             shadow_height: 1024,
+            textures_dumped: false,
         }
     }
 
@@ -104,11 +114,10 @@ impl DemoImpl {
 
         // init shaders
         let path = "./demo/shadow_mapping";
-        self.shader = Shaders::from_files(
-            &system.gl,
+        self.shaders = ShaderLibrary::new(
             &format!("{}/shadow_mapping.vs", path),
             &format!("{}/shadow_mapping.fs", path),
-        )?;
+        );
         self.simple_depth_shader = Shaders::from_files(
             &system.gl,
             &format!("{}/shadow_mapping_depth.vs", path),
@@ -120,29 +129,47 @@ impl DemoImpl {
             &format!("{}/debug_quad.fs", path),
         )?;
 
-        self.shader.use_program(&system.gl);
-        self.shader.set_i32(&system.gl, "diffuseTexture", 0);
-        self.shader.set_i32(&system.gl, "shadowMap", 1);
+        self.camera_ubo = UniformBuffer::new(&system.gl, CAMERA_BINDING)?;
+        self.bind_blocks(&system.gl)?;
 
         // init scene
         self.plane = ModelWrapT::Some(Box::new(setup_model_plane(PLANE_VERTICES)));
         self.plane.as_mut().unwrap().setup(&system.gl)?;
 
-        self.cube = ModelWrapT::Some(Box::new(setup_model_box(DEFAULT_POS_NORM_TEX_CUBE_VERT)));
+        self.cube = ModelWrapT::Some(Box::new(primitives::cube(1.0, 1)));
         self.cube.as_mut().unwrap().setup(&system.gl)?;
 
-        self.tex_wood = load_texture(&system.gl, "./demo/wood.png")?;
+        self.tex_wood = load_texture_or_fallback(&system.gl, "./demo/wood.png");
+
+        // only the cubes cast shadows, the shadow map is fitted around them
+        let cube_aabb = self.cube.as_ref().unwrap().aabb;
+        self.cubes_aabb = cube_transforms().iter().fold(Aabb::default(), |acc, m| {
+            acc.union(&cube_aabb.transformed(m))
+        });
 
         // init depth map fbo
         self.init_depth_map_fbo(&system.gl);
         Ok(())
     }
 
+    // the camera block binding, redone whenever the program is relinked
+    fn bind_blocks(&mut self, gl: &GlFns) -> Result<(), String> {
+        let shader = self.shaders.variant(gl, SHADOW_FEATURES)?;
+        self.camera_ubo.bind_block(gl, shader, "Camera")
+    }
+
     fn update_logic(&mut self, system: &system::System) -> Result<(), String> {
         if self.timer.elapsed().as_millis() > 10 || self.first_logic_pass {
             self.first_logic_pass = false;
             self.timer = Instant::now();
 
+            // edit the shadow_mapping shaders while the demo runs
+            if self.shaders.reload_if_changed(&system.gl) {
+                self.bind_blocks(&system.gl)?;
+            }
+            self.simple_depth_shader.reload_if_changed(&system.gl);
+            self.debug_depth_shader.reload_if_changed(&system.gl);
+
             if self.inputs.process_io(&mut self.camera, system) {
                 self.build_projection_matrix(system, self.camera.zoom);
             }
@@ -153,26 +180,24 @@ impl DemoImpl {
 
     fn render(&mut self, system: &system::System) -> Result<(), String> {
         self.mvp.view = self.camera.get_view_matrix();
+        self.camera_ubo.update(
+            &system.gl,
+            &CameraBlock::new(self.mvp.projection, self.mvp.view, self.camera.position),
+        );
 
         check_gl_err(&system.gl);
 
-        // render depth of scene to texture from light's perspective
-        let near_plane = 1.0;
-        let far_plane = 7.5;
-        let light_projection =
-            projection::rh_yup::orthographic_gl(-10.0, 10.0, -10.0, 10.0, near_plane, far_plane);
-        let light_view = Mat4::look_at(
-            self.light_pos,
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 1.0, 0.0),
+        // render depth of scene to texture from light's perspective, the light
+        // shines from light_pos towards the origin
+        let light_space_matrix = bounds::fit_directional_shadow(
+            -self.light_pos,
+            &self.cubes_aabb,
+            &self.plane.as_ref().unwrap().aabb,
         );
-        let light_space_matrix = light_projection * light_view;
         // render
         self.simple_depth_shader.use_program(&system.gl);
         self.simple_depth_shader
             .set_mat4fv_uv(&system.gl, "lightSpaceMatrix", &light_space_matrix);
-        self.simple_depth_shader
-            .set_mat4fv_uv(&system.gl, "model", &Mat4::default());
 
         unsafe {
             system
@@ -185,12 +210,26 @@ impl DemoImpl {
             system.gl.ActiveTexture(gl33::GL_TEXTURE0);
             system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_wood);
         }
-        self.plane
-            .as_mut()
-            .unwrap()
-            .draw(&system.gl, &self.simple_depth_shader);
+        self.plane.as_ref().unwrap().draw_at(
+            &system.gl,
+            &self.simple_depth_shader,
+            &Mat4::default(),
+        );
+
+        Self::render_cubes(
+            &system.gl,
+            self.cube.as_ref().unwrap(),
+            &self.simple_depth_shader,
+        );
 
-        self.render_cubes(system, &self.simple_depth_shader.clone(), true);
+        if !self.textures_dumped {
+            self.textures_dumped = true;
+            if let Some(dir) = texture_dump_dir() {
+                let path = format!("{}/demo20_depth_map.png", dir);
+                save_texture(&system.gl, self.depth_map, GL_TEXTURE_2D, 0, &path)?;
+                println!("depth map saved to {}", path);
+            }
+        }
 
         unsafe {
             system.gl.BindFramebuffer(gl33::GL_FRAMEBUFFER, 0);
@@ -201,59 +240,32 @@ impl DemoImpl {
         }
 
         // render scene as normal
-        self.shader.use_program(&system.gl);
-        self.shader.set_vec3(
+        let shader = self.shaders.variant(&system.gl, SHADOW_FEATURES)?;
+        shader.use_program(&system.gl);
+        shader.set_vec3(
             &system.gl,
             "lightPos",
             self.light_pos.x,
             self.light_pos.y,
             self.light_pos.x,
         );
-        self.shader
-            .set_mat4fv_uv(&system.gl, "lightSpaceMatrix", &light_space_matrix);
-        self.mvp.model = Mat4::default();
-        self.mvp.pass_uniforms(&system.gl, &self.shader);
-        self.plane.as_mut().unwrap().draw(&system.gl, &self.shader);
+        shader.set_mat4fv_uv(&system.gl, "lightSpaceMatrix", &light_space_matrix);
+        self.plane
+            .as_ref()
+            .unwrap()
+            .draw_at(&system.gl, shader, &Mat4::default());
 
-        unsafe {
-            system.gl.ActiveTexture(gl33::GL_TEXTURE0);
-            system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_wood);
-            system.gl.ActiveTexture(GL_TEXTURE1);
-            system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.depth_map);
-        }
+        shader.bind_texture(&system.gl, "diffuseTexture", self.tex_wood);
+        shader.bind_texture(&system.gl, "shadowMap", self.depth_map);
 
-        self.render_cubes(system, &self.shader.clone(), false);
+        Self::render_cubes(&system.gl, self.cube.as_ref().unwrap(), shader);
 
         Ok(())
     }
 
-    fn render_cubes(&mut self, system: &system::System, shader: &Shaders, primitive: bool) {
-        // translate_vec, rot_angle, rot_vec, scale
-        let trans: [(Vec3, f32, Vec4, f32); 3] = [
-            (Vec3::new(0.0, 1.5, 0.0), 0.0, Vec4::default(), 0.5),
-            (Vec3::new(2.0, 0.0, 1.0), 0.0, Vec4::default(), 0.5),
-            (
-                Vec3::new(-1.0, 0.0, 2.0),
-                60.0,
-                Vec4::new(1.0, 0.0, 1.0, 1.0),
-                0.25,
-            ),
-        ];
-
-        for t in trans {
-            self.mvp.model = Mat4::default();
-            self.mvp.model.translate(&t.0);
-            self.mvp.model = self.mvp.model * Mat4::from_rotation_around(t.2, t.1.to_radians());
-            if t.3 != 0.0 {
-                self.mvp.model = self.mvp.model * Mat4::from_scale(t.3);
-            }
-
-            if primitive {
-                shader.set_mat4fv_uv(&system.gl, "model", &self.mvp.model);
-            } else {
-                self.mvp.pass_uniforms(&system.gl, shader);
-            }
-            self.cube.as_mut().unwrap().draw(&system.gl, shader);
+    fn render_cubes(gl: &GlFns, cube: &Model, shader: &Shaders) {
+        for m in cube_transforms() {
+            cube.draw_at(gl, shader, &m);
         }
     }
 
@@ -340,3 +352,34 @@ const PLANE_VERTICES: [f32; 48] = [
         -25.0, -0.5, -25.0,  0.0, 1.0, 0.0,   0.0, 25.0,
          25.0, -0.5, -25.0,  0.0, 1.0, 0.0,  25.0, 25.0
 ];
+
+/// Model matrices of the cubes standing on the plane
+// PCF over a 5x5 texel kernel
+const SHADOW_FEATURES: &[&str] = &["USE_SHADOWS", "PCF_KERNEL=5"];
+
+fn cube_transforms() -> Vec<Mat4> {
+    // translate_vec, rot_angle, rot_vec, scale
+    let trans: [(Vec3, f32, Vec4, f32); 3] = [
+        (Vec3::new(0.0, 1.5, 0.0), 0.0, Vec4::default(), 0.5),
+        (Vec3::new(2.0, 0.0, 1.0), 0.0, Vec4::default(), 0.5),
+        (
+            Vec3::new(-1.0, 0.0, 2.0),
+            60.0,
+            Vec4::new(1.0, 0.0, 1.0, 1.0),
+            0.25,
+        ),
+    ];
+
+    trans
+        .iter()
+        .map(|t| {
+            let mut model = Mat4::default();
+            model.translate(&t.0);
+            model = model * Mat4::from_rotation_around(t.2, t.1.to_radians());
+            if t.3 != 0.0 {
+                model = model * Mat4::from_scale(t.3);
+            }
+            model
+        })
+        .collect()
+}
@@ -1,11 +1,13 @@
 use super::common::*;
 use crate::demos::Demo;
 use crate::gfx::camera::Camera;
-use crate::gfx::glutils::{check_gl_err, load_texture};
-use crate::gfx::lights::VSMatrices;
+use crate::gfx::glutils::{check_gl_err, load_texture, save_texture};
+use crate::gfx::lights::{CameraBlock, VSMatrices};
 use crate::gfx::models::Model;
+use crate::gfx::primitives;
 use crate::gfx::shaders::Shaders;
 use crate::gfx::system;
+use crate::gfx::uniform_buffer::{UniformBuffer, CAMERA_BINDING};
 use gl33::*;
 // use rand::Rng;
 use std::time::Instant;
@@ -59,6 +61,7 @@ pub struct DemoImpl {
     timer: Instant,
     first_logic_pass: bool,
     camera: Camera,
+    camera_ubo: UniformBuffer<CameraBlock>,
     // shaders
     shader: Shaders,
     simple_depth_shader: Shaders,
@@ -71,6 +74,7 @@ pub struct DemoImpl {
     light_pos: Vec3,
     shadow_width: i32,
     shadow_height: i32,
+    textures_dumped: bool,
 }
 
 impl DemoImpl {
@@ -81,6 +85,7 @@ impl DemoImpl {
             timer: Instant::now(),
             first_logic_pass: true,
             camera: Camera::new(),
+            camera_ubo: UniformBuffer::default(),
             // shaders
             shader: Shaders::default(),
             simple_depth_shader: Shaders::default(),
@@ -93,6 +98,7 @@ impl DemoImpl {
             light_pos: Vec3::new(0.0, 0.0, 0.0),
             shadow_width: 1024,
             shadow_height: 1024,
+            textures_dumped: false,
         }
     }
 
@@ -119,13 +125,13 @@ impl DemoImpl {
             &format!("{}/point_shadows_depth.gs", path),
         )?;
 
-        self.shader.use_program(&system.gl);
-        self.shader.set_i32(&system.gl, "diffuseTexture", 0);
-        self.shader.set_i32(&system.gl, "depthMap", 1);
+        self.camera_ubo = UniformBuffer::new(&system.gl, CAMERA_BINDING)?;
+        self.camera_ubo
+            .bind_block(&system.gl, &self.shader, "Camera")?;
 
         // init scene
 
-        self.cube = ModelWrapT::Some(Box::new(setup_model_box(CUBE_VERTICES)));
+        self.cube = ModelWrapT::Some(Box::new(primitives::cube(2.0, 1)));
         self.cube.as_mut().unwrap().setup(&system.gl)?;
 
         self.tex_wood = load_texture(&system.gl, "./demo/wood.png")?;
@@ -151,6 +157,10 @@ impl DemoImpl {
 
     fn render(&mut self, system: &system::System) -> Result<(), String> {
         self.mvp.view = self.camera.get_view_matrix();
+        self.camera_ubo.update(
+            &system.gl,
+            &CameraBlock::new(self.mvp.projection, self.mvp.view, self.camera.position),
+        );
 
         check_gl_err(&system.gl);
 
@@ -231,7 +241,29 @@ impl DemoImpl {
             // system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_wood);
         }
 
-        self.render_scene(system, &self.simple_depth_shader.clone());
+        Self::render_scene(
+            &system.gl,
+            self.cube.as_ref().unwrap(),
+            &mut self.mvp,
+            &self.simple_depth_shader,
+        );
+
+        if !self.textures_dumped {
+            self.textures_dumped = true;
+            if let Some(dir) = texture_dump_dir() {
+                for i in 0..6 {
+                    let path = format!("{}/demo21_depth_cube_map_{}.png", dir, i);
+                    save_texture(
+                        &system.gl,
+                        self.depth_cube_map,
+                        GLenum(GL_TEXTURE_CUBE_MAP_POSITIVE_X.0 + i),
+                        0,
+                        &path,
+                    )?;
+                    println!("depth cube map face saved to {}", path);
+                }
+            }
+        }
 
         unsafe {
             system.gl.BindFramebuffer(gl33::GL_FRAMEBUFFER, 0);
@@ -253,38 +285,39 @@ impl DemoImpl {
         );
         self.shader.set_f32(&system.gl, "far_plane", far_plane);
         self.shader.set_i32(&system.gl, "shadows", 1);
-        self.mvp.model = Mat4::default();
-        self.mvp.pass_uniforms(&system.gl, &self.shader);
+        self.shader
+            .set_mat4fv_uv(&system.gl, "model", &Mat4::default());
 
         check_gl_err(&system.gl);
-        unsafe {
-            system.gl.ActiveTexture(gl33::GL_TEXTURE0);
-            system.gl.BindTexture(gl33::GL_TEXTURE_2D, self.tex_wood);
-            system.gl.ActiveTexture(GL_TEXTURE1);
-            system
-                .gl
-                .BindTexture(gl33::GL_TEXTURE_CUBE_MAP, self.depth_cube_map);
-        }
+        self.shader
+            .bind_texture(&system.gl, "diffuseTexture", self.tex_wood);
+        self.shader
+            .bind_texture(&system.gl, "depthMap", self.depth_cube_map);
 
-        self.render_scene(system, &self.shader.clone());
+        Self::render_scene(
+            &system.gl,
+            self.cube.as_ref().unwrap(),
+            &mut self.mvp,
+            &self.shader,
+        );
         check_gl_err(&system.gl);
 
         Ok(())
     }
 
-    fn render_scene(&mut self, system: &system::System, shader: &Shaders) {
+    fn render_scene(gl: &GlFns, cube: &Model, mvp: &mut VSMatrices, shader: &Shaders) {
         unsafe {
-            system.gl.Disable(gl33::GL_CULL_FACE);
+            gl.Disable(gl33::GL_CULL_FACE);
         }
-        self.mvp.model = Mat4::from_scale(5.0);
-        self.mvp.try_pass_uniforms(&system.gl, shader);
-        shader.try_set_i32(&system.gl, "reverse_normals", 1);
-        check_gl_err(&system.gl);
-        self.render_cube(system, shader);
-        check_gl_err(&system.gl);
-        shader.try_set_i32(&system.gl, "reverse_normals", 0);
+        mvp.model = Mat4::from_scale(5.0);
+        mvp.try_pass_uniforms(gl, shader);
+        shader.try_set_i32(gl, "reverse_normals", 1);
+        check_gl_err(gl);
+        cube.draw_at(gl, shader, &mvp.model);
+        check_gl_err(gl);
+        shader.try_set_i32(gl, "reverse_normals", 0);
         unsafe {
-            system.gl.Enable(gl33::GL_CULL_FACE);
+            gl.Enable(gl33::GL_CULL_FACE);
         }
         // translate_vec, rot_angle, rot_vec, scale
         let trans: [(Vec3, f32, Vec4, f32); 5] = [
@@ -301,21 +334,17 @@ impl DemoImpl {
         ];
 
         for t in trans {
-            self.mvp.model = Mat4::default();
-            self.mvp.model.translate(&t.0);
-            self.mvp.model = self.mvp.model * Mat4::from_rotation_around(t.2, t.1.to_radians());
+            mvp.model = Mat4::default();
+            mvp.model.translate(&t.0);
+            mvp.model = mvp.model * Mat4::from_rotation_around(t.2, t.1.to_radians());
             if t.3 != 0.0 {
-                self.mvp.model = self.mvp.model * Mat4::from_scale(t.3);
+                mvp.model = mvp.model * Mat4::from_scale(t.3);
             }
-            self.mvp.try_pass_uniforms(&system.gl, shader);
-            self.render_cube(system, shader);
+            mvp.try_pass_uniforms(gl, shader);
+            cube.draw_at(gl, shader, &mvp.model);
         }
     }
 
-    fn render_cube(&mut self, system: &system::System, shader: &Shaders) {
-        self.cube.as_mut().unwrap().draw(&system.gl, shader);
-    }
-
     fn build_projection_matrix(&mut self, system: &system::System, fov_rad: f32) {
         self.mvp.projection = projection::rh_yup::perspective_gl(
             fov_rad,
@@ -396,49 +425,3 @@ impl DemoImpl {
         }
     }
 }
-
-#[rustfmt::skip]
-const CUBE_VERTICES: [f32; 288] = [
-            // back ace
-            -1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 0.0, // bottom-let
-             1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 1.0, // top-right
-             1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 0.0, // bottom-right         
-             1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 1.0, // top-right
-            -1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 0.0, // bottom-let
-            -1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 1.0, // top-let
-            // ront ace
-            -1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 0.0, // bottom-let
-             1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 0.0, // bottom-right
-             1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 1.0, // top-right
-             1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 1.0, // top-right
-            -1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 1.0, // top-let
-            -1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 0.0, // bottom-let
-            // let ace
-            -1.0,  1.0,  1.0, -1.0,  0.0,  0.0, 1.0, 0.0, // top-right
-            -1.0,  1.0, -1.0, -1.0,  0.0,  0.0, 1.0, 1.0, // top-let
-            -1.0, -1.0, -1.0, -1.0,  0.0,  0.0, 0.0, 1.0, // bottom-let
-            -1.0, -1.0, -1.0, -1.0,  0.0,  0.0, 0.0, 1.0, // bottom-let
-            -1.0, -1.0,  1.0, -1.0,  0.0,  0.0, 0.0, 0.0, // bottom-right
-            -1.0,  1.0,  1.0, -1.0,  0.0,  0.0, 1.0, 0.0, // top-right
-            // right ace
-             1.0,  1.0,  1.0,  1.0,  0.0,  0.0, 1.0, 0.0, // top-let
-             1.0, -1.0, -1.0,  1.0,  0.0,  0.0, 0.0, 1.0, // bottom-right
-             1.0,  1.0, -1.0,  1.0,  0.0,  0.0, 1.0, 1.0, // top-right         
-             1.0, -1.0, -1.0,  1.0,  0.0,  0.0, 0.0, 1.0, // bottom-right
-             1.0,  1.0,  1.0,  1.0,  0.0,  0.0, 1.0, 0.0, // top-let
-             1.0, -1.0,  1.0,  1.0,  0.0,  0.0, 0.0, 0.0, // bottom-let     
-            // bottom ace
-            -1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 0.0, 1.0, // top-right
-             1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 1.0, 1.0, // top-let
-             1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 1.0, 0.0, // bottom-let
-             1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 1.0, 0.0, // bottom-let
-            -1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 0.0, 0.0, // bottom-right
-            -1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 0.0, 1.0, // top-right
-            // top ace
-            -1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 0.0, 1.0, // top-let
-             1.0,  1.0 , 1.0,  0.0,  1.0,  0.0, 1.0, 0.0, // bottom-right
-             1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 1.0, 1.0, // top-right     
-             1.0,  1.0,  1.0,  0.0,  1.0,  0.0, 1.0, 0.0, // bottom-right
-            -1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 0.0, 1.0, // top-let
-            -1.0,  1.0,  1.0,  0.0,  1.0,  0.0, 0.0, 0.0  // bottom-let        
-];
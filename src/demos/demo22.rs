@@ -0,0 +1,284 @@
+use super::common::*;
+use crate::demos::Demo;
+use crate::gfx::animation::{AnimationClip, Animator, Channel, Key};
+use crate::gfx::camera::Camera;
+use crate::gfx::glutils::{check_gl_err, load_texture_or_fallback};
+use crate::gfx::lights::VSMatrices;
+use crate::gfx::models::{Mesh, MeshInstance, Model, Node, TexType, Texture, Vertex};
+use crate::gfx::shaders::Shaders;
+use crate::gfx::system;
+use std::f32::consts::PI;
+use std::time::Instant;
+use ultraviolet::*;
+
+pub struct DemoN {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+impl_demo_trait!(DemoN);
+
+impl DemoN {
+    fn main(&self) -> Result<(), String> {
+        let mut system = system::System::new(800, 600);
+
+        let mut demo = DemoImpl::new();
+        demo.init(&system)?;
+
+        loop {
+            if !system.process_io_events() {
+                break;
+            } else {
+                // logic code here
+                demo.update_logic(&system)?;
+                // end logic code
+
+                system.clear_screen(0.1, 0.1, 0.1);
+                unsafe {
+                    system.gl.Clear(gl33::GL_COLOR_BUFFER_BIT);
+                }
+                // graphics render here
+                check_gl_err(&system.gl);
+                demo.render(&system)?;
+                check_gl_err(&system.gl);
+                // end graphics render
+
+                system.draw_to_screen();
+            }
+        }
+        Ok(())
+    }
+}
+
+// the tentacle is a cylinder along +y with one bone per unit of height
+const BONES: usize = 4;
+const RINGS_PER_BONE: usize = 8;
+const SEGMENTS: usize = 16;
+const RADIUS: f32 = 0.3;
+const CLIP_DURATION: f32 = 4.0;
+
+type ModelWrapT = Option<Box<Model>>;
+
+pub struct DemoImpl {
+    mvp: VSMatrices,
+    inputs: usr_inputs::Io,
+    timer: Instant,
+    start: Instant,
+    first_logic_pass: bool,
+    camera: Camera,
+    shader: Shaders,
+    tentacle: ModelWrapT,
+    animator: Animator,
+}
+
+impl DemoImpl {
+    fn new() -> Self {
+        DemoImpl {
+            mvp: VSMatrices::default(),
+            inputs: Default::default(),
+            timer: Instant::now(),
+            start: Instant::now(),
+            first_logic_pass: true,
+            camera: Camera::new(),
+            shader: Shaders::default(),
+            tentacle: ModelWrapT::None,
+            animator: Animator::new(0),
+        }
+    }
+
+    fn init(&mut self, system: &system::System) -> Result<(), String> {
+        unsafe {
+            system.gl.Enable(gl33::GL_DEPTH_TEST);
+        }
+        self.build_projection_matrix(system, 45.0f32.to_radians());
+        self.camera.mouse_sensitivity = 0.1;
+
+        self.shader = Shaders::from_files(
+            &system.gl,
+            "./demo/demo22_skinning.vs",
+            "./demo/demo14_model.fs",
+        )?;
+
+        let texture = load_texture_or_fallback(&system.gl, "./demo/wood.png");
+        self.tentacle = ModelWrapT::Some(Box::new(setup_tentacle(texture)));
+        self.tentacle.as_mut().unwrap().setup(&system.gl)?;
+        // the bounds are of the bind pose, leave room for the swing
+        let mut sphere = self.tentacle.as_ref().unwrap().sphere;
+        sphere.radius *= 1.3;
+        self.camera.frame(
+            &sphere,
+            45.0f32.to_radians(),
+            (system.w as f32) / (system.h as f32),
+        );
+
+        // "wave" plays, "twist" is blended in and out over time
+        self.animator.play(0);
+        Ok(())
+    }
+
+    fn update_logic(&mut self, system: &system::System) -> Result<(), String> {
+        if self.timer.elapsed().as_millis() > 10 || self.first_logic_pass {
+            self.first_logic_pass = false;
+            let dt = self.timer.elapsed().as_secs_f32();
+            self.timer = Instant::now();
+
+            if self.shader.reload_if_changed(&system.gl) {
+                println!("skinning shader reloaded");
+            }
+            if self.inputs.process_io(&mut self.camera, system) {
+                self.build_projection_matrix(system, self.camera.zoom);
+            }
+
+            let t = self.start.elapsed().as_secs_f32();
+            self.animator.blend_with(1, 0.5 - 0.5 * (t * 0.5).cos());
+            self.animator.advance(dt);
+            self.animator.update(self.tentacle.as_ref().unwrap());
+        }
+
+        Ok(())
+    }
+
+    fn render(&mut self, system: &system::System) -> Result<(), String> {
+        self.mvp.view = self.camera.get_view_matrix();
+        self.mvp.model = Mat4::default();
+
+        self.shader.use_program(&system.gl);
+        self.mvp.pass_uniforms(&system.gl, &self.shader);
+        self.shader
+            .set(&system.gl, "lightDir", &Vec3::new(-0.3, -0.5, -1.0));
+        self.shader
+            .set(&system.gl, "viewPos", &self.camera.position);
+        self.animator.upload(&system.gl, &self.shader);
+
+        self.tentacle
+            .as_ref()
+            .unwrap()
+            .draw_at(&system.gl, &self.shader, &self.mvp.model);
+
+        Ok(())
+    }
+
+    fn build_projection_matrix(&mut self, system: &system::System, fov_rad: f32) {
+        self.mvp.projection = projection::rh_yup::perspective_gl(
+            fov_rad,
+            (system.w as f32) / (system.h as f32),
+            0.1,
+            100.0,
+        );
+    }
+}
+
+/// A skinned cylinder with a chain of bones and two clips, standing in for an
+/// imported character
+fn setup_tentacle(texture: u32) -> Model {
+    let mut model = Model::default();
+    let height = BONES as f32;
+
+    let mut mesh = Mesh {
+        skinned: true,
+        ..Default::default()
+    };
+    let rings = BONES * RINGS_PER_BONE;
+    for r in 0..=rings {
+        let y = height * r as f32 / rings as f32;
+        // narrower towards the tip
+        let radius = RADIUS * (1.0 - 0.6 * y / height);
+        for s in 0..=SEGMENTS {
+            let angle = 2.0 * PI * s as f32 / SEGMENTS as f32;
+            let (sin, cos) = angle.sin_cos();
+            let mut v = Vertex {
+                position: Vec3::new(radius * cos, y, radius * sin),
+                normal: Vec3::new(cos, 0.0, sin),
+                tex_coords: Vec2::new(s as f32 / SEGMENTS as f32, y / height),
+                ..Default::default()
+            };
+            // blend between the two nearest bones
+            let along = y.min(height - 1.0);
+            let bone = along.floor() as usize;
+            let t = along - bone as f32;
+            v.add_bone_weight(bone, 1.0 - t);
+            if bone + 1 < BONES {
+                v.add_bone_weight(bone + 1, t);
+            }
+            v.normalize_bone_weights();
+            mesh.vertices.push(v);
+        }
+    }
+    for r in 0..rings {
+        for s in 0..SEGMENTS {
+            let a = (r * (SEGMENTS + 1) + s) as u32;
+            let up = a + SEGMENTS as u32 + 1;
+            mesh.indices
+                .extend_from_slice(&[a, up, a + 1, a + 1, up, up + 1]);
+        }
+    }
+    mesh.generate_tangents();
+    mesh.textures.push(Texture {
+        id: texture,
+        tex_type: TexType::Diffuse,
+        ..Default::default()
+    });
+    model.meshes.push(mesh);
+
+    // root, then the bone chain, each bone one unit above its parent
+    model.nodes.push(Node {
+        name: "root".to_string(),
+        parent: None,
+        children: vec![1],
+        local: Mat4::identity(),
+        world: Mat4::identity(),
+    });
+    for b in 0..BONES {
+        let name = format!("bone{}", b);
+        let index = model.nodes.len();
+        model.nodes.push(Node {
+            name: name.clone(),
+            parent: Some(index - 1),
+            children: if b + 1 < BONES {
+                vec![index + 1]
+            } else {
+                vec![]
+            },
+            local: Mat4::from_translation(Vec3::new(0.0, if b == 0 { 0.0 } else { 1.0 }, 0.0)),
+            world: Mat4::identity(),
+        });
+        model.skeleton.add_bone(
+            &name,
+            Mat4::from_translation(Vec3::new(0.0, -(b as f32), 0.0)),
+        );
+    }
+    model.update_world_matrices();
+    model.instances.push(MeshInstance { mesh: 0, node: 0 });
+
+    model.animations = vec![
+        swing_clip("wave", |phase| Rotor3::from_rotation_xy(0.35 * phase.sin())),
+        swing_clip("twist", |phase| {
+            Rotor3::from_rotation_yz(0.3 * phase.sin())
+                * Rotor3::from_rotation_xz(0.5 * phase.cos())
+        }),
+    ];
+    model
+}
+
+/// A looping clip rotating every bone by `rotation(phase)`, each bone a bit
+/// behind its parent so the motion travels up the chain
+fn swing_clip(name: &str, rotation: impl Fn(f32) -> Rotor3) -> AnimationClip {
+    let keys = 16;
+    let channels = (0..BONES)
+        .map(|b| Channel {
+            node: format!("bone{}", b),
+            rotations: (0..=keys)
+                .map(|k| {
+                    let time = CLIP_DURATION * k as f32 / keys as f32;
+                    let phase = 2.0 * PI * time / CLIP_DURATION - b as f32 * 0.8;
+                    Key {
+                        time,
+                        value: rotation(phase),
+                    }
+                })
+                .collect(),
+            ..Default::default()
+        })
+        .collect();
+    AnimationClip::new(name, CLIP_DURATION, channels)
+}
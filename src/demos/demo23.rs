@@ -0,0 +1,328 @@
+use super::common::*;
+use crate::demos::Demo;
+use crate::gfx::camera::Camera;
+use crate::gfx::compute::ComputeProgram;
+use crate::gfx::glext::{self, GL_PATCHES, GL_PIXEL_BUFFER_BARRIER_BIT, GL_SHADER_STORAGE_BUFFER};
+use crate::gfx::glutils::check_gl_err;
+use crate::gfx::lights::VSMatrices;
+use crate::gfx::models::Model;
+use crate::gfx::preprocessor::Defines;
+use crate::gfx::primitives;
+use crate::gfx::program::{ProgramBuilder, ShaderStage};
+use crate::gfx::shaders::Shaders;
+use crate::gfx::system;
+use gl33::*;
+use std::time::Instant;
+use ultraviolet::*;
+
+pub struct DemoN {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+impl_demo_trait!(DemoN);
+
+impl DemoN {
+    fn main(&self) -> Result<(), String> {
+        let mut system = system::System::new(800, 600);
+
+        let mut demo = DemoImpl::new();
+        demo.init(&system)?;
+
+        loop {
+            if !system.process_io_events() {
+                break;
+            } else {
+                // logic code here
+                demo.update_logic(&system)?;
+                // end logic code
+
+                system.clear_screen(0.1, 0.1, 0.1);
+                unsafe {
+                    system.gl.Clear(GL_COLOR_BUFFER_BIT);
+                }
+                // graphics render here
+                check_gl_err(&system.gl);
+                demo.render(&system)?;
+                check_gl_err(&system.gl);
+                // end graphics render
+
+                system.draw_to_screen();
+            }
+        }
+        demo.release(&system.gl);
+        Ok(())
+    }
+}
+
+// heightmap texels per side
+const HEIGHTS_SIZE: usize = 128;
+const PLANE_SIZE: f32 = 4.0;
+const PLANE_CELLS: u32 = 8;
+
+type ModelWrapT = Option<Box<Model>>;
+
+pub struct DemoImpl {
+    mvp: VSMatrices,
+    inputs: usr_inputs::Io,
+    timer: Instant,
+    start: Instant,
+    first_logic_pass: bool,
+    camera: Camera,
+    plane: ModelWrapT,
+    // None when the context has no compute (4.3) or tessellation (4.0) shaders
+    compute: Option<ComputeProgram>,
+    terrain: Option<Shaders>,
+    plain: Shaders,
+    heights: u32,
+    heights_buffer: u32,
+}
+
+impl DemoImpl {
+    fn new() -> Self {
+        DemoImpl {
+            mvp: VSMatrices::default(),
+            inputs: Default::default(),
+            timer: Instant::now(),
+            start: Instant::now(),
+            first_logic_pass: true,
+            camera: Camera::from(Vec3::zero(), Vec3::unit_y(), -90.0, -40.0),
+            plane: ModelWrapT::None,
+            compute: None,
+            terrain: None,
+            plain: Shaders::default(),
+            heights: 0,
+            heights_buffer: 0,
+        }
+    }
+
+    fn init(&mut self, system: &system::System) -> Result<(), String> {
+        let gl = &system.gl;
+        unsafe {
+            gl.Enable(GL_DEPTH_TEST);
+        }
+        self.build_projection_matrix(system, 45.0f32.to_radians());
+        self.camera.mouse_sensitivity = 0.1;
+
+        self.plane = ModelWrapT::Some(Box::new(primitives::plane(
+            PLANE_SIZE,
+            PLANE_SIZE,
+            PLANE_CELLS,
+            PLANE_CELLS,
+        )));
+        self.plane.as_mut().unwrap().setup(gl)?;
+        let mut sphere = self.plane.as_ref().unwrap().sphere;
+        sphere.radius *= 0.8;
+        self.camera.frame(
+            &sphere,
+            45.0f32.to_radians(),
+            (system.w as f32) / (system.h as f32),
+        );
+
+        unsafe {
+            gl.GenTextures(1, &mut self.heights);
+            gl.BindTexture(GL_TEXTURE_2D, self.heights);
+            gl.TexImage2D(
+                GL_TEXTURE_2D,
+                0,
+                GL_R32F.0 as i32,
+                HEIGHTS_SIZE as i32,
+                HEIGHTS_SIZE as i32,
+                0,
+                GL_RED,
+                GL_FLOAT,
+                std::ptr::null(),
+            );
+            gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR.0 as i32);
+            gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR.0 as i32);
+            gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE.0 as i32);
+            gl.TexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE.0 as i32);
+        }
+
+        match ShaderStage::Compute.check_supported(gl) {
+            Ok(()) => {
+                let compute =
+                    ComputeProgram::from_file(gl, "./demo/demo23_heights.cs", &Defines::new())?;
+                println!(
+                    "heights computed on the GPU, local size {:?}",
+                    compute.local_size()
+                );
+                unsafe {
+                    gl.GenBuffers(1, &mut self.heights_buffer);
+                    gl.BindBuffer(GL_SHADER_STORAGE_BUFFER, self.heights_buffer);
+                    gl.BufferData(
+                        GL_SHADER_STORAGE_BUFFER,
+                        (HEIGHTS_SIZE * HEIGHTS_SIZE * std::mem::size_of::<f32>()) as isize,
+                        std::ptr::null(),
+                        GL_DYNAMIC_COPY,
+                    );
+                    gl.BindBuffer(GL_SHADER_STORAGE_BUFFER, 0);
+                }
+                self.compute = Some(compute);
+            }
+            Err(e) => println!("{}, heights computed on the CPU", e),
+        }
+
+        match ShaderStage::TessControl.check_supported(gl) {
+            Ok(()) => {
+                let terrain = ProgramBuilder::new()
+                    .file(ShaderStage::Vertex, "./demo/demo23_terrain.vs")
+                    .file(ShaderStage::TessControl, "./demo/demo23_terrain.tcs")
+                    .file(ShaderStage::TessEvaluation, "./demo/demo23_terrain.tes")
+                    .file(ShaderStage::Fragment, "./demo/demo23_terrain.fs")
+                    .build(gl)?;
+                self.terrain = Some(terrain);
+            }
+            Err(e) => println!("{}, the plane is drawn as it is", e),
+        }
+        self.plain = Shaders::from_files(gl, "./demo/demo23_plain.vs", "./demo/demo23_terrain.fs")?;
+        Ok(())
+    }
+
+    fn update_logic(&mut self, system: &system::System) -> Result<(), String> {
+        if self.timer.elapsed().as_millis() > 10 || self.first_logic_pass {
+            self.first_logic_pass = false;
+            self.timer = Instant::now();
+
+            if let Some(compute) = self.compute.as_mut() {
+                compute.reload_if_changed(&system.gl);
+            }
+            if let Some(terrain) = self.terrain.as_ref() {
+                terrain.reload_if_changed(&system.gl);
+            }
+            if self.inputs.process_io(&mut self.camera, system) {
+                self.build_projection_matrix(system, self.camera.zoom.to_radians());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn render(&mut self, system: &system::System) -> Result<(), String> {
+        let gl = &system.gl;
+        let time = self.start.elapsed().as_secs_f32();
+        self.update_heights(gl, time)?;
+
+        self.mvp.view = self.camera.get_view_matrix();
+        self.mvp.model = Mat4::default();
+        let plane = self.plane.as_ref().unwrap();
+
+        // wireframe, to see the tessellation change
+        unsafe {
+            gl.PolygonMode(GL_FRONT_AND_BACK, GL_LINE);
+        }
+        match &self.terrain {
+            Some(terrain) => {
+                terrain.use_program(gl);
+                self.mvp.pass_uniforms(gl, terrain);
+                terrain.set(gl, "tessLevel", &(8.5 - 7.5 * (time * 0.5).cos()));
+                terrain.bind_texture(gl, "heights", self.heights);
+                glext::set_patch_vertices(3)?;
+
+                let mesh = &plane.meshes[0];
+                gl.BindVertexArray(mesh.gl_vao);
+                unsafe {
+                    gl.DrawElements(
+                        GL_PATCHES,
+                        mesh.indices.len() as i32,
+                        mesh.index_type(),
+                        std::ptr::null(),
+                    );
+                }
+            }
+            None => {
+                self.plain.use_program(gl);
+                self.mvp.pass_uniforms(gl, &self.plain);
+                self.plain.bind_texture(gl, "heights", self.heights);
+                plane.draw_at(gl, &self.plain, &self.mvp.model);
+            }
+        }
+        unsafe {
+            gl.PolygonMode(GL_FRONT_AND_BACK, GL_FILL);
+        }
+
+        Ok(())
+    }
+
+    /// Fills the heightmap for `time`, with the compute shader when there is one
+    fn update_heights(&self, gl: &GlFns, time: f32) -> Result<(), String> {
+        let size = HEIGHTS_SIZE as i32;
+        match &self.compute {
+            Some(compute) => {
+                compute.shaders().use_program(gl);
+                compute.shaders().set(gl, "size", &size);
+                compute.shaders().set(gl, "time", &time);
+                unsafe {
+                    gl.BindBufferBase(GL_SHADER_STORAGE_BUFFER, 0, self.heights_buffer);
+                }
+                compute.dispatch_items(gl, [HEIGHTS_SIZE as u32, HEIGHTS_SIZE as u32, 1])?;
+                // the texture upload below reads what the shader wrote
+                glext::memory_barrier(GL_PIXEL_BUFFER_BARRIER_BIT)?;
+                unsafe {
+                    gl.BindBuffer(GL_PIXEL_UNPACK_BUFFER, self.heights_buffer);
+                    gl.BindTexture(GL_TEXTURE_2D, self.heights);
+                    gl.TexSubImage2D(
+                        GL_TEXTURE_2D,
+                        0,
+                        0,
+                        0,
+                        size,
+                        size,
+                        GL_RED,
+                        GL_FLOAT,
+                        std::ptr::null(),
+                    );
+                    gl.BindBuffer(GL_PIXEL_UNPACK_BUFFER, 0);
+                }
+            }
+            None => {
+                let last = (HEIGHTS_SIZE - 1) as f32;
+                let data: Vec<f32> = (0..HEIGHTS_SIZE * HEIGHTS_SIZE)
+                    .map(|i| {
+                        let (x, y) = (i % HEIGHTS_SIZE, i / HEIGHTS_SIZE);
+                        height(x as f32 / last, y as f32 / last, time)
+                    })
+                    .collect();
+                unsafe {
+                    gl.BindTexture(GL_TEXTURE_2D, self.heights);
+                    gl.TexSubImage2D(
+                        GL_TEXTURE_2D,
+                        0,
+                        0,
+                        0,
+                        size,
+                        size,
+                        GL_RED,
+                        GL_FLOAT,
+                        data.as_ptr().cast(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn release(&mut self, gl: &GlFns) {
+        if let Some(compute) = self.compute.take() {
+            compute.delete(gl);
+        }
+        unsafe {
+            gl.DeleteTextures(1, &self.heights);
+            gl.DeleteBuffers(1, &self.heights_buffer);
+        }
+    }
+
+    fn build_projection_matrix(&mut self, system: &system::System, fov_rad: f32) {
+        self.mvp.projection = projection::rh_yup::perspective_gl(
+            fov_rad,
+            (system.w as f32) / (system.h as f32),
+            0.1,
+            100.0,
+        );
+    }
+}
+
+/// Height of the waves at texture coordinates (u, v), demo23_heights.cs on the CPU
+fn height(u: f32, v: f32, time: f32) -> f32 {
+    0.3 * (u * 9.0 + time).sin() * (v * 7.0 - time * 0.7).cos()
+}
@@ -13,6 +13,8 @@ mod demo19;
 mod demo2;
 mod demo20;
 mod demo21;
+mod demo22;
+mod demo23;
 mod demo3;
 mod demo4;
 mod demo5;
@@ -129,6 +131,17 @@ static DEMO21: demo21::DemoN = demo21::DemoN {
         "Learn OpenGL - Graphics Programming ('Advanced Lighting'): omnidirectional shadow maps",
 };
 
+static DEMO22: demo22::DemoN = demo22::DemoN {
+    name: "demo22",
+    description: "Learn OpenGL - Graphics Programming ('Guest Articles'): skeletal animation",
+};
+
+static DEMO23: demo23::DemoN = demo23::DemoN {
+    name: "demo23",
+    description:
+        "Learn OpenGL - Graphics Programming ('Guest Articles'): tessellation and compute shaders",
+};
+
 pub trait Demo {
     fn run(&self) -> Result<(), String>;
     fn name(&self) -> String;
@@ -139,5 +152,16 @@ pub fn get_all_demos() -> Vec<&'static dyn Demo> {
     vec![
         &DEMO1, &DEMO2, &DEMO3, &DEMO4, &DEMO5, &DEMO6, &DEMO7, &DEMO8, &DEMO9, &DEMO10, &DEMO11,
         &DEMO12, &DEMO13, &DEMO14, &DEMO15, &DEMO16, &DEMO17, &DEMO18, &DEMO19, &DEMO20, &DEMO21,
+        &DEMO22, &DEMO23,
     ]
 }
+
+/// Every GLSL source embedded in the demo code as (name, stage file extension, code),
+/// so it can be validated like the shader files under demo/
+pub fn inline_shaders() -> Vec<(&'static str, &'static str, &'static str)> {
+    let mut shaders = vec![];
+    shaders.extend_from_slice(&demo16::INLINE_SHADERS);
+    shaders.extend_from_slice(&demo17::INLINE_SHADERS);
+    shaders.extend_from_slice(&demo19::INLINE_SHADERS);
+    shaders
+}
@@ -0,0 +1,362 @@
+use super::models::Model;
+use super::shaders::Shaders;
+use gl33::*;
+use std::collections::HashMap;
+use ultraviolet::*;
+
+/// Size of the bone palette of the skinning shader, see demo/include/skinning.glsl
+pub const MAX_BONES: usize = 100;
+/// Bones influencing one vertex, extra weights are dropped on import
+pub const MAX_BONE_INFLUENCE: usize = 4;
+
+/// Ticks per second assumed for clips that don't state it
+#[cfg(feature = "assimp")]
+const DEFAULT_TICKS_PER_SECOND: f64 = 25.0;
+
+#[derive(Clone, Debug)]
+pub struct Bone {
+    /// Name of the node the bone follows
+    pub name: String,
+    /// Mesh space to bone space in bind pose (the assimp offset matrix)
+    pub offset: Mat4,
+}
+
+/// Bones shared by all meshes of a model, vertex bone ids index `bones`
+#[derive(Clone, Debug, Default)]
+pub struct Skeleton {
+    pub bones: Vec<Bone>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Skeleton {
+    pub fn bone(&self, name: &str) -> Option<usize> {
+        self.by_name.get(name).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bones.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bones.is_empty()
+    }
+
+    /// Index of bone `name`, added with `offset` the first time it is seen
+    pub fn add_bone(&mut self, name: &str, offset: Mat4) -> usize {
+        if let Some(index) = self.bone(name) {
+            return index;
+        }
+        self.bones.push(Bone {
+            name: name.to_string(),
+            offset,
+        });
+        self.by_name.insert(name.to_string(), self.bones.len() - 1);
+        self.bones.len() - 1
+    }
+}
+
+/// Local transform split the way keys are interpolated
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Rotor3,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: Vec3::zero(),
+            rotation: Rotor3::identity(),
+            scale: Vec3::one(),
+        }
+    }
+}
+
+impl Transform {
+    /// Splits a matrix without shear, like the node matrices of imported scenes
+    pub fn from_mat4(m: &Mat4) -> Transform {
+        let columns = [m.cols[0].xyz(), m.cols[1].xyz(), m.cols[2].xyz()];
+        let scale = Vec3::new(columns[0].mag(), columns[1].mag(), columns[2].mag());
+        let axis = |i: usize| {
+            if scale[i] > 0.0 {
+                columns[i] / scale[i]
+            } else {
+                Vec3::zero()
+            }
+        };
+        Transform {
+            translation: m.cols[3].xyz(),
+            rotation: Mat3::new(axis(0), axis(1), axis(2))
+                .into_rotor3()
+                .normalized(),
+            scale,
+        }
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_translation(self.translation)
+            * self.rotation.into_matrix().into_homogeneous()
+            * Mat4::from_nonuniform_scale(self.scale)
+    }
+
+    /// `self` moved towards `other` by `t` in 0..=1
+    pub fn blend(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Key<T> {
+    /// Seconds from the start of the clip
+    pub time: f32,
+    pub value: T,
+}
+
+/// Keys animating one node, components without keys keep the rest pose
+#[derive(Clone, Debug, Default)]
+pub struct Channel {
+    pub node: String,
+    pub positions: Vec<Key<Vec3>>,
+    pub rotations: Vec<Key<Rotor3>>,
+    pub scales: Vec<Key<Vec3>>,
+}
+
+impl Channel {
+    pub fn sample(&self, time: f32, rest: &Transform) -> Transform {
+        Transform {
+            translation: sample_keys(&self.positions, time, |a, b, t| a.lerp(b, t))
+                .unwrap_or(rest.translation),
+            rotation: sample_keys(&self.rotations, time, |a, b, t| a.slerp(b, t))
+                .unwrap_or(rest.rotation),
+            scale: sample_keys(&self.scales, time, |a, b, t| a.lerp(b, t)).unwrap_or(rest.scale),
+        }
+    }
+}
+
+// keys are sorted by time, before the first and after the last key the
+// value is held
+fn sample_keys<T: Copy>(keys: &[Key<T>], time: f32, mix: impl Fn(T, T, f32) -> T) -> Option<T> {
+    let first = keys.first()?;
+    let next = keys.partition_point(|k| k.time <= time);
+    if next == 0 {
+        return Some(first.value);
+    }
+    if next == keys.len() {
+        return Some(keys[next - 1].value);
+    }
+    let (a, b) = (&keys[next - 1], &keys[next]);
+    let span = b.time - a.time;
+    let t = if span > 0.0 {
+        (time - a.time) / span
+    } else {
+        0.0
+    };
+    Some(mix(a.value, b.value, t))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AnimationClip {
+    pub name: String,
+    /// Seconds
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+    by_node: HashMap<String, usize>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, duration: f32, channels: Vec<Channel>) -> Self {
+        let by_node = channels
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.node.clone(), i))
+            .collect();
+        AnimationClip {
+            name: name.to_string(),
+            duration,
+            channels,
+            by_node,
+        }
+    }
+
+    /// Converts key times from ticks to seconds
+    #[cfg(feature = "assimp")]
+    pub fn from_assimp(anim: &russimp::animation::Animation) -> Self {
+        let ticks_per_second = if anim.ticks_per_second > 0.0 {
+            anim.ticks_per_second
+        } else {
+            DEFAULT_TICKS_PER_SECOND
+        };
+        let seconds = |ticks: f64| (ticks / ticks_per_second) as f32;
+        let channels = anim
+            .channels
+            .iter()
+            .map(|c| Channel {
+                node: c.name.clone(),
+                positions: c
+                    .position_keys
+                    .iter()
+                    .map(|k| Key {
+                        time: seconds(k.time),
+                        value: Vec3::new(k.value.x, k.value.y, k.value.z),
+                    })
+                    .collect(),
+                rotations: c
+                    .rotation_keys
+                    .iter()
+                    .map(|k| Key {
+                        time: seconds(k.time),
+                        value: Rotor3::from_quaternion_array([
+                            k.value.x, k.value.y, k.value.z, k.value.w,
+                        ]),
+                    })
+                    .collect(),
+                scales: c
+                    .scaling_keys
+                    .iter()
+                    .map(|k| Key {
+                        time: seconds(k.time),
+                        value: Vec3::new(k.value.x, k.value.y, k.value.z),
+                    })
+                    .collect(),
+            })
+            .collect();
+        Self::new(&anim.name, seconds(anim.duration), channels)
+    }
+
+    pub fn channel(&self, node: &str) -> Option<&Channel> {
+        self.by_node.get(node).map(|i| &self.channels[*i])
+    }
+
+    /// Looping clip time for `time` seconds of playback
+    pub fn wrap(&self, time: f32) -> f32 {
+        if self.duration > 0.0 {
+            time.rem_euclid(self.duration)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Plays the clips of a model, optionally blending two of them, and keeps the
+/// resulting bone matrices for the skinning shader.
+///
+/// ```ignore
+/// animator.advance(dt);
+/// animator.update(&model);
+/// animator.upload(gl, &shader);
+/// model.draw_at(gl, &shader, &placement);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Animator {
+    pub clip: usize,
+    pub time: f32,
+    blend: Option<(usize, f32)>,
+    blend_time: f32,
+    palette: Vec<Mat4>,
+}
+
+impl Animator {
+    pub fn new(clip: usize) -> Self {
+        Animator {
+            clip,
+            ..Default::default()
+        }
+    }
+
+    /// Switches to `clip` from its start, ending any blend
+    pub fn play(&mut self, clip: usize) {
+        self.clip = clip;
+        self.time = 0.0;
+        self.blend = None;
+    }
+
+    /// Mixes `clip` in with `weight` (0 = only the current clip, 1 = only `clip`)
+    pub fn blend_with(&mut self, clip: usize, weight: f32) {
+        if self.blend.map(|(c, _)| c) != Some(clip) {
+            self.blend_time = 0.0;
+        }
+        self.blend = Some((clip, weight.clamp(0.0, 1.0)));
+    }
+
+    pub fn stop_blend(&mut self) {
+        self.blend = None;
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        self.time += dt;
+        self.blend_time += dt;
+    }
+
+    /// Poses the node hierarchy of `model` and computes the bone palette
+    pub fn update(&mut self, model: &Model) {
+        let clips = &model.animations;
+        let current = clips.get(self.clip);
+        let blend = self
+            .blend
+            .and_then(|(c, w)| clips.get(c).map(|clip| (clip, w)));
+
+        self.palette.clear();
+        self.palette
+            .resize(model.skeleton.len().min(MAX_BONES), Mat4::identity());
+
+        // vertices are in mesh space, undo the root transform assimp bakes in
+        let global_inverse = model
+            .nodes
+            .first()
+            .map(|n| n.world.inversed())
+            .unwrap_or(Mat4::identity());
+
+        let mut globals: Vec<Mat4> = Vec::with_capacity(model.nodes.len());
+        for node in model.nodes.iter() {
+            let channel_a = current.and_then(|c| c.channel(&node.name));
+            let channel_b = blend.and_then(|(c, _)| c.channel(&node.name));
+            let local = match (channel_a, channel_b, blend) {
+                (None, None, _) => node.local,
+                (Some(a), None, None) => {
+                    let rest = Transform::from_mat4(&node.local);
+                    a.sample(current.unwrap().wrap(self.time), &rest).to_mat4()
+                }
+                (a, b, _) => {
+                    let rest = Transform::from_mat4(&node.local);
+                    let pose_a = match a {
+                        Some(a) => a.sample(current.unwrap().wrap(self.time), &rest),
+                        None => rest,
+                    };
+                    let (clip_b, weight) = blend.unwrap();
+                    let pose_b = match b {
+                        Some(b) => b.sample(clip_b.wrap(self.blend_time), &rest),
+                        None => rest,
+                    };
+                    pose_a.blend(&pose_b, weight).to_mat4()
+                }
+            };
+            let global = match node.parent {
+                Some(p) => globals[p] * local,
+                None => local,
+            };
+            globals.push(global);
+
+            if let Some(bone) = model.skeleton.bone(&node.name) {
+                if bone < self.palette.len() {
+                    self.palette[bone] =
+                        global_inverse * global * model.skeleton.bones[bone].offset;
+                }
+            }
+        }
+    }
+
+    /// Bone matrices of the last `update`, indexed like `Skeleton::bones`
+    pub fn palette(&self) -> &[Mat4] {
+        &self.palette
+    }
+
+    /// Sets the `bones` array of the skinning shader, which must be in use
+    pub fn upload(&self, gl: &GlFns, shader: &Shaders) {
+        shader.set(gl, "bones", self.palette.as_slice());
+    }
+}
@@ -0,0 +1,330 @@
+use super::glutils;
+use super::models::Model;
+use super::shaders::Shaders;
+use gl33::*;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+
+/// OpenGL object owned by an asset, released by `AssetManager::collect_garbage`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlObject {
+    Texture(u32),
+    Buffer(u32),
+    VertexArray(u32),
+    Program(u32),
+}
+
+impl GlObject {
+    fn delete(&self, gl: &GlFns) {
+        unsafe {
+            match self {
+                GlObject::Texture(id) => gl.DeleteTextures(1, id),
+                GlObject::Buffer(id) => gl.DeleteBuffers(1, id),
+                GlObject::VertexArray(id) => gl.DeleteVertexArrays(1, id),
+                GlObject::Program(id) => gl.DeleteProgram(*id),
+            }
+        }
+    }
+}
+
+/// Anything that owns GPU objects and can hand them over for deletion
+pub trait GpuResource {
+    fn gl_objects(&self) -> Vec<GlObject>;
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GpuTexture {
+    pub id: u32,
+    pub target: GLenum,
+}
+
+impl Default for GpuTexture {
+    fn default() -> Self {
+        GpuTexture {
+            id: 0,
+            target: GL_TEXTURE_2D,
+        }
+    }
+}
+
+impl GpuResource for GpuTexture {
+    fn gl_objects(&self) -> Vec<GlObject> {
+        if self.id == 0 {
+            return vec![];
+        }
+        vec![GlObject::Texture(self.id)]
+    }
+}
+
+impl GpuResource for Model {
+    fn gl_objects(&self) -> Vec<GlObject> {
+        let mut objects = vec![];
+        for m in self.meshes.iter() {
+            if m.gl_vao != 0 {
+                objects.push(GlObject::VertexArray(m.gl_vao));
+            }
+            if m.gl_vbo != 0 {
+                objects.push(GlObject::Buffer(m.gl_vbo));
+            }
+            if m.gl_ebo != 0 {
+                objects.push(GlObject::Buffer(m.gl_ebo));
+            }
+        }
+        objects.extend(self.owned_textures().into_iter().map(GlObject::Texture));
+        objects
+    }
+}
+
+impl GpuResource for Shaders {
+    fn gl_objects(&self) -> Vec<GlObject> {
+        if self.id() == 0 {
+            return vec![];
+        }
+        vec![GlObject::Program(self.id())]
+    }
+}
+
+type Graveyard = Rc<RefCell<Vec<GlObject>>>;
+
+struct Asset<T: GpuResource> {
+    value: T,
+    graveyard: Option<Graveyard>,
+}
+
+impl<T: GpuResource> Drop for Asset<T> {
+    fn drop(&mut self) {
+        if let Some(graveyard) = &self.graveyard {
+            graveyard.borrow_mut().extend(self.value.gl_objects());
+        }
+    }
+}
+
+/// Reference-counted handle to a shared asset.
+///
+/// GPU objects are queued for deletion when the last handle drops and freed
+/// by the next `AssetManager::collect_garbage` call. A default handle is not
+/// tracked by any manager and never frees anything.
+pub struct Handle<T: GpuResource> {
+    asset: Rc<Asset<T>>,
+}
+
+impl<T: GpuResource> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle {
+            asset: self.asset.clone(),
+        }
+    }
+}
+
+impl<T: GpuResource + Default> Default for Handle<T> {
+    fn default() -> Self {
+        Handle {
+            asset: Rc::new(Asset {
+                value: T::default(),
+                graveyard: None,
+            }),
+        }
+    }
+}
+
+impl<T: GpuResource> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.asset.value
+    }
+}
+
+impl<T: GpuResource> Handle<T> {
+    pub fn ref_count(&self) -> usize {
+        Rc::strong_count(&self.asset)
+    }
+}
+
+pub type TextureHandle = Handle<GpuTexture>;
+pub type ModelHandle = Handle<Model>;
+pub type ShadersHandle = Handle<Shaders>;
+
+#[derive(PartialEq, Eq, Hash)]
+struct TextureKey {
+    path: PathBuf,
+    params: Vec<(GLenum, GLenum, GLenum)>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct ShadersKey {
+    vertex: PathBuf,
+    fragment: PathBuf,
+    geometry: Option<PathBuf>,
+}
+
+/// Central cache of textures, models and shader programs.
+///
+/// Assets are keyed by canonical path (and loading options) so the same file
+/// is uploaded to the GPU only once, no matter how many places ask for it.
+#[derive(Default)]
+pub struct AssetManager {
+    textures: HashMap<TextureKey, Weak<Asset<GpuTexture>>>,
+    models: HashMap<PathBuf, Weak<Asset<Model>>>,
+    shaders: HashMap<ShadersKey, Weak<Asset<Shaders>>>,
+    graveyard: Graveyard,
+}
+
+impl AssetManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn texture(&mut self, gl: &GlFns, filename: &str) -> Result<TextureHandle, String> {
+        let params = [
+            (GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT),
+            (GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT),
+            (GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR),
+            (GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR),
+        ];
+        self.texture_params(gl, filename, &params)
+    }
+
+    pub fn texture_params(
+        &mut self,
+        gl: &GlFns,
+        filename: &str,
+        params: &[(GLenum, GLenum, GLenum)],
+    ) -> Result<TextureHandle, String> {
+        let key = TextureKey {
+            path: canonical_path(filename),
+            params: params.to_vec(),
+        };
+        if let Some(asset) = self.textures.get(&key).and_then(Weak::upgrade) {
+            return Ok(Handle { asset });
+        }
+
+        let id = glutils::load_texture_params(gl, filename, params)?;
+        let texture = GpuTexture {
+            id,
+            target: GL_TEXTURE_2D,
+        };
+        Ok(Self::track(
+            &mut self.textures,
+            key,
+            texture,
+            &self.graveyard,
+        ))
+    }
+
+    /// Loads and uploads (`Model::setup`) a model, sharing its textures with
+    /// every other asset loaded through this manager
+    pub fn model(&mut self, gl: &GlFns, filename: &str) -> Result<ModelHandle, String> {
+        let key = canonical_path(filename);
+        if let Some(asset) = self.models.get(&key).and_then(Weak::upgrade) {
+            return Ok(Handle { asset });
+        }
+
+        let mut model = Model::from_with_assets(gl, filename, self)?;
+        model.setup(gl)?;
+        Ok(Self::track(&mut self.models, key, model, &self.graveyard))
+    }
+
+    pub fn shaders(
+        &mut self,
+        gl: &GlFns,
+        vertex_file: &str,
+        fragment_file: &str,
+    ) -> Result<ShadersHandle, String> {
+        self.shaders_full(gl, vertex_file, fragment_file, "")
+    }
+
+    pub fn shaders_full(
+        &mut self,
+        gl: &GlFns,
+        vertex_file: &str,
+        fragment_file: &str,
+        geometry_file: &str,
+    ) -> Result<ShadersHandle, String> {
+        let key = ShadersKey {
+            vertex: canonical_path(vertex_file),
+            fragment: canonical_path(fragment_file),
+            geometry: if geometry_file.is_empty() {
+                None
+            } else {
+                Some(canonical_path(geometry_file))
+            },
+        };
+        if let Some(asset) = self.shaders.get(&key).and_then(Weak::upgrade) {
+            return Ok(Handle { asset });
+        }
+
+        let shaders = Shaders::from_files_full(gl, vertex_file, fragment_file, geometry_file)?;
+        Ok(Self::track(
+            &mut self.shaders,
+            key,
+            shaders,
+            &self.graveyard,
+        ))
+    }
+
+    /// Hot reloads the live shader programs whose source files changed, see
+    /// `Shaders::reload_if_changed`. Every handle sees the new program. Returns
+    /// true when at least one program was swapped.
+    pub fn reload_changed_shaders(&self, gl: &GlFns) -> bool {
+        let mut reloaded = false;
+        for asset in self.shaders.values().filter_map(Weak::upgrade) {
+            reloaded |= asset.value.reload_if_changed(gl);
+        }
+        reloaded
+    }
+
+    /// Deletes GPU objects of assets whose last handle has been dropped.
+    /// Call it regularly (e.g. once per frame) and before tearing down the context.
+    pub fn collect_garbage(&mut self, gl: &GlFns) {
+        self.textures.retain(|_, w| w.strong_count() > 0);
+        self.models.retain(|_, w| w.strong_count() > 0);
+        self.shaders.retain(|_, w| w.strong_count() > 0);
+
+        for obj in self.graveyard.borrow_mut().drain(..) {
+            obj.delete(gl);
+        }
+        glutils::check_gl_err(gl);
+    }
+
+    /// Number of assets that are still referenced by at least one handle
+    pub fn live_assets(&self) -> usize {
+        self.textures
+            .values()
+            .filter(|w| w.strong_count() > 0)
+            .count()
+            + self
+                .models
+                .values()
+                .filter(|w| w.strong_count() > 0)
+                .count()
+            + self
+                .shaders
+                .values()
+                .filter(|w| w.strong_count() > 0)
+                .count()
+    }
+
+    fn track<K: std::hash::Hash + Eq, T: GpuResource>(
+        cache: &mut HashMap<K, Weak<Asset<T>>>,
+        key: K,
+        value: T,
+        graveyard: &Graveyard,
+    ) -> Handle<T> {
+        let asset = Rc::new(Asset {
+            value,
+            graveyard: Some(graveyard.clone()),
+        });
+        cache.insert(key, Rc::downgrade(&asset));
+        Handle { asset }
+    }
+}
+
+fn canonical_path(filename: &str) -> PathBuf {
+    Path::new(filename)
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from(filename))
+}
@@ -0,0 +1,289 @@
+use ultraviolet::*;
+
+/// Axis-aligned bounding box, empty (min > max) until a point is added
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb {
+            min: Vec3::broadcast(f32::INFINITY),
+            max: Vec3::broadcast(f32::NEG_INFINITY),
+        }
+    }
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut aabb = Aabb::default();
+        points.into_iter().for_each(|p| aabb.add_point(p));
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn add_point(&mut self, p: Vec3) {
+        self.min = self.min.min_by_component(p);
+        self.max = self.max.max_by_component(p);
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min_by_component(other.min),
+            max: self.max.max_by_component(other.max),
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z),
+            Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z),
+            Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z),
+            Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z),
+            Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    /// Box around this box transformed by `m` (Arvo's method, no corner loop)
+    pub fn transformed(&self, m: &Mat4) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        let center = m.transform_point3(self.center());
+        let half = self.size() * 0.5;
+        let mut extent = Vec3::zero();
+        for col in 0..3 {
+            extent += m.cols[col].xyz().abs() * half[col];
+        }
+        Aabb {
+            min: center - extent,
+            max: center + extent,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    /// Sphere around `points` centered on their bounding box, not the smallest
+    /// one but close for typical meshes
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let center = Aabb::from_points(points.iter().copied()).center();
+        let radius = points
+            .iter()
+            .map(|p| (*p - center).mag())
+            .fold(0.0, f32::max);
+        Sphere { center, radius }
+    }
+
+    /// Scaled by the largest scale of `m`, so non-uniform scales give a
+    /// sphere that still contains the shape
+    pub fn transformed(&self, m: &Mat4) -> Sphere {
+        let scale = (0..3).map(|c| m.cols[c].xyz().mag()).fold(0.0, f32::max);
+        Sphere {
+            center: m.transform_point3(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// The six planes of a view frustum, normals pointing inwards
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    /// (normal, distance), points inside have `dot(normal, p) + distance >= 0`
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Planes of `projection * view` (Gribb and Hartmann), in world space. With
+    /// `projection * view * model` they are in the model space instead.
+    pub fn from_matrix(m: &Mat4) -> Self {
+        let row = |r: usize| Vec4::new(m.cols[0][r], m.cols[1][r], m.cols[2][r], m.cols[3][r]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, w + z, w - z].map(|p| {
+            let len = p.xyz().mag();
+            if len > 0.0 {
+                p / len
+            } else {
+                p
+            }
+        });
+        Frustum { planes }
+    }
+
+    /// False only when the box is completely outside one plane, boxes near
+    /// the frustum corners can pass without being visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|p| {
+            // the corner furthest along the normal
+            let corner = Vec3::new(
+                if p.x >= 0.0 { aabb.max.x } else { aabb.min.x },
+                if p.y >= 0.0 { aabb.max.y } else { aabb.min.y },
+                if p.z >= 0.0 { aabb.max.z } else { aabb.min.z },
+            );
+            p.xyz().dot(corner) + p.w >= 0.0
+        })
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|p| p.xyz().dot(sphere.center) + p.w >= -sphere.radius)
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Hit distances are in multiples of its length
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// World space ray through the pixel (`x`, `y`), measured from the top left
+    /// corner of a `width` x `height` window like mouse positions
+    pub fn from_screen(
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        projection: &Mat4,
+        view: &Mat4,
+    ) -> Ray {
+        let ndc = Vec2::new(2.0 * x / width - 1.0, 1.0 - 2.0 * y / height);
+        let inverse = (*projection * *view).inversed();
+        let unproject = |z: f32| {
+            let p = inverse * Vec4::new(ndc.x, ndc.y, z, 1.0);
+            p.xyz() / p.w
+        };
+        let near = unproject(-1.0);
+        Ray {
+            origin: near,
+            direction: (unproject(1.0) - near).normalized(),
+        }
+    }
+
+    /// The same ray in the space `m` maps to, distances along it are unchanged
+    pub fn transformed(&self, m: &Mat4) -> Ray {
+        Ray {
+            origin: m.transform_point3(self.origin),
+            direction: m.transform_vec3(self.direction),
+        }
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    /// Distance to the box, 0 when the origin is inside
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let (mut near, mut far) = (0.0f32, f32::INFINITY);
+        for i in 0..3 {
+            let inv = 1.0 / self.direction[i];
+            let t0 = (aabb.min[i] - self.origin[i]) * inv;
+            let t1 = (aabb.max[i] - self.origin[i]) * inv;
+            // NaN from a zero direction on the slab boundary keeps the bounds
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        (near <= far).then_some(near)
+    }
+
+    pub fn intersect_sphere(&self, sphere: &Sphere) -> Option<f32> {
+        let oc = self.origin - sphere.center;
+        let a = self.direction.mag_sq();
+        let b = oc.dot(self.direction);
+        let c = oc.mag_sq() - sphere.radius * sphere.radius;
+        let discriminant = b * b - a * c;
+        if discriminant < 0.0 || a == 0.0 {
+            return None;
+        }
+        let sqrt = discriminant.sqrt();
+        let t = (-b - sqrt) / a;
+        if t >= 0.0 {
+            Some(t)
+        } else {
+            let t = (-b + sqrt) / a;
+            (t >= 0.0).then_some(0.0)
+        }
+    }
+
+    /// Möller-Trumbore, triangles are hit from both sides
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+        let (e1, e2) = (b - a, c - a);
+        let p = self.direction.cross(e2);
+        let det = e1.dot(p);
+        // parallel to the triangle, relative to the sizes so small meshes still hit
+        if det.abs() <= f32::EPSILON * e1.mag() * e2.mag() * self.direction.mag() {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(e1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = e2.dot(q) * inv_det;
+        (t >= 0.0).then_some(t)
+    }
+}
+
+/// Light space matrix (projection * view) of a directional light shining
+/// along `direction`, with the orthographic frustum fitted around the shadow
+/// `casters` so the whole shadow map resolution goes to their shadows. The
+/// depth range reaches far enough to include the `receivers`, parts of them
+/// outside the map must be taken as lit (border depth 1).
+pub fn fit_directional_shadow(direction: Vec3, casters: &Aabb, receivers: &Aabb) -> Mat4 {
+    let direction = direction.normalized();
+    let center = casters.center();
+    let up = if direction.cross(Vec3::unit_y()).mag_sq() < 1e-6 {
+        Vec3::unit_z()
+    } else {
+        Vec3::unit_y()
+    };
+    let view = Mat4::look_at(center - direction, center, up);
+    let to_light =
+        |aabb: &Aabb| Aabb::from_points(aabb.corners().map(|c| view.transform_point3(c)));
+    let light = to_light(casters);
+    let far = to_light(receivers).min.z.min(light.min.z);
+    // the light looks down -z
+    let projection = projection::rh_yup::orthographic_gl(
+        light.min.x,
+        light.max.x,
+        light.min.y,
+        light.max.y,
+        -light.max.z,
+        -far,
+    );
+    projection * view
+}
@@ -1,3 +1,4 @@
+use super::bounds::Sphere;
 use ultraviolet::*;
 
 pub enum CamMovement {
@@ -62,6 +63,15 @@ impl Camera {
         Mat4::look_at(self.position, self.position + self.front, self.up)
     }
 
+    /// Moves the camera back along its view direction until `sphere` fits in a
+    /// perspective projection of `fov_y` (radians) and `aspect`
+    pub fn frame(&mut self, sphere: &Sphere, fov_y: f32, aspect: f32) {
+        let half_y = fov_y * 0.5;
+        let half_x = (half_y.tan() * aspect).atan();
+        let distance = sphere.radius / half_y.min(half_x).sin();
+        self.position = sphere.center - self.front * distance;
+    }
+
     pub fn process_keyboard(&mut self, direction: CamMovement, delta_time: f32) {
         let velocity = self.movement_speed * delta_time;
 
@@ -0,0 +1,119 @@
+use super::glext::{
+    self, GL_COMPUTE_WORK_GROUP_SIZE, GL_DISPATCH_INDIRECT_BUFFER, GL_MAX_COMPUTE_WORK_GROUP_COUNT,
+};
+use super::preprocessor::Defines;
+use super::program::{ProgramBuilder, ShaderStage};
+use super::shaders::Shaders;
+use gl33::*;
+
+/// A program made of a single compute shader (GL 4.3 or GL_ARB_compute_shader).
+///
+/// Uniforms are set through `shaders()`, results written to images or storage
+/// buffers must be made visible with `glext::memory_barrier` before use.
+pub struct ComputeProgram {
+    shaders: Shaders,
+    local_size: [u32; 3],
+}
+
+impl ComputeProgram {
+    pub fn from_file(gl: &GlFns, path: &str, defines: &Defines) -> Result<ComputeProgram, String> {
+        let shaders = ProgramBuilder::new()
+            .defines(defines)
+            .file(ShaderStage::Compute, path)
+            .build(gl)?;
+        Ok(Self::new(gl, shaders))
+    }
+
+    pub fn from_str(gl: &GlFns, code: &str) -> Result<ComputeProgram, String> {
+        let shaders = ProgramBuilder::new()
+            .source(ShaderStage::Compute, code)
+            .build(gl)?;
+        Ok(Self::new(gl, shaders))
+    }
+
+    fn new(gl: &GlFns, shaders: Shaders) -> ComputeProgram {
+        let local_size = query_local_size(gl, shaders.id());
+        ComputeProgram {
+            shaders,
+            local_size,
+        }
+    }
+
+    pub fn shaders(&self) -> &Shaders {
+        &self.shaders
+    }
+
+    /// `local_size_x/y/z` declared by the shader
+    pub fn local_size(&self) -> [u32; 3] {
+        self.local_size
+    }
+
+    pub fn reload_if_changed(&mut self, gl: &GlFns) -> bool {
+        let reloaded = self.shaders.reload_if_changed(gl);
+        if reloaded {
+            self.local_size = query_local_size(gl, self.shaders.id());
+        }
+        reloaded
+    }
+
+    /// Runs `groups` work groups, binding the program first
+    pub fn dispatch(&self, gl: &GlFns, groups: [u32; 3]) -> Result<(), String> {
+        let dispatch_compute = glext::ext()
+            .dispatch_compute
+            .ok_or("glDispatchCompute not available")?;
+        for (axis, count) in groups.iter().enumerate() {
+            let mut max = 0;
+            unsafe {
+                gl.GetIntegeri_v(GL_MAX_COMPUTE_WORK_GROUP_COUNT, axis as u32, &mut max);
+            }
+            if *count > max as u32 {
+                return Err(format!(
+                    "program({}): {} work groups on axis {} exceed the limit of {}",
+                    self.shaders.id(),
+                    count,
+                    axis,
+                    max
+                ));
+            }
+        }
+        self.shaders.use_program(gl);
+        unsafe { dispatch_compute(groups[0], groups[1], groups[2]) };
+        Ok(())
+    }
+
+    /// Runs enough work groups to cover `items` invocations per axis, the shader
+    /// has to skip the invocations past the end
+    pub fn dispatch_items(&self, gl: &GlFns, items: [u32; 3]) -> Result<(), String> {
+        let mut groups = [0; 3];
+        for axis in 0..3 {
+            groups[axis] = items[axis].div_ceil(self.local_size[axis].max(1));
+        }
+        self.dispatch(gl, groups)
+    }
+
+    /// Reads the group counts (three u32) from `buffer` at `offset`
+    pub fn dispatch_indirect(&self, gl: &GlFns, buffer: u32, offset: usize) -> Result<(), String> {
+        let dispatch_compute_indirect = glext::ext()
+            .dispatch_compute_indirect
+            .ok_or("glDispatchComputeIndirect not available")?;
+        self.shaders.use_program(gl);
+        unsafe {
+            gl.BindBuffer(GL_DISPATCH_INDIRECT_BUFFER, buffer);
+            dispatch_compute_indirect(offset as isize);
+            gl.BindBuffer(GL_DISPATCH_INDIRECT_BUFFER, 0);
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, gl: &GlFns) {
+        gl.DeleteProgram(self.shaders.id());
+    }
+}
+
+fn query_local_size(gl: &GlFns, program: u32) -> [u32; 3] {
+    let mut size = [0i32; 3];
+    unsafe {
+        gl.GetProgramiv(program, GL_COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
+    }
+    size.map(|s| s.max(0) as u32)
+}
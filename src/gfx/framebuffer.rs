@@ -1,3 +1,4 @@
+use super::glutils;
 use gl33::*;
 use ultraviolet::*;
 
@@ -155,6 +156,14 @@ impl FrameBuffer {
         }
     }
 
+    /// Writes the `index`-th attached texture to an image file (see `glutils::save_texture`)
+    pub fn save_attachment(&self, gl: &GlFns, index: usize, path: &str) -> Result<(), String> {
+        match self.tex.get(index) {
+            Some(tex) => glutils::save_texture(gl, *tex, gl33::GL_TEXTURE_2D, 0, path),
+            None => Err(format!("framebuffer has no attachment #{}", index)),
+        }
+    }
+
     pub fn delete(&mut self, gl: &GlFns) {
         unsafe {
             gl.DeleteFramebuffers(1, &self.fbo);
@@ -0,0 +1,142 @@
+use fermium::video::SDL_GL_GetProcAddress;
+use gl33::*;
+use std::ffi::{c_void, CStr};
+use std::sync::OnceLock;
+
+// enums missing from the 3.3 core bindings
+pub const GL_PROGRAM_BINARY_RETRIEVABLE_HINT: GLenum = GLenum(0x8257);
+pub const GL_PROGRAM_BINARY_LENGTH: GLenum = GLenum(0x8741);
+pub const GL_NUM_PROGRAM_BINARY_FORMATS: GLenum = GLenum(0x87FE);
+pub const GL_TESS_CONTROL_SHADER: GLenum = GLenum(0x8E88);
+pub const GL_TESS_EVALUATION_SHADER: GLenum = GLenum(0x8E87);
+pub const GL_PATCHES: GLenum = GLenum(0x000E);
+pub const GL_PATCH_VERTICES: GLenum = GLenum(0x8E72);
+pub const GL_COMPUTE_SHADER: GLenum = GLenum(0x91B9);
+pub const GL_COMPUTE_WORK_GROUP_SIZE: GLenum = GLenum(0x8267);
+pub const GL_MAX_COMPUTE_WORK_GROUP_COUNT: GLenum = GLenum(0x91BE);
+pub const GL_DISPATCH_INDIRECT_BUFFER: GLenum = GLenum(0x90EE);
+pub const GL_SHADER_STORAGE_BUFFER: GLenum = GLenum(0x90D2);
+pub const GL_SHADER_IMAGE_ACCESS_BARRIER_BIT: u32 = 0x0000_0020;
+pub const GL_TEXTURE_FETCH_BARRIER_BIT: u32 = 0x0000_0008;
+pub const GL_PIXEL_BUFFER_BARRIER_BIT: u32 = 0x0000_0080;
+pub const GL_SHADER_STORAGE_BARRIER_BIT: u32 = 0x0000_2000;
+pub const GL_ALL_BARRIER_BITS: u32 = 0xFFFF_FFFF;
+
+type GetProgramBinaryFn = unsafe extern "system" fn(u32, i32, *mut i32, *mut GLenum, *mut c_void);
+type ProgramBinaryFn = unsafe extern "system" fn(u32, GLenum, *const c_void, i32);
+type ProgramParameteriFn = unsafe extern "system" fn(u32, GLenum, i32);
+type PatchParameteriFn = unsafe extern "system" fn(GLenum, i32);
+type DispatchComputeFn = unsafe extern "system" fn(u32, u32, u32);
+type DispatchComputeIndirectFn = unsafe extern "system" fn(isize);
+type MemoryBarrierFn = unsafe extern "system" fn(u32);
+
+/// GL entry points newer than the 3.3 core `GlFns` covers, `None` when the
+/// driver doesn't export them. Check the matching extension before calling.
+pub struct GlExt {
+    pub get_program_binary: Option<GetProgramBinaryFn>,
+    pub program_binary: Option<ProgramBinaryFn>,
+    pub program_parameteri: Option<ProgramParameteriFn>,
+    pub patch_parameteri: Option<PatchParameteriFn>,
+    pub dispatch_compute: Option<DispatchComputeFn>,
+    pub dispatch_compute_indirect: Option<DispatchComputeIndirectFn>,
+    pub memory_barrier: Option<MemoryBarrierFn>,
+}
+
+static GL_EXT: OnceLock<GlExt> = OnceLock::new();
+
+/// Entry points of the current context, loaded on first use
+pub fn ext() -> &'static GlExt {
+    GL_EXT.get_or_init(|| unsafe {
+        GlExt {
+            get_program_binary: std::mem::transmute::<*mut c_void, Option<GetProgramBinaryFn>>(
+                proc_address(c"glGetProgramBinary"),
+            ),
+            program_binary: std::mem::transmute::<*mut c_void, Option<ProgramBinaryFn>>(
+                proc_address(c"glProgramBinary"),
+            ),
+            program_parameteri: std::mem::transmute::<*mut c_void, Option<ProgramParameteriFn>>(
+                proc_address(c"glProgramParameteri"),
+            ),
+            patch_parameteri: std::mem::transmute::<*mut c_void, Option<PatchParameteriFn>>(
+                proc_address(c"glPatchParameteri"),
+            ),
+            dispatch_compute: std::mem::transmute::<*mut c_void, Option<DispatchComputeFn>>(
+                proc_address(c"glDispatchCompute"),
+            ),
+            dispatch_compute_indirect: std::mem::transmute::<
+                *mut c_void,
+                Option<DispatchComputeIndirectFn>,
+            >(proc_address(c"glDispatchComputeIndirect")),
+            memory_barrier: std::mem::transmute::<*mut c_void, Option<MemoryBarrierFn>>(
+                proc_address(c"glMemoryBarrier"),
+            ),
+        }
+    })
+}
+
+unsafe fn proc_address(name: &CStr) -> *mut c_void {
+    SDL_GL_GetProcAddress(name.as_ptr().cast())
+}
+
+/// True when the context lists `name` (e.g. "GL_ARB_get_program_binary")
+pub fn has_extension(gl: &GlFns, name: &str) -> bool {
+    let mut count = 0;
+    unsafe {
+        gl.GetIntegerv(GL_NUM_EXTENSIONS, &mut count);
+    }
+    (0..count as u32).any(|i| gl_string_i(gl, GL_EXTENSIONS, i) == name)
+}
+
+/// (major, minor) version of the current context
+pub fn gl_version(gl: &GlFns) -> (i32, i32) {
+    let mut major = 0;
+    let mut minor = 0;
+    unsafe {
+        gl.GetIntegerv(GL_MAJOR_VERSION, &mut major);
+        gl.GetIntegerv(GL_MINOR_VERSION, &mut minor);
+    }
+    (major, minor)
+}
+
+/// True when the context is at least `major.minor` or lists `extension`
+pub fn has_feature(gl: &GlFns, major: i32, minor: i32, extension: &str) -> bool {
+    gl_version(gl) >= (major, minor) || has_extension(gl, extension)
+}
+
+/// Vertices per patch for the following `GL_PATCHES` draw calls (GL 4.0)
+pub fn set_patch_vertices(count: i32) -> Result<(), String> {
+    match ext().patch_parameteri {
+        Some(f) => {
+            unsafe { f(GL_PATCH_VERTICES, count) };
+            Ok(())
+        }
+        None => Err("glPatchParameteri not available".to_string()),
+    }
+}
+
+/// Orders memory accesses of shaders (e.g. compute writes) against later commands (GL 4.2)
+pub fn memory_barrier(barriers: u32) -> Result<(), String> {
+    match ext().memory_barrier {
+        Some(f) => {
+            unsafe { f(barriers) };
+            Ok(())
+        }
+        None => Err("glMemoryBarrier not available".to_string()),
+    }
+}
+
+/// glGetString as a Rust string, empty when the query fails
+pub fn gl_string(gl: &GlFns, name: GLenum) -> String {
+    unsafe { to_string(gl.GetString(name)) }
+}
+
+fn gl_string_i(gl: &GlFns, name: GLenum, index: u32) -> String {
+    unsafe { to_string(gl.GetStringi(name, index)) }
+}
+
+unsafe fn to_string(p: *const u8) -> String {
+    if p.is_null() {
+        return String::new();
+    }
+    CStr::from_ptr(p.cast()).to_string_lossy().to_string()
+}
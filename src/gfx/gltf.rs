@@ -0,0 +1,780 @@
+use super::animation::Transform;
+use super::assets::AssetManager;
+use super::glutils;
+use super::models::{
+    Material, Mesh, MeshInstance, Model, Node, TexType, Texture, Vertex, WHITE_TEXTURE,
+};
+use base64::Engine;
+use gl33::*;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use ultraviolet::*;
+
+// "glTF", "JSON" and "BIN\0" in little endian
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_CHUNK_JSON: u32 = 0x4E4F_534A;
+const GLB_CHUNK_BIN: u32 = 0x004E_4942;
+
+// accessor component types, the GL enums of the same name
+const BYTE: u64 = 5120;
+const UNSIGNED_BYTE: u64 = 5121;
+const SHORT: u64 = 5122;
+const UNSIGNED_SHORT: u64 = 5123;
+const UNSIGNED_INT: u64 = 5125;
+const FLOAT: u64 = 5126;
+
+const MODE_TRIANGLES: u64 = 4;
+
+// extensions a file may require that the importer handles
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_texture_transform"];
+
+pub fn is_gltf(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("gltf") || e.eq_ignore_ascii_case("glb"))
+}
+
+/// Imports a glTF 2.0 file (.gltf with external or embedded buffers, or .glb).
+///
+/// Every triangle primitive becomes a `Mesh` with its material factors in
+/// `Mesh::material`. The metallic-roughness texture is added both as
+/// `TexType::Roughness` and `TexType::Metallic`, roughness is in its green and
+/// metalness in its blue channel. Skins, morph targets and animations aren't
+/// imported.
+pub fn load(
+    gl: &GlFns,
+    filename: &str,
+    assets: Option<&mut AssetManager>,
+) -> Result<Model, String> {
+    let error = |e: String| format!("error importing {}: {}", filename, e);
+
+    let data = std::fs::read(filename).map_err(|e| error(e.to_string()))?;
+    let (json, bin) = if data.starts_with(b"glTF") {
+        split_glb(&data).map_err(error)?
+    } else {
+        (data.as_slice(), None)
+    };
+    let doc: Value = serde_json::from_slice(json).map_err(|e| error(e.to_string()))?;
+    check_document(&doc).map_err(error)?;
+
+    let dir = Path::new(filename)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let buffers = load_buffers(&doc, &dir, bin).map_err(error)?;
+
+    let mut importer = Importer {
+        doc: &doc,
+        buffers,
+        dir,
+        meshes: HashMap::new(),
+        model: Model::empty(filename),
+        assets,
+    };
+    importer.load_scene(gl).map_err(error)?;
+    importer.model.update_world_matrices();
+    Ok(importer.model)
+}
+
+/// JSON chunk and optional binary chunk of a .glb file
+pub fn split_glb(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    if le_u32(data, 0) != Some(GLB_MAGIC) {
+        return Err("not a binary glTF file".to_string());
+    }
+    if le_u32(data, 4) != Some(2) {
+        return Err("only binary glTF version 2 is supported".to_string());
+    }
+    let length = (le_u32(data, 8).unwrap_or(0) as usize).min(data.len());
+
+    let mut json = None;
+    let mut bin = None;
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let chunk_length = le_u32(data, offset).unwrap() as usize;
+        let chunk_type = le_u32(data, offset + 4).unwrap();
+        let start = offset + 8;
+        let chunk = start
+            .checked_add(chunk_length)
+            .and_then(|end| data.get(start..end))
+            .ok_or_else(|| "truncated chunk".to_string())?;
+        match chunk_type {
+            GLB_CHUNK_JSON if json.is_none() => json = Some(chunk),
+            GLB_CHUNK_BIN if bin.is_none() => bin = Some(chunk),
+            // unknown chunks must be ignored
+            _ => {}
+        }
+        // chunks are padded to 4 bytes
+        offset = start + chunk_length.next_multiple_of(4);
+    }
+
+    json.map(|j| (j, bin))
+        .ok_or_else(|| "no JSON chunk".to_string())
+}
+
+fn check_document(doc: &Value) -> Result<(), String> {
+    let version = doc["asset"]["version"].as_str().unwrap_or("");
+    if !version.starts_with("2.") {
+        return Err(format!("glTF version '{}' is not supported", version));
+    }
+    for ext in doc["extensionsRequired"].as_array().into_iter().flatten() {
+        let ext = ext.as_str().unwrap_or("");
+        if !SUPPORTED_EXTENSIONS.contains(&ext) {
+            return Err(format!("required extension {} is not supported", ext));
+        }
+    }
+    Ok(())
+}
+
+fn load_buffers(doc: &Value, dir: &Path, bin: Option<&[u8]>) -> Result<Vec<Vec<u8>>, String> {
+    let mut buffers = vec![];
+    for (i, buffer) in array(doc, "buffers").iter().enumerate() {
+        let data = match buffer["uri"].as_str() {
+            Some(uri) => load_uri(uri, dir)?,
+            // the GLB binary chunk, only the first buffer may refer to it
+            None if i == 0 => bin
+                .ok_or_else(|| "buffer 0 has no uri and there is no binary chunk".to_string())?
+                .to_vec(),
+            None => return Err(format!("buffer {} has no uri", i)),
+        };
+        let length = buffer["byteLength"].as_u64().unwrap_or(0) as usize;
+        if data.len() < length {
+            return Err(format!(
+                "buffer {} holds {} bytes, expected {}",
+                i,
+                data.len(),
+                length
+            ));
+        }
+        buffers.push(data);
+    }
+    Ok(buffers)
+}
+
+// contents of a base64 data URI or of a file relative to the glTF file
+fn load_uri(uri: &str, dir: &Path) -> Result<Vec<u8>, String> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let (header, payload) = data
+            .split_once(',')
+            .ok_or_else(|| "malformed data uri".to_string())?;
+        if !header.ends_with(";base64") {
+            return Err("only base64 data uris are supported".to_string());
+        }
+        return base64::engine::general_purpose::STANDARD
+            .decode(payload)
+            .map_err(|e| format!("data uri: {}", e));
+    }
+    let path = dir.join(percent_decode(uri));
+    std::fs::read(&path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Decodes a percent encoded uri, e.g. "my%20texture.png"
+pub fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+// array `name` of an object, empty when it has none
+fn array<'a>(doc: &'a Value, name: &str) -> &'a [Value] {
+    doc[name].as_array().map_or(&[], Vec::as_slice)
+}
+
+fn element<'a>(doc: &'a Value, name: &str, index: usize) -> Result<&'a Value, String> {
+    array(doc, name)
+        .get(index)
+        .ok_or_else(|| format!("{}[{}] does not exist", name, index))
+}
+
+fn index(value: &Value) -> Option<usize> {
+    value.as_u64().map(|i| i as usize)
+}
+
+fn floats<const N: usize>(value: &Value, default: [f32; N]) -> [f32; N] {
+    let mut out = default;
+    if let Some(values) = value.as_array() {
+        for (o, v) in out.iter_mut().zip(values) {
+            *o = v.as_f64().unwrap_or(0.0) as f32;
+        }
+    }
+    out
+}
+
+fn float(value: &Value, default: f32) -> f32 {
+    value.as_f64().map_or(default, |f| f as f32)
+}
+
+struct Importer<'a> {
+    doc: &'a Value,
+    buffers: Vec<Vec<u8>>,
+    dir: PathBuf,
+    /// glTF mesh to the `Model::meshes` of its primitives
+    meshes: HashMap<usize, Vec<usize>>,
+    model: Model,
+    assets: Option<&'a mut AssetManager>,
+}
+
+impl Importer<'_> {
+    fn load_scene(&mut self, gl: &GlFns) -> Result<(), String> {
+        let doc = self.doc;
+        let scene = index(&doc["scene"]).unwrap_or(0);
+        let roots: Vec<usize> = match array(doc, "scenes").get(scene) {
+            Some(s) => array(s, "nodes").iter().filter_map(index).collect(),
+            // no scene, every node without a parent is a root
+            None => {
+                let children: Vec<usize> = array(doc, "nodes")
+                    .iter()
+                    .flat_map(|n| array(n, "children").iter().filter_map(index))
+                    .collect();
+                (0..array(doc, "nodes").len())
+                    .filter(|n| !children.contains(n))
+                    .collect()
+            }
+        };
+
+        // a single root like assimp's, a glTF scene can have several
+        self.model.nodes.push(Node {
+            name: "root".to_string(),
+            parent: None,
+            children: vec![],
+            local: Mat4::identity(),
+            world: Mat4::identity(),
+        });
+        let mut visited = vec![false; array(doc, "nodes").len()];
+        for root in roots {
+            self.load_node(gl, root, 0, &mut visited)?;
+        }
+        Ok(())
+    }
+
+    fn load_node(
+        &mut self,
+        gl: &GlFns,
+        node: usize,
+        parent: usize,
+        visited: &mut [bool],
+    ) -> Result<(), String> {
+        // a valid file is a forest, don't loop on broken ones
+        match visited.get_mut(node) {
+            Some(seen) if !*seen => *seen = true,
+            Some(_) => return Err(format!("node {} has several parents", node)),
+            None => return Err(format!("nodes[{}] does not exist", node)),
+        }
+        let value = &self.doc["nodes"][node];
+
+        let local = match value["matrix"].as_array() {
+            // column major like ultraviolet
+            Some(_) => Mat4::from(floats(&value["matrix"], [0.0; 16])),
+            None => {
+                let [x, y, z] = floats(&value["translation"], [0.0; 3]);
+                let rotation = floats(&value["rotation"], [0.0, 0.0, 0.0, 1.0]);
+                let [sx, sy, sz] = floats(&value["scale"], [1.0; 3]);
+                Transform {
+                    translation: Vec3::new(x, y, z),
+                    rotation: Rotor3::from_quaternion_array(rotation).normalized(),
+                    scale: Vec3::new(sx, sy, sz),
+                }
+                .to_mat4()
+            }
+        };
+
+        let model_node = self.model.nodes.len();
+        self.model.nodes.push(Node {
+            name: value["name"]
+                .as_str()
+                .map_or_else(|| format!("node{}", node), str::to_string),
+            parent: Some(parent),
+            children: vec![],
+            local,
+            world: Mat4::identity(),
+        });
+        self.model.nodes[parent].children.push(model_node);
+
+        if let Some(mesh) = index(&value["mesh"]) {
+            for mesh in self.load_mesh(gl, mesh)? {
+                self.model.instances.push(MeshInstance {
+                    mesh,
+                    node: model_node,
+                });
+            }
+        }
+
+        let children: Vec<usize> = array(value, "children").iter().filter_map(index).collect();
+        for child in children {
+            self.load_node(gl, child, model_node, visited)?;
+        }
+        Ok(())
+    }
+
+    // meshes used by several nodes are imported once
+    fn load_mesh(&mut self, gl: &GlFns, mesh: usize) -> Result<Vec<usize>, String> {
+        if let Some(meshes) = self.meshes.get(&mesh) {
+            return Ok(meshes.clone());
+        }
+        let value = element(self.doc, "meshes", mesh)?;
+        let mut meshes = vec![];
+        for (p, primitive) in array(value, "primitives").iter().enumerate() {
+            let mode = primitive["mode"].as_u64().unwrap_or(MODE_TRIANGLES);
+            if mode != MODE_TRIANGLES {
+                eprintln!(
+                    "warning: mesh {} primitive {}: skipping mode {}, only triangles are supported",
+                    mesh, p, mode
+                );
+                continue;
+            }
+            let m = self
+                .load_primitive(gl, primitive)
+                .map_err(|e| format!("mesh {} primitive {}: {}", mesh, p, e))?;
+            self.model.meshes.push(m);
+            meshes.push(self.model.meshes.len() - 1);
+        }
+        self.meshes.insert(mesh, meshes.clone());
+        Ok(meshes)
+    }
+
+    fn load_primitive(&mut self, gl: &GlFns, primitive: &Value) -> Result<Mesh, String> {
+        let attributes = &primitive["attributes"];
+        let attribute = |name: &str, width: usize| -> Result<Option<Vec<f32>>, String> {
+            match index(&attributes[name]) {
+                Some(a) => read_floats(self.doc, &self.buffers, a, width).map(Some),
+                None => Ok(None),
+            }
+        };
+
+        let positions =
+            attribute("POSITION", 3)?.ok_or_else(|| "primitive has no POSITION".to_string())?;
+        let count = positions.len() / 3;
+        let normals = attribute("NORMAL", 3)?;
+        let tangents = attribute("TANGENT", 4)?;
+        let tex_coords = attribute("TEXCOORD_0", 2)?;
+        let tex_coords1 = attribute("TEXCOORD_1", 2)?;
+
+        let mut mesh = Mesh::default();
+        for i in 0..count {
+            let vec3 = |v: &[f32]| Vec3::new(v[3 * i], v[3 * i + 1], v[3 * i + 2]);
+            // glTF puts the UV origin at the top left of images, which are
+            // flipped on load
+            let uv = |v: &[f32]| Vec2::new(v[2 * i], 1.0 - v[2 * i + 1]);
+            let mut vert = Vertex {
+                position: vec3(&positions),
+                ..Default::default()
+            };
+            if let Some(n) = &normals {
+                vert.normal = vec3(n);
+            }
+            if let Some(t) = &tex_coords {
+                vert.tex_coords = uv(t);
+            }
+            if let Some(t) = &tex_coords1 {
+                vert.tex_coords1 = uv(t);
+            }
+            if let Some(t) = &tangents {
+                let tangent = Vec3::new(t[4 * i], t[4 * i + 1], t[4 * i + 2]);
+                // w is the handedness, the bitangent points up the image
+                // like the flipped v
+                vert.tangent = tangent;
+                vert.bitangent = vert.normal.cross(tangent) * t[4 * i + 3];
+            }
+            mesh.vertices.push(vert);
+        }
+
+        mesh.indices = match index(&primitive["indices"]) {
+            Some(a) => read_indices(self.doc, &self.buffers, a)?,
+            None => (0..count as u32).collect(),
+        };
+        if mesh.indices.len() % 3 != 0 || mesh.indices.iter().any(|i| *i as usize >= count) {
+            return Err("invalid triangle indices".to_string());
+        }
+
+        if normals.is_none() {
+            mesh.generate_normals();
+        }
+        if tangents.is_none() || normals.is_none() {
+            mesh.generate_tangents();
+        }
+        mesh.compute_bounds();
+
+        if let Some(material) = index(&primitive["material"]) {
+            self.load_material(gl, material, &mut mesh)?;
+        }
+        Ok(mesh)
+    }
+
+    fn load_material(
+        &mut self,
+        gl: &GlFns,
+        material: usize,
+        mesh: &mut Mesh,
+    ) -> Result<(), String> {
+        let value = element(self.doc, "materials", material)?;
+        let pbr = &value["pbrMetallicRoughness"];
+        let base_color = floats(&pbr["baseColorFactor"], [1.0; 4]);
+        let emissive = floats(&value["emissiveFactor"], [0.0; 3]);
+        mesh.material = Material {
+            base_color: Vec4::from(base_color),
+            metallic: float(&pbr["metallicFactor"], 1.0),
+            roughness: float(&pbr["roughnessFactor"], 1.0),
+            emissive: Vec3::from(emissive),
+            normal_scale: float(&value["normalTexture"]["scale"], 1.0),
+            occlusion_strength: float(&value["occlusionTexture"]["strength"], 1.0),
+            ..Default::default()
+        };
+
+        let slots: [(&Value, &[TexType]); 5] = [
+            (&pbr["baseColorTexture"], &[TexType::Diffuse]),
+            (
+                &pbr["metallicRoughnessTexture"],
+                &[TexType::Roughness, TexType::Metallic],
+            ),
+            (&value["normalTexture"], &[TexType::Normal]),
+            (&value["occlusionTexture"], &[TexType::AmbientOcclusion]),
+            (&value["emissiveTexture"], &[TexType::Emissive]),
+        ];
+        if pbr["baseColorTexture"].is_null() {
+            mesh.textures.push(Texture {
+                id: self.model.white_texture(gl),
+                tex_type: TexType::Diffuse,
+                path: WHITE_TEXTURE.to_string(),
+                ..Default::default()
+            });
+        }
+        for (info, types) in slots {
+            let Some(texture) = index(&info["index"]) else {
+                continue;
+            };
+            let id = self.load_texture(gl, texture)?;
+            let (uv_set, transform) = texture_transform(info);
+            for tex_type in types {
+                mesh.textures.push(Texture {
+                    id,
+                    tex_type: *tex_type,
+                    uv_set,
+                    transform,
+                    ..Default::default()
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // textures are cached per image and sampler, the same image can be sampled
+    // with different wrap modes
+    fn load_texture(&mut self, gl: &GlFns, texture: usize) -> Result<u32, String> {
+        let value = element(self.doc, "textures", texture)?;
+        let source =
+            index(&value["source"]).ok_or_else(|| format!("texture {} has no image", texture))?;
+        let image = element(self.doc, "images", source)?;
+        let sampler = index(&value["sampler"]);
+
+        // plain image files with the default sampler can be shared
+        if let (Some(uri), None) = (image["uri"].as_str(), sampler) {
+            if !uri.starts_with("data:") {
+                let path = self.dir.join(percent_decode(uri));
+                return Ok(self.model.load_texture_or_fallback(
+                    gl,
+                    &path.to_string_lossy(),
+                    &mut self.assets,
+                ));
+            }
+        }
+
+        let params = match sampler {
+            Some(s) => sampler_params(element(self.doc, "samplers", s)?),
+            None => sampler_params(&Value::Null),
+        };
+        let key = format!(
+            "{}#image{}:sampler{}",
+            self.dir.display(),
+            source,
+            sampler.map_or(-1, |s| s as i64)
+        );
+        let name = image["name"]
+            .as_str()
+            .or(image["uri"].as_str().filter(|u| !u.starts_with("data:")))
+            .map_or_else(|| format!("image {}", source), str::to_string);
+        let data = self.image_data(image);
+        Ok(self.model.cached_texture(gl, &key, || {
+            glutils::load_texture_from_memory(gl, &data?, &name, &params)
+        }))
+    }
+
+    fn image_data(&self, image: &Value) -> Result<Vec<u8>, String> {
+        if let Some(uri) = image["uri"].as_str() {
+            return load_uri(uri, &self.dir);
+        }
+        let view = index(&image["bufferView"])
+            .ok_or_else(|| "image has neither uri nor bufferView".to_string())?;
+        Ok(buffer_view(self.doc, &self.buffers, view)?.0.to_vec())
+    }
+}
+
+// bytes of a buffer view and its stride (0 when tightly packed)
+fn buffer_view<'a>(
+    doc: &Value,
+    buffers: &'a [Vec<u8>],
+    view: usize,
+) -> Result<(&'a [u8], usize), String> {
+    let value = element(doc, "bufferViews", view)?;
+    let buffer = index(&value["buffer"])
+        .and_then(|b| buffers.get(b))
+        .ok_or_else(|| format!("bufferView {} has no valid buffer", view))?;
+    let offset = value["byteOffset"].as_u64().unwrap_or(0) as usize;
+    let length = value["byteLength"].as_u64().unwrap_or(0) as usize;
+    let stride = value["byteStride"].as_u64().unwrap_or(0) as usize;
+    let bytes = offset
+        .checked_add(length)
+        .and_then(|end| buffer.get(offset..end))
+        .ok_or_else(|| format!("bufferView {} is out of its buffer", view))?;
+    Ok((bytes, stride))
+}
+
+/// Accessor `accessor` of `doc` as floats, `width` per element. Normalized
+/// integers are mapped to 0..=1 (or -1..=1), others converted as they are.
+pub fn read_floats(
+    doc: &Value,
+    buffers: &[Vec<u8>],
+    accessor: usize,
+    width: usize,
+) -> Result<Vec<f32>, String> {
+    let value = element(doc, "accessors", accessor)?;
+    let normalized = value["normalized"].as_bool().unwrap_or(false);
+    let component_type = value["componentType"].as_u64().unwrap_or(0);
+    read_accessor(
+        doc,
+        buffers,
+        accessor,
+        width,
+        |bytes| match component_type {
+            FLOAT => f32::from_le_bytes(bytes.try_into().unwrap()),
+            BYTE if normalized => (bytes[0] as i8 as f32 / 127.0).max(-1.0),
+            UNSIGNED_BYTE if normalized => bytes[0] as f32 / 255.0,
+            SHORT if normalized => {
+                (i16::from_le_bytes(bytes.try_into().unwrap()) as f32 / 32767.0).max(-1.0)
+            }
+            UNSIGNED_SHORT if normalized => {
+                u16::from_le_bytes(bytes.try_into().unwrap()) as f32 / 65535.0
+            }
+            BYTE => bytes[0] as i8 as f32,
+            UNSIGNED_BYTE => bytes[0] as f32,
+            SHORT => i16::from_le_bytes(bytes.try_into().unwrap()) as f32,
+            UNSIGNED_SHORT => u16::from_le_bytes(bytes.try_into().unwrap()) as f32,
+            _ => u32::from_le_bytes(bytes.try_into().unwrap()) as f32,
+        },
+    )
+}
+
+/// Index accessor `accessor` of `doc`
+pub fn read_indices(doc: &Value, buffers: &[Vec<u8>], accessor: usize) -> Result<Vec<u32>, String> {
+    let value = element(doc, "accessors", accessor)?;
+    match value["componentType"].as_u64().unwrap_or(0) {
+        UNSIGNED_BYTE | UNSIGNED_SHORT | UNSIGNED_INT => {}
+        t => return Err(format!("indices of component type {}", t)),
+    }
+    read_accessor(doc, buffers, accessor, 1, le_uint)
+}
+
+fn le_uint(bytes: &[u8]) -> u32 {
+    match bytes.len() {
+        1 => bytes[0] as u32,
+        2 => u16::from_le_bytes(bytes.try_into().unwrap()) as u32,
+        _ => u32::from_le_bytes(bytes.try_into().unwrap()),
+    }
+}
+
+fn component_bytes(component_type: u64) -> Option<usize> {
+    match component_type {
+        BYTE | UNSIGNED_BYTE => Some(1),
+        SHORT | UNSIGNED_SHORT => Some(2),
+        UNSIGNED_INT | FLOAT => Some(4),
+        _ => None,
+    }
+}
+
+// `convert` gets the little endian bytes of each component
+fn read_accessor<T: Default + Clone>(
+    doc: &Value,
+    buffers: &[Vec<u8>],
+    accessor: usize,
+    width: usize,
+    convert: impl Fn(&[u8]) -> T,
+) -> Result<Vec<T>, String> {
+    let value = element(doc, "accessors", accessor)?;
+    let error = |e: &str| format!("accessor {}: {}", accessor, e);
+    let components = match value["type"].as_str().unwrap_or("") {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        "VEC4" => 4,
+        t => return Err(error(&format!("unsupported type '{}'", t))),
+    };
+    if components != width {
+        return Err(error(&format!(
+            "expected {} components, found {}",
+            width, components
+        )));
+    }
+    let component_type = value["componentType"].as_u64().unwrap_or(0);
+    let component_size = component_bytes(component_type)
+        .ok_or_else(|| error(&format!("unknown component type {}", component_type)))?;
+    let count = value["count"].as_u64().unwrap_or(0) as usize;
+    let element_size = component_size * width;
+    let len = count
+        .checked_mul(width)
+        .ok_or_else(|| error("count is too large"))?;
+
+    // without a buffer view all components are zero
+    let mut out = match index(&value["bufferView"]) {
+        None => vec![T::default(); len],
+        Some(view) => {
+            let (bytes, stride) = buffer_view(doc, buffers, view)?;
+            let stride = if stride > 0 { stride } else { element_size };
+            let offset = value["byteOffset"].as_u64().unwrap_or(0) as usize;
+            let end = count
+                .saturating_sub(1)
+                .checked_mul(stride)
+                .and_then(|e| e.checked_add(offset))
+                .and_then(|e| e.checked_add(element_size));
+            if count > 0 && end.is_none_or(|end| end > bytes.len()) {
+                return Err(error("out of its buffer view"));
+            }
+            let mut out = Vec::with_capacity(len);
+            for i in 0..count {
+                let start = offset + i * stride;
+                for c in 0..width {
+                    let at = start + c * component_size;
+                    out.push(convert(&bytes[at..at + component_size]));
+                }
+            }
+            out
+        }
+    };
+
+    // sparse elements replace the ones at their index
+    let sparse = &value["sparse"];
+    if !sparse.is_null() {
+        let sparse_count = sparse["count"].as_u64().unwrap_or(0) as usize;
+        let index_type = sparse["indices"]["componentType"].as_u64().unwrap_or(0);
+        let index_size = match index_type {
+            UNSIGNED_BYTE | UNSIGNED_SHORT | UNSIGNED_INT => component_bytes(index_type).unwrap(),
+            t => return Err(error(&format!("sparse indices of component type {}", t))),
+        };
+        let indices = packed(doc, buffers, &sparse["indices"], sparse_count, index_size)
+            .map_err(|e| error(&format!("sparse indices {}", e)))?;
+        let values = packed(doc, buffers, &sparse["values"], sparse_count, element_size)
+            .map_err(|e| error(&format!("sparse values {}", e)))?;
+        for (i, v) in indices
+            .chunks_exact(index_size)
+            .zip(values.chunks_exact(element_size))
+        {
+            let i = le_uint(i) as usize;
+            if i >= count {
+                return Err(error(&format!("sparse index {} is past the count", i)));
+            }
+            for (c, component) in v.chunks_exact(component_size).enumerate() {
+                out[i * width + c] = convert(component);
+            }
+        }
+    }
+    Ok(out)
+}
+
+// `count` tightly packed elements of `size` bytes from the sparse indices or values
+fn packed<'a>(
+    doc: &Value,
+    buffers: &'a [Vec<u8>],
+    value: &Value,
+    count: usize,
+    size: usize,
+) -> Result<&'a [u8], String> {
+    let view = index(&value["bufferView"]).ok_or_else(|| "have no bufferView".to_string())?;
+    let (bytes, _) = buffer_view(doc, buffers, view)?;
+    let offset = value["byteOffset"].as_u64().unwrap_or(0) as usize;
+    count
+        .checked_mul(size)
+        .and_then(|length| length.checked_add(offset))
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| "are out of their buffer view".to_string())
+}
+
+// wrap and filter modes use the GL enum values
+fn sampler_params(sampler: &Value) -> Vec<(GLenum, GLenum, GLenum)> {
+    let mode =
+        |key: &str, default: GLenum| sampler[key].as_u64().map_or(default, |m| GLenum(m as u32));
+    vec![
+        (GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, mode("wrapS", GL_REPEAT)),
+        (GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, mode("wrapT", GL_REPEAT)),
+        (
+            GL_TEXTURE_2D,
+            GL_TEXTURE_MIN_FILTER,
+            mode("minFilter", GL_LINEAR_MIPMAP_LINEAR),
+        ),
+        (
+            GL_TEXTURE_2D,
+            GL_TEXTURE_MAG_FILTER,
+            mode("magFilter", GL_LINEAR),
+        ),
+    ]
+}
+
+/// UV set and UV transform of a texture info, from KHR_texture_transform when
+/// present. The matrix applies to the flipped UVs of `Vertex`.
+pub fn texture_transform(info: &Value) -> (u32, Mat3) {
+    let mut uv_set = info["texCoord"].as_u64().unwrap_or(0);
+    let ext = &info["extensions"]["KHR_texture_transform"];
+    let transform = if ext.is_null() {
+        Mat3::identity()
+    } else {
+        uv_set = ext["texCoord"].as_u64().unwrap_or(uv_set);
+        let [x, y] = floats(&ext["offset"], [0.0; 2]);
+        let [sx, sy] = floats(&ext["scale"], [1.0; 2]);
+        let (s, c) = float(&ext["rotation"], 0.0).sin_cos();
+        let translation = Mat3::new(
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(x, y, 1.0),
+        );
+        let rotation = Mat3::new(
+            Vec3::new(c, -s, 0.0),
+            Vec3::new(s, c, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        let scale = Mat3::from_nonuniform_scale_homogeneous(Vec2::new(sx, sy));
+        // the transform works on glTF UVs, v is flipped before and after
+        let flip = Mat3::new(
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 1.0),
+        );
+        flip * translation * rotation * scale * flip
+    };
+    if uv_set > 1 {
+        eprintln!(
+            "warning: UV set {} is not supported, using the first",
+            uv_set
+        );
+        uv_set = 0;
+    }
+    (uv_set as u32, transform)
+}
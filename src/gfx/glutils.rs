@@ -1,3 +1,4 @@
+use super::procedural;
 use gl33::*;
 
 pub fn check_gl_err(gl: &GlFns) {
@@ -43,20 +44,6 @@ pub fn gl_buffer_data_element_stat<T: Sized>(gl: &GlFns, buffer: &[T]) {
     }
 }
 
-pub fn gl_vertex_attrib_ptr_enab(gl: &GlFns, index: u32, size: u32, stride: u32, pointer: usize) {
-    unsafe {
-        gl.VertexAttribPointer(
-            index,
-            size as i32,
-            GL_FLOAT,
-            1, //gl33::GL_FALSE,
-            (stride as usize * std::mem::size_of::<f32>()) as i32,
-            (pointer * std::mem::size_of::<f32>()) as *const _,
-        );
-        gl.EnableVertexAttribArray(index);
-    }
-}
-
 pub fn load_texture(gl: &GlFns, filename: &str) -> Result<u32, String> {
     use gl33::*;
     let params = [
@@ -68,11 +55,65 @@ pub fn load_texture(gl: &GlFns, filename: &str) -> Result<u32, String> {
     load_texture_params(gl, filename, &params)
 }
 
+/// Like `load_texture` but never fails: a missing or broken file is reported
+/// on stderr and replaced with a magenta checkerboard
+pub fn load_texture_or_fallback(gl: &GlFns, filename: &str) -> u32 {
+    match load_texture(gl, filename) {
+        Ok(tex) => tex,
+        Err(e) => {
+            eprintln!("warning: {}, using fallback texture", e);
+            procedural::upload(gl, &procedural::missing_texture())
+        }
+    }
+}
+
 pub fn load_texture_params(
     gl: &GlFns,
     filename: &str,
     params: &[(GLenum, GLenum, GLenum)],
 ) -> Result<u32, String> {
+    unsafe {
+        stb_image::stb_image::bindgen::stbi_set_flip_vertically_on_load(1);
+    }
+    let img = decoded_image(stb_image::image::load(filename), filename)?;
+    Ok(upload_image(gl, &img, params))
+}
+
+/// Like `load_texture_params` for an encoded image (PNG, JPEG, ...) already in
+/// memory, `name` is only used in error messages
+pub fn load_texture_from_memory(
+    gl: &GlFns,
+    data: &[u8],
+    name: &str,
+    params: &[(GLenum, GLenum, GLenum)],
+) -> Result<u32, String> {
+    unsafe {
+        stb_image::stb_image::bindgen::stbi_set_flip_vertically_on_load(1);
+    }
+    let img = decoded_image(stb_image::image::load_from_memory(data), name)?;
+    Ok(upload_image(gl, &img, params))
+}
+
+fn decoded_image(
+    result: stb_image::image::LoadResult,
+    name: &str,
+) -> Result<stb_image::image::Image<u8>, String> {
+    match result {
+        stb_image::image::LoadResult::ImageF32(_) => {
+            Err("32-bit images not supported here".to_string())
+        }
+        stb_image::image::LoadResult::ImageU8(img) => Ok(img),
+        stb_image::image::LoadResult::Error(e) => {
+            Err(format!("loading image {} error: {}", name, e))
+        }
+    }
+}
+
+fn upload_image(
+    gl: &GlFns,
+    img: &stb_image::image::Image<u8>,
+    params: &[(GLenum, GLenum, GLenum)],
+) -> u32 {
     let mut texture = 0;
     unsafe {
         gl.GenTextures(1, &mut texture);
@@ -85,19 +126,6 @@ pub fn load_texture_params(
             );
         }
     }
-    unsafe {
-        stb_image::stb_image::bindgen::stbi_set_flip_vertically_on_load(1);
-    }
-    let img = match stb_image::image::load(filename) {
-        stb_image::image::LoadResult::ImageF32(_) => {
-            return Err("32-bit images not supported here".to_string());
-            // img
-        }
-        stb_image::image::LoadResult::ImageU8(img) => img,
-        stb_image::image::LoadResult::Error(e) => {
-            return Err(format!("loading image {} error: {}", filename, e))
-        }
-    };
 
     let mut format = gl33::GL_RGB;
     if img.depth == 1 {
@@ -109,6 +137,8 @@ pub fn load_texture_params(
     }
 
     unsafe {
+        // rows of RGB and single channel images aren't 4 byte aligned
+        gl.PixelStorei(gl33::GL_UNPACK_ALIGNMENT, 1);
         gl.TexImage2D(
             gl33::GL_TEXTURE_2D,
             0,
@@ -120,11 +150,12 @@ pub fn load_texture_params(
             gl33::GL_UNSIGNED_BYTE,
             img.data.as_ptr().cast(),
         );
+        gl.PixelStorei(gl33::GL_UNPACK_ALIGNMENT, 4);
         check_gl_err(gl);
         gl.GenerateMipmap(gl33::GL_TEXTURE_2D);
     }
 
-    Ok(texture)
+    texture
 }
 
 /// Creates a cube map texture
@@ -217,3 +248,238 @@ pub fn load_cube_map_texture_params(
 
     Ok(texture)
 }
+
+/// Saves one level of a texture to an image file
+///
+/// # Arguments
+///
+/// * `gl` - OpenGl context
+/// * `tex` - texture id
+/// * `target` - `GL_TEXTURE_2D` or a single cube map face, e.g. `GL_TEXTURE_CUBE_MAP_POSITIVE_X`
+/// * `level` - mipmap level
+/// * `path` - output file, `.png` or `.hdr`
+///
+/// Depth textures are normalized to their min..max range so they are visible.
+/// Float textures are written as-is to `.hdr` or tone-mapped (Reinhard + gamma) to `.png`.
+/// GL errors (bad id, target not matching the texture) are returned, and the
+/// previous texture binding and pack alignment are restored either way.
+pub fn save_texture(
+    gl: &GlFns,
+    tex: u32,
+    target: GLenum,
+    level: i32,
+    path: &str,
+) -> Result<(), String> {
+    let (bind_target, binding) = if is_cube_map_face(target) {
+        (GL_TEXTURE_CUBE_MAP, GL_TEXTURE_BINDING_CUBE_MAP)
+    } else if target == GL_TEXTURE_2D {
+        (GL_TEXTURE_2D, GL_TEXTURE_BINDING_2D)
+    } else {
+        return Err(format!("save_texture: unsupported target {:?}", target));
+    };
+
+    // an earlier error would be reported as ours
+    check_gl_err(gl);
+    let (mut previous, mut alignment) = (0, 0);
+    unsafe {
+        gl.GetIntegerv(binding, &mut previous);
+        gl.GetIntegerv(GL_PACK_ALIGNMENT, &mut alignment);
+        gl.PixelStorei(GL_PACK_ALIGNMENT, 1);
+        gl.BindTexture(bind_target, tex);
+    }
+    let result =
+        gl_result(gl, "binding texture").and_then(|_| write_texture(gl, tex, target, level, path));
+    unsafe {
+        gl.BindTexture(bind_target, previous as u32);
+        gl.PixelStorei(GL_PACK_ALIGNMENT, alignment);
+    }
+    result
+}
+
+/// Error raised by the GL calls since the last check, if any
+fn gl_result(gl: &GlFns, what: &str) -> Result<(), String> {
+    let err = unsafe { gl.GetError() };
+    if err == GL_NO_ERROR {
+        Ok(())
+    } else {
+        Err(format!("save_texture: {} failed with {:?}", what, err))
+    }
+}
+
+/// Reads back the bound texture and writes it to `path`
+fn write_texture(
+    gl: &GlFns,
+    tex: u32,
+    target: GLenum,
+    level: i32,
+    path: &str,
+) -> Result<(), String> {
+    let (mut w, mut h, mut internal_format) = (0, 0, 0);
+    unsafe {
+        gl.GetTexLevelParameteriv(target, level, GL_TEXTURE_WIDTH, &mut w);
+        gl.GetTexLevelParameteriv(target, level, GL_TEXTURE_HEIGHT, &mut h);
+        gl.GetTexLevelParameteriv(
+            target,
+            level,
+            GL_TEXTURE_INTERNAL_FORMAT,
+            &mut internal_format,
+        );
+    }
+    gl_result(gl, "querying the level size")?;
+    if w <= 0 || h <= 0 {
+        return Err(format!(
+            "save_texture: texture {} has no image at level {}",
+            tex, level
+        ));
+    }
+    let (w, h) = (w as usize, h as usize);
+    let internal_format = GLenum(internal_format as u32);
+    let hdr_output = path.to_lowercase().ends_with(".hdr");
+
+    let rgba_f32 = if is_depth_format(internal_format) {
+        let mut depth = vec![0.0f32; w * h];
+        unsafe {
+            gl.GetTexImage(
+                target,
+                level,
+                GL_DEPTH_COMPONENT,
+                GL_FLOAT,
+                depth.as_mut_ptr().cast(),
+            );
+        }
+        gl_result(gl, "reading the image")?;
+        let (min, max) = depth
+            .iter()
+            .fold((f32::MAX, f32::MIN), |(lo, hi), d| (lo.min(*d), hi.max(*d)));
+        let range = if max > min { max - min } else { 1.0 };
+        Some(
+            depth
+                .iter()
+                .flat_map(|d| {
+                    let v = (d - min) / range;
+                    [v, v, v, 1.0]
+                })
+                .collect::<Vec<f32>>(),
+        )
+    } else if is_float_format(internal_format) || hdr_output {
+        let mut data = vec![0.0f32; w * h * 4];
+        unsafe {
+            gl.GetTexImage(target, level, GL_RGBA, GL_FLOAT, data.as_mut_ptr().cast());
+        }
+        gl_result(gl, "reading the image")?;
+        if !hdr_output {
+            for px in data.chunks_mut(4) {
+                for c in px.iter_mut().take(3) {
+                    *c = (*c / (1.0 + *c)).max(0.0).powf(1.0 / 2.2);
+                }
+            }
+        }
+        Some(data)
+    } else {
+        None
+    };
+
+    if hdr_output {
+        return write_hdr(path, w, h, &rgba_f32.unwrap_or_default());
+    }
+
+    let rgba_u8 = match rgba_f32 {
+        Some(data) => data
+            .iter()
+            .map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect(),
+        None => {
+            let mut data = vec![0u8; w * h * 4];
+            unsafe {
+                gl.GetTexImage(
+                    target,
+                    level,
+                    GL_RGBA,
+                    GL_UNSIGNED_BYTE,
+                    data.as_mut_ptr().cast(),
+                );
+            }
+            gl_result(gl, "reading the image")?;
+            data
+        }
+    };
+
+    write_png(path, w, h, &rgba_u8)
+}
+
+fn is_cube_map_face(target: GLenum) -> bool {
+    (GL_TEXTURE_CUBE_MAP_POSITIVE_X.0..=GL_TEXTURE_CUBE_MAP_NEGATIVE_Z.0).contains(&target.0)
+}
+
+fn is_depth_format(f: GLenum) -> bool {
+    [
+        GL_DEPTH_COMPONENT,
+        GL_DEPTH_COMPONENT16,
+        GL_DEPTH_COMPONENT24,
+        GL_DEPTH_COMPONENT32,
+        GL_DEPTH_COMPONENT32F,
+        GL_DEPTH24_STENCIL8,
+        GL_DEPTH32F_STENCIL8,
+    ]
+    .contains(&f)
+}
+
+fn is_float_format(f: GLenum) -> bool {
+    [
+        GL_R16F,
+        GL_RG16F,
+        GL_RGB16F,
+        GL_RGBA16F,
+        GL_R32F,
+        GL_RG32F,
+        GL_RGB32F,
+        GL_RGBA32F,
+        GL_R11F_G11F_B10F,
+    ]
+    .contains(&f)
+}
+
+// OpenGL returns rows bottom-up, image files expect them top-down
+fn flip_rows<T: Clone>(data: &[T], row_len: usize) -> Vec<T> {
+    data.chunks(row_len).rev().flatten().cloned().collect()
+}
+
+fn write_png(path: &str, w: usize, h: usize, rgba: &[u8]) -> Result<(), String> {
+    let file =
+        std::fs::File::create(path).map_err(|e| format!("creating {} error: {}", path, e))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), w as u32, h as u32);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&flip_rows(rgba, w * 4)))
+        .map_err(|e| format!("writing {} error: {}", path, e))
+}
+
+// Radiance RGBE, uncompressed scanlines
+fn write_hdr(path: &str, w: usize, h: usize, rgba: &[f32]) -> Result<(), String> {
+    use std::io::Write;
+
+    let mut out = format!("#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", h, w).into_bytes();
+    out.reserve(w * h * 4);
+    for px in flip_rows(rgba, w * 4).chunks(4) {
+        let max = px[0].max(px[1]).max(px[2]);
+        if max < 1e-32 {
+            out.extend_from_slice(&[0, 0, 0, 0]);
+            continue;
+        }
+        // max = mantissa * 2^exp, mantissa in [0.5, 1)
+        let exp = max.log2().floor() as i32 + 1;
+        let scale = 256.0 / 2f32.powi(exp);
+        out.extend_from_slice(&[
+            (px[0].max(0.0) * scale) as u8,
+            (px[1].max(0.0) * scale) as u8,
+            (px[2].max(0.0) * scale) as u8,
+            (exp + 128) as u8,
+        ]);
+    }
+
+    std::fs::File::create(path)
+        .and_then(|mut f| f.write_all(&out))
+        .map_err(|e| format!("writing {} error: {}", path, e))
+}
@@ -1,4 +1,6 @@
 use super::shaders::*;
+use super::uniform_buffer::{std140_struct, Pad};
+use super::uniforms::UniformValue;
 use gl33::*;
 use std::ffi::CString;
 use ultraviolet::*;
@@ -569,9 +571,9 @@ impl Default for VSMatrices {
 
 impl VSMatrices {
     pub fn pass_uniforms(&self, gl: &GlFns, shader: &Shaders) {
-        shader.set_mat4fv_uv(gl, "projection", &self.projection);
-        shader.set_mat4fv_uv(gl, "model", &self.model);
-        shader.set_mat4fv_uv(gl, "view", &self.view);
+        shader.set(gl, "projection", &self.projection);
+        shader.set(gl, "model", &self.model);
+        shader.set(gl, "view", &self.view);
     }
     pub fn try_pass_uniforms(&self, gl: &GlFns, shader: &Shaders) {
         shader.try_set_mat4fv_uv(gl, "projection", &self.projection);
@@ -579,3 +581,173 @@ impl VSMatrices {
         shader.try_set_mat4fv_uv(gl, "view", &self.view);
     }
 }
+
+// The impls below set the light as the GLSL struct `name` (e.g. "pointLights[2]"),
+// ignoring the prefix given to `new`, so lights can be passed as arrays of structs.
+
+impl UniformValue for DirLight {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        shader.set(gl, &format!("{}.direction", name), &self.direction);
+        shader.set(gl, &format!("{}.ambient", name), &self.ambient);
+        shader.set(gl, &format!("{}.diffuse", name), &self.diffuse);
+        shader.set(gl, &format!("{}.specular", name), &self.specular);
+    }
+}
+
+impl UniformValue for PointLight {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        shader.set(gl, &format!("{}.position", name), &self.position);
+        shader.set(gl, &format!("{}.ambient", name), &self.ambient);
+        shader.set(gl, &format!("{}.diffuse", name), &self.diffuse);
+        shader.set(gl, &format!("{}.specular", name), &self.specular);
+        shader.set(gl, &format!("{}.constant", name), &self.constant);
+        shader.set(gl, &format!("{}.linear", name), &self.linear);
+        shader.set(gl, &format!("{}.quadratic", name), &self.quadratic);
+    }
+}
+
+impl UniformValue for SpotLight {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        shader.set(gl, &format!("{}.position", name), &self.position);
+        shader.set(gl, &format!("{}.direction", name), &self.direction);
+        shader.set(gl, &format!("{}.cutOff", name), &self.cut_off);
+        shader.set(gl, &format!("{}.outerCutOff", name), &self.outer_cut_off);
+        shader.set(gl, &format!("{}.ambient", name), &self.ambient);
+        shader.set(gl, &format!("{}.diffuse", name), &self.diffuse);
+        shader.set(gl, &format!("{}.specular", name), &self.specular);
+        shader.set(gl, &format!("{}.constant", name), &self.constant);
+        shader.set(gl, &format!("{}.linear", name), &self.linear);
+        shader.set(gl, &format!("{}.quadratic", name), &self.quadratic);
+    }
+}
+
+/// Array size of `LightsBlock::point_lights` (`NUM_POINT_LIGHTS` in GLSL)
+pub const MAX_POINT_LIGHTS: usize = 4;
+
+std140_struct! {
+    /// `Camera` block of demo/include/camera.glsl
+    #[derive(Default)]
+    pub struct CameraBlock {
+        pub projection: Mat4,
+        pub view: Mat4,
+        pub view_pos: Vec3,
+        _pad0: Pad<4>,
+    }
+}
+
+impl CameraBlock {
+    pub fn new(projection: Mat4, view: Mat4, view_pos: Vec3) -> Self {
+        CameraBlock {
+            projection,
+            view,
+            view_pos,
+            ..Default::default()
+        }
+    }
+}
+
+std140_struct! {
+    #[derive(Default)]
+    pub struct DirLightStd140 {
+        pub direction: Vec3,
+        _pad0: Pad<4>,
+        pub ambient: Vec3,
+        _pad1: Pad<4>,
+        pub diffuse: Vec3,
+        _pad2: Pad<4>,
+        pub specular: Vec3,
+        _pad3: Pad<4>,
+    }
+}
+
+impl From<&DirLight> for DirLightStd140 {
+    fn from(l: &DirLight) -> Self {
+        DirLightStd140 {
+            direction: l.direction,
+            ambient: l.ambient,
+            diffuse: l.diffuse,
+            specular: l.specular,
+            ..Default::default()
+        }
+    }
+}
+
+std140_struct! {
+    #[derive(Default)]
+    pub struct PointLightStd140 {
+        pub position: Vec3,
+        pub constant: f32,
+        pub linear: f32,
+        pub quadratic: f32,
+        _pad0: Pad<8>,
+        pub ambient: Vec3,
+        _pad1: Pad<4>,
+        pub diffuse: Vec3,
+        _pad2: Pad<4>,
+        pub specular: Vec3,
+        _pad3: Pad<4>,
+    }
+}
+
+impl From<&PointLight> for PointLightStd140 {
+    fn from(l: &PointLight) -> Self {
+        PointLightStd140 {
+            position: l.position,
+            constant: l.constant,
+            linear: l.linear,
+            quadratic: l.quadratic,
+            ambient: l.ambient,
+            diffuse: l.diffuse,
+            specular: l.specular,
+            ..Default::default()
+        }
+    }
+}
+
+std140_struct! {
+    #[derive(Default)]
+    pub struct SpotLightStd140 {
+        pub position: Vec3,
+        _pad0: Pad<4>,
+        pub direction: Vec3,
+        pub cut_off: f32,
+        pub outer_cut_off: f32,
+        pub constant: f32,
+        pub linear: f32,
+        pub quadratic: f32,
+        pub ambient: Vec3,
+        _pad1: Pad<4>,
+        pub diffuse: Vec3,
+        _pad2: Pad<4>,
+        pub specular: Vec3,
+        _pad3: Pad<4>,
+    }
+}
+
+impl From<&SpotLight> for SpotLightStd140 {
+    fn from(l: &SpotLight) -> Self {
+        SpotLightStd140 {
+            position: l.position,
+            direction: l.direction,
+            cut_off: l.cut_off,
+            outer_cut_off: l.outer_cut_off,
+            constant: l.constant,
+            linear: l.linear,
+            quadratic: l.quadratic,
+            ambient: l.ambient,
+            diffuse: l.diffuse,
+            specular: l.specular,
+            ..Default::default()
+        }
+    }
+}
+
+std140_struct! {
+    /// `Lights` block of demo/include/lights_block.glsl
+    #[derive(Default)]
+    pub struct LightsBlock {
+        pub dir_light: DirLightStd140,
+        pub point_lights: [PointLightStd140; MAX_POINT_LIGHTS],
+        pub spot_light: SpotLightStd140,
+    }
+}
@@ -1,9 +1,29 @@
+pub mod animation;
+pub mod assets;
+pub mod bounds;
 pub mod camera;
+pub mod compute;
 pub mod framebuffer;
+pub mod glext;
+pub mod gltf;
 pub mod glutils;
 pub mod lights;
 pub mod matutils;
+pub mod model_cache;
 pub mod models;
+pub mod obj;
+pub mod optimize;
+pub mod preprocessor;
+pub mod primitives;
+pub mod procedural;
+pub mod program;
+pub mod program_cache;
+pub mod reflection;
+pub mod shader_library;
+pub mod shader_log;
 pub mod shaders;
 pub mod system;
+pub mod uniform_buffer;
+pub mod uniforms;
 pub mod utils;
+pub mod vertex;
@@ -0,0 +1,534 @@
+use super::animation::{AnimationClip, Channel, Key, Skeleton};
+use super::assets::AssetManager;
+use super::models::*;
+use super::program_cache::Fnv1a;
+use gl33::GlFns;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use ultraviolet::*;
+
+static CACHE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+const MAGIC: &[u8; 4] = b"GLMC";
+/// Bumped whenever the layout changes, older files are imported again
+pub const VERSION: u32 = 1;
+
+/// Puts the caches of all models imported afterwards in `dir` (created when
+/// needed), with `None` they are written next to the model files
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+    *CACHE_DIR.lock().unwrap() = dir;
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    CACHE_DIR.lock().unwrap().clone()
+}
+
+// the same file imported by assimp and by gfx::obj gives different models
+fn importer() -> &'static str {
+    if cfg!(feature = "assimp") {
+        "assimp"
+    } else {
+        "obj"
+    }
+}
+
+/// Cache file of the model `source`, e.g. `backpack.obj.mcache`
+pub fn cache_path(source: &str) -> PathBuf {
+    match cache_dir() {
+        Some(dir) => {
+            // models with the same name in different directories must not collide
+            let full = fs::canonicalize(source).unwrap_or_else(|_| PathBuf::from(source));
+            let mut hash = Fnv1a::new();
+            hash.write(full.to_string_lossy().as_bytes());
+            let name = Path::new(source)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            dir.join(format!("{:016x}-{}.mcache", hash.finish(), name))
+        }
+        None => PathBuf::from(format!("{}.mcache", source)),
+    }
+}
+
+/// Files the imported model depends on, the cache is stale once one of them
+/// is newer. For OBJ files these are the material libraries.
+fn dependencies(source: &str) -> Vec<String> {
+    let mut files = vec![source.to_string()];
+    let is_obj = Path::new(source)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("obj"));
+    if let (true, Ok(text)) = (is_obj, fs::read_to_string(source)) {
+        let dir = Path::new(source).parent().unwrap_or(Path::new(""));
+        for line in text.lines() {
+            if let Some(libs) = line.trim().strip_prefix("mtllib ") {
+                files.extend(
+                    libs.split_whitespace()
+                        .map(|lib| dir.join(lib).to_string_lossy().to_string()),
+                );
+            }
+        }
+    }
+    files
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The cached model of `source` with its textures loaded, `None` when there is
+/// no cache or it is older than the files it was imported from
+pub fn load(gl: &GlFns, source: &str, assets: &mut Option<&mut AssetManager>) -> Option<Model> {
+    let path = cache_path(source);
+    let cached = modified(&path)?;
+    let data = fs::read(&path).ok()?;
+
+    let header = match read_header(&data) {
+        Ok(h) => h,
+        Err(e) => {
+            eprintln!("model cache: {}: {}, importing again", path.display(), e);
+            return None;
+        }
+    };
+    if header.version != VERSION || header.importer != importer() {
+        return None;
+    }
+    let stale = header
+        .dependencies
+        .iter()
+        .any(|f| modified(Path::new(f)).is_none_or(|m| m > cached));
+    if stale {
+        return None;
+    }
+
+    let mut model = match decode(&data, source) {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!("model cache: {}: {}, importing again", path.display(), e);
+            return None;
+        }
+    };
+    for m in 0..model.meshes.len() {
+        for t in 0..model.meshes[m].textures.len() {
+            let file = model.meshes[m].textures[t].path.clone();
+            model.meshes[m].textures[t].id = if file == WHITE_TEXTURE {
+                model.white_texture(gl)
+            } else {
+                model.load_texture_or_fallback(gl, &file, assets)
+            };
+        }
+    }
+    Some(model)
+}
+
+/// Writes the cache of `model`, imported from `source`
+pub fn store(model: &Model, source: &str) -> Result<(), String> {
+    let data = encode(model, &dependencies(source))?;
+    let path = cache_path(source);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("error creating {}: {}", dir.display(), e))?;
+    }
+    fs::write(&path, data).map_err(|e| format!("error writing {}: {}", path.display(), e))
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn u32(&mut self, v: u32) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn len(&mut self, v: usize) {
+        self.u32(v as u32);
+    }
+
+    fn f32s(&mut self, v: &[f32]) {
+        v.iter()
+            .for_each(|f| self.0.extend_from_slice(&f.to_le_bytes()));
+    }
+
+    fn str(&mut self, s: &str) {
+        self.len(s.len());
+        self.0.extend_from_slice(s.as_bytes());
+    }
+
+    fn mat4(&mut self, m: &Mat4) {
+        m.cols.iter().for_each(|c| self.f32s(c.as_slice()));
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(n).filter(|e| *e <= self.data.len());
+        let end = end.ok_or_else(|| "unexpected end of file".to_string())?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn len(&mut self) -> Result<usize, String> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn f32(&mut self) -> Result<f32, String> {
+        Ok(f32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn f32s<const N: usize>(&mut self) -> Result<[f32; N], String> {
+        let mut v = [0.0; N];
+        for f in v.iter_mut() {
+            *f = self.f32()?;
+        }
+        Ok(v)
+    }
+
+    fn str(&mut self) -> Result<String, String> {
+        let n = self.len()?;
+        String::from_utf8(self.bytes(n)?.to_vec()).map_err(|e| e.to_string())
+    }
+
+    fn vec3(&mut self) -> Result<Vec3, String> {
+        Ok(Vec3::from(self.f32s::<3>()?))
+    }
+
+    fn mat4(&mut self) -> Result<Mat4, String> {
+        let c = self.f32s::<16>()?;
+        Ok(Mat4::new(
+            Vec4::new(c[0], c[1], c[2], c[3]),
+            Vec4::new(c[4], c[5], c[6], c[7]),
+            Vec4::new(c[8], c[9], c[10], c[11]),
+            Vec4::new(c[12], c[13], c[14], c[15]),
+        ))
+    }
+
+    /// A count of items, each at least `min_size` bytes, checked against the
+    /// remaining data so a corrupt file can't ask for a huge allocation
+    fn count(&mut self, min_size: usize) -> Result<usize, String> {
+        let n = self.len()?;
+        if n.saturating_mul(min_size) > self.data.len() - self.pos {
+            return Err("corrupt item count".to_string());
+        }
+        Ok(n)
+    }
+}
+
+const TEX_TYPES: [TexType; 9] = [
+    TexType::Diffuse,
+    TexType::Specular,
+    TexType::Normal,
+    TexType::Height,
+    TexType::Emissive,
+    TexType::AmbientOcclusion,
+    TexType::Roughness,
+    TexType::Metallic,
+    TexType::Opacity,
+];
+
+pub struct Header {
+    pub version: u32,
+    /// "assimp" or "obj", the importer the model went through
+    pub importer: String,
+    pub dependencies: Vec<String>,
+}
+
+pub fn read_header(data: &[u8]) -> Result<Header, String> {
+    let mut r = Reader { data, pos: 0 };
+    if r.bytes(4)? != MAGIC {
+        return Err("not a model cache file".to_string());
+    }
+    let version = r.u32()?;
+    if version != VERSION {
+        // the rest may be laid out differently
+        return Ok(Header {
+            version,
+            importer: String::new(),
+            dependencies: vec![],
+        });
+    }
+    let importer = r.str()?;
+    let n = r.count(4)?;
+    let dependencies = (0..n).map(|_| r.str()).collect::<Result<_, _>>()?;
+    Ok(Header {
+        version,
+        importer,
+        dependencies,
+    })
+}
+
+/// Serializes everything the importers produce, the GL objects excluded.
+/// Fails for textures that can't be loaded by path.
+pub fn encode(model: &Model, dependencies: &[String]) -> Result<Vec<u8>, String> {
+    let mut w = Writer(Vec::new());
+    w.0.extend_from_slice(MAGIC);
+    w.u32(VERSION);
+    w.str(importer());
+    w.len(dependencies.len());
+    dependencies.iter().for_each(|d| w.str(d));
+
+    w.len(model.meshes.len());
+    for mesh in model.meshes.iter() {
+        w.u8(mesh.skinned as u8);
+        w.u8(mesh.short_indices as u8);
+        let m = &mesh.material;
+        w.f32s(m.base_color.as_slice());
+        w.f32s(&[m.metallic, m.roughness]);
+        w.f32s(m.emissive.as_slice());
+        w.f32s(&[m.normal_scale, m.occlusion_strength]);
+        w.f32s(m.specular.as_slice());
+        w.f32s(&[m.shininess]);
+
+        w.len(mesh.textures.len());
+        for t in mesh.textures.iter() {
+            if t.path.is_empty() {
+                return Err(format!(
+                    "{} texture without a file can't be cached",
+                    t.tex_type.uniform_prefix()
+                ));
+            }
+            w.u8(TEX_TYPES.iter().position(|x| *x == t.tex_type).unwrap() as u8);
+            w.u32(t.uv_set);
+            t.transform.cols.iter().for_each(|c| w.f32s(c.as_slice()));
+            w.str(&t.path);
+        }
+
+        w.len(mesh.vertices.len());
+        for v in mesh.vertices.iter() {
+            w.f32s(v.position.as_slice());
+            w.f32s(v.normal.as_slice());
+            w.f32s(v.tex_coords.as_slice());
+            w.f32s(v.tex_coords1.as_slice());
+            w.f32s(v.tangent.as_slice());
+            w.f32s(v.bitangent.as_slice());
+            v.bone_ids.iter().for_each(|i| w.u32(*i as u32));
+            w.f32s(&v.bone_weights);
+        }
+        w.len(mesh.indices.len());
+        mesh.indices.iter().for_each(|i| w.u32(*i));
+    }
+
+    w.len(model.nodes.len());
+    for node in model.nodes.iter() {
+        w.str(&node.name);
+        w.u32(node.parent.map_or(u32::MAX, |p| p as u32));
+        w.len(node.children.len());
+        node.children.iter().for_each(|c| w.len(*c));
+        w.mat4(&node.local);
+    }
+    w.len(model.instances.len());
+    for instance in model.instances.iter() {
+        w.len(instance.mesh);
+        w.len(instance.node);
+    }
+
+    w.len(model.skeleton.bones.len());
+    for bone in model.skeleton.bones.iter() {
+        w.str(&bone.name);
+        w.mat4(&bone.offset);
+    }
+    w.len(model.animations.len());
+    for clip in model.animations.iter() {
+        w.str(&clip.name);
+        w.f32s(&[clip.duration]);
+        w.len(clip.channels.len());
+        for c in clip.channels.iter() {
+            w.str(&c.node);
+            w.len(c.positions.len());
+            for k in c.positions.iter() {
+                w.f32s(&[k.time]);
+                w.f32s(k.value.as_slice());
+            }
+            w.len(c.rotations.len());
+            for k in c.rotations.iter() {
+                let r = k.value;
+                w.f32s(&[k.time, r.s, r.bv.xy, r.bv.xz, r.bv.yz]);
+            }
+            w.len(c.scales.len());
+            for k in c.scales.iter() {
+                w.f32s(&[k.time]);
+                w.f32s(k.value.as_slice());
+            }
+        }
+    }
+    Ok(w.0)
+}
+
+/// The model stored in `data`, as imported from `source`. Textures only have
+/// their paths set, `load` loads them.
+pub fn decode(data: &[u8], source: &str) -> Result<Model, String> {
+    let header = read_header(data)?;
+    if header.version != VERSION {
+        return Err(format!("version {}, expected {}", header.version, VERSION));
+    }
+    let mut r = Reader { data, pos: 0 };
+    // skip the header, read again for its size
+    r.bytes(8)?;
+    r.str()?;
+    for _ in 0..r.len()? {
+        r.str()?;
+    }
+
+    let mut model = Model::empty(source);
+    for _ in 0..r.count(2)? {
+        let mut mesh = Mesh {
+            skinned: r.u8()? != 0,
+            short_indices: r.u8()? != 0,
+            ..Default::default()
+        };
+        mesh.material = Material {
+            base_color: Vec4::from(r.f32s::<4>()?),
+            metallic: r.f32()?,
+            roughness: r.f32()?,
+            emissive: r.vec3()?,
+            normal_scale: r.f32()?,
+            occlusion_strength: r.f32()?,
+            specular: r.vec3()?,
+            shininess: r.f32()?,
+        };
+
+        for _ in 0..r.count(45)? {
+            let tex_type = *TEX_TYPES
+                .get(r.u8()? as usize)
+                .ok_or_else(|| "unknown texture type".to_string())?;
+            let uv_set = r.u32()?;
+            let c = r.f32s::<9>()?;
+            mesh.textures.push(Texture {
+                tex_type,
+                uv_set,
+                transform: Mat3::new(
+                    Vec3::new(c[0], c[1], c[2]),
+                    Vec3::new(c[3], c[4], c[5]),
+                    Vec3::new(c[6], c[7], c[8]),
+                ),
+                path: r.str()?,
+                ..Default::default()
+            });
+        }
+
+        let n = r.count(96)?;
+        mesh.vertices.reserve_exact(n);
+        for _ in 0..n {
+            let mut v = Vertex {
+                position: r.vec3()?,
+                normal: r.vec3()?,
+                tex_coords: Vec2::from(r.f32s::<2>()?),
+                tex_coords1: Vec2::from(r.f32s::<2>()?),
+                tangent: r.vec3()?,
+                bitangent: r.vec3()?,
+                ..Default::default()
+            };
+            for id in v.bone_ids.iter_mut() {
+                *id = r.u32()? as i32;
+            }
+            v.bone_weights = r.f32s()?;
+            mesh.vertices.push(v);
+        }
+        let n = r.count(4)?;
+        mesh.indices = (0..n).map(|_| r.u32()).collect::<Result<_, _>>()?;
+        if mesh
+            .indices
+            .iter()
+            .any(|i| *i as usize >= mesh.vertices.len())
+        {
+            return Err("index out of range".to_string());
+        }
+        mesh.compute_bounds();
+        model.meshes.push(mesh);
+    }
+
+    for _ in 0..r.count(76)? {
+        let name = r.str()?;
+        let parent = r.u32()?;
+        let children = (0..r.count(4)?)
+            .map(|_| r.len())
+            .collect::<Result<_, _>>()?;
+        model.nodes.push(Node {
+            name,
+            parent: (parent != u32::MAX).then_some(parent as usize),
+            children,
+            local: r.mat4()?,
+            world: Mat4::identity(),
+        });
+    }
+    // parents first, as update_world_matrices expects
+    let nodes = model.nodes.len();
+    for (i, node) in model.nodes.iter().enumerate() {
+        if node.parent.is_some_and(|p| p >= i) || node.children.iter().any(|c| *c >= nodes) {
+            return Err("node tree out of order".to_string());
+        }
+    }
+    for _ in 0..r.count(8)? {
+        let instance = MeshInstance {
+            mesh: r.len()?,
+            node: r.len()?,
+        };
+        if instance.mesh >= model.meshes.len() || instance.node >= nodes {
+            return Err("mesh instance out of range".to_string());
+        }
+        model.instances.push(instance);
+    }
+
+    let mut skeleton = Skeleton::default();
+    for _ in 0..r.count(68)? {
+        let name = r.str()?;
+        skeleton.add_bone(&name, r.mat4()?);
+    }
+    model.skeleton = skeleton;
+    for _ in 0..r.count(12)? {
+        let name = r.str()?;
+        let duration = r.f32()?;
+        let mut channels = Vec::new();
+        for _ in 0..r.count(16)? {
+            let mut channel = Channel {
+                node: r.str()?,
+                ..Default::default()
+            };
+            for _ in 0..r.count(16)? {
+                channel.positions.push(Key {
+                    time: r.f32()?,
+                    value: r.vec3()?,
+                });
+            }
+            for _ in 0..r.count(20)? {
+                let [time, s, xy, xz, yz] = r.f32s()?;
+                channel.rotations.push(Key {
+                    time,
+                    value: Rotor3::new(s, Bivec3::new(xy, xz, yz)),
+                });
+            }
+            for _ in 0..r.count(16)? {
+                channel.scales.push(Key {
+                    time: r.f32()?,
+                    value: r.vec3()?,
+                });
+            }
+            channels.push(channel);
+        }
+        model
+            .animations
+            .push(AnimationClip::new(&name, duration, channels));
+    }
+    if r.pos != data.len() {
+        return Err("trailing data".to_string());
+    }
+
+    model.update_world_matrices();
+    Ok(model)
+}
@@ -1,37 +1,206 @@
+use super::animation::{AnimationClip, Skeleton, MAX_BONE_INFLUENCE};
+use super::assets::{AssetManager, TextureHandle};
+use super::bounds::{Aabb, Frustum, Ray, Sphere};
+use super::gltf;
 use super::glutils::{self, *};
+use super::model_cache;
+#[cfg(not(feature = "assimp"))]
+use super::obj;
+use super::optimize::{self, MeshStats};
+use super::procedural;
 use super::shaders::Shaders;
+use super::uniforms::UniformValue;
+use super::vertex::{build_vao, VertexLayout};
 use gl33::*;
-use russimp;
+#[cfg(feature = "assimp")]
 use russimp::material::TextureType;
+#[cfg(feature = "assimp")]
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::path::Path;
+#[cfg(feature = "assimp")]
 use std::rc::Rc;
 use ultraviolet::*;
 
-fn offset_of<B, T>(base: &B, val: &T) -> usize {
-    let base = base as *const _ as usize;
-    val as *const _ as usize - base
+// bit patterns, so only exactly equal vertices are merged
+fn vertex_key(v: &Vertex) -> Vec<u32> {
+    let floats = [
+        v.position.as_slice(),
+        v.normal.as_slice(),
+        v.tex_coords.as_slice(),
+        v.tex_coords1.as_slice(),
+        v.tangent.as_slice(),
+        v.bitangent.as_slice(),
+        &v.bone_weights,
+    ];
+    floats
+        .iter()
+        .flat_map(|s| s.iter().map(|f| f.to_bits()))
+        .chain(v.bone_ids.iter().map(|i| *i as u32))
+        .collect()
 }
 
-#[derive(Default)]
+#[derive(Clone, Default, VertexLayout)]
 pub struct Vertex {
+    #[vertex(location = 0)]
     pub position: Vec3,
+    #[vertex(location = 1)]
     pub normal: Vec3,
+    #[vertex(location = 2)]
     pub tex_coords: Vec2,
+    /// Second UV set, for textures with `uv_set` 1 (e.g. light or occlusion maps)
+    #[vertex(location = 7)]
+    pub tex_coords1: Vec2,
+    #[vertex(location = 3)]
+    pub tangent: Vec3,
+    #[vertex(location = 4)]
+    pub bitangent: Vec3,
+    /// Indices into `Model::skeleton`, unused slots have a zero weight
+    #[vertex(location = 5)]
+    pub bone_ids: [i32; MAX_BONE_INFLUENCE],
+    #[vertex(location = 6)]
+    pub bone_weights: [f32; MAX_BONE_INFLUENCE],
 }
 
-#[derive(Default)]
+impl Vertex {
+    /// Keeps the strongest `MAX_BONE_INFLUENCE` weights
+    pub fn add_bone_weight(&mut self, bone: usize, weight: f32) {
+        let (slot, smallest) = self
+            .bone_weights
+            .iter()
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, w)| (i, *w))
+            .unwrap();
+        if weight > smallest {
+            self.bone_ids[slot] = bone as i32;
+            self.bone_weights[slot] = weight;
+        }
+    }
+
+    /// Scales the bone weights to sum up to 1
+    pub fn normalize_bone_weights(&mut self) {
+        let sum: f32 = self.bone_weights.iter().sum();
+        if sum > 0.0 {
+            self.bone_weights.iter_mut().for_each(|w| *w /= sum);
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum TexType {
     #[default]
     Diffuse,
     Specular,
+    Normal,
+    Height,
+    Emissive,
+    AmbientOcclusion,
+    Roughness,
+    Metallic,
+    Opacity,
 }
 
-#[derive(Default)]
+impl TexType {
+    /// Sampler names are this prefix plus a 1-based index, e.g. `texture_normal1`
+    pub fn uniform_prefix(self) -> &'static str {
+        match self {
+            TexType::Diffuse => "texture_diffuse",
+            TexType::Specular => "texture_specular",
+            TexType::Normal => "texture_normal",
+            TexType::Height => "texture_height",
+            TexType::Emissive => "texture_emissive",
+            TexType::AmbientOcclusion => "texture_ao",
+            TexType::Roughness => "texture_roughness",
+            TexType::Metallic => "texture_metallic",
+            TexType::Opacity => "texture_opacity",
+        }
+    }
+
+    // Wavefront `map_Bump` holds normal maps in practice, assimp reports it as a
+    // height map, so OBJ height maps are taken as normal maps
+    #[cfg(feature = "assimp")]
+    fn from_assimp(semantic: TextureType, is_obj: bool) -> Option<TexType> {
+        let t = match semantic {
+            TextureType::Diffuse | TextureType::BaseColor => TexType::Diffuse,
+            TextureType::Specular => TexType::Specular,
+            TextureType::Normals | TextureType::NormalCamera => TexType::Normal,
+            TextureType::Height if is_obj => TexType::Normal,
+            TextureType::Height | TextureType::Displacement => TexType::Height,
+            TextureType::Emissive | TextureType::EmissionColor => TexType::Emissive,
+            TextureType::AmbientOcclusion | TextureType::LightMap => TexType::AmbientOcclusion,
+            TextureType::Roughness => TexType::Roughness,
+            TextureType::Metalness => TexType::Metallic,
+            TextureType::Opacity => TexType::Opacity,
+            _ => return None,
+        };
+        Some(t)
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct Texture {
     pub id: u32,
     pub tex_type: TexType,
+    /// UV set sampled, 0 for `Vertex::tex_coords`, 1 for `Vertex::tex_coords1`
+    pub uv_set: u32,
+    /// Applied to the UVs before sampling, identity unless imported from glTF
+    /// with KHR_texture_transform
+    pub transform: Mat3,
+    /// Image file the texture was loaded from, `WHITE_TEXTURE` for the white
+    /// placeholder, empty when it can't be loaded again (e.g. embedded images)
+    pub path: String,
+}
+
+/// `Texture::path` of `Model::white_texture`
+pub const WHITE_TEXTURE: &str = "#white";
+
+/// Factors multiplied with the matching textures, the defaults leave textures
+/// unchanged. glTF files set the metallic-roughness ones, MTL files the base
+/// color (Kd and d), specular and shininess.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Material {
+    pub base_color: Vec4,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vec3,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    pub specular: Vec3,
+    pub shininess: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Material {
+            base_color: Vec4::one(),
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: Vec3::zero(),
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            specular: Vec3::one(),
+            shininess: 32.0,
+        }
+    }
+}
+
+// members the shader doesn't use are skipped, most shaders only use a few
+impl UniformValue for Material {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        shader.try_set(gl, &format!("{}.baseColor", name), &self.base_color);
+        shader.try_set(gl, &format!("{}.metallic", name), &self.metallic);
+        shader.try_set(gl, &format!("{}.roughness", name), &self.roughness);
+        shader.try_set(gl, &format!("{}.emissive", name), &self.emissive);
+        shader.try_set(gl, &format!("{}.normalScale", name), &self.normal_scale);
+        shader.try_set(
+            gl,
+            &format!("{}.occlusionStrength", name),
+            &self.occlusion_strength,
+        );
+        shader.try_set(gl, &format!("{}.specular", name), &self.specular);
+        shader.try_set(gl, &format!("{}.shininess", name), &self.shininess);
+    }
 }
 
 #[derive(Default)]
@@ -39,6 +208,14 @@ pub struct Mesh {
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u32>,
     pub textures: Vec<Texture>,
+    pub material: Material,
+    /// Bounds of the vertices, see `compute_bounds`
+    pub aabb: Aabb,
+    pub sphere: Sphere,
+    /// Vertices follow the skeleton, node transforms don't apply
+    pub skinned: bool,
+    /// Upload the indices as u16, set by `optimize` when they fit
+    pub short_indices: bool,
     pub gl_vao: u32,
     pub gl_vbo: u32,
     pub gl_ebo: u32,
@@ -46,34 +223,213 @@ pub struct Mesh {
 
 impl Mesh {
     pub fn prepare_tex(&self, gl: &GlFns, shader: &Shaders) {
-        let mut diffuse_nr = 1;
-        let mut specular_nr = 1;
+        let mut counts: HashMap<TexType, u32> = HashMap::new();
+        let mut unit0_used = false;
+        let mut unbound_diffuse = None;
+
+        for t in self.textures.iter() {
+            let nr = counts.entry(t.tex_type).or_insert(0);
+            *nr += 1;
+            let uniform_name = format!("{}{}", t.tex_type.uniform_prefix(), nr);
 
-        for (i, t) in self.textures.iter().enumerate() {
+            match shader.texture_unit(&uniform_name) {
+                Some(sampler) => {
+                    unit0_used |= sampler.unit == 0;
+                    shader.bind_texture(gl, &uniform_name, t.id);
+                }
+                None if t.tex_type == TexType::Diffuse && *nr == 1 => unbound_diffuse = Some(t.id),
+                None => {}
+            }
+            shader.try_set(gl, &format!("{}_uv", uniform_name), &(t.uv_set as i32));
+            shader.try_set(gl, &format!("{}_transform", uniform_name), &t.transform);
+        }
+        // shaders with a plain `sampler2D texture1` read the diffuse texture from unit 0
+        if let (Some(id), false) = (unbound_diffuse, unit0_used) {
             unsafe {
-                let tex_id: gl33::GLenum = GLenum(gl33::GL_TEXTURE0.0 + i as u32);
-                gl.ActiveTexture(tex_id);
+                gl.ActiveTexture(GL_TEXTURE0);
+                gl.BindTexture(GL_TEXTURE_2D, id);
             }
+        }
+        shader.set(gl, "material", &self.material);
+    }
 
-            let uniform_name;
-            match t.tex_type {
-                TexType::Diffuse => {
-                    uniform_name = format!("texture_diffuse{}", diffuse_nr);
-                    diffuse_nr += 1;
-                }
-                TexType::Specular => {
-                    uniform_name = format!("texture_specular{}", specular_nr);
-                    specular_nr += 1;
+    /// Updates `aabb` and `sphere` after the vertices changed, importers call
+    /// it for every mesh (skinned meshes get their bind pose bounds)
+    pub fn compute_bounds(&mut self) {
+        let points: Vec<Vec3> = self.vertices.iter().map(|v| v.position).collect();
+        self.aabb = Aabb::from_points(points.iter().copied());
+        self.sphere = Sphere::from_points(&points);
+    }
+
+    /// Deduplicates the vertices, reorders the triangles for the post-transform
+    /// cache and then against overdraw, and the vertices in the order they are
+    /// fetched. Returns the statistics before and after, call it before
+    /// `setup_mesh`.
+    pub fn optimize(&mut self) -> (MeshStats, MeshStats) {
+        let before = self.stats();
+        let mut indices = std::mem::take(&mut self.indices);
+        let vertices = optimize::deduplicate(&self.vertices, &mut indices, vertex_key);
+        let indices = optimize::optimize_vertex_cache(&indices, vertices.len());
+        let positions: Vec<Vec3> = vertices.iter().map(|v| v.position).collect();
+        let mut indices = optimize::optimize_overdraw(&indices, &positions, 1.05);
+        self.vertices = optimize::optimize_vertex_fetch(&vertices, &mut indices);
+        self.indices = indices;
+        self.short_indices = self.vertices.len() <= u16::MAX as usize + 1;
+        self.compute_bounds();
+        (before, self.stats())
+    }
+
+    pub fn stats(&self) -> MeshStats {
+        let positions: Vec<Vec3> = self.vertices.iter().map(|v| v.position).collect();
+        let index_size = if self.short_indices { 2 } else { 4 };
+        MeshStats::analyze(
+            &self.indices,
+            &positions,
+            std::mem::size_of::<Vertex>(),
+            index_size,
+        )
+    }
+
+    /// GL type of the uploaded indices
+    pub fn index_type(&self) -> GLenum {
+        if self.short_indices {
+            GL_UNSIGNED_SHORT
+        } else {
+            GL_UNSIGNED_INT
+        }
+    }
+
+    /// Closest triangle hit by `ray` (in mesh space) as (triangle, distance)
+    pub fn pick(&self, ray: &Ray) -> Option<(usize, f32)> {
+        ray.intersect_aabb(&self.aabb)?;
+        self.indices
+            .chunks_exact(3)
+            .enumerate()
+            .filter_map(|(i, t)| {
+                let p = |k: usize| self.vertices[t[k] as usize].position;
+                ray.intersect_triangle(p(0), p(1), p(2)).map(|d| (i, d))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    /// Smooth normals from the triangles sharing each vertex, weighted by area,
+    /// for meshes imported without normals
+    pub fn generate_normals(&mut self) {
+        let mut normals = vec![Vec3::zero(); self.vertices.len()];
+        for tri in self.indices.chunks_exact(3) {
+            let idx = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            let p = idx.map(|i| self.vertices[i].position);
+            // the cross product is twice the area of the triangle
+            let n = (p[1] - p[0]).cross(p[2] - p[0]);
+            idx.iter().for_each(|i| normals[*i] += n);
+        }
+        for (v, n) in self.vertices.iter_mut().zip(normals) {
+            v.normal = if n.mag_sq() > 0.0 {
+                n.normalized()
+            } else {
+                Vec3::unit_y()
+            };
+        }
+    }
+
+    /// Fills tangents and bitangents from positions, normals and tex coords, for
+    /// meshes that come without them (procedural meshes, imports without UVs
+    /// for assimp to work from).
+    ///
+    /// Follows MikkTSpace in weighting the face tangents by the corner angle,
+    /// orthogonalizing against the vertex normal and keeping the handedness in
+    /// the bitangent sign. Vertices shared by faces of opposite handedness
+    /// (mirrored UVs) are split so each side keeps its own tangent.
+    pub fn generate_tangents(&mut self) {
+        let n = self.vertices.len();
+        // face index, mirrored, tangent and bitangent of the faces with usable UVs
+        let mut faces = Vec::with_capacity(self.indices.len() / 3);
+        let mut used_right = vec![false; n];
+        let mut used_left = vec![false; n];
+
+        for (f, tri) in self.indices.chunks_exact(3).enumerate() {
+            let idx = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            if idx.iter().any(|i| *i >= n) {
+                continue;
+            }
+            let p = idx.map(|i| self.vertices[i].position);
+            let uv = idx.map(|i| self.vertices[i].tex_coords);
+
+            let e1 = p[1] - p[0];
+            let e2 = p[2] - p[0];
+            let d1 = uv[1] - uv[0];
+            let d2 = uv[2] - uv[0];
+            let det = d1.x * d2.y - d2.x * d1.y;
+            // relative to the UV edges, tiny atlas islands are still mapped
+            if det.abs() <= f32::EPSILON * d1.mag() * d2.mag() {
+                // no usable UV mapping on this face
+                continue;
+            }
+            let r = 1.0 / det;
+            let tangent = (e1 * d2.y - e2 * d1.y) * r;
+            let bitangent = (e2 * d1.x - e1 * d2.x) * r;
+            // the UVs wind against the positions on mirrored faces
+            let mirrored = e1.cross(e2).dot(tangent.cross(bitangent)) < 0.0;
+            for i in idx {
+                if mirrored {
+                    used_left[i] = true;
+                } else {
+                    used_right[i] = true;
                 }
             }
+            faces.push((f, mirrored, tangent, bitangent));
+        }
 
-            shader.try_set_i32(gl, &uniform_name.to_string(), i as i32);
-            unsafe {
-                gl.BindTexture(gl33::GL_TEXTURE_2D, t.id);
+        // mirrored faces move to a copy of the vertices the other faces use too
+        let mut mirror_copy: Vec<u32> = (0..n as u32).collect();
+        for i in 0..n {
+            if used_left[i] && used_right[i] {
+                mirror_copy[i] = self.vertices.len() as u32;
+                self.vertices.push(self.vertices[i].clone());
             }
         }
-        unsafe {
-            gl.ActiveTexture(GL_TEXTURE0);
+
+        let mut tangents = vec![Vec3::zero(); self.vertices.len()];
+        let mut bitangents = vec![Vec3::zero(); self.vertices.len()];
+        for (f, mirrored, tangent, bitangent) in faces {
+            let tri = &mut self.indices[f * 3..f * 3 + 3];
+            if mirrored {
+                tri.iter_mut().for_each(|i| *i = mirror_copy[*i as usize]);
+            }
+            let idx = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+            let p = idx.map(|i| self.vertices[i].position);
+            for corner in 0..3 {
+                let a = p[(corner + 1) % 3] - p[corner];
+                let b = p[(corner + 2) % 3] - p[corner];
+                if a.mag_sq() == 0.0 || b.mag_sq() == 0.0 {
+                    continue;
+                }
+                let angle = a.normalized().dot(b.normalized()).clamp(-1.0, 1.0).acos();
+                tangents[idx[corner]] += tangent * angle;
+                bitangents[idx[corner]] += bitangent * angle;
+            }
+        }
+
+        for (i, v) in self.vertices.iter_mut().enumerate() {
+            let normal = v.normal;
+            // Gram-Schmidt, any perpendicular will do where the UVs gave nothing
+            let mut t = tangents[i] - normal * normal.dot(tangents[i]);
+            if t.mag_sq() < f32::EPSILON {
+                let axis = if normal.x.abs() < 0.9 {
+                    Vec3::unit_x()
+                } else {
+                    Vec3::unit_y()
+                };
+                t = axis - normal * normal.dot(axis);
+            }
+            t.normalize();
+            let sign = if normal.cross(t).dot(bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            v.tangent = t;
+            v.bitangent = normal.cross(t) * sign;
         }
     }
 
@@ -85,18 +441,13 @@ impl Mesh {
             gl.DrawElements(
                 gl33::GL_TRIANGLES,
                 self.indices.len() as i32,
-                gl33::GL_UNSIGNED_INT,
+                self.index_type(),
                 std::ptr::null(),
             );
         }
     }
     pub fn setup_mesh(&mut self, gl: &GlFns) -> Result<(), String> {
         unsafe {
-            gl.GenVertexArrays(1, &mut self.gl_vao);
-            if self.gl_vao == 0 {
-                return Err("failed setup_mesh: gl.GenVertexArrays(1, &mut gl_vao)".to_string());
-            }
-
             gl.GenBuffers(1, &mut self.gl_vbo);
             if self.gl_vbo == 0 {
                 return Err("failed setup_mesh: gl.GenBuffers(1, &mut gl_vbo)".to_string());
@@ -107,118 +458,221 @@ impl Mesh {
                 return Err("failed setup_mesh: gl.GenBuffers(1, &mut gl_ebo)".to_string());
             }
 
-            gl.BindVertexArray(self.gl_vao);
-            check_gl_err(gl);
-
             gl.BindBuffer(GL_ARRAY_BUFFER, self.gl_vbo);
             gl_buffer_data_arr_stat(gl, &self.vertices);
             check_gl_err(gl);
+        }
 
-            gl.BindBuffer(GL_ELEMENT_ARRAY_BUFFER, self.gl_ebo);
+        // the element buffer binding is part of the VAO, fill it once bound
+        self.gl_vao = build_vao::<Vertex>(gl, self.gl_vbo, Some(self.gl_ebo))?;
+        if self.short_indices {
+            let indices: Vec<u16> = self.indices.iter().map(|i| *i as u16).collect();
+            gl_buffer_data_element_stat(gl, &indices);
+        } else {
             gl_buffer_data_element_stat(gl, &self.indices);
-            check_gl_err(gl);
-
-            let vert_size = std::mem::size_of::<Vertex>() as i32;
-            // position attribute
-            gl.VertexAttribPointer(
-                0,
-                3,
-                GL_FLOAT,
-                0, //gl33::GL_FALSE,
-                vert_size,
-                (offset_of(&self.vertices[0], &self.vertices[0].position)) as *const _,
-            );
-            gl.EnableVertexAttribArray(0);
-            check_gl_err(gl);
-
-            // normals attribute
-            gl.VertexAttribPointer(
-                1,
-                3,
-                GL_FLOAT,
-                0, //gl33::GL_FALSE,
-                vert_size,
-                (offset_of(&self.vertices[0], &self.vertices[0].normal)) as *const _,
-            );
-            gl.EnableVertexAttribArray(1);
-            check_gl_err(gl);
-
-            // tex coords attribute
-            gl.VertexAttribPointer(
-                2,
-                2,
-                GL_FLOAT,
-                0, //gl33::GL_FALSE,
-                vert_size,
-                (offset_of(&self.vertices[0], &self.vertices[0].tex_coords)) as *const _,
-            );
-            gl.EnableVertexAttribArray(2);
-            check_gl_err(gl);
         }
+        check_gl_err(gl);
 
         Ok(())
     }
 }
 
+/// Node of an imported scene, parts of a model are placed relative to their parent
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Transform relative to the parent node
+    pub local: Mat4,
+    /// Accumulated transform from the root, see `Model::update_world_matrices`
+    pub world: Mat4,
+}
+
+/// Result of `Model::pick`
+#[derive(Clone, Copy, Debug)]
+pub struct ModelHit {
+    pub mesh: usize,
+    /// Index of the triangle in the mesh indices, divided by 3
+    pub triangle: usize,
+    pub distance: f32,
+    /// World space
+    pub point: Vec3,
+}
+
+/// A mesh placed in the scene by a node, a mesh can be referenced by several nodes
+#[derive(Clone, Copy, Debug)]
+pub struct MeshInstance {
+    pub mesh: usize,
+    pub node: usize,
+}
+
 #[derive(Default)]
 pub struct Model {
     pub meshes: Vec<Mesh>,
+    /// Node tree in depth first order, the root first. Empty for models built
+    /// by hand, those draw every mesh untransformed.
+    pub nodes: Vec<Node>,
+    pub instances: Vec<MeshInstance>,
+    pub skeleton: Skeleton,
+    pub animations: Vec<AnimationClip>,
+    /// Bounds of all mesh instances with their node transforms, in model space
+    pub aabb: Aabb,
+    pub sphere: Sphere,
     texture_cache: HashMap<String, u32>,
+    shared_textures: Vec<TextureHandle>,
     path: String,
+    // texture semantics depend on the file format, see TexType::from_assimp
+    #[cfg(feature = "assimp")]
+    is_obj: bool,
 }
 
 impl Model {
     pub fn from(gl: &GlFns, filename: &str) -> Result<Self, String> {
+        Self::import(gl, filename, None)
+    }
+
+    /// Same as `from` but textures are loaded through (and shared with) the asset manager
+    pub fn from_with_assets(
+        gl: &GlFns,
+        filename: &str,
+        assets: &mut AssetManager,
+    ) -> Result<Self, String> {
+        Self::import(gl, filename, Some(assets))
+    }
+
+    // glTF files are always read by gfx::gltf, other formats by assimp or,
+    // without the "assimp" feature, OBJ files by gfx::obj. Those two are slow,
+    // their results go through the model cache.
+    fn import(
+        gl: &GlFns,
+        filename: &str,
+        mut assets: Option<&mut AssetManager>,
+    ) -> Result<Self, String> {
+        if gltf::is_gltf(filename) {
+            return gltf::load(gl, filename, assets);
+        }
+        if let Some(model) = model_cache::load(gl, filename, &mut assets) {
+            return Ok(model);
+        }
+        let model = Self::import_uncached(gl, filename, assets)?;
+        if let Err(e) = model_cache::store(&model, filename) {
+            eprintln!("warning: model cache of {}: {}", filename, e);
+        }
+        Ok(model)
+    }
+
+    fn import_uncached(
+        gl: &GlFns,
+        filename: &str,
+        assets: Option<&mut AssetManager>,
+    ) -> Result<Self, String> {
+        #[cfg(feature = "assimp")]
+        return Self::import_assimp(gl, filename, assets);
+        #[cfg(not(feature = "assimp"))]
+        if obj::is_obj(filename) {
+            obj::load(gl, filename, assets)
+        } else {
+            Err(format!(
+                "error importing {}: only glTF and OBJ files can be read without the assimp feature",
+                filename
+            ))
+        }
+    }
+
+    #[cfg(feature = "assimp")]
+    fn import_assimp(
+        gl: &GlFns,
+        filename: &str,
+        mut assets: Option<&mut AssetManager>,
+    ) -> Result<Self, String> {
         let scene = russimp::scene::Scene::from_file(
             filename,
             vec![
-                // russimp::scene::PostProcess::CalculateTangentSpace,
+                russimp::scene::PostProcess::CalculateTangentSpace,
                 russimp::scene::PostProcess::Triangulate,
+                russimp::scene::PostProcess::GenerateSmoothNormals,
                 russimp::scene::PostProcess::FlipUVs,
                 russimp::scene::PostProcess::JoinIdenticalVertices,
+                russimp::scene::PostProcess::LimitBoneWeights,
                 // russimp::scene::PostProcess::SortByPrimitiveType,
             ],
         )
-        .unwrap();
+        .map_err(|e| format!("error importing {}: {}", filename, e))?;
 
-        let mut model = Model {
-            path: Path::new(filename)
-                .parent()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-            ..Default::default()
-        };
+        let root = scene
+            .root
+            .clone()
+            .ok_or_else(|| format!("error importing {}: scene has no root node", filename))?;
+
+        let mut model = Model::empty(filename);
 
-        model.process_node(gl, scene.root.as_ref().unwrap().clone(), &scene);
+        let mut scene_meshes = HashMap::new();
+        model.process_node(gl, root, None, &scene, &mut scene_meshes, &mut assets);
+        model.update_world_matrices();
+        model.animations = scene
+            .animations
+            .iter()
+            .map(AnimationClip::from_assimp)
+            .collect();
 
         Ok(model)
     }
 
+    #[cfg(feature = "assimp")]
     fn process_node(
         &mut self,
         gl: &GlFns,
         node: Rc<RefCell<russimp::node::Node>>,
+        parent: Option<usize>,
         scene: &russimp::scene::Scene,
+        scene_meshes: &mut HashMap<u32, usize>,
+        assets: &mut Option<&mut AssetManager>,
     ) {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            name: node.borrow().name.clone(),
+            parent,
+            children: vec![],
+            local: to_mat4(&node.borrow().transformation),
+            world: Mat4::identity(),
+        });
+        if let Some(p) = parent {
+            self.nodes[p].children.push(index);
+        }
+
         for mid in node.borrow().meshes.iter() {
-            let mesh = &scene.meshes[*mid as usize];
-            self.process_mesh(gl, mesh, scene);
+            // meshes shared by several nodes are imported once
+            let mesh = match scene_meshes.get(mid) {
+                Some(m) => *m,
+                None => match scene.meshes.get(*mid as usize) {
+                    Some(mesh) => {
+                        self.process_mesh(gl, mesh, scene, assets);
+                        scene_meshes.insert(*mid, self.meshes.len() - 1);
+                        self.meshes.len() - 1
+                    }
+                    None => continue,
+                },
+            };
+            self.instances.push(MeshInstance { mesh, node: index });
         }
 
         for n in node.borrow().children.iter() {
-            self.process_node(gl, n.clone(), scene);
+            self.process_node(gl, n.clone(), Some(index), scene, scene_meshes, assets);
         }
     }
 
+    #[cfg(feature = "assimp")]
     fn process_mesh(
         &mut self,
         gl: &GlFns,
         mesh: &russimp::mesh::Mesh,
         scene: &russimp::scene::Scene,
+        assets: &mut Option<&mut AssetManager>,
     ) {
         let mut m = Mesh::default();
+        let tex_coords = mesh.texture_coords.first().and_then(|c| c.as_ref());
+        let tex_coords1 = mesh.texture_coords.get(1).and_then(|c| c.as_ref());
 
         for i in 0..mesh.vertices.len() {
             let mut vert = Vertex::default();
@@ -226,59 +680,78 @@ impl Model {
             vert.position.x = mesh.vertices[i].x;
             vert.position.y = mesh.vertices[i].y;
             vert.position.z = mesh.vertices[i].z;
-            // normals
-            vert.normal.x = mesh.normals[i].x;
-            vert.normal.y = mesh.normals[i].y;
-            vert.normal.z = mesh.normals[i].z;
+            // normals, generated on import, only points and lines have none
+            if let Some(n) = mesh.normals.get(i) {
+                vert.normal.x = n.x;
+                vert.normal.y = n.y;
+                vert.normal.z = n.z;
+            }
             // tex coords
-            if !mesh.texture_coords.is_empty() {
-                vert.tex_coords.x = mesh.texture_coords[0].as_ref().unwrap()[i].x;
-                vert.tex_coords.y = mesh.texture_coords[0].as_ref().unwrap()[i].y;
+            if let Some(t) = tex_coords.and_then(|c| c.get(i)) {
+                vert.tex_coords.x = t.x;
+                vert.tex_coords.y = t.y;
+            }
+            if let Some(t) = tex_coords1.and_then(|c| c.get(i)) {
+                vert.tex_coords1 = Vec2::new(t.x, t.y);
+            }
+            // tangent space, computed by assimp for meshes with normals and UVs
+            if let (Some(t), Some(b)) = (mesh.tangents.get(i), mesh.bitangents.get(i)) {
+                vert.tangent = Vec3::new(t.x, t.y, t.z);
+                vert.bitangent = Vec3::new(b.x, b.y, b.z);
             }
 
             m.vertices.push(vert);
         }
 
-        // process indices
-        for f in mesh.faces.iter() {
-            m.indices.push(f.0[0]);
-            m.indices.push(f.0[1]);
-            m.indices.push(f.0[2]);
+        // process indices, skipping the points and lines Triangulate leaves alone
+        for f in mesh.faces.iter().filter(|f| f.0.len() == 3) {
+            m.indices.extend_from_slice(&f.0);
+        }
+
+        // bone weights
+        for bone in mesh.bones.iter() {
+            let index = self
+                .skeleton
+                .add_bone(&bone.name, to_mat4(&bone.offset_matrix));
+            for w in bone.weights.iter() {
+                if let Some(v) = m.vertices.get_mut(w.vertex_id as usize) {
+                    v.add_bone_weight(index, w.weight);
+                }
+            }
+        }
+        if !mesh.bones.is_empty() {
+            m.skinned = true;
+            m.vertices
+                .iter_mut()
+                .for_each(|v| v.normalize_bone_weights());
         }
 
+        if mesh.tangents.len() != mesh.vertices.len() {
+            m.generate_tangents();
+        }
+        m.compute_bounds();
+
         // process materials
-        if mesh.material_index > 0 {
-            let mat = &scene.materials[mesh.material_index as usize];
-            // println!("Materials: {:?}", mat);
+        if let Some(mat) = scene.materials.get(mesh.material_index as usize) {
             for p in mat.properties.iter() {
-                if p.key == "$tex.file" {
-                    match p.semantic {
-                        TextureType::Diffuse => {
-                            let mut t = Texture {
-                                id: 0,
-                                tex_type: TexType::Diffuse,
-                            };
-                            if let russimp::material::PropertyTypeInfo::String(filename) = &p.data {
-                                t.id = self
-                                    .load_texture(gl, &format!("{}/{}", self.path, filename))
-                                    .unwrap();
-                                m.textures.push(t);
-                            }
-                        }
-                        TextureType::Specular => {
-                            let mut t = Texture {
-                                id: 0,
-                                tex_type: TexType::Specular,
-                            };
-                            if let russimp::material::PropertyTypeInfo::String(filename) = &p.data {
-                                t.id = self
-                                    .load_texture(gl, &format!("{}/{}", self.path, filename))
-                                    .unwrap();
-                                m.textures.push(t);
-                            }
-                        }
-                        _ => {}
-                    }
+                if p.key != "$tex.file" {
+                    continue;
+                }
+                let tex_type = match TexType::from_assimp(p.semantic, self.is_obj) {
+                    Some(t) => t,
+                    None => continue,
+                };
+                if let russimp::material::PropertyTypeInfo::String(filename) = &p.data {
+                    let path = Path::new(&self.path).join(filename);
+                    let path = path.to_string_lossy().to_string();
+                    let id = self.load_texture_or_fallback(gl, &path, assets);
+                    m.textures.push(Texture {
+                        id,
+                        tex_type,
+                        uv_set: mat_uv_set(mat, p.semantic, p.index),
+                        path,
+                        ..Default::default()
+                    });
                 }
             }
         }
@@ -286,7 +759,44 @@ impl Model {
         self.meshes.push(m);
     }
 
-    fn load_texture(&mut self, gl: &GlFns, filename: &str) -> Result<u32, String> {
+    // a model with a broken texture reference still renders, with the
+    // checkerboard owned by the model in place of the texture
+    pub(crate) fn load_texture_or_fallback(
+        &mut self,
+        gl: &GlFns,
+        filename: &str,
+        assets: &mut Option<&mut AssetManager>,
+    ) -> u32 {
+        // also holds the fallbacks of files that failed before
+        if let Some(id) = self.texture_cache.get(filename) {
+            return *id;
+        }
+        match self.load_texture(gl, filename, assets) {
+            Ok(id) => id,
+            Err(e) => {
+                eprintln!("warning: {}, using fallback texture", e);
+                let id = procedural::upload(gl, &procedural::missing_texture());
+                self.texture_cache.insert(filename.to_string(), id);
+                id
+            }
+        }
+    }
+
+    fn load_texture(
+        &mut self,
+        gl: &GlFns,
+        filename: &str,
+        assets: &mut Option<&mut AssetManager>,
+    ) -> Result<u32, String> {
+        // shared textures are owned by the asset manager
+        if let Some(assets) = assets.as_deref_mut() {
+            let tex = assets.texture(gl, filename)?;
+            let id = tex.id;
+            if !self.shared_textures.iter().any(|t| t.id == id) {
+                self.shared_textures.push(tex);
+            }
+            return Ok(id);
+        }
         // lookup cache
         if self.texture_cache.contains_key(filename) {
             return Ok(*self.texture_cache.get(filename).unwrap());
@@ -298,17 +808,246 @@ impl Model {
         Ok(tex)
     }
 
+    /// Model without meshes, with textures looked up next to `filename`
+    pub(crate) fn empty(filename: &str) -> Self {
+        Model {
+            path: Path::new(filename)
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            #[cfg(feature = "assimp")]
+            is_obj: Path::new(filename)
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("obj")),
+            ..Default::default()
+        }
+    }
+
+    /// Cached texture `key`, or the one `load` creates. Failures are cached as
+    /// the fallback texture too, so they are reported once.
+    pub(crate) fn cached_texture(
+        &mut self,
+        gl: &GlFns,
+        key: &str,
+        load: impl FnOnce() -> Result<u32, String>,
+    ) -> u32 {
+        if let Some(id) = self.texture_cache.get(key) {
+            return *id;
+        }
+        let id = load().unwrap_or_else(|e| {
+            eprintln!("warning: {}, using fallback texture", e);
+            procedural::upload(gl, &procedural::missing_texture())
+        });
+        self.texture_cache.insert(key.to_string(), id);
+        id
+    }
+
+    /// 1x1 white texture for materials given by their factors alone, shaders
+    /// sample texture_diffuse1 whatever the material
+    pub(crate) fn white_texture(&mut self, gl: &GlFns) -> u32 {
+        self.cached_texture(gl, WHITE_TEXTURE, || {
+            Ok(procedural::upload(
+                gl,
+                &procedural::TexImage::from_fn(1, 1, |_, _| procedural::WHITE),
+            ))
+        })
+    }
+
+    /// Directory of the model file, relative texture paths start there
+    pub(crate) fn dir(&self) -> &Path {
+        Path::new(&self.path)
+    }
+
+    /// Textures loaded by the model itself (i.e. not shared through an asset manager)
+    pub fn owned_textures(&self) -> Vec<u32> {
+        self.texture_cache.values().copied().collect()
+    }
+
+    /// Runs `Mesh::optimize` on every mesh and returns the totals before and
+    /// after, call it before `setup`
+    pub fn optimize(&mut self) -> (MeshStats, MeshStats) {
+        let (mut before, mut after) = (MeshStats::default(), MeshStats::default());
+        for m in self.meshes.iter_mut() {
+            let (b, a) = m.optimize();
+            before = before + b;
+            after = after + a;
+        }
+        self.update_bounds();
+        (before, after)
+    }
+
     pub fn setup(&mut self, gl: &GlFns) -> Result<(), String> {
         for m in self.meshes.iter_mut() {
             m.setup_mesh(gl)?;
+            // meshes built by hand
+            if m.aabb.is_empty() && !m.vertices.is_empty() {
+                m.compute_bounds();
+            }
         }
+        self.update_bounds();
 
         Ok(())
     }
 
-    pub fn draw(&self, gl: &GlFns, shader: &Shaders) {
-        for m in self.meshes.iter() {
-            m.draw(gl, shader);
+    /// Index of the first node called `name`
+    pub fn find_node(&self, name: &str) -> Option<usize> {
+        self.nodes.iter().position(|n| n.name == name)
+    }
+
+    /// Recomputes the world matrices after local matrices were changed
+    pub fn update_world_matrices(&mut self) {
+        // parents come before their children
+        for i in 0..self.nodes.len() {
+            self.nodes[i].world = match self.nodes[i].parent {
+                Some(p) => self.nodes[p].world * self.nodes[i].local,
+                None => self.nodes[i].local,
+            };
+        }
+        self.update_bounds();
+    }
+
+    /// Recomputes `aabb` and `sphere` from the mesh bounds, done by
+    /// `update_world_matrices`
+    pub fn update_bounds(&mut self) {
+        let placed: Vec<(Aabb, Sphere)> = self
+            .placements()
+            .map(|(mesh, m)| {
+                let mesh = &self.meshes[mesh];
+                (mesh.aabb.transformed(&m), mesh.sphere.transformed(&m))
+            })
+            .filter(|(aabb, _)| !aabb.is_empty())
+            .collect();
+        self.aabb = placed
+            .iter()
+            .fold(Aabb::default(), |acc, (aabb, _)| acc.union(aabb));
+        let center = self.aabb.center();
+        self.sphere = Sphere {
+            center,
+            radius: placed
+                .iter()
+                .map(|(_, s)| (s.center - center).mag() + s.radius)
+                .fold(0.0, f32::max),
+        };
+    }
+
+    /// Draws the mesh instances whose box intersects `frustum` (in world
+    /// space, from `projection * view`), with "model" set like `draw_at` does.
+    /// Returns how many were drawn.
+    pub fn draw_culled(
+        &self,
+        gl: &GlFns,
+        shader: &Shaders,
+        model: &Mat4,
+        frustum: &Frustum,
+    ) -> usize {
+        let mut drawn = 0;
+        for (mesh, m) in self.placements() {
+            let world = *model * m;
+            let mesh = &self.meshes[mesh];
+            if frustum.intersects_aabb(&mesh.aabb.transformed(&world)) {
+                shader.try_set(gl, "model", &world);
+                mesh.draw(gl, shader);
+                drawn += 1;
+            }
+        }
+        drawn
+    }
+
+    /// Closest triangle hit by the world space `ray`, for the model drawn with
+    /// `model` as its matrix
+    pub fn pick(&self, ray: &Ray, model: &Mat4) -> Option<ModelHit> {
+        let mut best: Option<ModelHit> = None;
+        for (mesh, m) in self.placements() {
+            let world = *model * m;
+            let closer = |d: f32| best.as_ref().is_none_or(|b| d < b.distance);
+            if !ray
+                .intersect_aabb(&self.meshes[mesh].aabb.transformed(&world))
+                .is_some_and(closer)
+            {
+                continue;
+            }
+            // distances along the unnormalized direction stay in world units
+            let local = ray.transformed(&world.inversed());
+            if let Some((triangle, distance)) = self.meshes[mesh].pick(&local) {
+                if closer(distance) {
+                    best = Some(ModelHit {
+                        mesh,
+                        triangle,
+                        distance,
+                        point: ray.at(distance),
+                    });
+                }
+            }
         }
+        best
     }
+
+    // every mesh drawn with its transform in the model, like draw_at places them
+    fn placements(&self) -> impl Iterator<Item = (usize, Mat4)> + '_ {
+        let instances = self.instances.iter().map(|i| {
+            let m = if self.meshes[i.mesh].skinned {
+                Mat4::identity()
+            } else {
+                self.nodes[i.node].world
+            };
+            (i.mesh, m)
+        });
+        let untransformed = (0..self.meshes.len()).map(|m| (m, Mat4::identity()));
+        // models built by hand have no nodes
+        let (instances, untransformed) = if self.nodes.is_empty() {
+            (None, Some(untransformed))
+        } else {
+            (Some(instances), None)
+        };
+        instances
+            .into_iter()
+            .flatten()
+            .chain(untransformed.into_iter().flatten())
+    }
+
+    /// Draws every mesh instance with "model" set to `model` times its node transform
+    pub fn draw_at(&self, gl: &GlFns, shader: &Shaders, model: &Mat4) {
+        // models built by hand have no nodes
+        if self.nodes.is_empty() {
+            shader.try_set(gl, "model", model);
+            for m in self.meshes.iter() {
+                m.draw(gl, shader);
+            }
+            return;
+        }
+        for instance in self.instances.iter() {
+            let mesh = &self.meshes[instance.mesh];
+            let world = if mesh.skinned {
+                *model
+            } else {
+                *model * self.nodes[instance.node].world
+            };
+            shader.try_set(gl, "model", &world);
+            mesh.draw(gl, shader);
+        }
+    }
+}
+
+/// assimp matrices are row major, ultraviolet's column major
+#[cfg(feature = "assimp")]
+pub(crate) fn to_mat4(m: &russimp::Matrix4x4) -> Mat4 {
+    Mat4::new(
+        Vec4::new(m.a1, m.b1, m.c1, m.d1),
+        Vec4::new(m.a2, m.b2, m.c2, m.d2),
+        Vec4::new(m.a3, m.b3, m.c3, m.d3),
+        Vec4::new(m.a4, m.b4, m.c4, m.d4),
+    )
+}
+
+/// UV set of a texture from its `$tex.uvwsrc` property, 0 when there is none
+#[cfg(feature = "assimp")]
+fn mat_uv_set(mat: &russimp::material::Material, semantic: TextureType, index: usize) -> u32 {
+    mat.properties
+        .iter()
+        .find(|p| p.key == "$tex.uvwsrc" && p.semantic == semantic && p.index == index)
+        .and_then(|p| match &p.data {
+            russimp::material::PropertyTypeInfo::IntegerArray(v) => v.first().copied(),
+            _ => None,
+        })
+        .map_or(0, |i| i.max(0) as u32)
 }
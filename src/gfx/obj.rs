@@ -0,0 +1,556 @@
+use super::assets::AssetManager;
+use super::models::{
+    Material, Mesh, MeshInstance, Model, Node, TexType, Texture, Vertex, WHITE_TEXTURE,
+};
+use gl33::GlFns;
+use std::collections::HashMap;
+use std::path::Path;
+use ultraviolet::*;
+
+pub fn is_obj(filename: &str) -> bool {
+    Path::new(filename)
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("obj"))
+}
+
+/// Geometry of an OBJ file, see `parse_obj`
+#[derive(Default)]
+pub struct ObjFile {
+    pub meshes: Vec<ObjMesh>,
+    /// Files of the `mtllib` statements, relative to the OBJ file
+    pub mtllibs: Vec<String>,
+}
+
+/// Faces of one group (`o` or `g`) using one material
+#[derive(Default)]
+pub struct ObjMesh {
+    pub group: String,
+    pub material: Option<String>,
+    pub mesh: Mesh,
+}
+
+/// A `newmtl` entry of an MTL file
+#[derive(Clone, Default)]
+pub struct ObjMaterial {
+    pub material: Material,
+    /// Texture files of the `map_*` statements, relative to the MTL file
+    pub maps: Vec<(TexType, String)>,
+}
+
+/// Imports an OBJ file with the materials of its MTL files, without assimp.
+///
+/// A missing MTL file or texture is reported and replaced by defaults, like
+/// the assimp import does.
+pub fn load(
+    gl: &GlFns,
+    filename: &str,
+    mut assets: Option<&mut AssetManager>,
+) -> Result<Model, String> {
+    let error = |e: String| format!("error importing {}: {}", filename, e);
+    let text = std::fs::read_to_string(filename).map_err(|e| error(e.to_string()))?;
+    let obj = parse_obj(&text).map_err(error)?;
+
+    let mut model = Model::empty(filename);
+    let dir = model.dir().to_path_buf();
+
+    let mut materials = HashMap::new();
+    for lib in obj.mtllibs.iter() {
+        let path = dir.join(lib);
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_mtl(&text));
+        match parsed {
+            Ok(mut m) => {
+                // map paths are relative to the MTL file
+                let lib_dir = Path::new(lib).parent().unwrap_or(Path::new(""));
+                for material in m.values_mut() {
+                    for (_, file) in material.maps.iter_mut() {
+                        *file = lib_dir.join(&*file).to_string_lossy().to_string();
+                    }
+                }
+                materials.extend(m)
+            }
+            Err(e) => eprintln!(
+                "warning: {}: {}, using default materials",
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    model.nodes.push(Node {
+        name: Path::new(filename)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        parent: None,
+        children: vec![],
+        local: Mat4::identity(),
+        world: Mat4::identity(),
+    });
+    let mut groups: HashMap<String, usize> = HashMap::new();
+
+    for ObjMesh {
+        group,
+        material,
+        mut mesh,
+    } in obj.meshes
+    {
+        let node = *groups.entry(group.clone()).or_insert_with(|| {
+            model.nodes.push(Node {
+                name: group,
+                parent: Some(0),
+                children: vec![],
+                local: Mat4::identity(),
+                world: Mat4::identity(),
+            });
+            model.nodes.len() - 1
+        });
+
+        let material = material.and_then(|m| materials.get(&m));
+        if let Some(material) = material {
+            mesh.material = material.material;
+            for (tex_type, file) in material.maps.iter() {
+                let path = dir.join(file).to_string_lossy().to_string();
+                let id = model.load_texture_or_fallback(gl, &path, &mut assets);
+                mesh.textures.push(Texture {
+                    id,
+                    tex_type: *tex_type,
+                    path,
+                    ..Default::default()
+                });
+            }
+        }
+        if !mesh.textures.iter().any(|t| t.tex_type == TexType::Diffuse) {
+            mesh.textures.push(Texture {
+                id: model.white_texture(gl),
+                tex_type: TexType::Diffuse,
+                path: WHITE_TEXTURE.to_string(),
+                ..Default::default()
+            });
+        }
+
+        model.meshes.push(mesh);
+        model.instances.push(MeshInstance {
+            mesh: model.meshes.len() - 1,
+            node,
+        });
+    }
+    for i in 1..model.nodes.len() {
+        model.nodes[0].children.push(i);
+    }
+    model.update_world_matrices();
+    Ok(model)
+}
+
+/// Parses the text of an OBJ file.
+///
+/// Polygons are triangulated, negative (relative) indices resolved and faces
+/// without normals get normals from their smoothing group: smooth across the
+/// faces of a group, flat with `s off`. Files without `s` statements are
+/// smoothed like assimp's GenerateSmoothNormals does. V is flipped like with
+/// assimp's FlipUVs, which the demo assets expect. Points and lines are
+/// skipped.
+pub fn parse_obj(text: &str) -> Result<ObjFile, String> {
+    let mut positions: Vec<Vec3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut tex_coords: Vec<Vec2> = vec![];
+
+    let mut file = ObjFile::default();
+    let mut builders: Vec<MeshBuilder> = vec![];
+    let mut by_key: HashMap<(String, Option<String>), usize> = HashMap::new();
+    let mut group = "default".to_string();
+    let mut material: Option<String> = None;
+    let mut current: Option<usize> = None;
+    // smoothing group 1 until an `s` statement says otherwise
+    let mut smoothing = 1u32;
+    let mut face_count = 0usize;
+
+    for (number, line) in logical_lines(text) {
+        let error = |e: &str| format!("line {}: {}", number, e);
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest = line[keyword.len()..].trim();
+        match keyword {
+            "v" => positions.push(parse_vec3(tokens).ok_or_else(|| error("invalid vertex"))?),
+            "vn" => normals.push(parse_vec3(tokens).ok_or_else(|| error("invalid normal"))?),
+            "vt" => {
+                let uv: Vec<f32> = tokens.take(2).filter_map(|t| t.parse().ok()).collect();
+                let u = *uv.first().ok_or_else(|| error("invalid tex coord"))?;
+                // v is optional and defaults to 0
+                let v = uv.get(1).copied().unwrap_or(0.0);
+                tex_coords.push(Vec2::new(u, 1.0 - v));
+            }
+            "o" | "g" => {
+                group = if rest.is_empty() { "default" } else { rest }.to_string();
+                current = None;
+            }
+            "usemtl" => {
+                material = Some(rest.to_string());
+                current = None;
+            }
+            "mtllib" => file
+                .mtllibs
+                .extend(rest.split_whitespace().map(|s| s.replace('\\', "/"))),
+            "s" => {
+                smoothing = match rest {
+                    "off" => 0,
+                    s => s.parse().map_err(|_| error("invalid smoothing group"))?,
+                }
+            }
+            "f" => {
+                let builder = *current.get_or_insert_with(|| {
+                    let key = (group.clone(), material.clone());
+                    *by_key.entry(key).or_insert_with(|| {
+                        builders.push(MeshBuilder::new(&group, &material));
+                        builders.len() - 1
+                    })
+                });
+                face_count += 1;
+                // flat faces share no vertices with other faces
+                let smooth_key = if smoothing == 0 {
+                    -(face_count as i64)
+                } else {
+                    smoothing as i64
+                };
+
+                let mut corners = vec![];
+                for t in tokens {
+                    let corner = parse_corner(t, &positions, &tex_coords, &normals)
+                        .map_err(|e| error(&e))?;
+                    corners.push(corner);
+                }
+                if corners.len() < 3 {
+                    return Err(error("face with less than 3 vertices"));
+                }
+                builders[builder].add_face(&corners, smooth_key, &positions, &tex_coords, &normals);
+            }
+            // free-form geometry, points, lines and render attributes
+            _ => {}
+        }
+    }
+
+    file.meshes = builders
+        .into_iter()
+        .filter(|b| !b.mesh.indices.is_empty())
+        .map(MeshBuilder::finish)
+        .collect();
+    Ok(file)
+}
+
+/// Parses the text of an MTL file into its materials by name.
+///
+/// Reads Kd, Ks, Ns, Ke, d (or Tr) and the map_Kd, map_Ks, map_Ke, map_d,
+/// map_Bump (or bump and norm, taken as normal maps like the assimp import
+/// does) and disp statements. Texture options such as `-bm 0.5` are skipped.
+pub fn parse_mtl(text: &str) -> Result<HashMap<String, ObjMaterial>, String> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, ObjMaterial)> = None;
+
+    for (number, line) in logical_lines(text) {
+        let error = |e: &str| format!("line {}: {}", number, e);
+        let mut tokens = line.split_whitespace();
+        let Some(keyword) = tokens.next() else {
+            continue;
+        };
+        let rest = line[keyword.len()..].trim();
+
+        if keyword == "newmtl" {
+            if let Some((name, m)) = current.take() {
+                materials.insert(name, m);
+            }
+            current = Some((rest.to_string(), ObjMaterial::default()));
+            continue;
+        }
+        let Some((_, m)) = current.as_mut() else {
+            // statements before the first newmtl have nothing to apply to
+            continue;
+        };
+        let color = || parse_vec3(rest.split_whitespace()).ok_or_else(|| error("invalid color"));
+        let scalar = || {
+            rest.parse::<f32>()
+                .map_err(|_| error(&format!("invalid {} value", keyword)))
+        };
+        match keyword {
+            "Kd" => {
+                let kd = color()?;
+                m.material.base_color = Vec4::new(kd.x, kd.y, kd.z, m.material.base_color.w);
+            }
+            "Ks" => m.material.specular = color()?,
+            "Ke" => m.material.emissive = color()?,
+            "Ns" => m.material.shininess = scalar()?,
+            "d" => m.material.base_color.w = scalar()?,
+            "Tr" => m.material.base_color.w = 1.0 - scalar()?,
+            _ => {
+                let tex_type = match keyword {
+                    "map_Kd" => TexType::Diffuse,
+                    "map_Ks" => TexType::Specular,
+                    "map_Ke" => TexType::Emissive,
+                    "map_d" => TexType::Opacity,
+                    "map_Bump" | "map_bump" | "bump" | "norm" => TexType::Normal,
+                    "disp" => TexType::Height,
+                    _ => continue,
+                };
+                let file = map_file(rest).ok_or_else(|| error("map without a file"))?;
+                m.maps.push((tex_type, file));
+            }
+        }
+    }
+    if let Some((name, m)) = current {
+        materials.insert(name, m);
+    }
+    Ok(materials)
+}
+
+// lines without comments, joined where they end with a backslash, numbered
+// from 1 by their first physical line
+fn logical_lines(text: &str) -> Vec<(usize, String)> {
+    let mut lines = vec![];
+    let mut pending: Option<(usize, String)> = None;
+    for (i, raw) in text.lines().enumerate() {
+        let line = raw.split('#').next().unwrap_or("");
+        let (number, mut joined) = pending.take().unwrap_or((i + 1, String::new()));
+        match line.trim_end().strip_suffix('\\') {
+            Some(start) => {
+                joined.push_str(start);
+                joined.push(' ');
+                pending = Some((number, joined));
+            }
+            None => {
+                joined.push_str(line);
+                lines.push((number, joined.trim().to_string()));
+            }
+        }
+    }
+    lines.extend(pending.map(|(n, l)| (n, l.trim().to_string())));
+    lines
+}
+
+fn parse_vec3<'a>(mut tokens: impl Iterator<Item = &'a str>) -> Option<Vec3> {
+    let mut c = || tokens.next()?.parse::<f32>().ok();
+    Some(Vec3::new(c()?, c()?, c()?))
+}
+
+// texture file of a map statement, after its options
+fn map_file(rest: &str) -> Option<String> {
+    let mut tokens = rest.split_whitespace().peekable();
+    while let Some(option) = tokens.peek().filter(|t| t.starts_with('-')) {
+        let max_args = match *option {
+            "-o" | "-s" | "-t" => 3,
+            "-mm" => 2,
+            _ => 1,
+        };
+        tokens.next();
+        // -o, -s and -t take 1 to 3 numbers
+        for _ in 0..max_args {
+            match tokens.peek() {
+                Some(t) if t.parse::<f32>().is_ok() || max_args == 1 => {
+                    tokens.next();
+                }
+                _ => break,
+            }
+        }
+    }
+    let file = tokens.collect::<Vec<_>>().join(" ");
+    (!file.is_empty()).then(|| file.replace('\\', "/"))
+}
+
+// 0-based position, tex coord and normal indices of a `v/vt/vn` face corner
+type Corner = (usize, Option<usize>, Option<usize>);
+
+fn parse_corner(
+    token: &str,
+    positions: &[Vec3],
+    tex_coords: &[Vec2],
+    normals: &[Vec3],
+) -> Result<Corner, String> {
+    let mut parts = token.split('/');
+    let mut index = |len: usize, what: &str| -> Result<Option<usize>, String> {
+        match parts.next() {
+            None | Some("") => Ok(None),
+            Some(p) => resolve_index(p, len)
+                .map(Some)
+                .ok_or_else(|| format!("invalid {} index '{}'", what, p)),
+        }
+    };
+    let position = index(positions.len(), "vertex")?
+        .ok_or_else(|| format!("face corner '{}' has no vertex", token))?;
+    let tex_coord = index(tex_coords.len(), "tex coord")?;
+    let normal = index(normals.len(), "normal")?;
+    Ok((position, tex_coord, normal))
+}
+
+// OBJ indices start at 1, negative ones count back from the last element
+fn resolve_index(token: &str, len: usize) -> Option<usize> {
+    let i: i64 = token.parse().ok()?;
+    let resolved = if i > 0 { i - 1 } else { len as i64 + i };
+    (i != 0 && resolved >= 0 && (resolved as usize) < len).then_some(resolved as usize)
+}
+
+struct MeshBuilder {
+    group: String,
+    material: Option<String>,
+    mesh: Mesh,
+    // corner and smoothing group to vertex
+    vertices: HashMap<(Corner, i64), u32>,
+    // face normal sums of the vertices without a normal in the file
+    normal_sums: HashMap<u32, Vec3>,
+}
+
+impl MeshBuilder {
+    fn new(group: &str, material: &Option<String>) -> Self {
+        MeshBuilder {
+            group: group.to_string(),
+            material: material.clone(),
+            mesh: Mesh::default(),
+            vertices: HashMap::new(),
+            normal_sums: HashMap::new(),
+        }
+    }
+
+    fn add_face(
+        &mut self,
+        corners: &[Corner],
+        smooth_key: i64,
+        positions: &[Vec3],
+        tex_coords: &[Vec2],
+        normals: &[Vec3],
+    ) {
+        let indices: Vec<u32> = corners
+            .iter()
+            .map(|c| {
+                // the smoothing group only matters for generated normals
+                let key = (*c, if c.2.is_some() { 0 } else { smooth_key });
+                *self.vertices.entry(key).or_insert_with(|| {
+                    self.mesh.vertices.push(Vertex {
+                        position: positions[c.0],
+                        tex_coords: c.1.map_or(Vec2::zero(), |t| tex_coords[t]),
+                        normal: c.2.map_or(Vec3::zero(), |n| normals[n]),
+                        ..Default::default()
+                    });
+                    let index = self.mesh.vertices.len() as u32 - 1;
+                    if c.2.is_none() {
+                        self.normal_sums.insert(index, Vec3::zero());
+                    }
+                    index
+                })
+            })
+            .collect();
+
+        let points: Vec<Vec3> = corners.iter().map(|c| positions[c.0]).collect();
+        for [a, b, c] in triangulate(&points) {
+            let tri = [indices[a], indices[b], indices[c]];
+            self.mesh.indices.extend_from_slice(&tri);
+            // weighted by area, the cross product is twice the triangle's
+            let normal = (points[b] - points[a]).cross(points[c] - points[a]);
+            for i in tri {
+                if let Some(sum) = self.normal_sums.get_mut(&i) {
+                    *sum += normal;
+                }
+            }
+        }
+    }
+
+    fn finish(mut self) -> ObjMesh {
+        for (i, sum) in self.normal_sums {
+            self.mesh.vertices[i as usize].normal = if sum.mag_sq() > 0.0 {
+                sum.normalized()
+            } else {
+                Vec3::unit_y()
+            };
+        }
+        self.mesh.generate_tangents();
+        self.mesh.compute_bounds();
+        ObjMesh {
+            group: self.group,
+            material: self.material,
+            mesh: self.mesh,
+        }
+    }
+}
+
+/// Splits a planar polygon into triangles by ear clipping, returned as indices
+/// into `points` with the winding of the polygon. Concave polygons are fine,
+/// self-intersecting ones and leftovers of degenerate ones get a fan.
+pub fn triangulate(points: &[Vec3]) -> Vec<[usize; 3]> {
+    let n = points.len();
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method, robust for concave polygons
+    let mut normal = Vec3::zero();
+    for i in 0..n {
+        let (p, q) = (points[i], points[(i + 1) % n]);
+        normal += Vec3::new(
+            (p.y - q.y) * (p.z + q.z),
+            (p.z - q.z) * (p.x + q.x),
+            (p.x - q.x) * (p.y + q.y),
+        );
+    }
+    let fan = |ring: &[usize]| -> Vec<[usize; 3]> {
+        (1..ring.len().saturating_sub(1))
+            .map(|i| [ring[0], ring[i], ring[i + 1]])
+            .collect()
+    };
+    if normal.mag_sq() == 0.0 {
+        return fan(&(0..n).collect::<Vec<_>>());
+    }
+
+    // project on the plane the normal is most perpendicular to, oriented so
+    // the polygon is counter-clockwise
+    let a = normal.abs();
+    let (x, y) = if a.x >= a.y && a.x >= a.z {
+        (1, 2)
+    } else if a.y >= a.z {
+        (2, 0)
+    } else {
+        (0, 1)
+    };
+    let sign = if normal[3 - x - y] >= 0.0 { 1.0 } else { -1.0 };
+    let p: Vec<Vec2> = points
+        .iter()
+        .map(|v| Vec2::new(v[x], v[y] * sign))
+        .collect();
+    let cross = |o: Vec2, a: Vec2, b: Vec2| (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x);
+
+    let mut ring: Vec<usize> = (0..n).collect();
+    let mut triangles = Vec::with_capacity(n - 2);
+    let mut i = 0;
+    let mut since_last_ear = 0;
+    while ring.len() > 3 {
+        if since_last_ear > ring.len() {
+            // no ear left, the polygon isn't simple
+            break;
+        }
+        let len = ring.len();
+        let (prev, cur, next) = (
+            ring[(i + len - 1) % len],
+            ring[i % len],
+            ring[(i + 1) % len],
+        );
+        let convex = cross(p[prev], p[cur], p[next]) > 0.0;
+        let is_ear = convex
+            && ring.iter().all(|&r| {
+                r == prev
+                    || r == cur
+                    || r == next
+                    || cross(p[prev], p[cur], p[r]) < 0.0
+                    || cross(p[cur], p[next], p[r]) < 0.0
+                    || cross(p[next], p[prev], p[r]) < 0.0
+            });
+        if is_ear {
+            triangles.push([prev, cur, next]);
+            ring.remove(i % len);
+            since_last_ear = 0;
+        } else {
+            i += 1;
+            since_last_ear += 1;
+        }
+        i %= ring.len();
+    }
+    triangles.extend(fan(&ring));
+    triangles
+}
@@ -0,0 +1,455 @@
+use super::bounds::Aabb;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::ops::Add;
+use ultraviolet::*;
+
+/// FIFO post-transform cache size used for the statistics and the overdraw
+/// clusters, close to what current GPUs reuse
+pub const STATS_CACHE_SIZE: usize = 16;
+/// The LRU cache size Forsyth's scores are tuned for
+const FORSYTH_CACHE_SIZE: usize = 32;
+const FETCH_LINE: usize = 64;
+const FETCH_LINES: usize = 1024;
+const OVERDRAW_RESOLUTION: usize = 256;
+
+/// Merges vertices with the same `key`, `indices` are rewritten to point
+/// into the returned vertices
+pub fn deduplicate<V: Clone, K: Hash + Eq>(
+    vertices: &[V],
+    indices: &mut [u32],
+    key: impl Fn(&V) -> K,
+) -> Vec<V> {
+    let mut unique = Vec::new();
+    let mut seen = HashMap::new();
+    let remap: Vec<u32> = vertices
+        .iter()
+        .map(|v| {
+            *seen.entry(key(v)).or_insert_with(|| {
+                unique.push(v.clone());
+                (unique.len() - 1) as u32
+            })
+        })
+        .collect();
+    for i in indices.iter_mut() {
+        *i = remap[*i as usize];
+    }
+    unique
+}
+
+fn forsyth_score(cache_position: Option<usize>, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache = match cache_position {
+        None => 0.0,
+        // the last triangle's vertices, scored lower so strips don't go back
+        Some(p) if p < 3 => 0.75,
+        Some(p) => (1.0 - (p - 3) as f32 / (FORSYTH_CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+    // vertices with few triangles left are finished first
+    cache + 2.0 * (remaining as f32).powf(-0.5)
+}
+
+/// Reorders the triangles for the post-transform vertex cache (Tom Forsyth's
+/// linear-speed algorithm)
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let indices = &indices[..indices.len() / 3 * 3];
+    let triangle_count = indices.len() / 3;
+
+    let mut remaining = vec![0u32; vertex_count];
+    for &i in indices {
+        remaining[i as usize] += 1;
+    }
+    // triangles of every vertex, the ones not emitted yet are kept in front
+    let mut offsets = vec![0usize; vertex_count + 1];
+    for v in 0..vertex_count {
+        offsets[v + 1] = offsets[v] + remaining[v] as usize;
+    }
+    let mut adjacency = vec![0usize; indices.len()];
+    let mut fill = offsets.clone();
+    for (t, tri) in indices.chunks_exact(3).enumerate() {
+        for &v in tri {
+            adjacency[fill[v as usize]] = t;
+            fill[v as usize] += 1;
+        }
+    }
+
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut score: Vec<f32> = remaining.iter().map(|r| forsyth_score(None, *r)).collect();
+    let triangle_score = |score: &[f32], t: usize| -> f32 {
+        indices[t * 3..t * 3 + 3]
+            .iter()
+            .map(|&v| score[v as usize])
+            .sum()
+    };
+    let mut tri_score: Vec<f32> = (0..triangle_count)
+        .map(|t| triangle_score(&score, t))
+        .collect();
+    let mut emitted = vec![false; triangle_count];
+    let mut cache: Vec<u32> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+    let mut result = Vec::with_capacity(indices.len());
+    let mut cursor = 0;
+
+    let mut best = (0..triangle_count).max_by(|a, b| tri_score[*a].total_cmp(&tri_score[*b]));
+    while let Some(t) = best {
+        emitted[t] = true;
+        let tri = &indices[t * 3..t * 3 + 3];
+        result.extend_from_slice(tri);
+
+        for &v in tri {
+            let v = v as usize;
+            let live = &mut adjacency[offsets[v]..offsets[v] + remaining[v] as usize];
+            if let Some(p) = live.iter().position(|&x| x == t) {
+                live.swap(p, live.len() - 1);
+                remaining[v] -= 1;
+            }
+        }
+
+        let mut new_cache: Vec<u32> = Vec::with_capacity(FORSYTH_CACHE_SIZE + 3);
+        for &v in tri.iter().chain(cache.iter()) {
+            if !new_cache.contains(&v) {
+                new_cache.push(v);
+            }
+        }
+        for (p, &v) in new_cache.iter().enumerate() {
+            let v = v as usize;
+            cache_position[v] = (p < FORSYTH_CACHE_SIZE).then_some(p);
+            score[v] = forsyth_score(cache_position[v], remaining[v]);
+        }
+
+        best = None;
+        let mut best_score = f32::NEG_INFINITY;
+        for (p, &v) in new_cache.iter().enumerate() {
+            let v = v as usize;
+            for &at in &adjacency[offsets[v]..offsets[v] + remaining[v] as usize] {
+                tri_score[at] = triangle_score(&score, at);
+                // vertices pushed out of the cache only get their scores updated
+                if p < FORSYTH_CACHE_SIZE && tri_score[at] > best_score {
+                    best_score = tri_score[at];
+                    best = Some(at);
+                }
+            }
+        }
+        new_cache.truncate(FORSYTH_CACHE_SIZE);
+        cache = new_cache;
+
+        if best.is_none() {
+            // nothing left around the cache, continue anywhere
+            while cursor < triangle_count && emitted[cursor] {
+                cursor += 1;
+            }
+            best = (cursor < triangle_count).then_some(cursor);
+        }
+    }
+    result
+}
+
+/// Cache misses of every triangle with a FIFO cache of `cache_size`
+fn triangle_misses(indices: &[u32], cache_size: usize) -> Vec<u32> {
+    let vertex_count = indices.iter().max().map_or(0, |m| *m as usize + 1);
+    let mut inserted = vec![usize::MAX; vertex_count];
+    let mut time = 0;
+    indices
+        .chunks_exact(3)
+        .map(|tri| {
+            let mut misses = 0;
+            for &v in tri {
+                let at = inserted[v as usize];
+                if at == usize::MAX || time - at >= cache_size {
+                    inserted[v as usize] = time;
+                    time += 1;
+                    misses += 1;
+                }
+            }
+            misses
+        })
+        .collect()
+}
+
+fn triangle_area_normal(positions: &[Vec3], tri: &[u32]) -> (Vec3, Vec3) {
+    let [a, b, c] = [0, 1, 2].map(|k| positions[tri[k] as usize]);
+    ((a + b + c) / 3.0, (b - a).cross(c - a))
+}
+
+/// Reorders clusters of triangles so the ones facing outwards, likely to
+/// hide others, are drawn first (Sander, Nehab and Barczak, "Fast
+/// Triangle Reordering for Vertex Locality and Reduced Overdraw"). `indices`
+/// should be optimized for the vertex cache already, clusters are cut where
+/// that order keeps the cache miss ratio within `threshold` (e.g. 1.05) and
+/// the input is returned when the ratio got worse anyway.
+pub fn optimize_overdraw(indices: &[u32], positions: &[Vec3], threshold: f32) -> Vec<u32> {
+    let misses = triangle_misses(indices, STATS_CACHE_SIZE);
+    let triangle_count = misses.len();
+    if triangle_count == 0 {
+        return indices.to_vec();
+    }
+
+    // hard boundaries where the cache order starts over, then soft ones
+    // inside them where the running miss ratio is good enough
+    let mut hard = vec![0];
+    hard.extend((1..triangle_count).filter(|&t| misses[t] == 3));
+    hard.push(triangle_count);
+    let mut clusters = Vec::new();
+    for w in hard.windows(2) {
+        let (start, end) = (w[0], w[1]);
+        let total: u32 = misses[start..end].iter().sum();
+        let limit = threshold * total as f32 / (end - start) as f32;
+        let mut from = start;
+        let mut running = 0;
+        for (t, m) in misses.iter().enumerate().take(end).skip(start) {
+            running += m;
+            if t + 1 < end && running as f32 <= limit * (t + 1 - from) as f32 {
+                clusters.push(from..t + 1);
+                from = t + 1;
+                running = 0;
+            }
+        }
+        clusters.push(from..end);
+    }
+
+    // area weighted centroid of the mesh and of each cluster
+    let mut mesh_centroid = Vec3::zero();
+    let mut mesh_area = 0.0;
+    let mut keys = Vec::with_capacity(clusters.len());
+    for range in &clusters {
+        let (mut centroid, mut normal, mut area) = (Vec3::zero(), Vec3::zero(), 0.0);
+        for tri in indices[range.start * 3..range.end * 3].chunks_exact(3) {
+            let (c, n) = triangle_area_normal(positions, tri);
+            let a = n.mag();
+            centroid += c * a;
+            normal += n;
+            area += a;
+        }
+        mesh_centroid += centroid;
+        mesh_area += area;
+        keys.push((centroid, normal, area));
+    }
+    if mesh_area > 0.0 {
+        mesh_centroid /= mesh_area;
+    }
+    let keys: Vec<f32> = keys
+        .into_iter()
+        .map(|(centroid, normal, area)| {
+            if area <= 0.0 || normal.mag_sq() == 0.0 {
+                return 0.0;
+            }
+            (centroid / area - mesh_centroid).dot(normal.normalized())
+        })
+        .collect();
+
+    let mut order: Vec<usize> = (0..clusters.len()).collect();
+    order.sort_by(|a, b| keys[*b].total_cmp(&keys[*a]));
+    let result: Vec<u32> = order
+        .iter()
+        .flat_map(|&c| &indices[clusters[c].start * 3..clusters[c].end * 3])
+        .copied()
+        .collect();
+
+    let acmr = |indices: &[u32]| {
+        triangle_misses(indices, STATS_CACHE_SIZE)
+            .iter()
+            .sum::<u32>()
+    };
+    if acmr(&result) as f32 > acmr(indices) as f32 * threshold {
+        return indices.to_vec();
+    }
+    result
+}
+
+/// Orders the vertices by their first use in `indices`, which are rewritten,
+/// unused vertices are dropped
+pub fn optimize_vertex_fetch<V: Clone>(vertices: &[V], indices: &mut [u32]) -> Vec<V> {
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut result = Vec::with_capacity(vertices.len());
+    for i in indices.iter_mut() {
+        let new = &mut remap[*i as usize];
+        if *new == u32::MAX {
+            *new = result.len() as u32;
+            result.push(vertices[*i as usize].clone());
+        }
+        *i = *new;
+    }
+    result
+}
+
+/// Fragments passing the depth test and pixels covered, rendering the front
+/// faces with orthographic views from all six axis directions
+fn overdraw(indices: &[u32], positions: &[Vec3]) -> (u64, u64) {
+    let aabb = Aabb::from_points(indices.iter().map(|&i| positions[i as usize]));
+    let extent = aabb.size().component_max();
+    if aabb.is_empty() || extent <= 0.0 {
+        return (0, 0);
+    }
+    let scale = OVERDRAW_RESOLUTION as f32 / extent * 0.999;
+    let (mut shaded, mut covered) = (0, 0);
+    let mut depth = vec![f32::INFINITY; OVERDRAW_RESOLUTION * OVERDRAW_RESOLUTION];
+
+    for axis in 0..3 {
+        let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+        for direction in [1.0f32, -1.0] {
+            depth.fill(f32::INFINITY);
+            for tri in indices.chunks_exact(3) {
+                let p = [0, 1, 2].map(|k| positions[tri[k] as usize]);
+                // looking along the axis, front faces point against it
+                if (p[1] - p[0]).cross(p[2] - p[0])[axis] * direction >= 0.0 {
+                    continue;
+                }
+                let s = p.map(|p| {
+                    Vec3::new(
+                        (p[u] - aabb.min[u]) * scale,
+                        (p[v] - aabb.min[v]) * scale,
+                        p[axis] * direction,
+                    )
+                });
+                let edge = |a: Vec3, b: Vec3, x: f32, y: f32| {
+                    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+                };
+                let area = edge(s[0], s[1], s[2].x, s[2].y);
+                if area.abs() < 1e-12 {
+                    continue;
+                }
+                let lo = s[0].min_by_component(s[1]).min_by_component(s[2]);
+                let hi = s[0].max_by_component(s[1]).max_by_component(s[2]);
+                let last = OVERDRAW_RESOLUTION - 1;
+                for y in (lo.y as usize).min(last)..=(hi.y as usize).min(last) {
+                    for x in (lo.x as usize).min(last)..=(hi.x as usize).min(last) {
+                        let (cx, cy) = (x as f32 + 0.5, y as f32 + 0.5);
+                        let w0 = edge(s[1], s[2], cx, cy) / area;
+                        let w1 = edge(s[2], s[0], cx, cy) / area;
+                        let w2 = 1.0 - w0 - w1;
+                        if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                            continue;
+                        }
+                        let z = w0 * s[0].z + w1 * s[1].z + w2 * s[2].z;
+                        let d = &mut depth[y * OVERDRAW_RESOLUTION + x];
+                        if z < *d {
+                            *d = z;
+                            shaded += 1;
+                        }
+                    }
+                }
+            }
+            covered += depth.iter().filter(|d| d.is_finite()).count() as u64;
+        }
+    }
+    (shaded, covered)
+}
+
+/// Bytes read from a vertex buffer through a direct mapped cache of 64 byte
+/// lines
+fn vertex_fetch(indices: &[u32], vertex_size: usize) -> usize {
+    let mut lines = vec![usize::MAX; FETCH_LINES];
+    let mut fetched = 0;
+    for &i in indices {
+        let start = i as usize * vertex_size;
+        for line in start / FETCH_LINE..=(start + vertex_size - 1) / FETCH_LINE {
+            let slot = &mut lines[line % FETCH_LINES];
+            if *slot != line {
+                *slot = line;
+                fetched += FETCH_LINE;
+            }
+        }
+    }
+    fetched
+}
+
+/// What drawing a mesh costs, the counters add up over several meshes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshStats {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// Vertex shader runs with a `STATS_CACHE_SIZE` FIFO cache
+    pub transformed: usize,
+    pub shaded: u64,
+    pub covered: u64,
+    pub fetched_bytes: usize,
+    pub vertex_bytes: usize,
+    pub index_bytes: usize,
+}
+
+fn ratio(a: f64, b: f64) -> f32 {
+    if b > 0.0 {
+        (a / b) as f32
+    } else {
+        0.0
+    }
+}
+
+impl MeshStats {
+    pub fn analyze(
+        indices: &[u32],
+        positions: &[Vec3],
+        vertex_size: usize,
+        index_size: usize,
+    ) -> Self {
+        let (shaded, covered) = overdraw(indices, positions);
+        MeshStats {
+            vertices: positions.len(),
+            triangles: indices.len() / 3,
+            transformed: triangle_misses(indices, STATS_CACHE_SIZE)
+                .iter()
+                .sum::<u32>() as usize,
+            shaded,
+            covered,
+            fetched_bytes: vertex_fetch(indices, vertex_size),
+            vertex_bytes: positions.len() * vertex_size,
+            index_bytes: indices.len() * index_size,
+        }
+    }
+
+    /// Average cache miss ratio, transformed vertices per triangle (0.5 at
+    /// best, 3 without any reuse)
+    pub fn acmr(&self) -> f32 {
+        ratio(self.transformed as f64, self.triangles as f64)
+    }
+
+    /// Average transformed vertex ratio, 1 when every vertex runs once
+    pub fn atvr(&self) -> f32 {
+        ratio(self.transformed as f64, self.vertices as f64)
+    }
+
+    /// Shaded fragments per covered pixel
+    pub fn overdraw(&self) -> f32 {
+        ratio(self.shaded as f64, self.covered as f64)
+    }
+
+    /// Bytes fetched per byte of the vertex buffer
+    pub fn overfetch(&self) -> f32 {
+        ratio(self.fetched_bytes as f64, self.vertex_bytes as f64)
+    }
+}
+
+impl Add for MeshStats {
+    type Output = MeshStats;
+
+    fn add(self, other: MeshStats) -> MeshStats {
+        MeshStats {
+            vertices: self.vertices + other.vertices,
+            triangles: self.triangles + other.triangles,
+            transformed: self.transformed + other.transformed,
+            shaded: self.shaded + other.shaded,
+            covered: self.covered + other.covered,
+            fetched_bytes: self.fetched_bytes + other.fetched_bytes,
+            vertex_bytes: self.vertex_bytes + other.vertex_bytes,
+            index_bytes: self.index_bytes + other.index_bytes,
+        }
+    }
+}
+
+impl fmt::Display for MeshStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} vertices, {} triangles, ACMR {:.3}, ATVR {:.3}, overdraw {:.3}, overfetch {:.3}, {} index bytes",
+            self.vertices,
+            self.triangles,
+            self.acmr(),
+            self.atvr(),
+            self.overdraw(),
+            self.overfetch(),
+            self.index_bytes
+        )
+    }
+}
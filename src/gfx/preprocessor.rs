@@ -0,0 +1,170 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// `#define`s injected right after `#version`, an empty value gives a bare `#define NAME`
+pub type Defines = BTreeMap<String, String>;
+
+/// GLSL source with its `#include`s resolved
+#[derive(Default, Clone, Debug)]
+pub struct ShaderSource {
+    pub code: String,
+    /// Every file that went into `code`, the `#line` source string numbers index this list
+    pub files: Vec<PathBuf>,
+    /// Original text of each of `files`, to map compiler messages back to them
+    pub texts: Vec<String>,
+}
+
+impl ShaderSource {
+    /// Source used as is, without include or define processing
+    pub fn from_code(code: &str) -> Self {
+        ShaderSource {
+            code: code.to_string(),
+            files: vec![PathBuf::from("<inline>")],
+            texts: vec![code.to_string()],
+        }
+    }
+
+    /// Name of the top level file, for messages
+    pub fn name(&self) -> String {
+        match self.files.first() {
+            Some(f) => f.display().to_string(),
+            None => "<empty>".to_string(),
+        }
+    }
+}
+
+/// Loads `filename` and resolves `#include "file.glsl"` directives relative to the
+/// including file. Each file is included at most once, so shared libraries don't
+/// need their own guards, and `#line` directives keep compiler logs pointing at
+/// the original file and line.
+pub fn preprocess_file(filename: &str, defines: &Defines) -> Result<ShaderSource, String> {
+    let mut source = ShaderSource::default();
+    let mut stack = vec![];
+    let mut code = String::new();
+    include_file(Path::new(filename), &mut source, &mut stack, &mut code)?;
+    source.code = inject_defines(&code, defines);
+    Ok(source)
+}
+
+/// Same as `preprocess_file` for inline sources, includes are resolved relative to `dir`
+pub fn preprocess_str(code: &str, dir: &str, defines: &Defines) -> Result<ShaderSource, String> {
+    let mut source = ShaderSource {
+        files: vec![PathBuf::from("<inline>")],
+        texts: vec![code.to_string()],
+        ..Default::default()
+    };
+    let mut stack = vec![];
+    let mut out = String::new();
+    expand(code, Path::new(dir), 0, &mut source, &mut stack, &mut out)?;
+    source.code = inject_defines(&out, defines);
+    Ok(source)
+}
+
+fn include_file(
+    path: &Path,
+    source: &mut ShaderSource,
+    stack: &mut Vec<PathBuf>,
+    out: &mut String,
+) -> Result<(), String> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(format!("circular #include of {}", path.display()));
+    }
+    let already_included = source
+        .files
+        .iter()
+        .any(|f| f.canonicalize().unwrap_or_else(|_| f.clone()) == canonical);
+    if already_included {
+        return Ok(());
+    }
+
+    let code = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => return Err(format!("error reading {}: {}", path.display(), e)),
+    };
+    let index = source.files.len();
+    source.files.push(path.to_path_buf());
+    source.texts.push(code.clone());
+
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    stack.push(canonical);
+    let res = expand(&code, dir, index, source, stack, out);
+    stack.pop();
+    res
+}
+
+fn expand(
+    code: &str,
+    dir: &Path,
+    index: usize,
+    source: &mut ShaderSource,
+    stack: &mut Vec<PathBuf>,
+    out: &mut String,
+) -> Result<(), String> {
+    let included = index != 0;
+    if included {
+        out.push_str(&format!("#line 1 {}\n", index));
+    }
+
+    for (n, line) in code.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(rest) = trimmed.strip_prefix("#include") {
+            let name = parse_include(rest).ok_or_else(|| {
+                format!(
+                    "{}:{}: malformed #include, expected #include \"file\"",
+                    source.files[index].display(),
+                    n + 1
+                )
+            })?;
+            include_file(&dir.join(name), source, stack, out)?;
+            out.push_str(&format!("#line {} {}\n", n + 2, index));
+        } else if included && trimmed.starts_with("#version") {
+            // only the top level file decides the version
+            out.push('\n');
+        } else {
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    Ok(())
+}
+
+fn parse_include(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let rest = rest.strip_prefix('"')?;
+    let end = rest.find('"')?;
+    Some(&rest[..end])
+}
+
+fn inject_defines(code: &str, defines: &Defines) -> String {
+    if defines.is_empty() {
+        return code.to_string();
+    }
+
+    let mut block = String::new();
+    for (name, value) in defines.iter() {
+        if value.is_empty() {
+            block.push_str(&format!("#define {}\n", name));
+        } else {
+            block.push_str(&format!("#define {} {}\n", name, value));
+        }
+    }
+
+    // #version must stay the first directive of the shader
+    let mut out = String::new();
+    let mut injected = false;
+    for (n, line) in code.lines().enumerate() {
+        out.push_str(line);
+        out.push('\n');
+        if !injected && line.trim_start().starts_with("#version") {
+            out.push_str(&block);
+            out.push_str(&format!("#line {} 0\n", n + 2));
+            injected = true;
+        }
+    }
+    if !injected {
+        out = format!("{}#line 1 0\n{}", block, out);
+    }
+    out
+}
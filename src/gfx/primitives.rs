@@ -0,0 +1,329 @@
+use super::models::{Mesh, Model, Vertex};
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use ultraviolet::*;
+
+// Every generator returns a single mesh model centered on the origin, Y up,
+// with counter-clockwise front faces, UVs with v pointing up and tangents.
+
+#[derive(Default)]
+struct Builder {
+    mesh: Mesh,
+}
+
+impl Builder {
+    fn vertex(&mut self, position: Vec3, normal: Vec3, uv: Vec2) -> u32 {
+        self.mesh.vertices.push(Vertex {
+            position,
+            normal,
+            tex_coords: uv,
+            ..Default::default()
+        });
+        (self.mesh.vertices.len() - 1) as u32
+    }
+
+    /// Adds the triangle facing the way its vertex normals point, degenerate
+    /// ones (at poles and apexes) are dropped
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let v = |i: u32| &self.mesh.vertices[i as usize];
+        let (ab, ac) = (v(b).position - v(a).position, v(c).position - v(a).position);
+        let face = ab.cross(ac);
+        // relative to the triangle's own size, tiny shapes keep their faces
+        let longest = ab.mag_sq().max(ac.mag_sq()).max((ac - ab).mag_sq());
+        if face.mag_sq() <= 1e-10 * longest * longest {
+            return;
+        }
+        if face.dot(v(a).normal + v(b).normal + v(c).normal) >= 0.0 {
+            self.mesh.indices.extend_from_slice(&[a, b, c]);
+        } else {
+            self.mesh.indices.extend_from_slice(&[a, c, b]);
+        }
+    }
+
+    /// `(columns + 1) x (rows + 1)` vertices from `f(u, v)` returning the
+    /// position and normal, u and v in [0, 1] are also the UVs
+    fn grid(&mut self, columns: u32, rows: u32, f: impl Fn(f32, f32) -> (Vec3, Vec3)) {
+        let base = self.mesh.vertices.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let uv = Vec2::new(column as f32 / columns as f32, row as f32 / rows as f32);
+                let (position, normal) = f(uv.x, uv.y);
+                self.vertex(position, normal, uv);
+            }
+        }
+        let at = |column: u32, row: u32| base + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let (a, b) = (at(column, row), at(column + 1, row));
+                let (c, d) = (at(column + 1, row + 1), at(column, row + 1));
+                self.triangle(a, b, c);
+                self.triangle(a, c, d);
+            }
+        }
+    }
+
+    /// Surface of revolution around Y. `profile` goes bottom to top as
+    /// (radius, y, normal as (outwards, up)), v follows its length.
+    fn lathe(&mut self, segments: u32, profile: &[(f32, f32, Vec2)]) {
+        let mut lengths = vec![0.0];
+        for w in profile.windows(2) {
+            let step = Vec2::new(w[1].0 - w[0].0, w[1].1 - w[0].1).mag();
+            lengths.push(lengths.last().unwrap() + step);
+        }
+        let total = lengths.last().copied().unwrap_or(0.0).max(f32::EPSILON);
+        let base = self.mesh.vertices.len() as u32;
+        for (&(radius, y, normal), length) in profile.iter().zip(lengths.iter()) {
+            for s in 0..=segments {
+                let u = s as f32 / segments as f32;
+                // u = 0 faces +z, growing towards +x
+                let (sin, cos) = (u * TAU).sin_cos();
+                let around = Vec3::new(sin, 0.0, cos);
+                self.vertex(
+                    around * radius + Vec3::unit_y() * y,
+                    (around * normal.x + Vec3::unit_y() * normal.y).normalized(),
+                    Vec2::new(u, length / total),
+                );
+            }
+        }
+        let at = |ring: u32, s: u32| base + ring * (segments + 1) + s;
+        for ring in 0..profile.len() as u32 - 1 {
+            for s in 0..segments {
+                let (a, b) = (at(ring, s), at(ring, s + 1));
+                let (c, d) = (at(ring + 1, s + 1), at(ring + 1, s));
+                self.triangle(a, b, c);
+                self.triangle(a, c, d);
+            }
+        }
+    }
+
+    /// Flat cap at `y` facing up or down
+    fn disc(&mut self, y: f32, radius: f32, segments: u32, up: bool) {
+        let normal = if up { Vec3::unit_y() } else { -Vec3::unit_y() };
+        let center = self.vertex(Vec3::unit_y() * y, normal, Vec2::broadcast(0.5));
+        let first = self.mesh.vertices.len() as u32;
+        for s in 0..=segments {
+            let (sin, cos) = (s as f32 / segments as f32 * TAU).sin_cos();
+            // seen from outside the texture isn't mirrored
+            let uv = Vec2::new(0.5 + 0.5 * sin, 0.5 + if up { -0.5 } else { 0.5 } * cos);
+            self.vertex(Vec3::new(sin * radius, y, cos * radius), normal, uv);
+        }
+        for s in 0..segments {
+            self.triangle(center, first + s, first + s + 1);
+        }
+    }
+
+    fn finish(mut self) -> Model {
+        self.mesh.generate_tangents();
+        self.mesh.compute_bounds();
+        let mut model = Model::default();
+        model.meshes.push(self.mesh);
+        model.update_bounds();
+        model
+    }
+}
+
+fn samples(count: u32, f: impl Fn(f32) -> (f32, f32, Vec2)) -> Vec<(f32, f32, Vec2)> {
+    (0..=count).map(|i| f(i as f32 / count as f32)).collect()
+}
+
+/// Cube with edges of `size`, every face split into `subdivisions` squared
+/// quads with the whole texture on it
+pub fn cube(size: f32, subdivisions: u32) -> Model {
+    let n = subdivisions.max(1);
+    let mut b = Builder::default();
+    // (normal, u direction, v direction), u x v = normal
+    let faces = [
+        (Vec3::unit_z(), Vec3::unit_x(), Vec3::unit_y()),
+        (-Vec3::unit_z(), -Vec3::unit_x(), Vec3::unit_y()),
+        (Vec3::unit_x(), -Vec3::unit_z(), Vec3::unit_y()),
+        (-Vec3::unit_x(), Vec3::unit_z(), Vec3::unit_y()),
+        (Vec3::unit_y(), Vec3::unit_x(), -Vec3::unit_z()),
+        (-Vec3::unit_y(), Vec3::unit_x(), Vec3::unit_z()),
+    ];
+    for (normal, u_dir, v_dir) in faces {
+        b.grid(n, n, |u, v| {
+            let p = (normal * 0.5 + u_dir * (u - 0.5) + v_dir * (v - 0.5)) * size;
+            (p, normal)
+        });
+    }
+    b.finish()
+}
+
+/// Plane of `width` (x) by `depth` (z) at y = 0 facing up, split into
+/// `subdivisions_x` by `subdivisions_z` quads; UVs span [0, 1], v grows
+/// towards -z
+pub fn plane(width: f32, depth: f32, subdivisions_x: u32, subdivisions_z: u32) -> Model {
+    let mut b = Builder::default();
+    b.grid(subdivisions_x.max(1), subdivisions_z.max(1), |u, v| {
+        let p = Vec3::new((u - 0.5) * width, 0.0, (0.5 - v) * depth);
+        (p, Vec3::unit_y())
+    });
+    b.finish()
+}
+
+/// Sphere of `segments` meridians and `rings` parallels, the texture wraps
+/// around it once (equirectangular)
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Model {
+    let mut b = Builder::default();
+    let profile = samples(rings.max(2), |t| {
+        let (sin, cos) = (t * PI - FRAC_PI_2).sin_cos();
+        (radius * cos, radius * sin, Vec2::new(cos, sin))
+    });
+    b.lathe(segments.max(3), &profile);
+    b.finish()
+}
+
+/// Sphere from an icosahedron with every triangle split in four
+/// `subdivisions` times, more even than `uv_sphere`. UVs are equirectangular,
+/// vertices on the seam are duplicated.
+pub fn icosphere(radius: f32, subdivisions: u32) -> Model {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let mut points: Vec<Vec3> = [
+        (-1.0, t, 0.0),
+        (1.0, t, 0.0),
+        (-1.0, -t, 0.0),
+        (1.0, -t, 0.0),
+        (0.0, -1.0, t),
+        (0.0, 1.0, t),
+        (0.0, -1.0, -t),
+        (0.0, 1.0, -t),
+        (t, 0.0, -1.0),
+        (t, 0.0, 1.0),
+        (-t, 0.0, -1.0),
+        (-t, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|&(x, y, z)| Vec3::new(x, y, z).normalized())
+    .collect();
+    #[rustfmt::skip]
+    let mut faces: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut midpoint = |a: u32, b: u32, points: &mut Vec<Vec3>| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push((points[a as usize] + points[b as usize]).normalized());
+                (points.len() - 1) as u32
+            })
+        };
+        faces = faces
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let ab = midpoint(a, b, &mut points);
+                let bc = midpoint(b, c, &mut points);
+                let ca = midpoint(c, a, &mut points);
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut b = Builder::default();
+    let mut emitted: HashMap<(u32, u32), u32> = HashMap::new();
+    for face in faces {
+        let mut us = face.map(|i| {
+            let p = points[i as usize];
+            (p.x.atan2(p.z) / TAU).rem_euclid(1.0)
+        });
+        // the triangle crosses the seam, continue past u = 1
+        if us.iter().cloned().fold(f32::MIN, f32::max) - us.iter().cloned().fold(f32::MAX, f32::min)
+            > 0.5
+        {
+            us.iter_mut().filter(|u| **u < 0.5).for_each(|u| *u += 1.0);
+        }
+        let mut corners = [0; 3];
+        for k in 0..3 {
+            let p = points[face[k] as usize];
+            // at the poles u is undefined, take the middle of the other two
+            let u = if p.y.abs() > 0.9999 {
+                (us[(k + 1) % 3] + us[(k + 2) % 3]) * 0.5
+            } else {
+                us[k]
+            };
+            let uv = Vec2::new(u, p.y.asin() / PI + 0.5);
+            corners[k] = *emitted
+                .entry((face[k], uv.x.to_bits()))
+                .or_insert_with(|| b.vertex(p * radius, p, uv));
+        }
+        b.triangle(corners[0], corners[1], corners[2]);
+    }
+    b.finish()
+}
+
+/// Tube closed by flat caps, `segments` around and `height_segments` along Y
+pub fn cylinder(radius: f32, height: f32, segments: u32, height_segments: u32) -> Model {
+    let segments = segments.max(3);
+    let mut b = Builder::default();
+    let profile = samples(height_segments.max(1), |t| {
+        (radius, (t - 0.5) * height, Vec2::new(1.0, 0.0))
+    });
+    b.lathe(segments, &profile);
+    b.disc(-0.5 * height, radius, segments, false);
+    b.disc(0.5 * height, radius, segments, true);
+    b.finish()
+}
+
+/// Cone with its base at y = -height / 2 and the apex above it
+pub fn cone(radius: f32, height: f32, segments: u32, height_segments: u32) -> Model {
+    let segments = segments.max(3);
+    let mut b = Builder::default();
+    let slope = Vec2::new(height, radius).normalized();
+    let profile = samples(height_segments.max(1), |t| {
+        (radius * (1.0 - t), (t - 0.5) * height, slope)
+    });
+    b.lathe(segments, &profile);
+    b.disc(-0.5 * height, radius, segments, false);
+    b.finish()
+}
+
+/// Ring around Y with the tube of `minor_radius` centered `major_radius` away
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> Model {
+    let mut b = Builder::default();
+    // the tube profile starts and ends on the inside
+    let profile = samples(minor_segments.max(3), |t| {
+        let (sin, cos) = (t * TAU + PI).sin_cos();
+        (
+            major_radius + minor_radius * cos,
+            -minor_radius * sin,
+            Vec2::new(cos, -sin),
+        )
+    });
+    b.lathe(major_segments.max(3), &profile);
+    b.finish()
+}
+
+/// Cylinder of `height` with hemispheres of `radius` on both ends, so
+/// `height + 2 * radius` tall; `rings` per hemisphere
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Model {
+    let rings = rings.max(1);
+    let mut b = Builder::default();
+    let hemisphere = |from: f32, y: f32| {
+        samples(rings, move |t| {
+            let (sin, cos) = ((from + t) * FRAC_PI_2).sin_cos();
+            (radius * cos, y + radius * sin, Vec2::new(cos, sin))
+        })
+    };
+    let mut profile = hemisphere(-1.0, -0.5 * height);
+    profile.extend(hemisphere(0.0, 0.5 * height));
+    b.lathe(segments.max(3), &profile);
+    b.finish()
+}
+
+/// Quad covering the viewport in normalized device coordinates (z = 0),
+/// facing +z, for post-processing passes
+pub fn fullscreen_quad() -> Model {
+    let mut b = Builder::default();
+    b.grid(1, 1, |u, v| {
+        (Vec3::new(u * 2.0 - 1.0, v * 2.0 - 1.0, 0.0), Vec3::unit_z())
+    });
+    b.finish()
+}
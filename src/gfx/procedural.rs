@@ -0,0 +1,343 @@
+use super::glutils::check_gl_err;
+use gl33::*;
+
+pub type Rgba = [u8; 4];
+
+pub const MAGENTA: Rgba = [255, 0, 255, 255];
+pub const BLACK: Rgba = [0, 0, 0, 255];
+pub const WHITE: Rgba = [255, 255, 255, 255];
+
+/// RGBA8 image, rows stored bottom-up (OpenGL convention)
+#[derive(Default, Clone)]
+pub struct TexImage {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<u8>,
+}
+
+impl TexImage {
+    pub fn new(width: usize, height: usize) -> Self {
+        TexImage {
+            width,
+            height,
+            data: vec![0; width * height * 4],
+        }
+    }
+
+    /// Builds an image by evaluating `f(u, v)` at every texel centre, u and v in [0, 1]
+    pub fn from_fn<F: Fn(f32, f32) -> Rgba>(width: usize, height: usize, f: F) -> Self {
+        let mut img = Self::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let u = (x as f32 + 0.5) / width as f32;
+                let v = (y as f32 + 0.5) / height as f32;
+                img.set(x, y, f(u, v));
+            }
+        }
+        img
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, col: Rgba) {
+        let i = (y * self.width + x) * 4;
+        self.data[i..i + 4].copy_from_slice(&col);
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Rgba {
+        let i = (y * self.width + x) * 4;
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+}
+
+pub fn checkerboard(width: usize, height: usize, cells: usize, c0: Rgba, c1: Rgba) -> TexImage {
+    let cells = cells.max(1) as f32;
+    TexImage::from_fn(width, height, |u, v| {
+        let cx = (u * cells) as usize;
+        let cy = (v * cells) as usize;
+        if (cx + cy) & 1 == 0 {
+            c0
+        } else {
+            c1
+        }
+    })
+}
+
+/// `line_width` is given in texels
+pub fn grid(
+    width: usize,
+    height: usize,
+    cells: usize,
+    line_width: usize,
+    background: Rgba,
+    line: Rgba,
+) -> TexImage {
+    let cells = cells.max(1);
+    let mut img = TexImage::new(width, height);
+    let cell_w = (width / cells).max(1);
+    let cell_h = (height / cells).max(1);
+    for y in 0..height {
+        for x in 0..width {
+            let on_line = x % cell_w < line_width || y % cell_h < line_width;
+            img.set(x, y, if on_line { line } else { background });
+        }
+    }
+    img
+}
+
+pub enum GradientDir {
+    Horizontal,
+    Vertical,
+    Radial,
+}
+
+pub fn gradient(width: usize, height: usize, dir: GradientDir, from: Rgba, to: Rgba) -> TexImage {
+    TexImage::from_fn(width, height, |u, v| {
+        let t = match dir {
+            GradientDir::Horizontal => u,
+            GradientDir::Vertical => v,
+            GradientDir::Radial => {
+                let (dx, dy) = (u - 0.5, v - 0.5);
+                ((dx * dx + dy * dy).sqrt() * 2.0).min(1.0)
+            }
+        };
+        lerp_rgba(from, to, t)
+    })
+}
+
+pub enum NoiseKind {
+    Value,
+    Perlin,
+    Simplex,
+}
+
+/// Greyscale fractal noise, `scale` is the number of base lattice cells across the texture
+pub fn noise(
+    width: usize,
+    height: usize,
+    kind: NoiseKind,
+    scale: f32,
+    octaves: u32,
+    seed: u32,
+) -> TexImage {
+    TexImage::from_fn(width, height, |u, v| {
+        let n = fbm(u * scale, v * scale, octaves, seed, |x, y, s| match kind {
+            NoiseKind::Value => value_noise_2d(x, y, s) * 2.0 - 1.0,
+            NoiseKind::Perlin => perlin_2d(x, y, s),
+            NoiseKind::Simplex => simplex_2d(x, y, s),
+        });
+        let c = to_u8(n * 0.5 + 0.5);
+        [c, c, c, 255]
+    })
+}
+
+/// Tangent space normal map of the height field `height(u, v)` (u, v in [0, 1])
+pub fn normal_map_from_height<F: Fn(f32, f32) -> f32>(
+    width: usize,
+    height: usize,
+    strength: f32,
+    height_fn: F,
+) -> TexImage {
+    let du = 1.0 / width as f32;
+    let dv = 1.0 / height as f32;
+    TexImage::from_fn(width, height, |u, v| {
+        let dx = (height_fn(u + du, v) - height_fn(u - du, v)) / (2.0 * du);
+        let dy = (height_fn(u, v + dv) - height_fn(u, v - dv)) / (2.0 * dv);
+        let n = ultraviolet::Vec3::new(-dx * strength, -dy * strength, 1.0).normalized();
+        [
+            to_u8(n.x * 0.5 + 0.5),
+            to_u8(n.y * 0.5 + 0.5),
+            to_u8(n.z * 0.5 + 0.5),
+            255,
+        ]
+    })
+}
+
+/// UV debug pattern: red grows with u, green with v, with a white grid every 1/8
+pub fn uv_debug(width: usize, height: usize) -> TexImage {
+    TexImage::from_fn(width, height, |u, v| {
+        let fu = (u * 8.0).fract();
+        let fv = (v * 8.0).fract();
+        // one texel wide lines
+        let edge = 8.0 / width.min(height) as f32;
+        if fu < edge || fv < edge {
+            return WHITE;
+        }
+        let checker = ((u * 8.0) as u32 + (v * 8.0) as u32) & 1 == 0;
+        let b = if checker { 64 } else { 160 };
+        [to_u8(u), to_u8(v), b, 255]
+    })
+}
+
+/// The texture used in place of assets that failed to load
+pub fn missing_texture() -> TexImage {
+    checkerboard(64, 64, 8, MAGENTA, BLACK)
+}
+
+/// Uploads an image as a new 2D texture (with mipmaps) and returns its id
+pub fn upload(gl: &GlFns, img: &TexImage) -> u32 {
+    let params = [
+        (GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT),
+        (GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT),
+        (
+            GL_TEXTURE_2D,
+            GL_TEXTURE_MIN_FILTER,
+            GL_LINEAR_MIPMAP_LINEAR,
+        ),
+        (GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR),
+    ];
+    upload_params(gl, img, &params)
+}
+
+pub fn upload_params(gl: &GlFns, img: &TexImage, params: &[(GLenum, GLenum, GLenum)]) -> u32 {
+    let mut texture = 0;
+    unsafe {
+        gl.GenTextures(1, &mut texture);
+        gl.BindTexture(GL_TEXTURE_2D, texture);
+
+        for (t, n, p) in params {
+            gl.TexParameteri(*t, *n, p.0 as i32);
+        }
+
+        gl.TexImage2D(
+            GL_TEXTURE_2D,
+            0,
+            GL_RGBA.0 as i32,
+            img.width as i32,
+            img.height as i32,
+            0,
+            GL_RGBA,
+            GL_UNSIGNED_BYTE,
+            img.data.as_ptr().cast(),
+        );
+        check_gl_err(gl);
+        gl.GenerateMipmap(GL_TEXTURE_2D);
+    }
+    texture
+}
+
+/// Sums `octaves` layers of `noise_fn`, each with double frequency and half amplitude.
+/// The result is normalized back to the range of a single layer.
+pub fn fbm<F: Fn(f32, f32, u32) -> f32>(
+    x: f32,
+    y: f32,
+    octaves: u32,
+    seed: u32,
+    noise_fn: F,
+) -> f32 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut frequency = 1.0;
+    let mut norm = 0.0;
+    for o in 0..octaves.max(1) {
+        sum += noise_fn(x * frequency, y * frequency, seed.wrapping_add(o)) * amplitude;
+        norm += amplitude;
+        amplitude *= 0.5;
+        frequency *= 2.0;
+    }
+    sum / norm
+}
+
+/// Smoothly interpolated lattice noise in [0, 1]
+pub fn value_noise_2d(x: f32, y: f32, seed: u32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (fade(x - x0), fade(y - y0));
+    let (ix, iy) = (x0 as i32, y0 as i32);
+
+    let v00 = lattice(ix, iy, seed);
+    let v10 = lattice(ix + 1, iy, seed);
+    let v01 = lattice(ix, iy + 1, seed);
+    let v11 = lattice(ix + 1, iy + 1, seed);
+
+    lerp(lerp(v00, v10, tx), lerp(v01, v11, tx), ty)
+}
+
+/// Classic gradient noise, roughly in [-1, 1]
+pub fn perlin_2d(x: f32, y: f32, seed: u32) -> f32 {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i32, y0 as i32);
+
+    let g00 = grad_dot(ix, iy, fx, fy, seed);
+    let g10 = grad_dot(ix + 1, iy, fx - 1.0, fy, seed);
+    let g01 = grad_dot(ix, iy + 1, fx, fy - 1.0, seed);
+    let g11 = grad_dot(ix + 1, iy + 1, fx - 1.0, fy - 1.0, seed);
+
+    let (u, v) = (fade(fx), fade(fy));
+    // gradients are unit length, so the raw range is [-sqrt(0.5), sqrt(0.5)]
+    lerp(lerp(g00, g10, u), lerp(g01, g11, u), v) * std::f32::consts::SQRT_2
+}
+
+/// 2D simplex noise, roughly in [-1, 1]
+pub fn simplex_2d(x: f32, y: f32, seed: u32) -> f32 {
+    const F2: f32 = 0.366_025_42; // (sqrt(3) - 1) / 2
+    const G2: f32 = 0.211_324_87; // (3 - sqrt(3)) / 6
+
+    let s = (x + y) * F2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * G2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+
+    let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+    let (x1, y1) = (x0 - i1 as f32 + G2, y0 - j1 as f32 + G2);
+    let (x2, y2) = (x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2);
+    let (ii, jj) = (i as i32, j as i32);
+
+    let corner = |cx: i32, cy: i32, dx: f32, dy: f32| {
+        let t = 0.5 - dx * dx - dy * dy;
+        if t < 0.0 {
+            0.0
+        } else {
+            t * t * t * t * grad_dot(cx, cy, dx, dy, seed)
+        }
+    };
+
+    let n =
+        corner(ii, jj, x0, y0) + corner(ii + i1, jj + j1, x1, y1) + corner(ii + 1, jj + 1, x2, y2);
+    // scale the result to cover [-1, 1] (unit length gradients)
+    99.0 * n
+}
+
+fn hash(x: i32, y: i32, seed: u32) -> u32 {
+    let mut h = seed
+        .wrapping_mul(0x27d4_eb2d)
+        .wrapping_add((x as u32).wrapping_mul(0x8da6_b343))
+        .wrapping_add((y as u32).wrapping_mul(0xd816_3841));
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^ (h >> 15)
+}
+
+fn lattice(x: i32, y: i32, seed: u32) -> f32 {
+    hash(x, y, seed) as f32 / u32::MAX as f32
+}
+
+fn grad_dot(ix: i32, iy: i32, dx: f32, dy: f32, seed: u32) -> f32 {
+    let angle = lattice(ix, iy, seed) * std::f32::consts::TAU;
+    angle.cos() * dx + angle.sin() * dy
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn lerp_rgba(a: Rgba, b: Rgba, t: f32) -> Rgba {
+    let mut out = [0; 4];
+    for i in 0..4 {
+        out[i] = lerp(a[i] as f32, b[i] as f32, t).round() as u8;
+    }
+    out
+}
+
+fn to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
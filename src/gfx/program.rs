@@ -0,0 +1,180 @@
+use super::glext::{self, GL_COMPUTE_SHADER, GL_TESS_CONTROL_SHADER, GL_TESS_EVALUATION_SHADER};
+use super::preprocessor::{self, Defines};
+use super::shaders::Shaders;
+use gl33::*;
+use std::path::PathBuf;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn gl_type(self) -> GLenum {
+        match self {
+            ShaderStage::Vertex => GL_VERTEX_SHADER,
+            ShaderStage::TessControl => GL_TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => GL_TESS_EVALUATION_SHADER,
+            ShaderStage::Geometry => GL_GEOMETRY_SHADER,
+            ShaderStage::Fragment => GL_FRAGMENT_SHADER,
+            ShaderStage::Compute => GL_COMPUTE_SHADER,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ShaderStage::Vertex => "vertex",
+            ShaderStage::TessControl => "tessellation control",
+            ShaderStage::TessEvaluation => "tessellation evaluation",
+            ShaderStage::Geometry => "geometry",
+            ShaderStage::Fragment => "fragment",
+            ShaderStage::Compute => "compute",
+        }
+    }
+
+    /// Stage of a shader file by the extensions used under demo/ (vs, tcs, tes, gs, fs, cs)
+    pub fn from_extension(ext: &str) -> Option<ShaderStage> {
+        match ext {
+            "vs" | "vert" => Some(ShaderStage::Vertex),
+            "tcs" | "tesc" => Some(ShaderStage::TessControl),
+            "tes" | "tese" => Some(ShaderStage::TessEvaluation),
+            "gs" | "geom" => Some(ShaderStage::Geometry),
+            "fs" | "frag" => Some(ShaderStage::Fragment),
+            "cs" | "comp" => Some(ShaderStage::Compute),
+            _ => None,
+        }
+    }
+
+    /// Tessellation needs GL 4.0 and compute GL 4.3 (or the matching ARB extension)
+    pub fn check_supported(self, gl: &GlFns) -> Result<(), String> {
+        let supported = match self {
+            ShaderStage::TessControl | ShaderStage::TessEvaluation => {
+                glext::has_feature(gl, 4, 0, "GL_ARB_tessellation_shader")
+            }
+            ShaderStage::Compute => glext::has_feature(gl, 4, 3, "GL_ARB_compute_shader"),
+            _ => true,
+        };
+        if supported {
+            Ok(())
+        } else {
+            let (major, minor) = glext::gl_version(gl);
+            Err(format!(
+                "{} shaders are not supported by this OpenGL {}.{} context",
+                self.name(),
+                major,
+                minor
+            ))
+        }
+    }
+}
+
+/// Checks that `stages` form a program GL can link: a compute shader alone, or
+/// a vertex shader with optional tessellation, geometry and fragment stages
+pub fn check_stages(stages: &[ShaderStage]) -> Result<(), String> {
+    for (i, s) in stages.iter().enumerate() {
+        if stages[..i].contains(s) {
+            return Err(format!("{} stage given twice", s.name()));
+        }
+    }
+    if stages.contains(&ShaderStage::Compute) {
+        if stages.len() > 1 {
+            return Err("a compute shader can't be linked with other stages".to_string());
+        }
+        return Ok(());
+    }
+    if !stages.contains(&ShaderStage::Vertex) {
+        return Err("program has no vertex shader".to_string());
+    }
+    if stages.contains(&ShaderStage::TessControl) && !stages.contains(&ShaderStage::TessEvaluation)
+    {
+        return Err(
+            "a tessellation control shader needs a tessellation evaluation shader".to_string(),
+        );
+    }
+    Ok(())
+}
+
+enum StageInput {
+    File(PathBuf),
+    Code(String),
+}
+
+/// Builds a program from any set of stages, for programs `Shaders::from_files`
+/// can't express (tessellation, compute).
+///
+/// ```ignore
+/// let terrain = ProgramBuilder::new()
+///     .file(ShaderStage::Vertex, "./demo/terrain/terrain.vs")
+///     .file(ShaderStage::TessControl, "./demo/terrain/terrain.tcs")
+///     .file(ShaderStage::TessEvaluation, "./demo/terrain/terrain.tes")
+///     .file(ShaderStage::Fragment, "./demo/terrain/terrain.fs")
+///     .build(gl)?;
+/// ```
+#[derive(Default)]
+pub struct ProgramBuilder {
+    stages: Vec<(ShaderStage, StageInput)>,
+    defines: Defines,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines injected into every stage, see `Shaders::from_files_with_defines`
+    pub fn defines(mut self, defines: &Defines) -> Self {
+        self.defines = defines.clone();
+        self
+    }
+
+    pub fn file(mut self, stage: ShaderStage, path: &str) -> Self {
+        self.stages
+            .push((stage, StageInput::File(PathBuf::from(path))));
+        self
+    }
+
+    /// Inline source, `#include`s are resolved relative to the working directory
+    pub fn source(mut self, stage: ShaderStage, code: &str) -> Self {
+        self.stages
+            .push((stage, StageInput::Code(code.to_string())));
+        self
+    }
+
+    /// Compiles and links the program. Programs built only from files can be hot
+    /// reloaded with `Shaders::reload_if_changed`.
+    pub fn build(self, gl: &GlFns) -> Result<Shaders, String> {
+        let all_files = self
+            .stages
+            .iter()
+            .all(|(_, input)| matches!(input, StageInput::File(_)));
+        if all_files {
+            let files: Vec<(ShaderStage, PathBuf)> = self
+                .stages
+                .into_iter()
+                .filter_map(|(stage, input)| match input {
+                    StageInput::File(path) => Some((stage, path)),
+                    StageInput::Code(_) => None,
+                })
+                .collect();
+            return Shaders::from_stage_files(gl, &files, &self.defines);
+        }
+
+        let mut sources = vec![];
+        for (stage, input) in self.stages.iter() {
+            let source = match input {
+                StageInput::File(path) => {
+                    preprocessor::preprocess_file(&path.to_string_lossy(), &self.defines)?
+                }
+                StageInput::Code(code) => preprocessor::preprocess_str(code, ".", &self.defines)?,
+            };
+            sources.push((*stage, source));
+        }
+        let stages: Vec<_> = sources.iter().map(|(stage, s)| (*stage, s)).collect();
+        Shaders::link_stages(gl, &stages)
+    }
+}
@@ -0,0 +1,189 @@
+use super::glext::{self, GL_NUM_PROGRAM_BINARY_FORMATS, GL_PROGRAM_BINARY_LENGTH};
+use super::glutils;
+use gl33::*;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+static CACHE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+const MAGIC: &[u8; 4] = b"GLPB";
+
+/// Enables (or with `None` disables) the program binary cache used by every
+/// `Shaders` built afterwards. The directory is created when needed.
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+    *CACHE_DIR.lock().unwrap() = dir;
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    CACHE_DIR.lock().unwrap().clone()
+}
+
+/// Linked program binaries stored on disk (GL_ARB_get_program_binary).
+///
+/// An entry is keyed by the preprocessed stage sources, which already hold the
+/// defines, and by the driver vendor, renderer and version, since binaries are
+/// only valid for the driver that produced them.
+pub struct ProgramCache {
+    path: PathBuf,
+}
+
+impl ProgramCache {
+    /// Cache entry for the given stage sources, `None` when caching is disabled
+    /// or the driver can't hand out program binaries
+    pub fn entry(gl: &GlFns, stage_sources: &[&str]) -> Option<ProgramCache> {
+        let dir = cache_dir()?;
+        if !is_supported(gl) {
+            return None;
+        }
+
+        let mut hash = Fnv1a::new();
+        for name in [
+            GL_VENDOR,
+            GL_RENDERER,
+            GL_VERSION,
+            GL_SHADING_LANGUAGE_VERSION,
+        ] {
+            hash.write(glext::gl_string(gl, name).as_bytes());
+            hash.write(&[0]);
+        }
+        for code in stage_sources {
+            hash.write(code.as_bytes());
+            hash.write(&[0]);
+        }
+
+        Some(ProgramCache {
+            path: dir.join(format!("{:016x}.bin", hash.finish())),
+        })
+    }
+
+    /// Creates a program from the cached binary. Returns `None` on a cache miss
+    /// or when the driver rejects the binary (e.g. after a driver update), the
+    /// stale entry is then removed and the caller compiles from source.
+    pub fn load(&self, gl: &GlFns) -> Option<u32> {
+        let program_binary = glext::ext().program_binary?;
+        let data = fs::read(&self.path).ok()?;
+        if data.len() < 8 || &data[..4] != MAGIC {
+            let _ = fs::remove_file(&self.path);
+            return None;
+        }
+        let format = u32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        let binary = &data[8..];
+
+        // earlier errors would be taken for a rejected binary
+        glutils::check_gl_err(gl);
+        let program = gl.CreateProgram();
+        let mut success = 0;
+        unsafe {
+            program_binary(
+                program,
+                GLenum(format),
+                binary.as_ptr().cast(),
+                binary.len() as i32,
+            );
+            // an unknown format raises GL_INVALID_ENUM and leaves the program
+            // unlinked, any other error is a bug
+            let err = gl.GetError();
+            if err != GL_NO_ERROR && err != GL_INVALID_ENUM {
+                panic!("error: {:?}", err);
+            }
+            gl.GetProgramiv(program, GL_LINK_STATUS, &mut success);
+        }
+        if success == 0 {
+            eprintln!(
+                "program cache: driver rejected {}, recompiling",
+                self.path.display()
+            );
+            gl.DeleteProgram(program);
+            let _ = fs::remove_file(&self.path);
+            return None;
+        }
+        Some(program)
+    }
+
+    /// Must be called before linking so the driver keeps the binary around
+    pub fn prepare(&self, program: u32) {
+        if let Some(program_parameteri) = glext::ext().program_parameteri {
+            unsafe {
+                program_parameteri(program, glext::GL_PROGRAM_BINARY_RETRIEVABLE_HINT, 1);
+            }
+        }
+    }
+
+    /// Writes the binary of the freshly linked `program`
+    pub fn store(&self, gl: &GlFns, program: u32) -> Result<(), String> {
+        let get_program_binary = match glext::ext().get_program_binary {
+            Some(f) => f,
+            None => return Err("glGetProgramBinary not available".to_string()),
+        };
+
+        let mut len = 0;
+        unsafe {
+            gl.GetProgramiv(program, GL_PROGRAM_BINARY_LENGTH, &mut len);
+        }
+        if len <= 0 {
+            return Err(format!("program({}): driver returned no binary", program));
+        }
+
+        let mut binary: Vec<u8> = vec![0; len as usize];
+        let mut written = 0;
+        let mut format = GLenum(0);
+        unsafe {
+            get_program_binary(
+                program,
+                len,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr().cast(),
+            );
+        }
+        binary.truncate(written.max(0) as usize);
+
+        let mut data = Vec::with_capacity(8 + binary.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&format.0.to_le_bytes());
+        data.extend_from_slice(&binary);
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("error creating {}: {}", dir.display(), e))?;
+        }
+        fs::write(&self.path, data)
+            .map_err(|e| format!("error writing {}: {}", self.path.display(), e))
+    }
+}
+
+fn is_supported(gl: &GlFns) -> bool {
+    let ext = glext::ext();
+    if ext.get_program_binary.is_none() || ext.program_binary.is_none() {
+        return false;
+    }
+    if !glext::has_extension(gl, "GL_ARB_get_program_binary") {
+        return false;
+    }
+    let mut formats = 0;
+    unsafe {
+        gl.GetIntegerv(GL_NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+    }
+    formats > 0
+}
+
+// FNV-1a, unlike DefaultHasher it is stable across Rust releases
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        self.0
+    }
+}
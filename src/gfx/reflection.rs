@@ -0,0 +1,306 @@
+use super::glutils;
+use gl33::*;
+use std::collections::HashMap;
+
+/// Active uniform of the default uniform block
+#[derive(Clone, Copy, Debug)]
+pub struct UniformInfo {
+    pub location: i32,
+    pub gl_type: GLenum,
+    /// Number of array elements, 1 for non arrays
+    pub size: i32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AttributeInfo {
+    pub location: i32,
+    pub gl_type: GLenum,
+    pub size: i32,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct UniformBlockInfo {
+    pub index: u32,
+    pub binding: u32,
+    pub data_size: i32,
+}
+
+/// Everything a linked program exposes, queried once after linking
+#[derive(Clone, Debug, Default)]
+pub struct ProgramReflection {
+    pub uniforms: HashMap<String, UniformInfo>,
+    pub attributes: HashMap<String, AttributeInfo>,
+    pub uniform_blocks: HashMap<String, UniformBlockInfo>,
+}
+
+impl ProgramReflection {
+    pub fn query(gl: &GlFns, program: u32) -> Self {
+        let mut reflection = ProgramReflection::default();
+        if program == 0 {
+            return reflection;
+        }
+
+        // uniforms
+        for index in 0..program_param(gl, program, GL_ACTIVE_UNIFORMS) as u32 {
+            let max_len = program_param(gl, program, GL_ACTIVE_UNIFORM_MAX_LENGTH);
+            let (name, size, gl_type) = read_name(max_len, |len, buf, size, gl_type| unsafe {
+                gl.GetActiveUniform(program, index, max_len, len, size, gl_type, buf);
+            });
+            let location = match std::ffi::CString::new(name.as_str()) {
+                Ok(c_name) => unsafe { gl.GetUniformLocation(program, c_name.as_ptr().cast()) },
+                Err(_) => -1,
+            };
+            // members of uniform blocks have no location
+            if location == -1 {
+                continue;
+            }
+            let info = UniformInfo {
+                location,
+                gl_type,
+                size,
+            };
+            // arrays are reported as "name[0]", make them reachable by "name" and
+            // by every element name as well
+            if let Some(base) = name.strip_suffix("[0]") {
+                reflection.uniforms.insert(base.to_string(), info);
+                for i in 1..size {
+                    let element = format!("{}[{}]", base, i);
+                    let c_name = std::ffi::CString::new(element.as_str()).unwrap_or_default();
+                    let location =
+                        unsafe { gl.GetUniformLocation(program, c_name.as_ptr().cast()) };
+                    if location != -1 {
+                        let info = UniformInfo {
+                            location,
+                            gl_type,
+                            size: size - i,
+                        };
+                        reflection.uniforms.insert(element, info);
+                    }
+                }
+            }
+            reflection.uniforms.insert(name, info);
+        }
+
+        // attributes
+        for index in 0..program_param(gl, program, GL_ACTIVE_ATTRIBUTES) as u32 {
+            let max_len = program_param(gl, program, GL_ACTIVE_ATTRIBUTE_MAX_LENGTH);
+            let (name, size, gl_type) = read_name(max_len, |len, buf, size, gl_type| unsafe {
+                gl.GetActiveAttrib(program, index, max_len, len, size, gl_type, buf);
+            });
+            let location = match std::ffi::CString::new(name.as_str()) {
+                Ok(c_name) => unsafe { gl.GetAttribLocation(program, c_name.as_ptr().cast()) },
+                Err(_) => -1,
+            };
+            reflection.attributes.insert(
+                name,
+                AttributeInfo {
+                    location,
+                    gl_type,
+                    size,
+                },
+            );
+        }
+
+        // uniform blocks
+        for index in 0..program_param(gl, program, GL_ACTIVE_UNIFORM_BLOCKS) as u32 {
+            let mut max_len = 0;
+            let mut binding = 0;
+            let mut data_size = 0;
+            unsafe {
+                gl.GetActiveUniformBlockiv(
+                    program,
+                    index,
+                    GL_UNIFORM_BLOCK_NAME_LENGTH,
+                    &mut max_len,
+                );
+                gl.GetActiveUniformBlockiv(program, index, GL_UNIFORM_BLOCK_BINDING, &mut binding);
+                gl.GetActiveUniformBlockiv(
+                    program,
+                    index,
+                    GL_UNIFORM_BLOCK_DATA_SIZE,
+                    &mut data_size,
+                );
+            }
+            let (name, _, _) = read_name(max_len, |len, buf, _, _| unsafe {
+                gl.GetActiveUniformBlockName(program, index, max_len, len, buf);
+            });
+            reflection.uniform_blocks.insert(
+                name,
+                UniformBlockInfo {
+                    index,
+                    binding: binding as u32,
+                    data_size,
+                },
+            );
+        }
+
+        glutils::check_gl_err(gl);
+        reflection
+    }
+}
+
+fn program_param(gl: &GlFns, program: u32, pname: GLenum) -> i32 {
+    let mut value = 0;
+    unsafe {
+        gl.GetProgramiv(program, pname, &mut value);
+    }
+    value
+}
+
+fn read_name<F>(max_len: i32, query: F) -> (String, i32, GLenum)
+where
+    F: FnOnce(*mut i32, *mut u8, *mut i32, *mut GLenum),
+{
+    let mut buf: Vec<u8> = vec![0; max_len.max(1) as usize];
+    let mut len = 0;
+    let mut size = 0;
+    let mut gl_type = GLenum(0);
+    query(&mut len, buf.as_mut_ptr(), &mut size, &mut gl_type);
+    buf.truncate(len.max(0) as usize);
+    (String::from_utf8_lossy(&buf).to_string(), size, gl_type)
+}
+
+/// GLSL name of a uniform / attribute type, for error messages
+#[allow(non_upper_case_globals)] // GL_FLOAT_MAT2x3 & co. are spelled that way by gl33
+pub fn type_name(gl_type: GLenum) -> &'static str {
+    match gl_type {
+        GL_FLOAT => "float",
+        GL_FLOAT_VEC2 => "vec2",
+        GL_FLOAT_VEC3 => "vec3",
+        GL_FLOAT_VEC4 => "vec4",
+        GL_INT => "int",
+        GL_INT_VEC2 => "ivec2",
+        GL_INT_VEC3 => "ivec3",
+        GL_INT_VEC4 => "ivec4",
+        GL_UNSIGNED_INT => "uint",
+        GL_UNSIGNED_INT_VEC2 => "uvec2",
+        GL_UNSIGNED_INT_VEC3 => "uvec3",
+        GL_UNSIGNED_INT_VEC4 => "uvec4",
+        GL_BOOL => "bool",
+        GL_BOOL_VEC2 => "bvec2",
+        GL_BOOL_VEC3 => "bvec3",
+        GL_BOOL_VEC4 => "bvec4",
+        GL_FLOAT_MAT2 => "mat2",
+        GL_FLOAT_MAT3 => "mat3",
+        GL_FLOAT_MAT4 => "mat4",
+        GL_FLOAT_MAT2x3 => "mat2x3",
+        GL_FLOAT_MAT2x4 => "mat2x4",
+        GL_FLOAT_MAT3x2 => "mat3x2",
+        GL_FLOAT_MAT3x4 => "mat3x4",
+        GL_FLOAT_MAT4x2 => "mat4x2",
+        GL_FLOAT_MAT4x3 => "mat4x3",
+        GL_SAMPLER_1D => "sampler1D",
+        GL_SAMPLER_2D => "sampler2D",
+        GL_SAMPLER_3D => "sampler3D",
+        GL_SAMPLER_CUBE => "samplerCube",
+        GL_SAMPLER_2D_SHADOW => "sampler2DShadow",
+        GL_SAMPLER_CUBE_SHADOW => "samplerCubeShadow",
+        GL_SAMPLER_2D_ARRAY => "sampler2DArray",
+        GL_SAMPLER_2D_ARRAY_SHADOW => "sampler2DArrayShadow",
+        GL_SAMPLER_2D_MULTISAMPLE => "sampler2DMS",
+        GL_SAMPLER_BUFFER => "samplerBuffer",
+        GL_INT_SAMPLER_2D => "isampler2D",
+        GL_UNSIGNED_INT_SAMPLER_2D => "usampler2D",
+        _ => "<unknown type>",
+    }
+}
+
+/// True for every sampler type, they are all set with glUniform1i
+pub fn is_sampler(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        GL_SAMPLER_1D
+            | GL_SAMPLER_2D
+            | GL_SAMPLER_3D
+            | GL_SAMPLER_CUBE
+            | GL_SAMPLER_1D_SHADOW
+            | GL_SAMPLER_2D_SHADOW
+            | GL_SAMPLER_1D_ARRAY
+            | GL_SAMPLER_2D_ARRAY
+            | GL_SAMPLER_1D_ARRAY_SHADOW
+            | GL_SAMPLER_2D_ARRAY_SHADOW
+            | GL_SAMPLER_2D_MULTISAMPLE
+            | GL_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | GL_SAMPLER_CUBE_SHADOW
+            | GL_SAMPLER_BUFFER
+            | GL_SAMPLER_2D_RECT
+            | GL_SAMPLER_2D_RECT_SHADOW
+            | GL_INT_SAMPLER_1D
+            | GL_INT_SAMPLER_2D
+            | GL_INT_SAMPLER_3D
+            | GL_INT_SAMPLER_CUBE
+            | GL_INT_SAMPLER_1D_ARRAY
+            | GL_INT_SAMPLER_2D_ARRAY
+            | GL_INT_SAMPLER_2D_MULTISAMPLE
+            | GL_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | GL_INT_SAMPLER_BUFFER
+            | GL_INT_SAMPLER_2D_RECT
+            | GL_UNSIGNED_INT_SAMPLER_1D
+            | GL_UNSIGNED_INT_SAMPLER_2D
+            | GL_UNSIGNED_INT_SAMPLER_3D
+            | GL_UNSIGNED_INT_SAMPLER_CUBE
+            | GL_UNSIGNED_INT_SAMPLER_1D_ARRAY
+            | GL_UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | GL_UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE
+            | GL_UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | GL_UNSIGNED_INT_SAMPLER_BUFFER
+            | GL_UNSIGNED_INT_SAMPLER_2D_RECT
+    )
+}
+
+/// Texture target a sampler type reads from, `None` for non samplers
+pub fn sampler_target(gl_type: GLenum) -> Option<GLenum> {
+    let target = match gl_type {
+        GL_SAMPLER_1D | GL_SAMPLER_1D_SHADOW | GL_INT_SAMPLER_1D | GL_UNSIGNED_INT_SAMPLER_1D => {
+            GL_TEXTURE_1D
+        }
+        GL_SAMPLER_2D | GL_SAMPLER_2D_SHADOW | GL_INT_SAMPLER_2D | GL_UNSIGNED_INT_SAMPLER_2D => {
+            GL_TEXTURE_2D
+        }
+        GL_SAMPLER_3D | GL_INT_SAMPLER_3D | GL_UNSIGNED_INT_SAMPLER_3D => GL_TEXTURE_3D,
+        GL_SAMPLER_CUBE
+        | GL_SAMPLER_CUBE_SHADOW
+        | GL_INT_SAMPLER_CUBE
+        | GL_UNSIGNED_INT_SAMPLER_CUBE => GL_TEXTURE_CUBE_MAP,
+        GL_SAMPLER_1D_ARRAY
+        | GL_SAMPLER_1D_ARRAY_SHADOW
+        | GL_INT_SAMPLER_1D_ARRAY
+        | GL_UNSIGNED_INT_SAMPLER_1D_ARRAY => GL_TEXTURE_1D_ARRAY,
+        GL_SAMPLER_2D_ARRAY
+        | GL_SAMPLER_2D_ARRAY_SHADOW
+        | GL_INT_SAMPLER_2D_ARRAY
+        | GL_UNSIGNED_INT_SAMPLER_2D_ARRAY => GL_TEXTURE_2D_ARRAY,
+        GL_SAMPLER_2D_MULTISAMPLE
+        | GL_INT_SAMPLER_2D_MULTISAMPLE
+        | GL_UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE => GL_TEXTURE_2D_MULTISAMPLE,
+        GL_SAMPLER_2D_MULTISAMPLE_ARRAY
+        | GL_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+        | GL_UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY => GL_TEXTURE_2D_MULTISAMPLE_ARRAY,
+        GL_SAMPLER_BUFFER | GL_INT_SAMPLER_BUFFER | GL_UNSIGNED_INT_SAMPLER_BUFFER => {
+            GL_TEXTURE_BUFFER
+        }
+        GL_SAMPLER_2D_RECT
+        | GL_SAMPLER_2D_RECT_SHADOW
+        | GL_INT_SAMPLER_2D_RECT
+        | GL_UNSIGNED_INT_SAMPLER_2D_RECT => GL_TEXTURE_RECTANGLE,
+        _ => return None,
+    };
+    Some(target)
+}
+
+/// Name of a texture target, for error messages
+pub fn target_name(target: GLenum) -> &'static str {
+    match target {
+        GL_TEXTURE_1D => "GL_TEXTURE_1D",
+        GL_TEXTURE_2D => "GL_TEXTURE_2D",
+        GL_TEXTURE_3D => "GL_TEXTURE_3D",
+        GL_TEXTURE_CUBE_MAP => "GL_TEXTURE_CUBE_MAP",
+        GL_TEXTURE_1D_ARRAY => "GL_TEXTURE_1D_ARRAY",
+        GL_TEXTURE_2D_ARRAY => "GL_TEXTURE_2D_ARRAY",
+        GL_TEXTURE_2D_MULTISAMPLE => "GL_TEXTURE_2D_MULTISAMPLE",
+        GL_TEXTURE_2D_MULTISAMPLE_ARRAY => "GL_TEXTURE_2D_MULTISAMPLE_ARRAY",
+        GL_TEXTURE_BUFFER => "GL_TEXTURE_BUFFER",
+        GL_TEXTURE_RECTANGLE => "GL_TEXTURE_RECTANGLE",
+        _ => "<unknown target>",
+    }
+}
@@ -0,0 +1,92 @@
+use super::preprocessor::Defines;
+use super::shaders::Shaders;
+use gl33::*;
+use std::collections::HashMap;
+
+/// Variants of one shader source selected by feature defines.
+///
+/// Each set of features (e.g. `["HAS_SPECULAR_MAP", "PCF_KERNEL=5"]`) is compiled
+/// on first use and cached, the source switches on them with `#ifdef` / `#if`.
+#[derive(Default)]
+pub struct ShaderLibrary {
+    vertex_file: String,
+    fragment_file: String,
+    geometry_file: String,
+    variants: HashMap<Defines, Shaders>,
+}
+
+impl ShaderLibrary {
+    pub fn new(vertex_file: &str, fragment_file: &str) -> Self {
+        Self::new_full(vertex_file, fragment_file, "")
+    }
+
+    pub fn new_full(vertex_file: &str, fragment_file: &str, geometry_file: &str) -> Self {
+        ShaderLibrary {
+            vertex_file: vertex_file.to_string(),
+            fragment_file: fragment_file.to_string(),
+            geometry_file: geometry_file.to_string(),
+            variants: HashMap::new(),
+        }
+    }
+
+    /// Returns the program built with `features`, compiling it on first request.
+    /// A feature is either a bare flag (`"USE_SHADOWS"`) or a `"NAME=value"` pair.
+    pub fn variant(&mut self, gl: &GlFns, features: &[&str]) -> Result<&Shaders, String> {
+        let defines = parse_features(features)?;
+        if !self.variants.contains_key(&defines) {
+            let shaders = Shaders::from_files_with_defines(
+                gl,
+                &self.vertex_file,
+                &self.fragment_file,
+                &self.geometry_file,
+                &defines,
+            )
+            .map_err(|e| format!("{} [{}]: {}", self.fragment_file, features.join(", "), e))?;
+            self.variants.insert(defines.clone(), shaders);
+        }
+        Ok(&self.variants[&defines])
+    }
+
+    /// Number of variants compiled so far
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    /// Hot reloads every compiled variant, see `Shaders::reload_if_changed`
+    pub fn reload_if_changed(&mut self, gl: &GlFns) -> bool {
+        let mut reloaded = false;
+        for shaders in self.variants.values_mut() {
+            reloaded |= shaders.reload_if_changed(gl);
+        }
+        reloaded
+    }
+
+    pub fn delete(&mut self, gl: &GlFns) {
+        for (_, shaders) in self.variants.drain() {
+            gl.DeleteProgram(shaders.id());
+        }
+    }
+}
+
+/// Turns `["FLAG", "NAME=value"]` into defines for `Shaders::from_files_with_defines`
+pub fn parse_features(features: &[&str]) -> Result<Defines, String> {
+    let mut defines = Defines::new();
+    for f in features.iter() {
+        let (name, value) = match f.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => (f.trim(), ""),
+        };
+        let valid = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!("invalid shader feature '{}'", f));
+        }
+        defines.insert(name.to_string(), value.to_string());
+    }
+    Ok(defines)
+}
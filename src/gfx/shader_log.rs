@@ -0,0 +1,123 @@
+use super::preprocessor::ShaderSource;
+
+/// Lines shown before and after the offending line
+const CONTEXT_LINES: usize = 1;
+
+/// One message of a compiler info log, located by `#line` source index and line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogMessage {
+    pub source: usize,
+    pub line: usize,
+    /// 1-based, only reported by some drivers (Mesa)
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// Parses one line of a compiler log in the formats drivers commonly use:
+///
+/// - Mesa: `0:12(5): error: ...`
+/// - NVIDIA: `0(12) : error C1008: ...`
+/// - AMD / Intel (Windows): `ERROR: 0:12: ...`
+pub fn parse_line(line: &str) -> Option<LogMessage> {
+    let mut rest = line.trim();
+    let mut severity = "";
+    for prefix in ["ERROR:", "WARNING:"] {
+        if let Some(r) = rest.strip_prefix(prefix) {
+            severity = prefix;
+            rest = r.trim_start();
+        }
+    }
+
+    let (source, r) = number(rest)?;
+    let (line, column, r) = if let Some(r) = r.strip_prefix(':') {
+        // Mesa, AMD, Intel
+        let (line, r) = number(r)?;
+        match r.strip_prefix('(') {
+            Some(r) => {
+                let (column, r) = number(r)?;
+                (line, Some(column), r.strip_prefix(')')?)
+            }
+            None => (line, None, r),
+        }
+    } else {
+        // NVIDIA
+        let (line, r) = number(r.strip_prefix('(')?)?;
+        (line, None, r.strip_prefix(')')?)
+    };
+    let text = r.trim_start().strip_prefix(':')?.trim();
+
+    let message = if severity.is_empty() {
+        text.to_string()
+    } else {
+        format!("{} {}", severity.to_lowercase(), text)
+    };
+    Some(LogMessage {
+        source,
+        line,
+        column,
+        message,
+    })
+}
+
+fn number(s: &str) -> Option<(usize, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n = s[..end].parse().ok()?;
+    Some((n, &s[end..]))
+}
+
+/// Rewrites a compiler log so every message names the original file and line,
+/// followed by that line with some context and a caret. Lines the parser
+/// doesn't understand are kept as they are.
+pub fn format_log(log: &str, source: &ShaderSource) -> String {
+    let mut out = String::new();
+    for line in log.lines().filter(|l| !l.trim().is_empty()) {
+        match parse_line(line) {
+            Some(msg) => format_message(&msg, source, &mut out),
+            None => {
+                out.push_str(line.trim_end());
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn format_message(msg: &LogMessage, source: &ShaderSource, out: &mut String) {
+    let file = match source.files.get(msg.source) {
+        Some(f) => f.display().to_string(),
+        None => format!("<source {}>", msg.source),
+    };
+    match msg.column {
+        Some(c) => out.push_str(&format!("{}:{}:{}: {}\n", file, msg.line, c, msg.message)),
+        None => out.push_str(&format!("{}:{}: {}\n", file, msg.line, msg.message)),
+    }
+
+    let lines: Vec<&str> = match source.texts.get(msg.source) {
+        Some(t) => t.lines().collect(),
+        None => return,
+    };
+    if msg.line == 0 || msg.line > lines.len() {
+        return;
+    }
+
+    let first = msg.line.saturating_sub(CONTEXT_LINES).max(1);
+    let last = (msg.line + CONTEXT_LINES).min(lines.len());
+    let width = last.to_string().len();
+    for n in first..=last {
+        let text = lines[n - 1];
+        out.push_str(&format!("{:>w$} | {}\n", n, text, w = width));
+        if n == msg.line {
+            // point at the reported column, or at the start of the statement
+            let column = match msg.column {
+                Some(c) => c.saturating_sub(1),
+                None => text.len() - text.trim_start().len(),
+            };
+            let indent: String = text
+                .chars()
+                .take(column)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            out.push_str(&format!("{:>w$} | {}^\n", "", indent, w = width));
+        }
+    }
+}
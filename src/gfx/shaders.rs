@@ -1,12 +1,82 @@
 use super::glutils;
+use super::preprocessor::{self, Defines, ShaderSource};
+use super::program::{self, ShaderStage};
+use super::program_cache::ProgramCache;
+use super::reflection::{self, ProgramReflection, UniformInfo};
+use super::shader_log;
 use gl33::*;
+use std::cell::{Ref, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::ffi::CString;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use ultraviolet::*;
 
-#[derive(Default, Clone, Copy)]
+#[derive(Default)]
 pub struct Shaders {
-    program_id: u32,
+    // swapped by `reload_if_changed`, which only needs `&self` so programs
+    // shared through asset handles can reload too
+    program: RefCell<LinkedProgram>,
+    sources: RefCell<Option<SourceFiles>>,
+    missing_uniforms: MissingUniforms,
+    // lenient mode reports each bad uniform only once
+    reported: RefCell<HashSet<String>>,
+}
+
+#[derive(Default)]
+struct LinkedProgram {
+    id: u32,
+    reflection: ProgramReflection,
+    texture_units: HashMap<String, SamplerUnit>,
+}
+
+/// What the `set_*` methods do with names that are not active uniforms of the
+/// program (or whose type doesn't match the setter)
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MissingUniforms {
+    /// Panic, catches typos and uniforms optimized away by the compiler
+    #[default]
+    Strict,
+    /// Skip the assignment, printing a warning once per name
+    Lenient,
+}
+
+/// Texture unit assigned to a sampler uniform after linking
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SamplerUnit {
+    pub unit: u32,
+    /// Target the sampler type reads from (GL_TEXTURE_2D, GL_TEXTURE_CUBE_MAP, ...)
+    pub target: GLenum,
+}
+
+/// Files a program was built from, watched by `Shaders::reload_if_changed`
+#[derive(Clone)]
+struct SourceFiles {
+    stages: Vec<(ShaderStage, PathBuf)>,
+    defines: Defines,
+    // stage files plus everything they #include
+    watched: Vec<PathBuf>,
+    modified: Vec<Option<SystemTime>>,
+}
+
+impl SourceFiles {
+    fn mtimes(&self) -> Vec<Option<SystemTime>> {
+        self.watched
+            .iter()
+            .map(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+
+    /// File named in reload messages, the fragment shader when there is one
+    fn main_file(&self) -> &Path {
+        self.stages
+            .iter()
+            .find(|(stage, _)| *stage == ShaderStage::Fragment)
+            .or(self.stages.first())
+            .map(|(_, path)| path.as_path())
+            .unwrap_or(Path::new(""))
+    }
 }
 
 impl Shaders {
@@ -24,27 +94,69 @@ impl Shaders {
         fragment_file: &str,
         geometry_file: &str,
     ) -> Result<Shaders, String> {
-        let vertex_code = match fs::read_to_string(vertex_file) {
-            Ok(v) => v,
-            Err(e) => return Err(format!("error reading {}: {}", vertex_file, e)),
-        };
+        Self::from_files_with_defines(
+            gl,
+            vertex_file,
+            fragment_file,
+            geometry_file,
+            &Defines::new(),
+        )
+    }
 
-        let fragment_code = match fs::read_to_string(fragment_file) {
-            Ok(v) => v,
-            Err(e) => return Err(format!("error reading {}: {}", fragment_file, e)),
-        };
+    /// Builds a program from files, resolving `#include`s and inserting `defines`
+    /// after the `#version` line of every stage
+    pub fn from_files_with_defines(
+        gl: &GlFns,
+        vertex_file: &str,
+        fragment_file: &str,
+        geometry_file: &str,
+        defines: &Defines,
+    ) -> Result<Shaders, String> {
+        let mut stages = vec![(ShaderStage::Vertex, PathBuf::from(vertex_file))];
+        // an explicitly requested geometry shader has to exist
+        if !geometry_file.is_empty() {
+            stages.push((ShaderStage::Geometry, PathBuf::from(geometry_file)));
+        }
+        stages.push((ShaderStage::Fragment, PathBuf::from(fragment_file)));
+        Self::from_stage_files(gl, &stages, defines)
+    }
 
-        let geometry_code = match fs::read_to_string(geometry_file) {
-            Ok(v) => v,
-            Err(_) => "".to_string(),
+    /// Builds a program from one file per stage, see `ProgramBuilder`
+    pub(crate) fn from_stage_files(
+        gl: &GlFns,
+        stages: &[(ShaderStage, PathBuf)],
+        defines: &Defines,
+    ) -> Result<Shaders, String> {
+        let mut sources = SourceFiles {
+            stages: stages.to_vec(),
+            defines: defines.clone(),
+            watched: vec![],
+            modified: vec![],
         };
 
-        Shaders::from_str_full(
-            gl,
-            vertex_code.as_str(),
-            fragment_code.as_str(),
-            geometry_code.as_str(),
-        )
+        let (mut shaders, watched) = Self::compile_files(gl, stages, defines)?;
+        sources.watched = watched;
+        sources.modified = sources.mtimes();
+        *shaders.sources.get_mut() = Some(sources);
+        Ok(shaders)
+    }
+
+    fn compile_files(
+        gl: &GlFns,
+        stages: &[(ShaderStage, PathBuf)],
+        defines: &Defines,
+    ) -> Result<(Shaders, Vec<PathBuf>), String> {
+        let mut sources = vec![];
+        for (stage, path) in stages {
+            let source = preprocessor::preprocess_file(&path.to_string_lossy(), defines)?;
+            sources.push((*stage, source));
+        }
+
+        let linked: Vec<_> = sources.iter().map(|(stage, s)| (*stage, s)).collect();
+        let shaders = Shaders::link_stages(gl, &linked)?;
+
+        let watched = sources.into_iter().flat_map(|(_, s)| s.files).collect();
+        Ok((shaders, watched))
     }
 
     pub fn from_str(gl: &GlFns, vertex_code: &str, fragment_code: &str) -> Result<Shaders, String> {
@@ -57,77 +169,170 @@ impl Shaders {
         fragment_code: &str,
         geometry_code: &str,
     ) -> Result<Shaders, String> {
-        // create vertex shader
-        let vertex_shader = gl.CreateShader(gl33::GL_VERTEX_SHADER);
-        if vertex_shader == 0 {
-            return Err("glCreateShader(GL_VERTEX_SHADER) failed".to_string());
+        let vertex = ShaderSource::from_code(vertex_code);
+        let fragment = ShaderSource::from_code(fragment_code);
+        let geometry = ShaderSource::from_code(geometry_code);
+        let mut stages = vec![(ShaderStage::Vertex, &vertex)];
+        if !geometry_code.is_empty() {
+            stages.push((ShaderStage::Geometry, &geometry));
         }
+        stages.push((ShaderStage::Fragment, &fragment));
+        Self::link_stages(gl, &stages)
+    }
 
-        if let Err(e) = Self::compile(gl, vertex_shader, vertex_code) {
-            return Err(format!("vertex shader compilation error: {}", e));
+    /// Compiles the preprocessed `stages` and links them into a program
+    pub(crate) fn link_stages(
+        gl: &GlFns,
+        stages: &[(ShaderStage, &ShaderSource)],
+    ) -> Result<Shaders, String> {
+        let kinds: Vec<ShaderStage> = stages.iter().map(|(stage, _)| *stage).collect();
+        program::check_stages(&kinds)?;
+        for stage in kinds.iter() {
+            stage.check_supported(gl)?;
         }
 
-        // create fragment shader
-        let fragment_shader = gl.CreateShader(gl33::GL_FRAGMENT_SHADER);
-        if fragment_shader == 0 {
-            return Err("glCreateShader(GL_FRAGMENT_SHADER) failed".to_string());
+        // skip compiling when a binary of the same sources is cached, stage names
+        // are part of the key so the same code in another stage doesn't collide
+        let mut key = vec![];
+        for (stage, source) in stages {
+            key.push(stage.name());
+            key.push(source.code.as_str());
         }
-
-        if let Err(e) = Self::compile(gl, fragment_shader, fragment_code) {
-            return Err(format!("fragment shader compilation error: {}", e));
+        let cache = ProgramCache::entry(gl, &key);
+        if let Some(program) = cache.as_ref().and_then(|c| c.load(gl)) {
+            return Ok(Shaders::linked(gl, program));
         }
 
-        // create program and link shaders
-        let shader_program = gl.CreateProgram();
-        gl.AttachShader(shader_program, vertex_shader);
-        gl.AttachShader(shader_program, fragment_shader);
-
-        let mut geometry_shader = 0;
-
-        if !geometry_code.is_empty() {
-            geometry_shader = gl.CreateShader(gl33::GL_GEOMETRY_SHADER);
-            if geometry_shader == 0 {
-                return Err("glCreateShader(GL_GEOMETRY_SHADER) failed".to_string());
+        let mut shader_ids = vec![];
+        for (stage, source) in stages {
+            let shader = gl.CreateShader(stage.gl_type());
+            if shader == 0 {
+                shader_ids.into_iter().for_each(|s| gl.DeleteShader(s));
+                return Err(format!("glCreateShader({} shader) failed", stage.name()));
             }
-
-            if let Err(e) = Self::compile(gl, geometry_shader, geometry_code) {
-                return Err(format!("geometry shader compilation error: {}", e));
+            if let Err(e) = Self::compile(gl, shader, source, stage.name()) {
+                shader_ids.into_iter().for_each(|s| gl.DeleteShader(s));
+                return Err(e);
             }
+            shader_ids.push(shader);
+        }
 
-            gl.AttachShader(shader_program, geometry_shader);
+        // create program and link shaders
+        let shader_program = gl.CreateProgram();
+        for shader in shader_ids.iter() {
+            gl.AttachShader(shader_program, *shader);
         }
 
+        if let Some(c) = &cache {
+            c.prepare(shader_program);
+        }
         gl.LinkProgram(shader_program);
 
+        // not needed anymore, the program keeps what it linked
+        for shader in shader_ids {
+            gl.DeleteShader(shader);
+        }
+
         let mut success = 0;
         unsafe {
             gl.GetProgramiv(shader_program, gl33::GL_LINK_STATUS, &mut success);
         }
         if success == 0 {
-            let mut v: Vec<u8> = Vec::with_capacity(1024);
-            let mut log_len = 0_i32;
+            let mut log_len = 0;
             unsafe {
-                gl.GetProgramInfoLog(shader_program, 1024, &mut log_len, v.as_mut_ptr().cast());
-                v.set_len(log_len.try_into().unwrap());
+                gl.GetProgramiv(shader_program, gl33::GL_INFO_LOG_LENGTH, &mut log_len);
             }
+            let log = read_info_log(log_len, |len, buf| unsafe {
+                gl.GetProgramInfoLog(shader_program, log_len, len, buf);
+            });
+            gl.DeleteProgram(shader_program);
+            let names: Vec<String> = stages.iter().map(|(_, s)| s.name()).collect();
             return Err(format!(
-                "program link error: {}",
-                String::from_utf8_lossy(&v)
+                "program link error ({}): {}",
+                names.join(", "),
+                log
             ));
         }
 
-        // not needed anymore
-        gl.DeleteShader(vertex_shader);
-        gl.DeleteShader(fragment_shader);
-        if geometry_shader != 0 {
-            gl.DeleteShader(geometry_shader);
+        if let Some(c) = &cache {
+            if let Err(e) = c.store(gl, shader_program) {
+                eprintln!("program cache: {}", e);
+            }
         }
 
-        Ok(Shaders {
-            program_id: shader_program,
-        })
+        Ok(Shaders::linked(gl, shader_program))
+    }
+
+    fn linked(gl: &GlFns, program: u32) -> Shaders {
+        let reflection = ProgramReflection::query(gl, program);
+        let texture_units = assign_texture_units(gl, program, &reflection);
+        Shaders {
+            program: RefCell::new(LinkedProgram {
+                id: program,
+                reflection,
+                texture_units,
+            }),
+            ..Default::default()
+        }
     }
-    fn compile(gl: &GlFns, shader_id: u32, shader_code: &str) -> Result<(), String> {
+
+    /// Recompiles and relinks the program if one of its source files changed on disk.
+    ///
+    /// The program id is swapped (and the old program deleted) only when the new
+    /// sources build successfully, otherwise the error is printed and the old
+    /// program stays in use. Returns true when a new program was swapped in, in
+    /// which case uniforms set once at init (e.g. sampler units) must be set again.
+    pub fn reload_if_changed(&self, gl: &GlFns) -> bool {
+        let mut sources = self.sources.borrow_mut();
+        let sources = match sources.as_mut() {
+            Some(s) => s,
+            None => return false,
+        };
+
+        let modified = sources.mtimes();
+        if modified == sources.modified {
+            return false;
+        }
+        sources.modified = modified;
+
+        let reloaded = Self::compile_files(gl, &sources.stages, &sources.defines);
+
+        match reloaded {
+            Ok((shaders, watched)) => {
+                // includes may have been added or removed
+                sources.watched = watched;
+                sources.modified = sources.mtimes();
+                let reloaded = shaders.program.into_inner();
+                let mut program = self.program.borrow_mut();
+                println!(
+                    "program({}): reloaded {} as program({})",
+                    program.id,
+                    sources.main_file().display(),
+                    reloaded.id
+                );
+                gl.DeleteProgram(program.id);
+                *program = reloaded;
+                self.reported.borrow_mut().clear();
+                true
+            }
+            Err(e) => {
+                eprintln!(
+                    "program({}): reload of {} failed, keeping the old program: {}",
+                    self.id(),
+                    sources.main_file().display(),
+                    e
+                );
+                false
+            }
+        }
+    }
+    fn compile(
+        gl: &GlFns,
+        shader_id: u32,
+        source: &ShaderSource,
+        stage: &str,
+    ) -> Result<(), String> {
+        let shader_code = &source.code;
         unsafe {
             gl.ShaderSource(
                 shader_id,
@@ -146,128 +351,260 @@ impl Shaders {
         }
 
         if success == 0 {
-            let mut v: Vec<u8> = Vec::with_capacity(1024);
-            let mut log_len = 0_i32;
+            let mut log_len = 0;
             unsafe {
-                gl.GetShaderInfoLog(shader_id, 1024, &mut log_len, v.as_mut_ptr().cast());
-                v.set_len(log_len.try_into().unwrap());
+                gl.GetShaderiv(shader_id, gl33::GL_INFO_LOG_LENGTH, &mut log_len);
             }
+            let log = read_info_log(log_len, |len, buf| unsafe {
+                gl.GetShaderInfoLog(shader_id, log_len, len, buf);
+            });
+            gl.DeleteShader(shader_id);
 
-            return Err(String::from_utf8_lossy(&v).to_string());
+            // the #line directives of the preprocessor let the driver report
+            // original files and lines, map them back to names and source text
+            return Err(format!(
+                "{} shader compilation error in {}:\n{}",
+                stage,
+                source.name(),
+                shader_log::format_log(&log, source)
+            ));
         }
         Ok(())
     }
 
-    fn get_uniform_location(&self, gl: &GlFns, name: &str) -> i32 {
-        let c_name = std::ffi::CString::new(name).unwrap_or_else(|_| {
-            panic!("get_uniform_location: CString::new failed for '{}'", name);
-        });
+    pub fn set_missing_uniforms(&mut self, mode: MissingUniforms) {
+        self.missing_uniforms = mode;
+    }
 
-        self.get_uniform_location_cstr(gl, &c_name)
+    /// Active uniforms, attributes and uniform blocks of the linked program
+    pub fn reflection(&self) -> Ref<'_, ProgramReflection> {
+        Ref::map(self.program.borrow(), |p| &p.reflection)
     }
 
-    fn get_uniform_location_cstr(&self, gl: &GlFns, c_name: &CString) -> i32 {
-        let location;
-        unsafe {
-            location = gl.GetUniformLocation(self.program_id, c_name.as_ptr().cast());
-        }
-        glutils::check_gl_err(gl);
-        if location == -1 {
-            let name = c_name.to_str().unwrap_or("<cstring decoding error>");
-            panic!(
+    pub fn uniform(&self, name: &str) -> Option<UniformInfo> {
+        self.program.borrow().reflection.uniforms.get(name).copied()
+    }
+
+    /// Cached location of `name`, checked against the GL types the caller can write.
+    /// Returns -1 (ignored by glUniform*) for unusable uniforms in lenient mode.
+    pub(crate) fn uniform_location(&self, name: &str, accepted: &[GLenum], lenient: bool) -> i32 {
+        let error = match self.uniform(name) {
+            // samplers are assigned their texture unit as an int
+            Some(u)
+                if accepted.contains(&u.gl_type)
+                    || (accepted.contains(&GL_INT) && reflection::is_sampler(u.gl_type)) =>
+            {
+                return u.location;
+            }
+            Some(u) => format!(
+                "program({}): uniform '{}' is a {}, can't set it as {}",
+                self.id(),
+                name,
+                reflection::type_name(u.gl_type),
+                accepted
+                    .iter()
+                    .map(|t| reflection::type_name(*t))
+                    .collect::<Vec<_>>()
+                    .join(" / ")
+            ),
+            None => format!(
                 "program({}): location '{}' does not correspond to an active uniform variable in program",
-                self.program_id,
+                self.id(),
                 name
-            );
+            ),
+        };
+
+        self.report(name, error, lenient);
+        -1
+    }
+
+    fn uniform_location_cstr(&self, c_name: &CString, accepted: &[GLenum]) -> i32 {
+        let name = c_name.to_str().unwrap_or("<cstring decoding error>");
+        self.uniform_location(name, accepted, false)
+    }
+
+    /// Unit the sampler `name` was given after linking, see `bind_texture`
+    pub fn texture_unit(&self, name: &str) -> Option<SamplerUnit> {
+        self.program.borrow().texture_units.get(name).copied()
+    }
+
+    /// Binds `texture` to the unit of the sampler `name`, using the target of the
+    /// sampler type. Every sampler gets its own unit when the program is linked,
+    /// so the sampler uniforms don't have to be set by hand. Unknown samplers and
+    /// textures created for another target are reported like bad uniforms.
+    pub fn bind_texture(&self, gl: &GlFns, name: &str, texture: u32) {
+        self.bind_texture_impl(gl, name, texture, false);
+    }
+
+    /// Like `bind_texture`, silently skipping samplers the program doesn't use
+    pub fn try_bind_texture(&self, gl: &GlFns, name: &str, texture: u32) {
+        self.bind_texture_impl(gl, name, texture, true);
+    }
+
+    fn bind_texture_impl(&self, gl: &GlFns, name: &str, texture: u32, lenient: bool) {
+        let sampler = match self.texture_unit(name) {
+            Some(s) => s,
+            None => {
+                let error = format!(
+                    "program({}): '{}' is not an active sampler of the program",
+                    self.id(),
+                    name
+                );
+                self.report(name, error, lenient);
+                return;
+            }
+        };
+
+        // earlier errors would be taken for a target mismatch
+        glutils::check_gl_err(gl);
+        unsafe {
+            gl.ActiveTexture(GLenum(GL_TEXTURE0.0 + sampler.unit));
+            gl.BindTexture(sampler.target, texture);
+            let err = gl.GetError();
+            gl.ActiveTexture(GL_TEXTURE0);
+            if err == GL_INVALID_OPERATION {
+                let error = format!(
+                    "program({}): texture {} can't be bound to sampler '{}' ({}), it was created for another target",
+                    self.id(),
+                    texture,
+                    name,
+                    reflection::target_name(sampler.target)
+                );
+                // a type mismatch is a bug even for optional samplers
+                self.report(name, error, false);
+            } else if err != GL_NO_ERROR {
+                panic!("error: {:?}", err);
+            }
+        }
+    }
+
+    // strict mode panics, lenient mode warns once per name, `lenient` callers
+    // (try_*) expect the name to be missing and stay quiet
+    fn report(&self, name: &str, error: String, lenient: bool) {
+        if lenient {
+            return;
+        }
+        if self.missing_uniforms == MissingUniforms::Lenient {
+            if self.reported.borrow_mut().insert(name.to_string()) {
+                eprintln!("{}", error);
+            }
+            return;
         }
-        location
+        panic!("{}", error);
+    }
+
+    pub fn id(&self) -> u32 {
+        self.program.borrow().id
     }
 
     pub fn use_program(&self, gl: &GlFns) {
-        gl.UseProgram(self.program_id);
+        gl.UseProgram(self.id());
         glutils::check_gl_err(gl);
     }
 
     pub fn set_bool(&self, gl: &GlFns, name: &str, value: bool) {
         unsafe {
-            gl.Uniform1i(self.get_uniform_location(gl, name), i32::from(value));
+            gl.Uniform1i(
+                self.uniform_location(name, &[GL_BOOL, GL_INT], false),
+                i32::from(value),
+            );
         }
     }
 
     pub fn set_bool_cstr(&self, gl: &GlFns, name: &CString, value: bool) {
         unsafe {
-            gl.Uniform1i(self.get_uniform_location_cstr(gl, name), i32::from(value));
+            gl.Uniform1i(
+                self.uniform_location_cstr(name, &[GL_BOOL, GL_INT]),
+                i32::from(value),
+            );
         }
     }
 
     pub fn set_i32(&self, gl: &GlFns, name: &str, value: i32) {
         unsafe {
-            gl.Uniform1i(self.get_uniform_location(gl, name), value);
+            gl.Uniform1i(
+                self.uniform_location(name, &[GL_INT, GL_BOOL], false),
+                value,
+            );
         }
     }
 
     pub fn set_i32_cstr(&self, gl: &GlFns, name: &CString, value: i32) {
         unsafe {
-            gl.Uniform1i(self.get_uniform_location_cstr(gl, name), value);
+            gl.Uniform1i(self.uniform_location_cstr(name, &[GL_INT, GL_BOOL]), value);
         }
     }
 
     pub fn try_set_i32(&self, gl: &GlFns, name: &str, value: i32) {
+        let location = self.uniform_location(name, &[GL_INT, GL_BOOL], true);
+        if location == -1 {
+            return;
+        }
         unsafe {
-            let c_name = std::ffi::CString::new(name).unwrap();
-            let location = gl.GetUniformLocation(self.program_id, c_name.as_ptr().cast());
-
-            if location == -1 {
-                return;
-            }
-
             gl.Uniform1i(location, value);
         }
     }
 
     pub fn set_f32(&self, gl: &GlFns, name: &str, value: f32) {
         unsafe {
-            let c_name = std::ffi::CString::new(name).unwrap_or_else(|_| {
-                panic!("get_uniform_location: CString::new failed for '{}'", name);
-            });
-
-            self.get_uniform_location_cstr(gl, &c_name);
-            gl.Uniform1f(self.get_uniform_location(gl, name), value);
+            gl.Uniform1f(self.uniform_location(name, &[GL_FLOAT], false), value);
         }
     }
 
     pub fn set_f32_cstr(&self, gl: &GlFns, name: &CString, value: f32) {
         unsafe {
-            gl.Uniform1f(self.get_uniform_location_cstr(gl, name), value);
+            gl.Uniform1f(self.uniform_location_cstr(name, &[GL_FLOAT]), value);
         }
     }
 
     pub fn set_vec3(&self, gl: &GlFns, name: &str, v0: f32, v1: f32, v2: f32) {
         unsafe {
-            gl.Uniform3f(self.get_uniform_location(gl, name), v0, v1, v2);
+            gl.Uniform3f(
+                self.uniform_location(name, &[GL_FLOAT_VEC3], false),
+                v0,
+                v1,
+                v2,
+            );
         }
     }
 
     pub fn set_vec3_cstr(&self, gl: &GlFns, name: &CString, v0: f32, v1: f32, v2: f32) {
         unsafe {
-            gl.Uniform3f(self.get_uniform_location_cstr(gl, name), v0, v1, v2);
+            gl.Uniform3f(
+                self.uniform_location_cstr(name, &[GL_FLOAT_VEC3]),
+                v0,
+                v1,
+                v2,
+            );
         }
     }
 
     pub fn set_vec4(&self, gl: &GlFns, name: &str, v0: f32, v1: f32, v2: f32, v3: f32) {
         unsafe {
-            gl.Uniform4f(self.get_uniform_location(gl, name), v0, v1, v2, v3);
+            gl.Uniform4f(
+                self.uniform_location(name, &[GL_FLOAT_VEC4], false),
+                v0,
+                v1,
+                v2,
+                v3,
+            );
         }
     }
 
     pub fn set_vec4_cstr(&self, gl: &GlFns, name: &CString, v0: f32, v1: f32, v2: f32, v3: f32) {
         unsafe {
-            gl.Uniform4f(self.get_uniform_location_cstr(gl, name), v0, v1, v2, v3);
+            gl.Uniform4f(
+                self.uniform_location_cstr(name, &[GL_FLOAT_VEC4]),
+                v0,
+                v1,
+                v2,
+                v3,
+            );
         }
     }
 
     pub fn set_mat4fv(&self, gl: &GlFns, name: &str, mat: &glm::Matrix4<f32>) {
-        let location = self.get_uniform_location(gl, name);
+        let location = self.uniform_location(name, &[GL_FLOAT_MAT4], false);
         unsafe {
             let arr: [f32; 16] = [
                 mat.c0[0], mat.c0[1], mat.c0[2], mat.c0[3], mat.c1[0], mat.c1[1], mat.c1[2],
@@ -279,29 +616,87 @@ impl Shaders {
     }
 
     pub fn set_mat4fv_uv(&self, gl: &GlFns, name: &str, mat: &Mat4) {
-        let location = self.get_uniform_location(gl, name);
+        let location = self.uniform_location(name, &[GL_FLOAT_MAT4], false);
         unsafe {
             gl.UniformMatrix4fv(location, 1, gl33::GL_FALSE.0 as u8, mat.as_slice().as_ptr());
         }
     }
 
     pub fn try_set_mat4fv_uv(&self, gl: &GlFns, name: &str, mat: &Mat4) {
+        let location = self.uniform_location(name, &[GL_FLOAT_MAT4], true);
+        if location == -1 {
+            return;
+        }
         unsafe {
-            let c_name = std::ffi::CString::new(name).unwrap();
-            let location = gl.GetUniformLocation(self.program_id, c_name.as_ptr().cast());
-
-            if location == -1 {
-                return;
-            }
-
             gl.UniformMatrix4fv(location, 1, gl33::GL_FALSE.0 as u8, mat.as_slice().as_ptr());
         }
     }
 
     pub fn set_mat4fv_uv_cstr(&self, gl: &GlFns, name: &CString, mat: &Mat4) {
-        let location = self.get_uniform_location_cstr(gl, name);
+        let location = self.uniform_location_cstr(name, &[GL_FLOAT_MAT4]);
         unsafe {
             gl.UniformMatrix4fv(location, 1, gl33::GL_FALSE.0 as u8, mat.as_slice().as_ptr());
         }
     }
 }
+
+fn read_info_log<F>(log_len: i32, query: F) -> String
+where
+    F: FnOnce(*mut i32, *mut u8),
+{
+    let mut v: Vec<u8> = vec![0; log_len.max(1) as usize];
+    let mut len = 0;
+    query(&mut len, v.as_mut_ptr());
+    v.truncate(len.max(0) as usize);
+    String::from_utf8_lossy(&v).to_string()
+}
+
+/// Gives every sampler uniform of `program` its own texture unit, in name order
+/// so a program gets the same units every time it is linked
+fn assign_texture_units(
+    gl: &GlFns,
+    program: u32,
+    reflection: &ProgramReflection,
+) -> HashMap<String, SamplerUnit> {
+    let mut samplers: Vec<(&String, &UniformInfo)> = reflection
+        .uniforms
+        .iter()
+        .filter(|(_, u)| reflection::is_sampler(u.gl_type))
+        .collect();
+    samplers.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut max_units = 0;
+    let mut previous = 0;
+    unsafe {
+        gl.GetIntegerv(GL_MAX_COMBINED_TEXTURE_IMAGE_UNITS, &mut max_units);
+        gl.GetIntegerv(GL_CURRENT_PROGRAM, &mut previous);
+    }
+    gl.UseProgram(program);
+
+    // "tex" and "tex[0]" are the same uniform, key units by location
+    let mut by_location: HashMap<i32, u32> = HashMap::new();
+    let mut units = HashMap::new();
+    for (name, info) in samplers {
+        let target = match reflection::sampler_target(info.gl_type) {
+            Some(t) => t,
+            None => continue,
+        };
+        let next = by_location.len() as u32;
+        let unit = *by_location.entry(info.location).or_insert(next);
+        if unit >= max_units as u32 {
+            eprintln!(
+                "program({}): no texture unit left for sampler '{}' ({} available)",
+                program, name, max_units
+            );
+            continue;
+        }
+        unsafe {
+            gl.Uniform1i(info.location, unit as i32);
+        }
+        units.insert(name.clone(), SamplerUnit { unit, target });
+    }
+
+    gl.UseProgram(previous as u32);
+    glutils::check_gl_err(gl);
+    units
+}
@@ -6,6 +6,9 @@ use gl33::*;
 const IO_DEBUG_PRINT: bool = false;
 const IO_DEBUG_PRINT_VERY_NOISY: bool = false; // eg mouse motion
 
+// core context versions to try, newest first
+const CONTEXT_VERSIONS: [(i32, i32); 2] = [(4, 3), (3, 3)];
+
 pub const KEY_RIGHT: i32 = SDLK_RIGHT.0;
 pub const KEY_LEFT: i32 = SDLK_LEFT.0;
 pub const KEY_DOWN: i32 = SDLK_DOWN.0;
@@ -55,14 +58,6 @@ impl System {
         unsafe {
             // initialize SLD with OpenGL context
             SDL_Init(SDL_INIT_VIDEO);
-            if SDL_GL_SetAttribute(SDL_GL_CONTEXT_MAJOR_VERSION, 3) != 0 {
-                panic!("SDL_GL_SetAttribute(SDL_GL_CONTEXT_MAJOR_VERSION, 3) failed")
-            }
-
-            if SDL_GL_SetAttribute(SDL_GL_CONTEXT_MINOR_VERSION, 3) != 0 {
-                panic!("SDL_GL_SetAttribute(SDL_GL_CONTEXT_MINOR_VERSION, 3) failed")
-            }
-
             if SDL_GL_SetAttribute(
                 SDL_GL_CONTEXT_PROFILE_MASK,
                 SDL_GL_CONTEXT_PROFILE_CORE.0 as _,
@@ -95,8 +90,28 @@ impl System {
                 println!();
                 panic!();
             }
-            // make context the window will use
-            let ctx = SDL_GL_CreateContext(win);
+            // make context the window will use, 4.3 for tessellation and compute
+            // shaders (demo23), 3.3 is enough for everything else
+            let mut ctx = SDL_GLContext(std::ptr::null_mut());
+            for (major, minor) in CONTEXT_VERSIONS {
+                if SDL_GL_SetAttribute(SDL_GL_CONTEXT_MAJOR_VERSION, major) != 0 {
+                    panic!(
+                        "SDL_GL_SetAttribute(SDL_GL_CONTEXT_MAJOR_VERSION, {}) failed",
+                        major
+                    )
+                }
+                if SDL_GL_SetAttribute(SDL_GL_CONTEXT_MINOR_VERSION, minor) != 0 {
+                    panic!(
+                        "SDL_GL_SetAttribute(SDL_GL_CONTEXT_MINOR_VERSION, {}) failed",
+                        minor
+                    )
+                }
+                ctx = SDL_GL_CreateContext(win);
+                if !ctx.0.is_null() {
+                    break;
+                }
+                println!("no OpenGL {}.{} core context", major, minor);
+            }
             if ctx.0.is_null() {
                 let mut v = Vec::with_capacity(4096);
                 let mut p = SDL_GetErrorMsg(v.as_mut_ptr(), v.capacity() as _);
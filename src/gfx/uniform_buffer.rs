@@ -0,0 +1,230 @@
+use super::glutils;
+use super::shaders::Shaders;
+use gl33::*;
+use std::marker::PhantomData;
+
+/// Binding points of the uniform blocks shared by every program
+pub const CAMERA_BINDING: u32 = 0;
+pub const LIGHTS_BINDING: u32 = 1;
+
+/// std140 base alignment and size of a block member.
+///
+/// # Safety
+/// `SIZE` must be the size the type occupies in a std140 block and the Rust
+/// type must have exactly that memory layout (`size_of::<Self>() == SIZE`,
+/// checked for every member by `std140_struct!`).
+pub unsafe trait Std140Member: Copy {
+    const ALIGN: usize;
+    const SIZE: usize;
+}
+
+/// Marker for types that can be uploaded as a whole uniform block,
+/// implemented by `std140_struct!`
+///
+/// # Safety
+/// The memory layout of the type must match the std140 layout of the block.
+pub unsafe trait Std140: Std140Member {}
+
+pub const fn std140_align_up(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+macro_rules! std140_member {
+    ($t:ty, $align:expr, $size:expr) => {
+        unsafe impl Std140Member for $t {
+            const ALIGN: usize = $align;
+            const SIZE: usize = $size;
+        }
+    };
+}
+
+std140_member!(f32, 4, 4);
+std140_member!(i32, 4, 4);
+std140_member!(u32, 4, 4);
+std140_member!(ultraviolet::Vec2, 8, 8);
+std140_member!(ultraviolet::Vec3, 16, 12);
+std140_member!(ultraviolet::Vec4, 16, 16);
+std140_member!(ultraviolet::IVec4, 16, 16);
+std140_member!(ultraviolet::UVec4, 16, 16);
+std140_member!(ultraviolet::Mat4, 16, 64);
+std140_member!(glm::Vector2<f32>, 8, 8);
+std140_member!(glm::Vector3<f32>, 16, 12);
+std140_member!(glm::Vector4<f32>, 16, 16);
+std140_member!(glm::Matrix4<f32>, 16, 64);
+
+/// Arrays have a stride of a multiple of 16 bytes in std140, so only arrays of
+/// 16 byte multiples (vec4, mat4, padded structs) match the Rust layout
+unsafe impl<T: Std140Member, const N: usize> Std140Member for [T; N] {
+    const ALIGN: usize = 16;
+    const SIZE: usize = N * std140_align_up(T::SIZE, 16);
+}
+
+/// Explicit padding bytes between members, not visible to GLSL
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct Pad<const N: usize>([u8; N]);
+
+impl<const N: usize> Default for Pad<N> {
+    fn default() -> Self {
+        Pad([0; N])
+    }
+}
+
+unsafe impl<const N: usize> Std140Member for Pad<N> {
+    const ALIGN: usize = 1;
+    const SIZE: usize = N;
+}
+
+/// Declares a `#[repr(C)]` struct mirroring a GLSL std140 block or struct.
+///
+/// Every member offset, and the total size, is checked against the std140 rules
+/// at compile time, so a missing `Pad<N>` (e.g. after a vec3) fails the build
+/// instead of silently shifting the following members on the GPU.
+macro_rules! std140_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $t:ty),+ $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy)]
+        #[repr(C)]
+        $vis struct $name {
+            $($field_vis $field: $t),+
+        }
+
+        const _: () = {
+            use $crate::gfx::uniform_buffer::{std140_align_up, Std140Member};
+            let mut offset = 0;
+            $(
+                offset = std140_align_up(offset, <$t as Std140Member>::ALIGN);
+                assert!(
+                    offset == std::mem::offset_of!($name, $field),
+                    concat!("std140: wrong offset of ", stringify!($name), "::", stringify!($field), ", add or fix Pad<N> members")
+                );
+                assert!(
+                    std::mem::size_of::<$t>() == <$t as Std140Member>::SIZE,
+                    concat!("std140: ", stringify!($name), "::", stringify!($field), " has no std140 compatible layout")
+                );
+                offset += <$t as Std140Member>::SIZE;
+            )+
+            assert!(
+                std::mem::size_of::<$name>() == std140_align_up(offset, 16),
+                concat!("std140: size of ", stringify!($name), " must be padded to a multiple of 16")
+            );
+        };
+
+        unsafe impl $crate::gfx::uniform_buffer::Std140Member for $name {
+            const ALIGN: usize = 16;
+            const SIZE: usize = std::mem::size_of::<$name>();
+        }
+
+        unsafe impl $crate::gfx::uniform_buffer::Std140 for $name {}
+    };
+}
+
+pub(crate) use std140_struct;
+
+/// GPU buffer holding one std140 block, bound to a fixed binding point so any
+/// program declaring the block sees the same data once `bind_block` was called on it
+pub struct UniformBuffer<T: Std140> {
+    ubo: u32,
+    binding: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Std140> Default for UniformBuffer<T> {
+    fn default() -> Self {
+        UniformBuffer {
+            ubo: 0,
+            binding: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    pub fn new(gl: &GlFns, binding: u32) -> Result<Self, String> {
+        let mut ubo = 0;
+        unsafe {
+            gl.GenBuffers(1, &mut ubo);
+            if ubo == 0 {
+                return Err("failed: gl.GenBuffers(1, &mut ubo)".to_string());
+            }
+            gl.BindBuffer(GL_UNIFORM_BUFFER, ubo);
+            gl.BufferData(
+                GL_UNIFORM_BUFFER,
+                std::mem::size_of::<T>() as isize,
+                std::ptr::null(),
+                GL_DYNAMIC_DRAW,
+            );
+            gl.BindBuffer(GL_UNIFORM_BUFFER, 0);
+            gl.BindBufferBase(GL_UNIFORM_BUFFER, binding, ubo);
+        }
+        glutils::check_gl_err(gl);
+
+        Ok(UniformBuffer {
+            ubo,
+            binding,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Uploads the whole block, e.g. once per frame
+    pub fn update(&self, gl: &GlFns, value: &T) {
+        unsafe {
+            gl.BindBuffer(GL_UNIFORM_BUFFER, self.ubo);
+            gl.BufferSubData(
+                GL_UNIFORM_BUFFER,
+                0,
+                std::mem::size_of::<T>() as isize,
+                (value as *const T).cast(),
+            );
+            gl.BindBuffer(GL_UNIFORM_BUFFER, 0);
+        }
+    }
+
+    /// Connects uniform block `block_name` of `shader` to this buffer's binding point.
+    /// Has to be repeated after the program is relinked (e.g. hot reloaded).
+    pub fn bind_block(&self, gl: &GlFns, shader: &Shaders, block_name: &str) -> Result<(), String> {
+        let block = match shader.reflection().uniform_blocks.get(block_name) {
+            Some(b) => *b,
+            None => {
+                return Err(format!(
+                    "program({}): no active uniform block '{}'",
+                    shader.id(),
+                    block_name
+                ))
+            }
+        };
+        // drivers may or may not include the trailing padding in the reported size
+        let gpu_size = std140_align_up(block.data_size as usize, 16);
+        if gpu_size != std::mem::size_of::<T>() {
+            return Err(format!(
+                "program({}): uniform block '{}' is {} bytes, {} is {}",
+                shader.id(),
+                block_name,
+                block.data_size,
+                std::any::type_name::<T>(),
+                std::mem::size_of::<T>()
+            ));
+        }
+        unsafe {
+            gl.UniformBlockBinding(shader.id(), block.index, self.binding);
+        }
+        glutils::check_gl_err(gl);
+        Ok(())
+    }
+
+    pub fn binding(&self) -> u32 {
+        self.binding
+    }
+
+    pub fn delete(&mut self, gl: &GlFns) {
+        unsafe {
+            gl.DeleteBuffers(1, &self.ubo);
+        }
+        self.ubo = 0;
+    }
+}
@@ -0,0 +1,166 @@
+use super::shaders::Shaders;
+use gl33::*;
+use ultraviolet as uv;
+
+/// Anything that can be written to a uniform with `Shaders::set`.
+///
+/// Plain GLSL types upload with a single glUniform* call, arrays of them with one
+/// call for the whole array. Structs implement `set_uniform` by setting their
+/// members as `name.member`, arrays of structs then get `name[i].member` for free.
+pub trait UniformValue {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str);
+
+    /// Sets a GLSL array from a slice, by default element by element as `name[i]`
+    fn set_uniform_array(values: &[Self], gl: &GlFns, shader: &Shaders, name: &str)
+    where
+        Self: Sized,
+    {
+        for (i, v) in values.iter().enumerate() {
+            v.set_uniform(gl, shader, &format!("{}[{}]", name, i));
+        }
+    }
+}
+
+impl Shaders {
+    /// Sets uniform `name` of the program, which must be in use
+    pub fn set<T: UniformValue + ?Sized>(&self, gl: &GlFns, name: &str, value: &T) {
+        value.set_uniform(gl, self, name);
+    }
+
+    /// Like `set` for uniforms a program may not have, does nothing when `name`
+    /// isn't an active uniform
+    pub fn try_set<T: UniformValue + ?Sized>(&self, gl: &GlFns, name: &str, value: &T) {
+        if self.uniform(name).is_some() {
+            value.set_uniform(gl, self, name);
+        }
+    }
+}
+
+impl<T: UniformValue + ?Sized> UniformValue for &T {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        (**self).set_uniform(gl, shader, name);
+    }
+}
+
+impl<T: UniformValue> UniformValue for [T] {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        T::set_uniform_array(self, gl, shader, name);
+    }
+}
+
+impl<T: UniformValue, const N: usize> UniformValue for [T; N] {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        T::set_uniform_array(self, gl, shader, name);
+    }
+}
+
+impl<T: UniformValue> UniformValue for Vec<T> {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        T::set_uniform_array(self, gl, shader, name);
+    }
+}
+
+// Number of elements to upload, never more than the uniform array holds
+fn array_len(shader: &Shaders, name: &str, len: usize) -> i32 {
+    match shader.uniform(name) {
+        Some(u) => (len as i32).min(u.size),
+        None => len as i32,
+    }
+}
+
+// $t must be repr(C) and made only of the scalars glUniform* expects
+macro_rules! impl_uniform_value {
+    ($t:ty => [$($gl_type:expr),+], $upload:ident) => {
+        impl UniformValue for $t {
+            fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+                Self::set_uniform_array(std::slice::from_ref(self), gl, shader, name);
+            }
+
+            fn set_uniform_array(values: &[Self], gl: &GlFns, shader: &Shaders, name: &str) {
+                let location = shader.uniform_location(name, &[$($gl_type),+], false);
+                if location == -1 || values.is_empty() {
+                    return;
+                }
+                unsafe {
+                    gl.$upload(location, array_len(shader, name, values.len()), values.as_ptr().cast());
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_uniform_matrix {
+    ($t:ty => $gl_type:expr, $upload:ident) => {
+        impl UniformValue for $t {
+            fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+                Self::set_uniform_array(std::slice::from_ref(self), gl, shader, name);
+            }
+
+            fn set_uniform_array(values: &[Self], gl: &GlFns, shader: &Shaders, name: &str) {
+                let location = shader.uniform_location(name, &[$gl_type], false);
+                if location == -1 || values.is_empty() {
+                    return;
+                }
+                // both ultraviolet and glm store matrices column major, like GLSL
+                unsafe {
+                    gl.$upload(
+                        location,
+                        array_len(shader, name, values.len()),
+                        GL_FALSE.0 as u8,
+                        values.as_ptr().cast(),
+                    );
+                }
+            }
+        }
+    };
+}
+
+// scalars, i32 also covers samplers (texture unit)
+impl_uniform_value!(f32 => [GL_FLOAT], Uniform1fv);
+impl_uniform_value!(i32 => [GL_INT, GL_BOOL], Uniform1iv);
+impl_uniform_value!(u32 => [GL_UNSIGNED_INT, GL_BOOL], Uniform1uiv);
+
+// ultraviolet
+impl_uniform_value!(uv::Vec2 => [GL_FLOAT_VEC2], Uniform2fv);
+impl_uniform_value!(uv::Vec3 => [GL_FLOAT_VEC3], Uniform3fv);
+impl_uniform_value!(uv::Vec4 => [GL_FLOAT_VEC4], Uniform4fv);
+impl_uniform_value!(uv::IVec2 => [GL_INT_VEC2, GL_BOOL_VEC2], Uniform2iv);
+impl_uniform_value!(uv::IVec3 => [GL_INT_VEC3, GL_BOOL_VEC3], Uniform3iv);
+impl_uniform_value!(uv::IVec4 => [GL_INT_VEC4, GL_BOOL_VEC4], Uniform4iv);
+impl_uniform_value!(uv::UVec2 => [GL_UNSIGNED_INT_VEC2], Uniform2uiv);
+impl_uniform_value!(uv::UVec3 => [GL_UNSIGNED_INT_VEC3], Uniform3uiv);
+impl_uniform_value!(uv::UVec4 => [GL_UNSIGNED_INT_VEC4], Uniform4uiv);
+impl_uniform_matrix!(uv::Mat2 => GL_FLOAT_MAT2, UniformMatrix2fv);
+impl_uniform_matrix!(uv::Mat3 => GL_FLOAT_MAT3, UniformMatrix3fv);
+impl_uniform_matrix!(uv::Mat4 => GL_FLOAT_MAT4, UniformMatrix4fv);
+
+// glm
+impl_uniform_value!(glm::Vector2<f32> => [GL_FLOAT_VEC2], Uniform2fv);
+impl_uniform_value!(glm::Vector3<f32> => [GL_FLOAT_VEC3], Uniform3fv);
+impl_uniform_value!(glm::Vector4<f32> => [GL_FLOAT_VEC4], Uniform4fv);
+impl_uniform_value!(glm::Vector2<i32> => [GL_INT_VEC2, GL_BOOL_VEC2], Uniform2iv);
+impl_uniform_value!(glm::Vector3<i32> => [GL_INT_VEC3, GL_BOOL_VEC3], Uniform3iv);
+impl_uniform_value!(glm::Vector4<i32> => [GL_INT_VEC4, GL_BOOL_VEC4], Uniform4iv);
+impl_uniform_value!(glm::Vector2<u32> => [GL_UNSIGNED_INT_VEC2], Uniform2uiv);
+impl_uniform_value!(glm::Vector3<u32> => [GL_UNSIGNED_INT_VEC3], Uniform3uiv);
+impl_uniform_value!(glm::Vector4<u32> => [GL_UNSIGNED_INT_VEC4], Uniform4uiv);
+impl_uniform_matrix!(glm::Matrix2<f32> => GL_FLOAT_MAT2, UniformMatrix2fv);
+impl_uniform_matrix!(glm::Matrix3<f32> => GL_FLOAT_MAT3, UniformMatrix3fv);
+impl_uniform_matrix!(glm::Matrix4<f32> => GL_FLOAT_MAT4, UniformMatrix4fv);
+impl_uniform_matrix!(glm::Matrix2x3<f32> => GL_FLOAT_MAT2x3, UniformMatrix2x3fv);
+impl_uniform_matrix!(glm::Matrix2x4<f32> => GL_FLOAT_MAT2x4, UniformMatrix2x4fv);
+impl_uniform_matrix!(glm::Matrix3x2<f32> => GL_FLOAT_MAT3x2, UniformMatrix3x2fv);
+impl_uniform_matrix!(glm::Matrix3x4<f32> => GL_FLOAT_MAT3x4, UniformMatrix3x4fv);
+impl_uniform_matrix!(glm::Matrix4x2<f32> => GL_FLOAT_MAT4x2, UniformMatrix4x2fv);
+impl_uniform_matrix!(glm::Matrix4x3<f32> => GL_FLOAT_MAT4x3, UniformMatrix4x3fv);
+
+impl UniformValue for bool {
+    fn set_uniform(&self, gl: &GlFns, shader: &Shaders, name: &str) {
+        Self::set_uniform_array(std::slice::from_ref(self), gl, shader, name);
+    }
+
+    fn set_uniform_array(values: &[Self], gl: &GlFns, shader: &Shaders, name: &str) {
+        let ints: Vec<i32> = values.iter().map(|b| i32::from(*b)).collect();
+        i32::set_uniform_array(&ints, gl, shader, name);
+    }
+}
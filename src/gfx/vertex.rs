@@ -0,0 +1,245 @@
+use gl33::*;
+use ultraviolet::*;
+
+pub use open_gl_derive::VertexLayout;
+
+/// How the components of an attribute are stored in the buffer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Component {
+    F32,
+    /// Half floats, stored as `u16`
+    F16,
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+}
+
+impl Component {
+    pub fn gl_type(self) -> GLenum {
+        match self {
+            Component::F32 => GL_FLOAT,
+            Component::F16 => GL_HALF_FLOAT,
+            Component::I8 => GL_BYTE,
+            Component::U8 => GL_UNSIGNED_BYTE,
+            Component::I16 => GL_SHORT,
+            Component::U16 => GL_UNSIGNED_SHORT,
+            Component::I32 => GL_INT,
+            Component::U32 => GL_UNSIGNED_INT,
+        }
+    }
+
+    pub fn size(self) -> usize {
+        match self {
+            Component::I8 | Component::U8 => 1,
+            Component::F16 | Component::I16 | Component::U16 => 2,
+            Component::F32 | Component::I32 | Component::U32 => 4,
+        }
+    }
+
+    pub fn is_integer(self) -> bool {
+        !matches!(self, Component::F32 | Component::F16)
+    }
+}
+
+/// Field types a vertex attribute can be read from. Matrices take one
+/// location per column.
+pub trait AttribFormat {
+    const COMPONENT: Component;
+    /// Components per location, 1 to 4
+    const COUNT: u32;
+    const LOCATIONS: u32 = 1;
+}
+
+macro_rules! impl_attrib_format {
+    ($($t:ty => $component:ident, $count:expr, $locations:expr;)+) => ($(
+        impl AttribFormat for $t {
+            const COMPONENT: Component = Component::$component;
+            const COUNT: u32 = $count;
+            const LOCATIONS: u32 = $locations;
+        }
+    )+)
+}
+
+impl_attrib_format! {
+    f32 => F32, 1, 1;
+    i8 => I8, 1, 1;
+    u8 => U8, 1, 1;
+    i16 => I16, 1, 1;
+    u16 => U16, 1, 1;
+    i32 => I32, 1, 1;
+    u32 => U32, 1, 1;
+    Vec2 => F32, 2, 1;
+    Vec3 => F32, 3, 1;
+    Vec4 => F32, 4, 1;
+    Mat3 => F32, 3, 3;
+    Mat4 => F32, 4, 4;
+}
+
+/// Up to 4 components fit a location, longer arrays take one per element
+impl<T: AttribFormat, const N: usize> AttribFormat for [T; N] {
+    const COMPONENT: Component = T::COMPONENT;
+    const COUNT: u32 = if T::LOCATIONS == 1 && T::COUNT * N as u32 <= 4 {
+        T::COUNT * N as u32
+    } else {
+        T::COUNT
+    };
+    const LOCATIONS: u32 = if T::LOCATIONS == 1 && T::COUNT * N as u32 <= 4 {
+        1
+    } else {
+        T::LOCATIONS * N as u32
+    };
+}
+
+/// One shader input read from a vertex struct field
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Attrib {
+    pub location: u32,
+    pub component: Component,
+    pub count: u32,
+    /// Consecutive locations taken, from `location` on
+    pub locations: u32,
+    /// Byte offset in the vertex
+    pub offset: usize,
+    /// Integers are read as floats in [0, 1] or [-1, 1] instead of as ints
+    pub normalized: bool,
+}
+
+impl Attrib {
+    pub fn new<T: AttribFormat>(location: u32, offset: usize) -> Self {
+        Attrib {
+            location,
+            component: T::COMPONENT,
+            count: T::COUNT,
+            locations: T::LOCATIONS,
+            offset,
+            normalized: false,
+        }
+    }
+
+    pub fn normalized(mut self) -> Self {
+        self.normalized = true;
+        self
+    }
+
+    /// Reads `u16` fields as half floats
+    pub fn half(mut self) -> Self {
+        assert_eq!(
+            self.component,
+            Component::U16,
+            "half floats are stored in u16 fields"
+        );
+        self.component = Component::F16;
+        self
+    }
+
+    /// Bytes taken by the attribute in the vertex
+    pub fn size(&self) -> usize {
+        self.component.size() * (self.count * self.locations) as usize
+    }
+}
+
+/// Vertex struct whose fields feed shader inputs, usually derived with
+/// `#[derive(VertexLayout)]` and `#[vertex(location = N)]` on the fields. The
+/// struct needs `#[repr(C)]` when its buffer is filled from raw floats.
+pub trait VertexLayout: Sized {
+    fn attributes() -> Vec<Attrib>;
+
+    fn stride() -> usize {
+        std::mem::size_of::<Self>()
+    }
+}
+
+/// Checks the attributes fit in `stride` bytes without sharing a location
+pub fn validate(attributes: &[Attrib], stride: usize) -> Result<(), String> {
+    let mut used = Vec::new();
+    for a in attributes.iter() {
+        if a.count == 0 || a.count > 4 {
+            return Err(format!(
+                "attribute {} has {} components",
+                a.location, a.count
+            ));
+        }
+        if a.normalized && !a.component.is_integer() {
+            return Err(format!(
+                "attribute {} is normalized but not an integer",
+                a.location
+            ));
+        }
+        if a.offset + a.size() > stride {
+            return Err(format!(
+                "attribute {} ends at byte {}, past the vertex size {}",
+                a.location,
+                a.offset + a.size(),
+                stride
+            ));
+        }
+        for location in a.location..a.location + a.locations {
+            if used.contains(&location) {
+                return Err(format!("location {} is used twice", location));
+            }
+            used.push(location);
+        }
+    }
+    Ok(())
+}
+
+/// Points the attributes of `V` at the bound `GL_ARRAY_BUFFER` in the bound
+/// VAO, a `divisor` above 0 makes them advance per instance
+pub fn bind_attributes<V: VertexLayout>(gl: &GlFns, divisor: u32) -> Result<(), String> {
+    let attributes = V::attributes();
+    validate(&attributes, V::stride())?;
+    let stride = V::stride() as i32;
+    for a in attributes.iter() {
+        for column in 0..a.locations {
+            let location = a.location + column;
+            let offset = a.offset + (column * a.count) as usize * a.component.size();
+            unsafe {
+                if a.component.is_integer() && !a.normalized {
+                    gl.VertexAttribIPointer(
+                        location,
+                        a.count as i32,
+                        a.component.gl_type(),
+                        stride,
+                        offset as *const _,
+                    );
+                } else {
+                    gl.VertexAttribPointer(
+                        location,
+                        a.count as i32,
+                        a.component.gl_type(),
+                        a.normalized as u8,
+                        stride,
+                        offset as *const _,
+                    );
+                }
+                gl.EnableVertexAttribArray(location);
+                if divisor > 0 {
+                    gl.VertexAttribDivisor(location, divisor);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates a VAO reading vertices of `V` from `vbo`, indexed by `ebo` when
+/// given. The VAO is left bound.
+pub fn build_vao<V: VertexLayout>(gl: &GlFns, vbo: u32, ebo: Option<u32>) -> Result<u32, String> {
+    let mut vao = 0;
+    unsafe {
+        gl.GenVertexArrays(1, &mut vao);
+        if vao == 0 {
+            return Err("failed build_vao: gl.GenVertexArrays(1, &mut vao)".to_string());
+        }
+        gl.BindVertexArray(vao);
+        gl.BindBuffer(GL_ARRAY_BUFFER, vbo);
+        if let Some(ebo) = ebo {
+            gl.BindBuffer(GL_ELEMENT_ARRAY_BUFFER, ebo);
+        }
+    }
+    bind_attributes::<V>(gl, 0)?;
+    Ok(vao)
+}
@@ -1,2 +1,5 @@
+// lets the code generated by open_gl_derive name the crate from inside it too
+extern crate self as open_gl;
+
 pub mod demos;
 pub mod gfx;
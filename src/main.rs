@@ -1,6 +1,7 @@
 /// https://rust-tutorials.github.io/learn-opengl/basics/index.html
 extern crate open_gl;
 use open_gl::demos::get_all_demos;
+use open_gl::gfx::{model_cache, program_cache};
 use std::io::prelude::*;
 use std::process;
 
@@ -29,6 +30,15 @@ fn main() {
         }
     };
 
+    // reuse linked shader programs between runs, e.g. SHADER_CACHE_DIR=./target/shader_cache
+    if let Ok(dir) = std::env::var("SHADER_CACHE_DIR") {
+        program_cache::set_cache_dir(Some(dir.into()));
+    }
+    // imported models are cached next to their files unless MODEL_CACHE_DIR is set
+    if let Ok(dir) = std::env::var("MODEL_CACHE_DIR") {
+        model_cache::set_cache_dir(Some(dir.into()));
+    }
+
     println!("Running {}...", prog.name());
     prog.run().unwrap_or_else(|e| {
         writeln!(&mut stderr, "Demo error: {}", e).expect("stderr failure");
@@ -0,0 +1,209 @@
+mod common;
+
+use common::close;
+use open_gl::gfx::animation::*;
+use open_gl::gfx::models::{Model, Node};
+use std::f32::consts::FRAC_PI_2;
+use ultraviolet::*;
+
+fn key<T>(time: f32, value: T) -> Key<T> {
+    Key { time, value }
+}
+
+fn turn(angle: f32) -> Rotor3 {
+    Rotor3::from_rotation_xy(angle)
+}
+
+#[test]
+fn keys_interpolate_and_hold() {
+    let channel = Channel {
+        node: "bone".to_string(),
+        positions: vec![key(1.0, Vec3::zero()), key(2.0, Vec3::new(2.0, 0.0, 0.0))],
+        ..Default::default()
+    };
+    let rest = Transform::default();
+    let x = |time| channel.sample(time, &rest).translation.x;
+    assert!((x(1.5) - 1.0).abs() < 1e-6);
+    assert!((x(1.75) - 1.5).abs() < 1e-6);
+    // held before the first and after the last key
+    assert_eq!(x(0.0), 0.0);
+    assert_eq!(x(-3.0), 0.0);
+    assert_eq!(x(2.0), 2.0);
+    assert_eq!(x(9.0), 2.0);
+    // components without keys keep the rest pose
+    let s = channel.sample(1.5, &rest);
+    assert_eq!(s.scale, Vec3::one());
+    assert_eq!(s.rotation, Rotor3::identity());
+}
+
+#[test]
+fn zero_span_keys_jump() {
+    // two keys at t = 1, a step from 1 to 5
+    let channel = Channel {
+        node: "bone".to_string(),
+        scales: vec![
+            key(0.0, Vec3::zero()),
+            key(1.0, Vec3::one()),
+            key(1.0, Vec3::one() * 5.0),
+            key(2.0, Vec3::one() * 7.0),
+        ],
+        ..Default::default()
+    };
+    let rest = Transform::default();
+    let x = |time| channel.sample(time, &rest).scale.x;
+    assert!((x(0.5) - 0.5).abs() < 1e-6);
+    assert_eq!(x(1.0), 5.0);
+    assert!((x(1.5) - 6.0).abs() < 1e-6);
+    for t in [0.999, 1.0, 1.001] {
+        assert!(x(t).is_finite());
+    }
+}
+
+#[test]
+fn wrap_loops_playback_time() {
+    let clip = AnimationClip::new("loop", 2.0, vec![]);
+    assert_eq!(clip.wrap(0.5), 0.5);
+    assert_eq!(clip.wrap(5.0), 1.0);
+    assert_eq!(clip.wrap(-0.5), 1.5);
+    let empty = AnimationClip::new("empty", 0.0, vec![]);
+    assert_eq!(empty.wrap(3.0), 0.0);
+    assert_eq!(empty.wrap(0.0), 0.0);
+}
+
+#[test]
+fn transform_from_mat4() {
+    let t = Transform {
+        translation: Vec3::new(1.0, 2.0, 3.0),
+        rotation: turn(0.7) * Rotor3::from_rotation_yz(0.3),
+        scale: Vec3::new(2.0, 0.5, 1.5),
+    };
+    let split = Transform::from_mat4(&t.to_mat4());
+    assert!(close(split.translation, t.translation));
+    assert!(close(split.scale, t.scale));
+    for v in [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()] {
+        assert!(close(split.rotation * v, t.rotation * v));
+    }
+
+    let identity = Transform::from_mat4(&Mat4::identity());
+    assert!(close(identity.scale, Vec3::one()));
+    assert!(close(identity.rotation * Vec3::unit_x(), Vec3::unit_x()));
+}
+
+#[test]
+fn transform_blend() {
+    let a = Transform::default();
+    let b = Transform {
+        translation: Vec3::new(4.0, 0.0, 0.0),
+        rotation: turn(FRAC_PI_2),
+        scale: Vec3::one() * 3.0,
+    };
+    let at = |w| a.blend(&b, w);
+
+    assert!(close(at(0.0).translation, a.translation));
+    assert!(close(at(0.0).rotation * Vec3::unit_x(), Vec3::unit_x()));
+    assert!(close(at(1.0).translation, b.translation));
+    assert!(close(at(1.0).scale, b.scale));
+    assert!(close(
+        at(1.0).rotation * Vec3::unit_x(),
+        b.rotation * Vec3::unit_x()
+    ));
+
+    let half = at(0.5);
+    assert!(close(half.translation, Vec3::new(2.0, 0.0, 0.0)));
+    assert!(close(half.scale, Vec3::one() * 2.0));
+    // half of a quarter turn
+    let x = half.rotation * Vec3::unit_x();
+    assert!((x.dot(Vec3::unit_x()) - 0.5f32.sqrt()).abs() < 1e-4);
+    assert!(close(half.rotation * x, b.rotation * Vec3::unit_x()));
+}
+
+/// root, "bone0" at the origin and "bone1" one unit above it
+fn two_bone_chain() -> Model {
+    let mut model = Model::default();
+    let node = |name: &str, parent, children, local| Node {
+        name: name.to_string(),
+        parent,
+        children,
+        local,
+        world: Mat4::identity(),
+    };
+    model
+        .nodes
+        .push(node("root", None, vec![1], Mat4::identity()));
+    model
+        .nodes
+        .push(node("bone0", Some(0), vec![2], Mat4::identity()));
+    model.nodes.push(node(
+        "bone1",
+        Some(1),
+        vec![],
+        Mat4::from_translation(Vec3::unit_y()),
+    ));
+    model.update_world_matrices();
+    model.skeleton.add_bone("bone0", Mat4::identity());
+    model
+        .skeleton
+        .add_bone("bone1", Mat4::from_translation(-Vec3::unit_y()));
+
+    let bend = |node: &str| Channel {
+        node: node.to_string(),
+        rotations: vec![key(0.0, turn(FRAC_PI_2))],
+        ..Default::default()
+    };
+    model.animations = vec![
+        AnimationClip::new("bend", 1.0, vec![bend("bone0"), bend("bone1")]),
+        // nothing for bone0, it stays at rest
+        AnimationClip::new("tip", 1.0, vec![bend("bone1")]),
+    ];
+    model
+}
+
+#[test]
+fn animator_poses_a_chain() {
+    let model = two_bone_chain();
+    let mut animator = Animator::new(0);
+    animator.update(&model);
+    let palette = animator.palette();
+    assert_eq!(palette.len(), 2);
+
+    // both joints turn a quarter, the tip ends a full unit below its start
+    let joint = palette[0].transform_point3(Vec3::unit_y());
+    let tip = palette[1].transform_point3(Vec3::unit_y() * 2.0);
+    assert!(joint.y.abs() < 1e-4 && (joint.x.abs() - 1.0).abs() < 1e-4);
+    assert!(close(tip - joint, -Vec3::unit_y()));
+    // the tip bone carries its parent's rotation
+    assert!(close(
+        palette[1].transform_vec3(Vec3::unit_x()),
+        -Vec3::unit_x()
+    ));
+
+    // the rest pose gives identity matrices
+    let mut rest = Animator::new(5);
+    rest.update(&model);
+    for m in rest.palette() {
+        assert!(close(
+            m.transform_point3(Vec3::new(1.0, 2.0, 3.0)),
+            Vec3::new(1.0, 2.0, 3.0)
+        ));
+    }
+}
+
+#[test]
+fn animator_blends_missing_channels_with_rest() {
+    let model = two_bone_chain();
+    let mut animator = Animator::new(0);
+    let joint = |animator: &mut Animator, weight| {
+        animator.blend_with(1, weight);
+        animator.update(&model);
+        animator.palette()[0].transform_point3(Vec3::unit_y())
+    };
+
+    let bent = joint(&mut animator, 0.0);
+    assert!(bent.y.abs() < 1e-4);
+    // "tip" has no bone0 channel, full weight is the rest pose
+    assert!(close(joint(&mut animator, 1.0), Vec3::unit_y()));
+    // halfway is an eighth of a turn
+    let half = joint(&mut animator, 0.5);
+    assert!((half.y - 0.5f32.sqrt()).abs() < 1e-4);
+    assert!((half.x - bent.x * 0.5f32.sqrt()).abs() < 1e-4);
+}
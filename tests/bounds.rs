@@ -0,0 +1,137 @@
+mod common;
+
+use common::close;
+use open_gl::gfx::bounds::{fit_directional_shadow, Aabb, Frustum, Ray, Sphere};
+use ultraviolet::*;
+
+fn unit_cube() -> Aabb {
+    Aabb::new(Vec3::broadcast(-1.0), Vec3::broadcast(1.0))
+}
+
+#[test]
+fn aabb_transformed_contains_rotated_corners() {
+    let m = Mat4::from_translation(Vec3::new(5.0, 0.0, 0.0))
+        * Mat4::from_rotation_y(0.7)
+        * Mat4::from_nonuniform_scale(Vec3::new(2.0, 1.0, 0.5));
+    let aabb = unit_cube().transformed(&m);
+    let exact = Aabb::from_points(unit_cube().corners().map(|c| m.transform_point3(c)));
+    assert!(close(aabb.min, exact.min));
+    assert!(close(aabb.max, exact.max));
+    assert!(Aabb::default().transformed(&m).is_empty());
+}
+
+#[test]
+fn sphere_contains_points_and_scales() {
+    let points = unit_cube().corners();
+    let sphere = Sphere::from_points(&points);
+    assert!(close(sphere.center, Vec3::zero()));
+    assert!((sphere.radius - 3.0f32.sqrt()).abs() < 1e-5);
+
+    let scaled = sphere.transformed(&Mat4::from_nonuniform_scale(Vec3::new(1.0, 3.0, 1.0)));
+    assert!((scaled.radius - 3.0 * 3.0f32.sqrt()).abs() < 1e-4);
+}
+
+#[test]
+fn frustum_culls_boxes_behind_and_beside() {
+    let projection = projection::rh_yup::perspective_gl(45.0f32.to_radians(), 1.0, 0.1, 100.0);
+    let view = Mat4::look_at(Vec3::zero(), -Vec3::unit_z(), Vec3::unit_y());
+    let frustum = Frustum::from_matrix(&(projection * view));
+    let at = |p: Vec3| unit_cube().transformed(&Mat4::from_translation(p));
+
+    assert!(frustum.intersects_aabb(&at(Vec3::new(0.0, 0.0, -10.0))));
+    assert!(!frustum.intersects_aabb(&at(Vec3::new(0.0, 0.0, 10.0))));
+    assert!(!frustum.intersects_aabb(&at(Vec3::new(50.0, 0.0, -10.0))));
+    assert!(!frustum.intersects_aabb(&at(Vec3::new(0.0, 0.0, -200.0))));
+    assert!(frustum.intersects_sphere(&Sphere {
+        center: Vec3::new(5.0, 0.0, -10.0),
+        radius: 2.0,
+    }));
+    assert!(!frustum.intersects_sphere(&Sphere {
+        center: Vec3::new(0.0, 0.0, 5.0),
+        radius: 2.0,
+    }));
+}
+
+#[test]
+fn ray_hits() {
+    let ray = Ray {
+        origin: Vec3::new(0.0, 0.0, 5.0),
+        direction: -Vec3::unit_z(),
+    };
+    assert_eq!(ray.intersect_aabb(&unit_cube()), Some(4.0));
+    let sphere = Sphere {
+        center: Vec3::zero(),
+        radius: 1.0,
+    };
+    assert_eq!(ray.intersect_sphere(&sphere), Some(4.0));
+    let t = ray
+        .intersect_triangle(
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+        .unwrap();
+    assert!(close(ray.at(t), Vec3::zero()));
+
+    let away = Ray {
+        origin: ray.origin,
+        direction: Vec3::unit_z(),
+    };
+    assert_eq!(away.intersect_aabb(&unit_cube()), None);
+    assert_eq!(away.intersect_sphere(&sphere), None);
+}
+
+#[test]
+fn ray_hits_small_triangles() {
+    // a millimetre wide, det is far below f32::EPSILON
+    let ray = Ray {
+        origin: Vec3::new(0.0, 0.0, 5.0),
+        direction: -Vec3::unit_z() * 0.01,
+    };
+    let s = 1e-3;
+    let t = ray
+        .intersect_triangle(
+            Vec3::new(-s, -s, 0.0),
+            Vec3::new(s, -s, 0.0),
+            Vec3::new(0.0, s, 0.0),
+        )
+        .unwrap();
+    assert!(close(ray.at(t), Vec3::zero()));
+
+    // grazing along the triangle's plane
+    let along = Ray {
+        origin: Vec3::new(-1.0, 0.0, 0.0),
+        direction: Vec3::unit_x(),
+    };
+    assert_eq!(
+        along.intersect_triangle(
+            Vec3::new(-s, -s, 0.0),
+            Vec3::new(s, -s, 0.0),
+            Vec3::new(0.0, s, 0.0),
+        ),
+        None
+    );
+}
+
+#[test]
+fn ray_from_screen_center_follows_the_view() {
+    let projection = projection::rh_yup::perspective_gl(45.0f32.to_radians(), 2.0, 0.1, 100.0);
+    let view = Mat4::look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::zero(), Vec3::unit_y());
+    let ray = Ray::from_screen(400.0, 200.0, 800.0, 400.0, &projection, &view);
+    assert!(close(ray.direction, -Vec3::unit_z()));
+    assert!(ray.intersect_aabb(&unit_cube()).is_some());
+}
+
+#[test]
+fn shadow_fit_maps_casters_into_the_unit_cube() {
+    let casters = Aabb::new(Vec3::new(-2.0, 0.0, -1.0), Vec3::new(3.0, 2.0, 1.0));
+    let receivers = Aabb::new(Vec3::new(-25.0, -0.5, -25.0), Vec3::new(25.0, -0.5, 25.0));
+    let light = fit_directional_shadow(Vec3::new(-2.0, -4.0, -1.0), &casters, &receivers);
+    for c in casters.corners() {
+        let p = light.transform_point3(c);
+        assert!(p.x.abs() <= 1.0001 && p.y.abs() <= 1.0001 && p.z.abs() <= 1.0001);
+    }
+    // receivers under the casters are inside the depth range
+    let p = light.transform_point3(Vec3::new(0.5, -0.5, 0.0));
+    assert!(p.z.abs() <= 1.0001);
+}
@@ -0,0 +1,5 @@
+use ultraviolet::*;
+
+pub fn close(a: Vec3, b: Vec3) -> bool {
+    (a - b).mag() < 1e-4
+}
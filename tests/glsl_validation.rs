@@ -0,0 +1,906 @@
+// Parses and validates every demo shader with naga's GLSL front end, so broken
+// GLSL is caught on machines without a GL driver.
+//
+// naga reads Vulkan flavoured GLSL 4.50, the OpenGL 3.30 sources are adapted first
+// (see `to_naga_glsl`). The adaptation only renames and annotates declarations,
+// so errors in the shader code itself still fail the tests.
+//
+// Not covered: naga has no geometry stage, geometry shaders are parsed as vertex
+// shaders after their inputs and built-ins are rewritten into plain globals, so
+// their layout qualifiers, input array sizes and EmitVertex/EndPrimitive use go
+// unchecked. Tessellation and compute shaders (.tcs, .tes, .cs) are skipped.
+
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::ShaderStage;
+use open_gl::demos::inline_shaders;
+use open_gl::gfx::preprocessor;
+use open_gl::gfx::shader_library::parse_features;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Feature sets the demos compile their shaders with, every file is checked with each
+const FEATURE_SETS: &[&[&str]] = &[
+    &[],
+    &["HAS_SPECULAR_MAP"],
+    &["HAS_NORMAL_MAP"],
+    &["CAMERA_BLOCK"],
+    &["USE_SHADOWS", "PCF_KERNEL=5"],
+];
+
+/// Locations reserved for an interface block without explicit location
+const BLOCK_LOCATIONS: u32 = 8;
+
+#[test]
+fn demo_shader_files_are_valid() {
+    let mut files = vec![];
+    shader_files(Path::new("demo"), &mut files);
+    files.sort();
+    assert!(!files.is_empty(), "no shader files found under demo/");
+
+    let mut errors = vec![];
+    for file in files.iter() {
+        let ext = file.extension().unwrap().to_string_lossy().to_string();
+        for features in FEATURE_SETS {
+            let name = format!("{} [{}]", file.display(), features.join(", "));
+            let defines = parse_features(features).unwrap();
+            let res = preprocessor::preprocess_file(&file.to_string_lossy(), &defines)
+                .and_then(|source| validate(&name, &ext, &source.code));
+            if let Err(e) = res {
+                errors.push(e);
+            }
+        }
+    }
+    assert!(errors.is_empty(), "{}", errors.join("\n\n"));
+}
+
+#[test]
+fn inline_shaders_are_valid() {
+    let shaders = inline_shaders();
+    assert!(!shaders.is_empty());
+
+    let mut errors = vec![];
+    for (name, ext, code) in shaders {
+        if let Err(e) = validate(name, ext, code) {
+            errors.push(e);
+        }
+    }
+    assert!(errors.is_empty(), "{}", errors.join("\n\n"));
+}
+
+#[test]
+fn conditionals_follow_defines() {
+    let code = "#define PCF_KERNEL 5
+#define USE_SHADOWS
+#if defined(USE_SHADOWS) && PCF_KERNEL > 3
+wide
+#elif defined USE_SHADOWS
+narrow
+#else
+none
+#endif
+#if !defined(HAS_NORMAL_MAP) || (PCF_KERNEL == 1)
+flat
+#elif 1
+never
+#endif
+#ifdef HAS_NORMAL_MAP
+#if PCF_KERNEL
+nested
+#endif
+#endif
+";
+    let kept: Vec<String> = resolve_conditionals(code)
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(String::from)
+        .collect();
+    assert_eq!(kept, ["wide", "flat"]);
+}
+
+#[test]
+fn broken_shader_is_rejected() {
+    let code = "#version 330 core
+out vec4 FragColor;
+in vec2 TexCoords;
+uniform sampler2D screenTexture;
+void main()
+{
+    FragColor = texture(screenTexture, TexCoords) * tint;
+}
+";
+    assert!(validate("broken.fs", "fs", code).is_err());
+}
+
+fn shader_files(dir: &Path, out: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            shader_files(&path, out);
+        } else if let Some("vs" | "fs" | "gs") = path.extension().and_then(|e| e.to_str()) {
+            out.push(path);
+        }
+    }
+}
+
+fn validate(name: &str, stage_ext: &str, code: &str) -> Result<(), String> {
+    // naga has no geometry stage, geometry shaders are checked as vertex shaders
+    let stage = match stage_ext {
+        "fs" => ShaderStage::Fragment,
+        _ => ShaderStage::Vertex,
+    };
+    let adapted = to_naga_glsl(code, stage_ext);
+
+    let module = Frontend::default()
+        .parse(&Options::from(stage), &adapted)
+        .map_err(|errors| {
+            let messages: Vec<String> = errors
+                .iter()
+                .map(|e| {
+                    let loc = e.meta.location(&adapted);
+                    let line = adapted.lines().nth(loc.line_number as usize - 1);
+                    format!(
+                        "  line {}: {}\n    | {}",
+                        loc.line_number,
+                        e.kind,
+                        line.unwrap_or_default().trim()
+                    )
+                })
+                .collect();
+            format!("{}:\n{}", name, messages.join("\n"))
+        })?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|e| format!("{}:\n  {:?}", name, e.into_inner()))?;
+    Ok(())
+}
+
+/// Rewrites OpenGL GLSL into what naga accepts: `#version 450`, explicit locations
+/// and bindings, separate texture and sampler objects in place of combined samplers
+/// (also inside uniform structs and function parameters), and geometry shader
+/// inputs and built-ins replaced by plain globals.
+fn to_naga_glsl(code: &str, stage_ext: &str) -> String {
+    // declarations may differ between #ifdef branches, only look at the active ones
+    let code = resolve_conditionals(&strip_comments(code));
+    let lines: Vec<&str> = code.lines().collect();
+    let geometry = stage_ext == "gs";
+    let samplers = Samplers::collect(&lines);
+    let mut locations = Locations::collect(&lines);
+    let mut binding = 0;
+
+    let mut out = String::new();
+    let mut depth = 0;
+    // struct being declared and whether its members are geometry inputs
+    let mut current_struct: Option<String> = None;
+    let mut geometry_input = false;
+
+    for line in lines.iter() {
+        let trimmed = line.trim();
+        let global = depth == 0;
+        depth += trimmed.matches('{').count() as i32 - trimmed.matches('}').count() as i32;
+
+        if trimmed.starts_with("#version") {
+            out.push_str("#version 450 core\n");
+            if geometry {
+                out.push_str(GEOMETRY_PRELUDE);
+            }
+            continue;
+        }
+        if trimmed.starts_with('#') || trimmed.is_empty() {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        }
+
+        if !global {
+            if depth == 0 {
+                if geometry_input {
+                    // "} gs_in[];" closing a geometry input block
+                    let instance = trimmed.trim_start_matches('}').trim();
+                    let instance = instance.trim_end_matches(';').replace("[]", "[3]");
+                    let name = current_struct.take().unwrap_or_default();
+                    out.push_str(&format!("}};\n{} {};\n", name, instance));
+                    geometry_input = false;
+                    continue;
+                }
+                current_struct = None;
+            }
+            // sampler members live outside of the struct, see `uniform_decl`
+            if let Some(name) = &current_struct {
+                let member = trimmed.split_whitespace().next().unwrap_or_default();
+                if sampler_parts(member).is_some() && samplers.structs.contains_key(name) {
+                    out.push('\n');
+                    continue;
+                }
+            }
+            out.push_str(&samplers.rewrite(line, geometry));
+            out.push('\n');
+            continue;
+        }
+
+        let (layout, rest) = split_layout(trimmed);
+        let mut words = rest.split_whitespace();
+        let first = words.next().unwrap_or_default();
+        let interpolation = matches!(first, "flat" | "smooth" | "noperspective");
+        let (qualifier, decl) = if interpolation {
+            (first, rest[first.len()..].trim_start())
+        } else {
+            ("", rest)
+        };
+        let storage = decl.split(|c: char| c.is_whitespace() || c == ';').next();
+
+        match storage {
+            // geometry primitive layouts, "layout (triangles) in;"
+            Some("in" | "out") if decl.trim_end_matches(';').trim().len() <= 3 => {
+                out.push('\n');
+            }
+            Some("in") if geometry => {
+                let decl = decl["in".len()..].trim();
+                if decl.ends_with('{') {
+                    let name = decl.trim_end_matches('{').trim().to_string();
+                    out.push_str(&format!("struct {} {{\n", name));
+                    current_struct = Some(name);
+                    geometry_input = true;
+                } else {
+                    out.push_str(&format!("{}\n", decl.replace("[]", "[3]")));
+                }
+            }
+            Some(dir @ ("in" | "out")) => {
+                let layout = match layout {
+                    Some(l) if l.contains("location") => l.to_string(),
+                    _ => {
+                        let count = if decl.ends_with('{') {
+                            BLOCK_LOCATIONS
+                        } else {
+                            1
+                        };
+                        format!("location = {}", locations.next(dir, count))
+                    }
+                };
+                match matrix_attribute(&layout, decl) {
+                    Some(columns) if stage_ext == "vs" && dir == "in" => out.push_str(&columns),
+                    _ => out.push_str(&format!("layout({}) {} {}\n", layout, qualifier, decl)),
+                }
+            }
+            Some("uniform") => {
+                out.push_str(&uniform_decl(layout, decl, &samplers, &mut binding));
+            }
+            _ if trimmed.starts_with("struct") => {
+                let name = trimmed["struct".len()..].trim_end_matches('{').trim();
+                current_struct = Some(name.to_string());
+                out.push_str(line);
+                out.push('\n');
+            }
+            _ if trimmed.contains('(') => {
+                out.push_str(&split_sampler_params(line));
+                out.push('\n');
+            }
+            _ => {
+                out.push_str(&samplers.rewrite(line, geometry));
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+const GEOMETRY_PRELUDE: &str = "struct GeomVertex { vec4 position; };
+GeomVertex geom_in[3];
+int geom_layer;
+void EmitVertex() {}
+void EndPrimitive() {}
+";
+
+/// naga doesn't take matrices as vertex inputs, "layout (location = 3) in mat4 m;"
+/// becomes one vec4 input per column and a macro assembling them
+fn matrix_attribute(layout: &str, decl: &str) -> Option<String> {
+    let location: u32 = layout.split_once('=')?.1.trim().parse().ok()?;
+    let words: Vec<&str> = decl.trim_end_matches(';').split_whitespace().collect();
+    let (columns, name) = match words.as_slice() {
+        ["in", "mat2", name] => (2, name),
+        ["in", "mat3", name] => (3, name),
+        ["in", "mat4", name] => (4, name),
+        _ => return None,
+    };
+
+    let mut out = String::new();
+    let mut names = vec![];
+    for c in 0..columns {
+        out.push_str(&format!(
+            "layout(location = {}) in vec{} {}_{};\n",
+            location + c,
+            columns,
+            name,
+            c
+        ));
+        names.push(format!("{}_{}", name, c));
+    }
+    out.push_str(&format!(
+        "#define {} mat{}({})\n",
+        name,
+        columns,
+        names.join(", ")
+    ));
+    Some(out)
+}
+
+/// `uniform` declaration with a binding, combined samplers become a texture and a sampler
+fn uniform_decl(
+    layout: Option<&str>,
+    decl: &str,
+    samplers: &Samplers,
+    binding: &mut u32,
+) -> String {
+    let mut words = decl["uniform".len()..].split_whitespace();
+    let ty = words.next().unwrap_or_default();
+    let name = words
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(';')
+        .to_string();
+
+    if let Some((texture, sampler)) = sampler_parts(ty) {
+        let decl = sampler_decl(&name, texture, sampler, *binding);
+        *binding += 2;
+        return decl;
+    }
+
+    let layout = match layout {
+        Some(l) if l.contains("binding") => l.to_string(),
+        Some(l) => format!("{}, binding = {}", l, *binding),
+        None => format!("binding = {}", *binding),
+    };
+    *binding += 1;
+    let mut out = format!("layout({}) {}\n", layout, decl);
+
+    if let Some(members) = samplers.structs.get(ty) {
+        for (member, member_ty) in members {
+            let (texture, sampler) = sampler_parts(member_ty).unwrap();
+            let flat = format!("{}_{}", name, member);
+            out.push_str(&sampler_decl(&flat, texture, sampler, *binding));
+            *binding += 2;
+        }
+    }
+    out
+}
+
+fn sampler_decl(name: &str, texture: &str, sampler: &str, binding: u32) -> String {
+    format!(
+        "layout(binding = {}) uniform {} {}_t;\nlayout(binding = {}) uniform {} {}_s;\n",
+        binding,
+        texture,
+        name,
+        binding + 1,
+        sampler,
+        name
+    )
+}
+
+/// "float f(sampler2D map, vec2 uv)" -> "float f(texture2D map_t, sampler map_s, vec2 uv)"
+fn split_sampler_params(line: &str) -> String {
+    let (open, close) = match (line.find('('), line.rfind(')')) {
+        (Some(o), Some(c)) if o < c => (o, c),
+        _ => return line.to_string(),
+    };
+    let params: Vec<String> = line[open + 1..close]
+        .split(',')
+        .map(|p| {
+            let words: Vec<&str> = p.split_whitespace().collect();
+            match words.as_slice() {
+                [ty, name] => match sampler_parts(ty) {
+                    Some((texture, sampler)) => {
+                        format!("{} {}_t, {} {}_s", texture, name, sampler, name)
+                    }
+                    None => p.trim().to_string(),
+                },
+                _ => p.trim().to_string(),
+            }
+        })
+        .collect();
+    format!(
+        "{}({}){}",
+        &line[..open],
+        params.join(", "),
+        &line[close + 1..]
+    )
+}
+
+/// Texture and sampler types a combined sampler type is split into
+fn sampler_parts(ty: &str) -> Option<(&'static str, &'static str)> {
+    let parts = match ty {
+        "sampler1D" => ("texture1D", "sampler"),
+        "sampler2D" => ("texture2D", "sampler"),
+        "sampler3D" => ("texture3D", "sampler"),
+        "samplerCube" => ("textureCube", "sampler"),
+        "sampler2DArray" => ("texture2DArray", "sampler"),
+        "sampler2DMS" => ("texture2DMS", "sampler"),
+        "sampler2DShadow" => ("texture2D", "samplerShadow"),
+        "samplerCubeShadow" => ("textureCube", "samplerShadow"),
+        "sampler2DArrayShadow" => ("texture2DArray", "samplerShadow"),
+        "isampler2D" => ("itexture2D", "sampler"),
+        "usampler2D" => ("utexture2D", "sampler"),
+        _ => return None,
+    };
+    Some(parts)
+}
+
+/// Combined samplers declared by a shader: uniforms, function parameters and
+/// struct members
+#[derive(Default)]
+struct Samplers {
+    // name -> combined sampler type, used as constructor of the texture/sampler pair
+    names: HashMap<String, String>,
+    // struct -> sampler members
+    structs: HashMap<String, Vec<(String, String)>>,
+    // uniform instance -> struct with sampler members
+    instances: HashMap<String, String>,
+    // functions taking split sampler parameters
+    functions: HashSet<String>,
+}
+
+impl Samplers {
+    fn collect(lines: &[&str]) -> Self {
+        let mut samplers = Samplers::default();
+        let mut depth = 0;
+        let mut current_struct: Option<String> = None;
+        for line in lines.iter() {
+            let trimmed = line.trim();
+            let global = depth == 0;
+            depth += trimmed.matches('{').count() as i32 - trimmed.matches('}').count() as i32;
+            let words: Vec<&str> = trimmed
+                .split(|c: char| c.is_whitespace() || c == ';')
+                .filter(|w| !w.is_empty())
+                .collect();
+
+            if !global {
+                if let (Some(s), [ty, name, ..]) = (&current_struct, words.as_slice()) {
+                    if sampler_parts(ty).is_some() {
+                        let members = samplers.structs.entry(s.clone()).or_default();
+                        members.push((name.to_string(), ty.to_string()));
+                    }
+                }
+                if depth == 0 {
+                    current_struct = None;
+                }
+                continue;
+            }
+
+            let (_, rest) = split_layout(trimmed);
+            let words: Vec<&str> = rest
+                .split(|c: char| c.is_whitespace() || c == ';')
+                .filter(|w| !w.is_empty())
+                .collect();
+            match words.as_slice() {
+                ["struct", name, ..] => {
+                    current_struct = Some(name.trim_end_matches('{').to_string());
+                }
+                ["uniform", ty, name, ..] => {
+                    if sampler_parts(ty).is_some() {
+                        samplers.names.insert(name.to_string(), ty.to_string());
+                    } else if samplers.structs.contains_key(*ty) {
+                        samplers.instances.insert(name.to_string(), ty.to_string());
+                    }
+                }
+                _ if trimmed.contains('(') && trimmed.contains(')') => {
+                    let open = trimmed.find('(').unwrap();
+                    let close = trimmed.rfind(')').unwrap();
+                    if open > close {
+                        continue;
+                    }
+                    let function = trimmed[..open]
+                        .split_whitespace()
+                        .last()
+                        .unwrap_or_default();
+                    for param in trimmed[open + 1..close].split(',') {
+                        if let [ty, name] = param.split_whitespace().collect::<Vec<_>>()[..] {
+                            if sampler_parts(ty).is_some() {
+                                samplers.names.insert(name.to_string(), ty.to_string());
+                                samplers.functions.insert(function.to_string());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        samplers
+    }
+
+    /// Replaces uses of combined samplers in an expression line: `tex` becomes
+    /// `sampler2D(tex_t, tex_s)`, or `tex_t, tex_s` as argument of a function with
+    /// split parameters, `material.diffuse` becomes `sampler2D(material_diffuse_t, ...)`
+    fn rewrite(&self, line: &str, geometry: bool) -> String {
+        let chars: Vec<char> = line.chars().collect();
+        let mut out = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let starts_ident = (c.is_ascii_alphabetic() || c == '_')
+                && (i == 0 || !(chars[i - 1].is_ascii_alphanumeric() || chars[i - 1] == '_'));
+            if !starts_ident {
+                out.push(c);
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let ident: String = chars[start..i].iter().collect();
+            let member_access = out.trim_end().ends_with('.');
+
+            if geometry && ident == "gl_Position" && out.trim_end().ends_with("].") {
+                out.push_str("position");
+            } else if member_access {
+                out.push_str(&ident);
+            } else if let Some(ty) = self.names.get(&ident) {
+                out.push_str(&self.sampler_expr(&out, &ident, ty));
+            } else if let Some(s) = self.instances.get(&ident) {
+                // instance.member
+                let rest: String = chars[i..].iter().collect();
+                let member = self.structs[s].iter().find(|(m, _)| {
+                    rest.strip_prefix('.')
+                        .and_then(|r| r.strip_prefix(m.as_str()))
+                        .is_some_and(|r| {
+                            !r.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+                        })
+                });
+                match member {
+                    Some((m, ty)) => {
+                        let flat = format!("{}_{}", ident, m);
+                        out.push_str(&self.sampler_expr(&out, &flat, ty));
+                        i += 1 + m.chars().count();
+                    }
+                    None => out.push_str(&ident),
+                }
+            } else if geometry && ident == "gl_in" {
+                out.push_str("geom_in");
+            } else if geometry && ident == "gl_Layer" {
+                out.push_str("geom_layer");
+            } else {
+                out.push_str(&ident);
+            }
+        }
+        out
+    }
+
+    fn sampler_expr(&self, before: &str, name: &str, ty: &str) -> String {
+        let argument_of_split_function = enclosing_call(before)
+            .map(|f| self.functions.contains(f))
+            .unwrap_or(false);
+        if argument_of_split_function {
+            format!("{0}_t, {0}_s", name)
+        } else {
+            format!("{0}({1}_t, {1}_s)", ty, name)
+        }
+    }
+}
+
+/// Name of the function whose argument list is open at the end of `before`
+fn enclosing_call(before: &str) -> Option<&str> {
+    let mut depth = 0;
+    for (i, c) in before.char_indices().rev() {
+        match c {
+            ')' => depth += 1,
+            '(' if depth == 0 => {
+                let head = before[..i].trim_end();
+                let start = head
+                    .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .map(|p| p + 1)
+                    .unwrap_or(0);
+                return Some(&head[start..]);
+            }
+            '(' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Locations taken by explicit `layout (location = n)` declarations, per direction
+struct Locations {
+    used: HashMap<String, HashSet<u32>>,
+    next: HashMap<String, u32>,
+}
+
+impl Locations {
+    fn collect(lines: &[&str]) -> Self {
+        let mut used: HashMap<String, HashSet<u32>> = HashMap::new();
+        for line in lines.iter() {
+            let (layout, rest) = split_layout(line.trim());
+            let dir = rest.split_whitespace().find(|w| *w == "in" || *w == "out");
+            if let (Some(layout), Some(dir)) = (layout, dir) {
+                let location = layout
+                    .split(',')
+                    .filter_map(|p| p.split_once('='))
+                    .find(|(k, _)| k.trim() == "location")
+                    .and_then(|(_, v)| v.trim().parse().ok());
+                if let Some(l) = location {
+                    used.entry(dir.to_string()).or_default().insert(l);
+                }
+            }
+        }
+        Locations {
+            used,
+            next: HashMap::new(),
+        }
+    }
+
+    fn next(&mut self, dir: &str, count: u32) -> u32 {
+        let used = self.used.entry(dir.to_string()).or_default();
+        let next = self.next.entry(dir.to_string()).or_insert(0);
+        while (*next..*next + count).any(|l| used.contains(&l)) {
+            *next += 1;
+        }
+        let location = *next;
+        *next += count;
+        location
+    }
+}
+
+/// "layout (location = 0) in vec3 aPos;" -> (Some("location = 0"), "in vec3 aPos;")
+fn split_layout(decl: &str) -> (Option<&str>, &str) {
+    let rest = match decl.strip_prefix("layout") {
+        Some(r) => r.trim_start(),
+        None => return (None, decl),
+    };
+    match (rest.strip_prefix('('), rest.find(')')) {
+        (Some(_), Some(close)) => (Some(rest[1..close].trim()), rest[close + 1..].trim_start()),
+        _ => (None, decl),
+    }
+}
+
+/// Drops the lines of inactive `#ifdef` / `#ifndef` / `#else` branches, keeping line
+/// breaks. `#define`s stay for naga to expand values like `PCF_KERNEL`.
+fn resolve_conditionals(code: &str) -> String {
+    let mut defined: HashMap<String, String> = HashMap::new();
+    // (branch active, a branch of the chain was taken, any enclosing branch inactive)
+    let mut stack: Vec<(bool, bool, bool)> = vec![];
+    let mut out = String::new();
+    for line in code.lines() {
+        let active = stack
+            .last()
+            .map(|(a, _, outer)| *a && !outer)
+            .unwrap_or(true);
+        let trimmed = line.trim_start();
+        let (directive, rest) = trimmed
+            .split_once(char::is_whitespace)
+            .unwrap_or((trimmed, ""));
+        let name = rest
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        match directive {
+            "#ifdef" | "#ifndef" | "#if" => {
+                let taken = match directive {
+                    "#if" => Condition::eval(rest, &defined) != 0,
+                    _ => defined.contains_key(&name) == (directive == "#ifdef"),
+                };
+                stack.push((taken, taken, !active));
+                out.push('\n');
+                continue;
+            }
+            "#elif" => {
+                let (_, done, outer) = stack.pop().expect("#elif without #if");
+                let taken = !done && Condition::eval(rest, &defined) != 0;
+                stack.push((taken, done || taken, outer));
+                out.push('\n');
+                continue;
+            }
+            "#else" => {
+                let (_, done, outer) = stack.pop().expect("#else without #if");
+                stack.push((!done, true, outer));
+                out.push('\n');
+                continue;
+            }
+            "#endif" => {
+                stack.pop().expect("#endif without #if");
+                out.push('\n');
+                continue;
+            }
+            _ => {}
+        }
+        if !active {
+            out.push('\n');
+            continue;
+        }
+        match directive {
+            "#define" => {
+                let value = rest.trim_start()[name.len()..].trim();
+                defined.insert(name, value.to_string());
+            }
+            "#undef" => {
+                defined.remove(&name);
+            }
+            _ => {}
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Integer `#if` expression: `defined`, `!`, comparisons, `&&`, `||` and
+/// parentheses. Names are replaced by their define, 0 when undefined.
+struct Condition<'a> {
+    tokens: Vec<String>,
+    pos: usize,
+    defined: &'a HashMap<String, String>,
+}
+
+impl Condition<'_> {
+    fn eval(expr: &str, defined: &HashMap<String, String>) -> i64 {
+        let mut c = Condition {
+            tokens: tokenize(expr),
+            pos: 0,
+            defined,
+        };
+        let value = c.or();
+        assert!(
+            c.pos == c.tokens.len(),
+            "can't evaluate #if {}",
+            expr.trim()
+        );
+        value
+    }
+
+    fn peek(&self) -> &str {
+        self.tokens
+            .get(self.pos)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    fn next(&mut self) -> String {
+        self.pos += 1;
+        self.tokens.get(self.pos - 1).cloned().unwrap_or_default()
+    }
+
+    fn or(&mut self) -> i64 {
+        let mut value = self.and();
+        while self.peek() == "||" {
+            self.next();
+            let rhs = self.and();
+            value = (value != 0 || rhs != 0) as i64;
+        }
+        value
+    }
+
+    fn and(&mut self) -> i64 {
+        let mut value = self.comparison();
+        while self.peek() == "&&" {
+            self.next();
+            let rhs = self.comparison();
+            value = (value != 0 && rhs != 0) as i64;
+        }
+        value
+    }
+
+    fn comparison(&mut self) -> i64 {
+        let mut value = self.unary();
+        while let op @ ("==" | "!=" | "<" | ">" | "<=" | ">=") = self.peek() {
+            let op = op.to_string();
+            self.next();
+            let rhs = self.unary();
+            value = match op.as_str() {
+                "==" => value == rhs,
+                "!=" => value != rhs,
+                "<" => value < rhs,
+                ">" => value > rhs,
+                "<=" => value <= rhs,
+                _ => value >= rhs,
+            } as i64;
+        }
+        value
+    }
+
+    fn unary(&mut self) -> i64 {
+        match self.peek() {
+            "!" => {
+                self.next();
+                (self.unary() == 0) as i64
+            }
+            "-" => {
+                self.next();
+                -self.unary()
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> i64 {
+        let token = self.next();
+        if token == "(" {
+            let value = self.or();
+            assert_eq!(self.next(), ")", "unbalanced parentheses in #if");
+            return value;
+        }
+        if token == "defined" {
+            let parenthesized = self.peek() == "(";
+            if parenthesized {
+                self.next();
+            }
+            let name = self.next();
+            if parenthesized {
+                assert_eq!(self.next(), ")", "unbalanced parentheses in #if");
+            }
+            return self.defined.contains_key(&name) as i64;
+        }
+        if let Ok(value) = token.parse() {
+            return value;
+        }
+        assert!(
+            token.starts_with(|c: char| c.is_alphabetic() || c == '_'),
+            "unexpected '{}' in #if",
+            token
+        );
+        match self.defined.get(&token) {
+            Some(value) if !value.is_empty() => Condition::eval(value, self.defined),
+            _ => 0,
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = expr.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if c.is_alphanumeric() || c == '_' {
+            let mut word = c.to_string();
+            while let Some(&c) = chars.peek().filter(|c| c.is_alphanumeric() || **c == '_') {
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(word);
+            continue;
+        }
+        let pair: String = [c, chars.peek().copied().unwrap_or(' ')].iter().collect();
+        if ["&&", "||", "==", "!=", "<=", ">="].contains(&pair.as_str()) {
+            chars.next();
+            tokens.push(pair);
+        } else {
+            tokens.push(c.to_string());
+        }
+    }
+    tokens
+}
+
+/// Removes comments, keeping line breaks so line numbers stay the same
+fn strip_comments(code: &str) -> String {
+    let mut out = String::new();
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
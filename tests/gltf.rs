@@ -0,0 +1,256 @@
+use open_gl::gfx::gltf::*;
+use serde_json::json;
+use ultraviolet::*;
+
+fn glb(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut body = Vec::new();
+    for (kind, data) in chunks {
+        let padded = data.len().next_multiple_of(4);
+        body.extend((padded as u32).to_le_bytes());
+        body.extend(*kind);
+        body.extend(*data);
+        body.resize(body.len() + padded - data.len(), b' ');
+    }
+    let mut out = b"glTF".to_vec();
+    out.extend(2u32.to_le_bytes());
+    out.extend((12 + body.len() as u32).to_le_bytes());
+    out.extend(body);
+    out
+}
+
+fn f32_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn close(a: &[f32], b: &[f32]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5)
+}
+
+#[test]
+fn glb_chunks() {
+    let json = br#"{"asset":{"version":"2.0"}}"#;
+    let bin = [1u8, 2, 3, 4, 5, 6, 7, 8];
+    let data = glb(&[(b"JSON", json), (b"XTRA", b"skip"), (b"BIN\0", &bin)]);
+    let (j, b) = split_glb(&data).unwrap();
+    // the JSON chunk keeps its space padding
+    assert_eq!(j.trim_ascii_end(), json);
+    assert_eq!(b, Some(&bin[..]));
+
+    let data = glb(&[(b"JSON", json)]);
+    assert_eq!(split_glb(&data).unwrap().1, None);
+}
+
+#[test]
+fn glb_errors() {
+    let json = br#"{"asset":{"version":"2.0"}}"#;
+    let data = glb(&[(b"JSON", json), (b"BIN\0", &[0; 16])]);
+
+    let mut wrong_magic = data.clone();
+    wrong_magic[0] = b'x';
+    assert!(split_glb(&wrong_magic).is_err());
+
+    let mut version1 = data.clone();
+    version1[4] = 1;
+    assert!(split_glb(&version1).unwrap_err().contains("version 2"));
+
+    // cut inside the binary chunk
+    let truncated = &data[..data.len() - 4];
+    assert_eq!(split_glb(truncated).unwrap_err(), "truncated chunk");
+
+    // a chunk length running past the end of memory
+    let mut huge = data.clone();
+    huge[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(split_glb(&huge).unwrap_err(), "truncated chunk");
+
+    assert!(split_glb(&glb(&[(b"BIN\0", &[0; 4])]))
+        .unwrap_err()
+        .contains("no JSON"));
+    assert!(split_glb(b"glTF").is_err());
+}
+
+#[test]
+fn accessor_stride_and_offset() {
+    // positions interleaved with a padding float, starting after 8 bytes
+    let mut bytes = vec![0xff; 8];
+    bytes.extend(f32_bytes(&[
+        1.0, 2.0, 3.0, -1.0, 4.0, 5.0, 6.0, -1.0, 7.0, 8.0, 9.0, -1.0,
+    ]));
+    let doc = json!({
+        "bufferViews": [{"buffer": 0, "byteOffset": 4, "byteLength": 52, "byteStride": 16}],
+        "accessors": [
+            {"bufferView": 0, "byteOffset": 4, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"bufferView": 0, "byteOffset": 12, "componentType": 5126, "count": 3, "type": "VEC3"},
+            {"componentType": 5126, "count": 2, "type": "VEC2"},
+        ]
+    });
+    let buffers = vec![bytes];
+    let floats = read_floats(&doc, &buffers, 0, 3).unwrap();
+    assert!(close(
+        &floats,
+        &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0]
+    ));
+    // the last element would end 4 bytes past the view
+    assert!(read_floats(&doc, &buffers, 1, 3)
+        .unwrap_err()
+        .contains("out of its buffer view"));
+    assert!(read_floats(&doc, &buffers, 0, 2).is_err());
+    // no buffer view, all zeros
+    assert_eq!(read_floats(&doc, &buffers, 2, 2).unwrap(), vec![0.0; 4]);
+}
+
+#[test]
+fn accessor_normalized_integers() {
+    let mut bytes = vec![0u8, 255, 0x80, 0x7f];
+    bytes.extend(0u16.to_le_bytes());
+    bytes.extend(u16::MAX.to_le_bytes());
+    bytes.extend(i16::MIN.to_le_bytes());
+    bytes.extend(i16::MAX.to_le_bytes());
+    let doc = json!({
+        "bufferViews": [{"buffer": 0, "byteLength": 12}],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5121, "normalized": true, "count": 1, "type": "VEC2"},
+            {"bufferView": 0, "byteOffset": 2, "componentType": 5120, "normalized": true, "count": 1, "type": "VEC2"},
+            {"bufferView": 0, "byteOffset": 4, "componentType": 5123, "normalized": true, "count": 1, "type": "VEC2"},
+            {"bufferView": 0, "byteOffset": 8, "componentType": 5122, "normalized": true, "count": 1, "type": "VEC2"},
+            {"bufferView": 0, "componentType": 5121, "count": 1, "type": "VEC2"},
+            {"bufferView": 0, "byteOffset": 8, "componentType": 5122, "count": 1, "type": "VEC2"},
+        ]
+    });
+    let buffers = vec![bytes];
+    let read = |a| read_floats(&doc, &buffers, a, 2).unwrap();
+    assert!(close(&read(0), &[0.0, 1.0]));
+    // -128 clamps to -1 like -127
+    assert!(close(&read(1), &[-1.0, 1.0]));
+    assert!(close(&read(2), &[0.0, 1.0]));
+    assert!(close(&read(3), &[-1.0, 1.0]));
+    assert!(close(&read(4), &[0.0, 255.0]));
+    assert!(close(&read(5), &[-32768.0, 32767.0]));
+}
+
+#[test]
+fn accessor_sparse() {
+    let mut bytes = f32_bytes(&[1.0, 2.0, 3.0, 4.0]);
+    // indices 3 and 1 as u16, then their values
+    bytes.extend([3u8, 0, 1, 0]);
+    bytes.extend(f32_bytes(&[30.0, 10.0]));
+    let sparse = |count: u32| {
+        json!({
+            "count": count,
+            "indices": {"bufferView": 1, "componentType": 5123},
+            "values": {"bufferView": 1, "byteOffset": 4},
+        })
+    };
+    let doc = json!({
+        "bufferViews": [
+            {"buffer": 0, "byteLength": 16},
+            {"buffer": 0, "byteOffset": 16, "byteLength": 12},
+        ],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 4, "type": "SCALAR",
+             "sparse": sparse(2)},
+            // sparse on top of zeros
+            {"componentType": 5126, "count": 4, "type": "SCALAR",
+             "sparse": sparse(2)},
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "SCALAR",
+             "sparse": sparse(2)},
+            {"bufferView": 0, "componentType": 5126, "count": 4, "type": "SCALAR",
+             "sparse": sparse(3)},
+        ]
+    });
+    let buffers = vec![bytes];
+    assert_eq!(
+        read_floats(&doc, &buffers, 0, 1).unwrap(),
+        vec![1.0, 10.0, 3.0, 30.0]
+    );
+    assert_eq!(
+        read_floats(&doc, &buffers, 1, 1).unwrap(),
+        vec![0.0, 10.0, 0.0, 30.0]
+    );
+    // index 3 is past a count of 3
+    assert!(read_floats(&doc, &buffers, 2, 1)
+        .unwrap_err()
+        .contains("past the count"));
+    // a third value would be past the view
+    assert!(read_floats(&doc, &buffers, 3, 1).is_err());
+}
+
+#[test]
+fn offsets_past_memory_are_errors() {
+    let doc = json!({
+        "bufferViews": [
+            {"buffer": 0, "byteOffset": u64::MAX, "byteLength": 16},
+            {"buffer": 0, "byteLength": 16},
+        ],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 1, "type": "SCALAR"},
+            {"bufferView": 1, "byteOffset": u64::MAX, "componentType": 5126, "count": 1, "type": "SCALAR"},
+            {"bufferView": 1, "componentType": 5126, "count": u64::MAX, "type": "SCALAR"},
+        ]
+    });
+    let buffers = vec![vec![0; 16]];
+    for a in 0..3 {
+        assert!(read_floats(&doc, &buffers, a, 1).is_err(), "accessor {}", a);
+    }
+}
+
+#[test]
+fn indices() {
+    let mut bytes = vec![];
+    bytes.extend([1u16, 2, 65535].iter().flat_map(|i| i.to_le_bytes()));
+    bytes.extend([0, 0]);
+    let doc = json!({
+        "bufferViews": [{"buffer": 0, "byteLength": 8}],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5123, "count": 3, "type": "SCALAR"},
+            {"bufferView": 0, "componentType": 5126, "count": 2, "type": "SCALAR"},
+        ]
+    });
+    let buffers = vec![bytes];
+    assert_eq!(read_indices(&doc, &buffers, 0).unwrap(), vec![1, 2, 65535]);
+    assert!(read_indices(&doc, &buffers, 1).is_err());
+}
+
+#[test]
+fn uri_decoding() {
+    assert_eq!(percent_decode("my%20texture.png"), "my texture.png");
+    assert_eq!(percent_decode("%E2%82%ACuro%2fmap.png"), "€uro/map.png");
+    // malformed escapes are kept as they are
+    assert_eq!(percent_decode("100%.png"), "100%.png");
+    assert_eq!(percent_decode("a%zzb%4"), "a%zzb%4");
+    assert_eq!(percent_decode("plain.png"), "plain.png");
+}
+
+#[test]
+fn texture_transforms() {
+    // applied to the UVs of `Vertex`, v flipped from glTF's
+    let apply = |m: Mat3, u: f32, v: f32| {
+        let t = m * Vec3::new(u, 1.0 - v, 1.0);
+        Vec2::new(t.x, 1.0 - t.y)
+    };
+
+    let (set, m) = texture_transform(&json!({"index": 0, "texCoord": 1}));
+    assert_eq!(set, 1);
+    assert_eq!(m, Mat3::identity());
+
+    let (set, m) = texture_transform(&json!({
+        "index": 0,
+        "extensions": {"KHR_texture_transform": {
+            "offset": [0.5, 0.25], "scale": [2.0, 3.0], "texCoord": 1
+        }}
+    }));
+    assert_eq!(set, 1);
+    let uv = apply(m, 0.1, 0.2);
+    assert!((uv - Vec2::new(0.7, 0.85)).mag() < 1e-5, "{:?}", uv);
+
+    // a quarter turn takes u to -v
+    let (_, m) = texture_transform(&json!({
+        "index": 0,
+        "extensions": {"KHR_texture_transform": {"rotation": std::f32::consts::FRAC_PI_2}}
+    }));
+    let uv = apply(m, 1.0, 0.0);
+    assert!((uv - Vec2::new(0.0, -1.0)).mag() < 1e-5, "{:?}", uv);
+
+    // only two UV sets are imported
+    let (set, _) = texture_transform(&json!({"index": 0, "texCoord": 2}));
+    assert_eq!(set, 0);
+}
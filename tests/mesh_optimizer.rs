@@ -0,0 +1,170 @@
+use gl33::{GL_UNSIGNED_INT, GL_UNSIGNED_SHORT};
+use open_gl::gfx::models::{Mesh, Vertex};
+use open_gl::gfx::optimize::*;
+use ultraviolet::*;
+
+const SIZE: usize = 40;
+
+/// A SIZE x SIZE quad grid with unshared corners, triangles in a shuffled order
+fn grid() -> (Vec<Vec3>, Vec<u32>) {
+    let mut positions = Vec::new();
+    let mut triangles = Vec::new();
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            let corner = |dx: usize, dy: usize| Vec3::new((x + dx) as f32, (y + dy) as f32, 0.0);
+            let base = positions.len() as u32;
+            positions.extend([corner(0, 0), corner(1, 0), corner(1, 1), corner(0, 1)]);
+            triangles.push([base, base + 1, base + 2]);
+            triangles.push([base, base + 2, base + 3]);
+        }
+    }
+    // deterministic shuffle
+    let n = triangles.len();
+    for i in 0..n {
+        triangles.swap(i, (i * 7919 + 13) % n);
+    }
+    (positions, triangles.concat())
+}
+
+fn sorted_triangles(positions: &[Vec3], indices: &[u32]) -> Vec<[i64; 9]> {
+    let mut tris: Vec<[i64; 9]> = indices
+        .chunks_exact(3)
+        .map(|t| {
+            // rotate so the smallest corner comes first, winding is kept
+            let p: Vec<[i64; 3]> = t
+                .iter()
+                .map(|&i| {
+                    let v = positions[i as usize];
+                    [v.x as i64, v.y as i64, v.z as i64]
+                })
+                .collect();
+            let start = (0..3).min_by_key(|&k| p[k]).unwrap();
+            let mut out = [0; 9];
+            for k in 0..3 {
+                out[k * 3..k * 3 + 3].copy_from_slice(&p[(start + k) % 3]);
+            }
+            out
+        })
+        .collect();
+    tris.sort();
+    tris
+}
+
+fn stats(positions: &[Vec3], indices: &[u32]) -> MeshStats {
+    MeshStats::analyze(indices, positions, 12, 4)
+}
+
+#[test]
+fn deduplicate_merges_equal_vertices() {
+    let (positions, mut indices) = grid();
+    let key = |p: &Vec3| [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+    let original = sorted_triangles(&positions, &indices);
+    let unique = deduplicate(&positions, &mut indices, key);
+    assert_eq!(unique.len(), (SIZE + 1) * (SIZE + 1));
+    assert_eq!(sorted_triangles(&unique, &indices), original);
+}
+
+#[test]
+fn pipeline_keeps_triangles_and_improves_stats() {
+    let (positions, mut indices) = grid();
+    let original = sorted_triangles(&positions, &indices);
+    let key = |p: &Vec3| [p.x.to_bits(), p.y.to_bits(), p.z.to_bits()];
+    let positions = deduplicate(&positions, &mut indices, key);
+    let before = stats(&positions, &indices);
+
+    let indices = optimize_vertex_cache(&indices, positions.len());
+    let cached = stats(&positions, &indices);
+    assert_eq!(sorted_triangles(&positions, &indices), original);
+    assert!(
+        cached.acmr() < before.acmr() * 0.6,
+        "{} -> {}",
+        before,
+        cached
+    );
+
+    let mut indices = optimize_overdraw(&indices, &positions, 1.05);
+    assert_eq!(sorted_triangles(&positions, &indices), original);
+    assert!(stats(&positions, &indices).acmr() <= cached.acmr() * 1.05);
+
+    let positions = optimize_vertex_fetch(&positions, &mut indices);
+    let after = stats(&positions, &indices);
+    assert_eq!(sorted_triangles(&positions, &indices), original);
+    assert!(after.overfetch() <= before.overfetch());
+    // a flat grid has nothing behind anything
+    assert!((after.overdraw() - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn vertex_fetch_order_follows_first_use_and_drops_unused() {
+    let vertices = vec!['a', 'b', 'c', 'd', 'e'];
+    let mut indices = vec![3, 1, 4, 1, 3, 4];
+    let ordered = optimize_vertex_fetch(&vertices, &mut indices);
+    assert_eq!(ordered, vec!['d', 'b', 'e']);
+    assert_eq!(indices, vec![0, 1, 2, 1, 0, 2]);
+}
+
+#[test]
+fn overdraw_counts_hidden_layers() {
+    // two stacked quads facing +z, the far one drawn first is shaded twice
+    let positions = vec![
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+        Vec3::new(1.0, 0.0, 1.0),
+        Vec3::new(1.0, 1.0, 1.0),
+        Vec3::new(0.0, 1.0, 1.0),
+    ];
+    let back_to_front = [0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7];
+    let front_to_back = [4, 5, 6, 4, 6, 7, 0, 1, 2, 0, 2, 3];
+    let worse = stats(&positions, &back_to_front).overdraw();
+    let better = stats(&positions, &front_to_back).overdraw();
+    assert!(worse > 1.9 && worse <= 2.0, "{}", worse);
+    assert!((better - 1.0).abs() < 1e-6, "{}", better);
+}
+
+#[test]
+fn small_meshes_switch_to_short_indices() {
+    let (positions, indices) = grid();
+    let mut mesh = Mesh {
+        vertices: positions
+            .iter()
+            .map(|p| Vertex {
+                position: *p,
+                ..Default::default()
+            })
+            .collect(),
+        indices,
+        ..Default::default()
+    };
+    let original = sorted_triangles(&positions, &mesh.indices);
+    assert_eq!(mesh.index_type(), GL_UNSIGNED_INT);
+
+    let (before, after) = mesh.optimize();
+    assert!(mesh.short_indices);
+    assert_eq!(mesh.index_type(), GL_UNSIGNED_SHORT);
+    let positions: Vec<Vec3> = mesh.vertices.iter().map(|v| v.position).collect();
+    assert_eq!(sorted_triangles(&positions, &mesh.indices), original);
+    assert!(after.acmr() < before.acmr());
+}
+
+#[test]
+fn large_meshes_keep_int_indices() {
+    // more vertices than u16 indices can address, none shared
+    let count = u16::MAX as u32 + 3;
+    let mut mesh = Mesh {
+        vertices: (0..count)
+            .map(|i| Vertex {
+                position: Vec3::new(i as f32, (i % 3) as f32, 0.0),
+                ..Default::default()
+            })
+            .collect(),
+        indices: (0..count).collect(),
+        ..Default::default()
+    };
+    mesh.optimize();
+    assert_eq!(mesh.vertices.len(), count as usize);
+    assert!(!mesh.short_indices);
+    assert_eq!(mesh.index_type(), GL_UNSIGNED_INT);
+}
@@ -0,0 +1,143 @@
+use open_gl::gfx::animation::{AnimationClip, Channel, Key};
+use open_gl::gfx::model_cache::{decode, encode, read_header, VERSION};
+use open_gl::gfx::models::*;
+use ultraviolet::*;
+
+fn sample_model() -> Model {
+    let mut model = Model::default();
+    let mut mesh = Mesh {
+        skinned: true,
+        short_indices: true,
+        material: Material {
+            base_color: Vec4::new(0.5, 0.25, 1.0, 0.75),
+            shininess: 8.0,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    for i in 0..4 {
+        let mut v = Vertex {
+            position: Vec3::new(i as f32, (i * i) as f32, -1.5),
+            normal: Vec3::unit_z(),
+            tex_coords: Vec2::new(0.25 * i as f32, 1.0),
+            tex_coords1: Vec2::new(1.0, 0.5),
+            tangent: Vec3::unit_x(),
+            bitangent: Vec3::unit_y(),
+            ..Default::default()
+        };
+        v.add_bone_weight(i % 2, 1.0);
+        mesh.vertices.push(v);
+    }
+    mesh.indices = vec![0, 1, 2, 2, 3, 0];
+    mesh.textures.push(Texture {
+        id: 7,
+        tex_type: TexType::Normal,
+        uv_set: 1,
+        transform: Mat3::from_scale(2.0),
+        path: "demo/wood.png".to_string(),
+    });
+    mesh.textures.push(Texture {
+        tex_type: TexType::Diffuse,
+        path: WHITE_TEXTURE.to_string(),
+        ..Default::default()
+    });
+    model.meshes.push(mesh);
+
+    model.nodes.push(Node {
+        name: "root".to_string(),
+        parent: None,
+        children: vec![1],
+        local: Mat4::identity(),
+        world: Mat4::identity(),
+    });
+    model.nodes.push(Node {
+        name: "arm".to_string(),
+        parent: Some(0),
+        children: vec![],
+        local: Mat4::from_translation(Vec3::new(0.0, 2.0, 0.0)),
+        world: Mat4::identity(),
+    });
+    model.instances.push(MeshInstance { mesh: 0, node: 1 });
+    model.skeleton.add_bone("root", Mat4::identity());
+    model.skeleton.add_bone("arm", Mat4::from_scale(0.5));
+    model.animations.push(AnimationClip::new(
+        "wave",
+        1.5,
+        vec![Channel {
+            node: "arm".to_string(),
+            positions: vec![Key {
+                time: 0.5,
+                value: Vec3::new(1.0, 2.0, 3.0),
+            }],
+            rotations: vec![Key {
+                time: 1.0,
+                value: Rotor3::from_rotation_xy(0.3),
+            }],
+            scales: vec![],
+        }],
+    ));
+    model
+}
+
+#[test]
+fn round_trip() {
+    let model = sample_model();
+    let deps = vec!["model.obj".to_string(), "model.mtl".to_string()];
+    let data = encode(&model, &deps).unwrap();
+
+    let header = read_header(&data).unwrap();
+    assert_eq!(header.version, VERSION);
+    assert_eq!(header.dependencies, deps);
+
+    let loaded = decode(&data, "dir/model.obj").unwrap();
+    let (a, b) = (&model.meshes[0], &loaded.meshes[0]);
+    assert!(b.skinned && b.short_indices);
+    assert_eq!(a.material, b.material);
+    assert_eq!(a.indices, b.indices);
+    for (x, y) in a.vertices.iter().zip(b.vertices.iter()) {
+        assert_eq!(x.position, y.position);
+        assert_eq!(x.tex_coords1, y.tex_coords1);
+        assert_eq!(x.bitangent, y.bitangent);
+        assert_eq!(x.bone_ids, y.bone_ids);
+        assert_eq!(x.bone_weights, y.bone_weights);
+    }
+    assert_eq!(b.textures.len(), 2);
+    assert_eq!(b.textures[0].id, 0);
+    assert_eq!(b.textures[0].tex_type, TexType::Normal);
+    assert_eq!(b.textures[0].uv_set, 1);
+    assert_eq!(b.textures[0].transform, Mat3::from_scale(2.0));
+    assert_eq!(b.textures[0].path, "demo/wood.png");
+    assert_eq!(b.textures[1].path, WHITE_TEXTURE);
+
+    assert_eq!(loaded.nodes[1].name, "arm");
+    assert_eq!(loaded.nodes[0].children, vec![1]);
+    assert_eq!(loaded.nodes[1].world, model.nodes[1].local);
+    assert_eq!(loaded.instances.len(), 1);
+    assert_eq!(loaded.skeleton.bone("arm"), Some(1));
+    assert_eq!(loaded.skeleton.bones[1].offset, Mat4::from_scale(0.5));
+
+    let clip = &loaded.animations[0];
+    assert_eq!((clip.name.as_str(), clip.duration), ("wave", 1.5));
+    let channel = clip.channel("arm").unwrap();
+    assert_eq!(channel.positions[0].value, Vec3::new(1.0, 2.0, 3.0));
+    assert_eq!(channel.rotations[0].value, Rotor3::from_rotation_xy(0.3));
+    assert!(channel.scales.is_empty());
+
+    // the bounds are computed again, skinned meshes ignore the node transforms
+    assert_eq!(loaded.aabb.max, Vec3::new(3.0, 9.0, -1.5));
+}
+
+#[test]
+fn rejects_broken_files() {
+    let data = encode(&sample_model(), &[]).unwrap();
+    assert!(decode(&data[..data.len() - 1], "model.obj").is_err());
+    assert!(decode(b"nope", "model.obj").is_err());
+
+    let mut newer = data.clone();
+    newer[4..8].copy_from_slice(&(VERSION + 1).to_le_bytes());
+    assert!(decode(&newer, "model.obj").is_err());
+
+    let mut embedded = sample_model();
+    embedded.meshes[0].textures[0].path.clear();
+    assert!(encode(&embedded, &[]).is_err());
+}
@@ -0,0 +1,126 @@
+use open_gl::gfx::models::TexType;
+use open_gl::gfx::obj::{parse_mtl, parse_obj, triangulate};
+use std::fs;
+use ultraviolet::*;
+
+const QUAD_AND_TRIANGLE: &str = "
+mtllib scene.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+o quad
+usemtl red
+# negative indices count back from the last element
+f -4/-4/-1 -3/-3/-1 -2/-2/-1 -1/-1/-1
+o triangle
+usemtl blue
+s off
+f 1 2 \\
+  3
+";
+
+#[test]
+fn faces_are_triangulated_and_split_by_group() {
+    let obj = parse_obj(QUAD_AND_TRIANGLE).unwrap();
+    assert_eq!(obj.mtllibs, vec!["scene.mtl"]);
+    assert_eq!(obj.meshes.len(), 2);
+
+    let quad = &obj.meshes[0];
+    assert_eq!(quad.group, "quad");
+    assert_eq!(quad.material.as_deref(), Some("red"));
+    assert_eq!(quad.mesh.vertices.len(), 4);
+    assert_eq!(quad.mesh.indices.len(), 6);
+    // v flipped like assimp's FlipUVs
+    assert_eq!(quad.mesh.vertices[3].tex_coords, Vec2::new(0.0, 0.0));
+
+    // no normals in the file, generated from the flat face
+    let triangle = &obj.meshes[1];
+    assert_eq!(triangle.material.as_deref(), Some("blue"));
+    assert_eq!(triangle.mesh.indices, vec![0, 1, 2]);
+    for v in triangle.mesh.vertices.iter() {
+        assert!((v.normal - Vec3::unit_z()).mag() < 1e-6);
+    }
+}
+
+#[test]
+fn smoothing_groups_control_generated_normals() {
+    // two faces folded along the x axis sharing an edge
+    let faces = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 0 0 1\nf 1 2 3\nf 2 1 4\n";
+    let smooth = parse_obj(&format!("s 1\n{}", faces)).unwrap();
+    assert_eq!(smooth.meshes[0].mesh.vertices.len(), 4);
+    let shared = smooth.meshes[0].mesh.vertices[0].normal;
+    assert!((shared - Vec3::new(0.0, 1.0, 1.0).normalized()).mag() < 1e-6);
+
+    let flat = parse_obj(&format!("s off\n{}", faces)).unwrap();
+    assert_eq!(flat.meshes[0].mesh.vertices.len(), 6);
+}
+
+#[test]
+fn concave_polygons_are_ear_clipped() {
+    // an L shape, a fan from the first corner would cover the notch
+    let l = [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(2.0, 0.0, 0.0),
+        Vec3::new(2.0, 1.0, 0.0),
+        Vec3::new(1.0, 1.0, 0.0),
+        Vec3::new(1.0, 2.0, 0.0),
+        Vec3::new(0.0, 2.0, 0.0),
+    ];
+    let triangles = triangulate(&l);
+    assert_eq!(triangles.len(), 4);
+    let area: f32 = triangles
+        .iter()
+        .map(|[a, b, c]| (l[*b] - l[*a]).cross(l[*c] - l[*a]).z / 2.0)
+        .sum();
+    assert!((area - 3.0).abs() < 1e-6);
+    assert!(triangles
+        .iter()
+        .all(|[a, b, c]| (l[*b] - l[*a]).cross(l[*c] - l[*a]).z > 0.0));
+}
+
+#[test]
+fn invalid_indices_are_reported() {
+    let err = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n")
+        .err()
+        .unwrap();
+    assert!(err.starts_with("line 4:"), "{}", err);
+    assert!(parse_obj("v 0 0 0\nf 0 1 1\n").is_err());
+}
+
+#[test]
+fn mtl_factors_and_maps() {
+    let materials = parse_mtl(
+        "newmtl shiny\nKd 0.5 0.25 1\nKs 0.1 0.2 0.3\nNs 64\nd 0.5\n\
+         map_Kd -s 2 2 1 textures\\diffuse map.png\nmap_Bump -bm 0.5 normal.png\n",
+    )
+    .unwrap();
+    let shiny = &materials["shiny"];
+    assert_eq!(shiny.material.base_color, Vec4::new(0.5, 0.25, 1.0, 0.5));
+    assert_eq!(shiny.material.specular, Vec3::new(0.1, 0.2, 0.3));
+    assert_eq!(shiny.material.shininess, 64.0);
+    assert_eq!(
+        shiny.maps,
+        vec![
+            (TexType::Diffuse, "textures/diffuse map.png".to_string()),
+            (TexType::Normal, "normal.png".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn demo_planet_parses() {
+    let obj = parse_obj(&fs::read_to_string("demo/planet/planet.obj").unwrap()).unwrap();
+    let materials = parse_mtl(&fs::read_to_string("demo/planet/planet.mtl").unwrap()).unwrap();
+    assert_eq!(obj.meshes.len(), 1);
+    let mesh = &obj.meshes[0];
+    assert_eq!(mesh.mesh.indices.len(), 768 * 3);
+    assert!(mesh.mesh.vertices.iter().all(|v| v.normal.mag() > 0.99));
+    let mars = &materials[mesh.material.as_deref().unwrap()];
+    assert_eq!(mars.maps, vec![(TexType::Diffuse, "mars.png".to_string())]);
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use common::close;
+use open_gl::gfx::models::Model;
+use open_gl::gfx::primitives::*;
+use ultraviolet::*;
+
+fn check(model: &Model, vertices: usize, triangles: usize) {
+    let mesh = &model.meshes[0];
+    assert_eq!(mesh.vertices.len(), vertices);
+    assert_eq!(mesh.indices.len(), triangles * 3);
+    for t in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|k| &mesh.vertices[t[k] as usize]);
+        let face = (b.position - a.position).cross(c.position - a.position);
+        assert!(face.mag() > 0.0);
+        // counter clockwise when looking at the front
+        assert!(face.dot(a.normal + b.normal + c.normal) > 0.0);
+    }
+    for v in mesh.vertices.iter() {
+        assert!((v.normal.mag() - 1.0).abs() < 1e-4);
+        // u runs past 1 on the icosphere seam
+        assert!(v.tex_coords.x >= 0.0);
+        assert!(v.tex_coords.y >= 0.0 && v.tex_coords.y <= 1.0);
+    }
+}
+
+#[test]
+fn cube_and_plane() {
+    let c = cube(2.0, 3);
+    check(&c, 6 * 16, 6 * 18);
+    assert!(close(c.aabb.max, Vec3::one()) && close(c.aabb.min, -Vec3::one()));
+
+    let p = plane(4.0, 2.0, 4, 2);
+    check(&p, 5 * 3, 16);
+    assert!(close(p.aabb.max, Vec3::new(2.0, 0.0, 1.0)));
+    assert!(p.meshes[0]
+        .vertices
+        .iter()
+        .all(|v| v.normal == Vec3::unit_y()));
+}
+
+#[test]
+fn spheres() {
+    for s in [uv_sphere(1.5, 16, 8), icosphere(1.5, 2)] {
+        assert_eq!(s.meshes[0].indices.len() % 3, 0);
+        for v in s.meshes[0].vertices.iter() {
+            assert!((v.position.mag() - 1.5).abs() < 1e-4);
+            assert!(close(v.normal, v.position / 1.5));
+        }
+    }
+    check(&icosphere(1.0, 0), 16, 20);
+    check(&uv_sphere(1.0, 16, 8), 17 * 9, 16 * 2 * 7);
+}
+
+#[test]
+fn small_scale_keeps_faces() {
+    // a centimetre wide, far below any absolute area threshold
+    check(&uv_sphere(0.01, 64, 32), 65 * 33, 64 * 2 * 31);
+    check(&plane(0.01, 0.01, 10, 10), 11 * 11, 200);
+    check(&cube(0.01, 4), 6 * 25, 6 * 32);
+}
+
+#[test]
+fn round_shapes() {
+    for m in [
+        cylinder(0.5, 2.0, 12, 2),
+        cone(0.5, 2.0, 12, 2),
+        torus(1.0, 0.25, 16, 8),
+        capsule(0.5, 2.0, 12, 6),
+    ] {
+        let mesh = &m.meshes[0];
+        assert!(!mesh.indices.is_empty());
+        for t in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| &mesh.vertices[t[k] as usize]);
+            let face = (b.position - a.position).cross(c.position - a.position);
+            assert!(face.dot(a.normal + b.normal + c.normal) > 0.0);
+        }
+    }
+    let t = torus(1.0, 0.25, 16, 8);
+    assert!((t.aabb.max.x - 1.25).abs() < 1e-4 && (t.aabb.max.y - 0.25).abs() < 1e-4);
+    let c = capsule(0.5, 2.0, 12, 6);
+    assert!((c.aabb.max.y - 1.5).abs() < 1e-4 && (c.aabb.min.y + 1.5).abs() < 1e-4);
+}
+
+#[test]
+fn quad_covers_clip_space() {
+    let q = fullscreen_quad();
+    check(&q, 4, 2);
+    assert!(close(q.aabb.min, Vec3::new(-1.0, -1.0, 0.0)));
+    assert!(close(q.aabb.max, Vec3::new(1.0, 1.0, 0.0)));
+}
@@ -0,0 +1,74 @@
+use open_gl::gfx::procedural::*;
+
+#[test]
+fn images_are_rgba8() {
+    let images = [
+        checkerboard(64, 32, 4, WHITE, BLACK),
+        grid(48, 48, 6, 1, BLACK, WHITE),
+        gradient(16, 8, GradientDir::Radial, BLACK, WHITE),
+        noise(32, 16, NoiseKind::Perlin, 4.0, 3, 1),
+        normal_map_from_height(20, 10, 1.0, |u, _| u),
+        uv_debug(64, 64),
+        missing_texture(),
+    ];
+    for img in images.iter() {
+        assert_eq!(img.data.len(), img.width * img.height * 4);
+        assert!(img.data.chunks_exact(4).all(|p| p[3] == 255));
+    }
+    assert_eq!((images[0].width, images[0].height), (64, 32));
+    assert_eq!((images[4].width, images[4].height), (20, 10));
+}
+
+#[test]
+fn checkerboard_cells() {
+    let img = checkerboard(64, 64, 8, WHITE, BLACK);
+    // 8 texels per cell, rows start at the bottom
+    assert_eq!(img.get(0, 0), WHITE);
+    assert_eq!(img.get(7, 7), WHITE);
+    assert_eq!(img.get(8, 0), BLACK);
+    assert_eq!(img.get(0, 8), BLACK);
+    assert_eq!(img.get(8, 8), WHITE);
+    assert_eq!(missing_texture().get(8, 0), BLACK);
+    assert_eq!(missing_texture().get(0, 0), MAGENTA);
+}
+
+#[test]
+fn noise_depends_only_on_the_seed() {
+    let kinds = || [NoiseKind::Value, NoiseKind::Perlin, NoiseKind::Simplex];
+    for (a, b) in kinds().into_iter().zip(kinds()) {
+        assert_eq!(
+            noise(32, 32, a, 4.0, 4, 7).data,
+            noise(32, 32, b, 4.0, 4, 7).data
+        );
+    }
+    let a = noise(32, 32, NoiseKind::Simplex, 4.0, 4, 7).data;
+    let c = noise(32, 32, NoiseKind::Simplex, 4.0, 4, 8).data;
+    assert_ne!(a, c);
+    // greyscale
+    assert!(a.chunks_exact(4).all(|p| p[0] == p[1] && p[1] == p[2]));
+
+    for i in 0..200 {
+        let (x, y) = (i as f32 * 0.37, i as f32 * 0.91);
+        assert!((0.0..=1.0).contains(&value_noise_2d(x, y, 3)));
+        assert!(perlin_2d(x, y, 3).abs() <= 1.0);
+        assert!(simplex_2d(x, y, 3).abs() <= 1.0);
+    }
+}
+
+#[test]
+fn normal_map_encoding() {
+    // a flat height field points straight out of the surface: (0, 0, 1)
+    let flat = normal_map_from_height(8, 8, 1.0, |_, _| 0.5);
+    assert!(flat.data.chunks_exact(4).all(|p| p[..3] == [128, 128, 255]));
+
+    // rising along u tilts the normals towards -x, v is untouched
+    let slope = normal_map_from_height(8, 8, 1.0, |u, _| u);
+    let [r, g, b, _] = slope.get(4, 4);
+    // n = (-1, 0, 1) / sqrt(2)
+    assert!((r as i32 - 37).abs() <= 1, "{}", r);
+    assert_eq!(g, 128);
+    assert!((b as i32 - 218).abs() <= 1, "{}", b);
+
+    let steeper = normal_map_from_height(8, 8, 4.0, |u, _| u);
+    assert!(steeper.get(4, 4)[0] < r);
+}
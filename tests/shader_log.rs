@@ -0,0 +1,114 @@
+use open_gl::gfx::preprocessor::{preprocess_str, Defines, ShaderSource};
+use open_gl::gfx::shader_log::*;
+use std::fs;
+
+const INCLUDE: &str = "// helpers\nfloat twice(float x)\n{\n    return 2.0 * undefined_value;\n}\n";
+
+const MAIN: &str = "#version 330 core\n#include \"shader_log_inc.glsl\"\nout vec4 FragColor;\nvoid main()\n{\n    FragColor = vec4(twice(0.5));\n    bad_call();\n}\n";
+
+/// Preprocesses `MAIN` against the include written to a temporary directory
+fn source() -> ShaderSource {
+    let dir = std::env::temp_dir().join(format!("shader_log_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("shader_log_inc.glsl"), INCLUDE).unwrap();
+    preprocess_str(MAIN, dir.to_str().unwrap(), &Defines::new()).unwrap()
+}
+
+/// Where a compiler would report the first code line containing `needle`,
+/// following the `#line` directives like the driver does
+fn reported(code: &str, needle: &str) -> (usize, usize) {
+    let (mut source, mut line) = (0, 1);
+    for text in code.lines() {
+        if let Some(directive) = text.strip_prefix("#line ") {
+            let mut parts = directive.split_whitespace().map(|p| p.parse().unwrap());
+            line = parts.next().unwrap();
+            source = parts.next().unwrap_or(source);
+            continue;
+        }
+        if text.contains(needle) {
+            return (source, line);
+        }
+        line += 1;
+    }
+    panic!("{} not found", needle)
+}
+
+#[test]
+fn parses_driver_formats() {
+    let mesa = parse_line("0:12(5): error: `foo' undeclared").unwrap();
+    assert_eq!((mesa.source, mesa.line, mesa.column), (0, 12, Some(5)));
+    assert_eq!(mesa.message, "error: `foo' undeclared");
+
+    let nvidia = parse_line("1(7) : error C1008: undefined variable \"foo\"").unwrap();
+    assert_eq!((nvidia.source, nvidia.line, nvidia.column), (1, 7, None));
+    assert_eq!(nvidia.message, "error C1008: undefined variable \"foo\"");
+
+    let amd = parse_line("ERROR: 2:30: 'foo' : undeclared identifier").unwrap();
+    assert_eq!((amd.source, amd.line, amd.column), (2, 30, None));
+    assert_eq!(amd.message, "error: 'foo' : undeclared identifier");
+
+    let warning = parse_line("WARNING: 0:3: extension not supported").unwrap();
+    assert_eq!(warning.message, "warning: extension not supported");
+
+    assert_eq!(
+        parse_line("ERROR: 2 compilation errors.  No code generated."),
+        None
+    );
+    assert_eq!(parse_line("Fragment info"), None);
+    assert_eq!(parse_line(""), None);
+}
+
+#[test]
+fn maps_messages_through_includes() {
+    let source = source();
+    let (inc, inc_line) = reported(&source.code, "undefined_value");
+    let (main, main_line) = reported(&source.code, "bad_call");
+    assert!(source.files[inc].ends_with("shader_log_inc.glsl"));
+    assert_eq!(inc_line, 4);
+    assert_eq!((main, main_line), (0, 7));
+
+    let logs = [
+        // Mesa
+        format!(
+            "{}:{}(12): error: `undefined_value' undeclared\n{}:{}(5): error: no function with name 'bad_call'\n",
+            inc, inc_line, main, main_line
+        ),
+        // NVIDIA
+        format!(
+            "{}({}) : error C1008: undefined variable \"undefined_value\"\n{}({}) : error C1115: unable to find compatible overloaded function \"bad_call\"\n",
+            inc, inc_line, main, main_line
+        ),
+        // AMD
+        format!(
+            "ERROR: {}:{}: 'undefined_value' : undeclared identifier\nERROR: {}:{}: 'bad_call' : no matching overloaded function found\nERROR: 2 compilation errors.  No code generated.\n",
+            inc, inc_line, main, main_line
+        ),
+    ];
+    let include_file = source.files[inc].display().to_string();
+    for log in logs.iter() {
+        let out = format_log(log, &source);
+        assert!(out.contains(&format!("{}:4", include_file)), "{}", out);
+        assert!(out.contains("<inline>:7"), "{}", out);
+        // the offending lines are quoted from the original files
+        assert!(
+            out.contains("4 |     return 2.0 * undefined_value;"),
+            "{}",
+            out
+        );
+        assert!(out.contains("7 |     bad_call();"), "{}", out);
+        assert!(
+            out.contains("6 |     FragColor = vec4(twice(0.5));"),
+            "{}",
+            out
+        );
+    }
+
+    // Mesa columns place the caret, otherwise it points at the statement
+    let mesa = format_log(&logs[0], &source);
+    assert!(mesa.contains(&format!("{}:4:12:", include_file)));
+    assert!(mesa.contains("  |            ^\n"), "{}", mesa);
+    let nvidia = format_log(&logs[1], &source);
+    assert!(nvidia.contains("  |     ^\n"), "{}", nvidia);
+    // unparsed lines are kept
+    assert!(format_log(&logs[2], &source).contains("ERROR: 2 compilation errors."));
+}
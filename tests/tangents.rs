@@ -0,0 +1,83 @@
+mod common;
+
+use common::close;
+use open_gl::gfx::models::*;
+use ultraviolet::*;
+
+fn vertex(x: f32, y: f32, u: f32) -> Vertex {
+    Vertex {
+        position: Vec3::new(x, y, 0.0),
+        normal: Vec3::unit_z(),
+        tex_coords: Vec2::new(u, y),
+        ..Default::default()
+    }
+}
+
+/// Two quads sharing the edge at x = 1, the right one with its U mirrored
+fn mirrored_quads() -> Mesh {
+    Mesh {
+        vertices: vec![
+            vertex(0.0, 0.0, 0.0),
+            vertex(1.0, 0.0, 1.0),
+            vertex(1.0, 1.0, 1.0),
+            vertex(0.0, 1.0, 0.0),
+            vertex(2.0, 0.0, 0.0),
+            vertex(2.0, 1.0, 0.0),
+        ],
+        indices: vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2],
+        ..Default::default()
+    }
+}
+
+#[test]
+fn mirrored_seam_is_split() {
+    let mut mesh = mirrored_quads();
+    mesh.generate_tangents();
+    // the two seam vertices get a copy for the mirrored side
+    assert_eq!(mesh.vertices.len(), 8);
+
+    for (f, tri) in mesh.indices.chunks_exact(3).enumerate() {
+        let expected = if f < 2 {
+            Vec3::unit_x()
+        } else {
+            -Vec3::unit_x()
+        };
+        for &i in tri {
+            let v = &mesh.vertices[i as usize];
+            assert!(close(v.tangent, expected), "face {} vertex {}", f, i);
+            assert!(
+                close(v.bitangent, Vec3::unit_y()),
+                "face {} vertex {}",
+                f,
+                i
+            );
+        }
+    }
+}
+
+#[test]
+fn consistent_uvs_keep_shared_vertices() {
+    let mut mesh = mirrored_quads();
+    // unmirror the right quad
+    mesh.vertices[4].tex_coords.x = 2.0;
+    mesh.vertices[5].tex_coords.x = 2.0;
+    mesh.generate_tangents();
+    assert_eq!(mesh.vertices.len(), 6);
+    assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2]);
+    for v in mesh.vertices.iter() {
+        assert!(close(v.tangent, Vec3::unit_x()));
+    }
+}
+
+#[test]
+fn tiny_uv_islands_get_tangents() {
+    let mut mesh = mirrored_quads();
+    // the left quad squeezed into a 1e-4 wide corner of the atlas
+    for v in mesh.vertices.iter_mut() {
+        v.tex_coords *= 1e-4;
+    }
+    mesh.generate_tangents();
+    for &i in &mesh.indices[..6] {
+        assert!(close(mesh.vertices[i as usize].tangent, Vec3::unit_x()));
+    }
+}
@@ -0,0 +1,69 @@
+use open_gl::gfx::models::Vertex;
+use open_gl::gfx::vertex::*;
+use std::mem::offset_of;
+use ultraviolet::*;
+
+#[repr(C)]
+#[derive(Clone, Copy, VertexLayout)]
+struct Particle {
+    #[vertex(location = 0)]
+    position: Vec3,
+    #[vertex(location = 1, normalized)]
+    color: [u8; 4],
+    #[vertex(location = 2, half)]
+    size: [u16; 2],
+    #[vertex(location = 3)]
+    material: u32,
+    // padding, not read by the shader
+    _age: f32,
+    #[vertex(location = 4)]
+    transform: Mat4,
+}
+
+#[test]
+fn derive_reads_fields() {
+    let attributes = Particle::attributes();
+    assert_eq!(attributes.len(), 5);
+    assert_eq!(Particle::stride(), std::mem::size_of::<Particle>());
+    validate(&attributes, Particle::stride()).unwrap();
+
+    let color = attributes[1];
+    assert_eq!(color.component, Component::U8);
+    assert_eq!((color.count, color.locations), (4, 1));
+    assert!(color.normalized);
+    assert_eq!(color.offset, offset_of!(Particle, color));
+
+    assert_eq!(attributes[2].component, Component::F16);
+    assert_eq!(attributes[2].count, 2);
+    assert_eq!(attributes[3].component, Component::U32);
+    assert!(!attributes[3].normalized);
+
+    let transform = attributes[4];
+    assert_eq!((transform.count, transform.locations), (4, 4));
+    assert_eq!(transform.size(), 64);
+    assert_eq!(transform.offset, offset_of!(Particle, transform));
+}
+
+#[test]
+fn mesh_vertex_layout() {
+    let attributes = Vertex::attributes();
+    validate(&attributes, Vertex::stride()).unwrap();
+    let mut locations: Vec<u32> = attributes.iter().map(|a| a.location).collect();
+    locations.sort();
+    assert_eq!(locations, (0..8).collect::<Vec<_>>());
+
+    let bone_ids = attributes.iter().find(|a| a.location == 5).unwrap();
+    assert_eq!(bone_ids.component, Component::I32);
+    assert_eq!(bone_ids.offset, offset_of!(Vertex, bone_ids));
+}
+
+#[test]
+fn validate_rejects_bad_layouts() {
+    let position = Attrib::new::<Vec3>(0, 0);
+    assert!(validate(&[position], 12).is_ok());
+    assert!(validate(&[position], 8).is_err());
+    assert!(validate(&[position, Attrib::new::<Vec2>(0, 12)], 20).is_err());
+    // a matrix at 1 takes 1 to 4
+    assert!(validate(&[Attrib::new::<Mat4>(1, 0), Attrib::new::<f32>(4, 64)], 68).is_err());
+    assert!(validate(&[position.normalized()], 12).is_err());
+}